@@ -0,0 +1,234 @@
+//! Timings on large generated configs: hundreds of components, thousands of
+//! lines. Compare a change against the main branch with
+//! `cargo bench -- --save-baseline main` there and `--baseline main` here.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use serde_json::json;
+use std::{fmt::Write, fs, path::PathBuf};
+use tower_lsp::lsp_types::Url;
+
+use alloy_hover_lsp::{document::Document, lint, syntax, workspace::Workspace};
+use common::TestServer;
+
+/// One metrics and one logs pipeline per `i`, named `{prefix}{i}`: seven
+/// components and about 60 lines. Every `forward_to` points at the shared
+/// writers in [`writers`].
+fn pipelines(prefix: &str, count: usize) -> String {
+    let mut out = String::new();
+    for i in 0..count {
+        let name = format!("{prefix}{i}");
+        write!(
+            out,
+            r#"
+// Pods of team {i}.
+discovery.kubernetes "{name}" {{
+  role = "pod"
+  namespaces {{
+    names = ["team-{i}"]
+  }}
+}}
+
+discovery.relabel "{name}" {{
+  targets = discovery.kubernetes.{name}.targets
+
+  rule {{
+    source_labels = ["__meta_kubernetes_pod_label_app"]
+    regex         = "(.+)"
+    target_label  = "app"
+  }}
+
+  rule {{
+    source_labels = ["__meta_kubernetes_pod_container_port_name"]
+    regex         = "metrics"
+    action        = "keep"
+  }}
+}}
+
+prometheus.scrape "{name}" {{
+  targets         = discovery.relabel.{name}.output
+  forward_to      = [prometheus.relabel.{name}.receiver]
+  scrape_interval = "30s"
+  scrape_timeout  = "10s"
+}}
+
+prometheus.relabel "{name}" {{
+  forward_to = [prometheus.remote_write.default.receiver]
+
+  rule {{
+    source_labels = ["__name__"]
+    regex         = "go_gc_.*"
+    action        = "drop"
+  }}
+}}
+
+local.file_match "{name}" {{
+  path_targets = [{{"__path__" = "/var/log/team-{i}/*.log"}}]
+}}
+
+loki.source.file "{name}" {{
+  targets    = local.file_match.{name}.targets
+  forward_to = [loki.process.{name}.receiver]
+}}
+
+loki.process "{name}" {{
+  forward_to = [loki.write.default.receiver]
+
+  stage.static_labels {{
+    values = {{ team = "team-{i}" }}
+  }}
+}}
+"#
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn writers() -> &'static str {
+    r#"
+prometheus.remote_write "default" {
+  endpoint {
+    url = env("PROMETHEUS_URL")
+  }
+}
+
+loki.write "default" {
+  endpoint {
+    url = env("LOKI_URL")
+  }
+}
+"#
+}
+
+/// A single 150-pipeline file (~9000 lines, ~1000 components).
+fn large_config() -> String {
+    format!("{}{}", writers(), pipelines("team_", 150))
+}
+
+/// The line and column of the first `needle` in `text`, plus `shift`
+/// columns.
+fn find(text: &str, needle: &str, shift: u32) -> (u32, u32) {
+    let (line, column) = text
+        .lines()
+        .enumerate()
+        .find_map(|(n, l)| l.find(needle).map(|c| (n, c)))
+        .unwrap_or_else(|| panic!("{needle:?} is in the config"));
+    (line as u32, column as u32 + shift)
+}
+
+fn parsing(c: &mut Criterion) {
+    let text = large_config();
+    let uri = Url::parse("file:///bench/config.alloy").unwrap();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+
+    group.bench_function("syntax", |b| b.iter(|| syntax::parse(&text)));
+    group.bench_function("lint", |b| {
+        let mut workspace = Workspace::default();
+        workspace.update(uri.clone(), &text);
+        let doc = Document::new(text.clone());
+        b.iter(|| lint::check(&doc, &uri, &workspace, None))
+    });
+
+    // Everything a `didChange` of the whole file triggers in the server,
+    // up to publishing diagnostics, without the debounce delay.
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let options = json!({ "diagnostics": { "debounceMs": 0 } });
+    let mut server = runtime.block_on(TestServer::with_options(options));
+    let uri = common::uri("config.alloy");
+    runtime.block_on(server.open(&uri, &text));
+    let mut version = 1;
+    group.bench_function("did_change", |b| {
+        b.iter(|| {
+            version += 1;
+            runtime.block_on(server.change(&uri, version, &text))
+        })
+    });
+    group.finish();
+}
+
+fn hover(c: &mut Criterion) {
+    let text = large_config();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut server = runtime.block_on(TestServer::start());
+    let uri = common::uri("config.alloy");
+    runtime.block_on(server.open(&uri, &text));
+
+    // The label and component cases sit near the end of the file, the worst
+    // case for anything that walks the document from the top.
+    let last = 149;
+    let cases = [
+        ("label", find(&text, &format!("prometheus.relabel \"team_{last}\""), 21)),
+        ("attribute", find(&text, "scrape_interval", 2)),
+        ("component", find(&text, &format!("loki.process \"team_{last}\""), 2)),
+    ];
+    let mut group = c.benchmark_group("hover");
+    for (name, (line, character)) in cases {
+        assert!(runtime.block_on(server.hover(&uri, line, character)).is_some(), "{name} hover");
+        // Alternate between two columns of the same word so that every
+        // hover misses the server's last-hover cache.
+        let mut column = character;
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                column = if column == character { character + 1 } else { character };
+                runtime.block_on(server.hover(&uri, line, column))
+            })
+        });
+    }
+    group.finish();
+}
+
+fn indexing(c: &mut Criterion) {
+    // 40 files of 10 pipelines each, as a large repo might split them.
+    let root = std::env::temp_dir().join(format!("alloy-hover-bench-{}", std::process::id()));
+    let files = 40;
+    for n in 0..files {
+        let dir = root.join(format!("team-{}", n % 8));
+        fs::create_dir_all(&dir).unwrap();
+        let text = format!("{}{}", writers(), pipelines(&format!("f{n}_"), 10));
+        fs::write(dir.join(format!("pipelines-{n}.alloy")), text).unwrap();
+    }
+
+    let mut group = c.benchmark_group("index");
+    group.bench_function("scan", |b| {
+        b.iter_batched(
+            || Workspace::new(Some(PathBuf::from(&root))),
+            |mut workspace| {
+                workspace.scan(None, 1);
+                workspace
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("scan_parallel", |b| {
+        b.iter_batched(
+            || Workspace::new(Some(PathBuf::from(&root))),
+            |mut workspace| {
+                workspace.scan(None, 4);
+                workspace
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    // A cold start with every file's index already cached.
+    let cache = root.join(".cache");
+    Workspace::new(Some(PathBuf::from(&root))).scan(Some(&cache), 1);
+    group.bench_function("scan_cached", |b| {
+        b.iter_batched(
+            || Workspace::new(Some(PathBuf::from(&root))),
+            |mut workspace| {
+                workspace.scan(Some(&cache), 1);
+                workspace
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+    let _ = fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, parsing, hover, indexing);
+criterion_main!(benches);
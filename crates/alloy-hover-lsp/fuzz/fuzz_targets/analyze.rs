@@ -0,0 +1,19 @@
+//! Everything that runs when a document changes, on arbitrary text.
+
+#![no_main]
+
+use alloy_hover_lsp::{document::Document, folding, lint, semantic_tokens, workspace::Workspace};
+use libfuzzer_sys::fuzz_target;
+use tower_lsp::lsp_types::Url;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data).into_owned();
+    let uri = Url::parse("file:///fuzz/config.alloy").unwrap();
+    let mut workspace = Workspace::default();
+    workspace.update(uri.clone(), &text);
+
+    let doc = Document::new(text);
+    let _ = lint::check(&doc, &uri, &workspace, None);
+    let _ = folding::ranges(&doc);
+    let _ = semantic_tokens::tokens(&doc);
+});
@@ -0,0 +1,26 @@
+//! The parser runs on every keystroke, usually on half-typed text: it must
+//! return (with errors) for any input, never panic or loop.
+
+#![no_main]
+
+use alloy_hover_lsp::document::Document;
+use alloy_hover_lsp::syntax::{self, lexer::{tokenize, TokenKind}};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    for token in tokenize(&text) {
+        if matches!(token.kind, TokenKind::String | TokenKind::RawString) {
+            let raw = &text[token.span.start..token.span.end];
+            let value = syntax::unquote(raw);
+            let _ = syntax::source_offset(raw, value.len());
+        }
+    }
+    let file = syntax::parse(&text);
+
+    // Spans must stay on character boundaries for position mapping.
+    let doc = Document::new(text.into_owned());
+    for err in &file.errors {
+        let _ = doc.range(err.span);
+    }
+});
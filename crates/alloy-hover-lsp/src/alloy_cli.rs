@@ -0,0 +1,172 @@
+//! Runs the real `alloy` binary and maps its output to diagnostics.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tower_lsp::lsp_types::*;
+
+use crate::config::AlloyCli;
+use crate::document::Document;
+
+pub const SOURCE: &str = "alloy";
+
+/// Resolves the configured binary, falling back to a PATH lookup.
+pub fn binary(settings: &AlloyCli) -> Option<PathBuf> {
+    if let Some(path) = &settings.path {
+        return path.is_file().then(|| path.clone());
+    }
+    let exe = if cfg!(windows) { "alloy.exe" } else { "alloy" };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(exe))
+        .find(|p| p.is_file())
+}
+
+/// Runs `alloy fmt` and `alloy validate` against the saved file. `doc` must
+/// hold the text that was saved so positions line up. Whether the file is
+/// formatted is found by comparing what `alloy fmt` prints with it rather
+/// than through a check-only flag, which would say that it differs but not
+/// where.
+pub async fn validate(alloy: &Path, file: &Path, doc: &Document) -> Vec<Diagnostic> {
+    let path = file.to_string_lossy();
+    let (fmt, validate) = tokio::join!(
+        Command::new(alloy).arg("fmt").arg(file).output(),
+        Command::new(alloy).arg("validate").arg(file).output(),
+    );
+
+    let mut diagnostics = Vec::new();
+    if let Ok(out) = fmt {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let errors = parse_output(&stderr, Some(&path), SOURCE, doc);
+        let formatted = String::from_utf8_lossy(&out.stdout);
+        let unformatted = first_difference(&doc.text, &formatted).filter(|_| out.status.success() && errors.is_empty());
+        if let Some(line) = unformatted {
+            let start = doc.offset(Position::new(line, 0));
+            let end = doc.text[start..].find(['\r', '\n']).map_or(doc.text.len(), |i| start + i);
+            diagnostics.push(Diagnostic {
+                range: Range::new(doc.position(start), doc.position(end)),
+                severity: Some(DiagnosticSeverity::HINT),
+                source: Some(SOURCE.into()),
+                message: "file is not formatted according to `alloy fmt`".into(),
+                ..Default::default()
+            });
+        }
+        diagnostics.extend(errors);
+    }
+    if let Ok(out) = validate {
+        // `validate` reports parse errors too; keep only what fmt didn't.
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        for diag in parse_output(&stderr, Some(&path), SOURCE, doc) {
+            if !diagnostics.iter().any(|d| d.range == diag.range && d.message == diag.message) {
+                diagnostics.push(diag);
+            }
+        }
+    }
+    diagnostics
+}
+
+/// The first line `text` and `formatted` differ on, if they do. `alloy fmt`
+/// writes `\n` line endings whatever the file had, so those do not count.
+fn first_difference(text: &str, formatted: &str) -> Option<u32> {
+    let (text, formatted) = (text.replace("\r\n", "\n"), formatted.replace("\r\n", "\n"));
+    if text == formatted {
+        return None;
+    }
+    let same = text.split('\n').zip(formatted.split('\n')).take_while(|(a, b)| a == b).count();
+    Some(same as u32)
+}
+
+/// Formats `text` with `alloy fmt`, fed on stdin so unsaved edits are
+/// formatted too. Errors carry what `alloy fmt` printed.
+pub async fn format(alloy: &Path, text: &str) -> Result<String, String> {
+    let mut child = Command::new(alloy)
+        .args(["fmt", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("running {}: {err}", alloy.display()))?;
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let input = text.to_string();
+    // Written from a task so a large file cannot fill the pipes both ways.
+    let writer = tokio::spawn(async move { stdin.write_all(input.as_bytes()).await });
+    let out = child.wait_with_output().await.map_err(|err| err.to_string())?;
+    let _ = writer.await;
+    if !out.status.success() {
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+    }
+    String::from_utf8(out.stdout).map_err(|err| err.to_string())
+}
+
+/// Parses lines of the form `Error: <path>:<line>:<col>[-<line>:<col>]: <msg>`
+/// (the `Error: ` / `Warning: ` prefix is optional). With `file` set, only
+/// lines about that path are kept; otherwise any path is accepted. Alloy
+/// reports 1-based lines and byte columns.
+pub fn parse_output(output: &str, file: Option<&str>, source: &str, doc: &Document) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (severity, rest) = if let Some(rest) = line.strip_prefix("Warning: ") {
+                (DiagnosticSeverity::WARNING, rest)
+            } else {
+                (DiagnosticSeverity::ERROR, line.strip_prefix("Error: ").unwrap_or(line))
+            };
+            let rest = match file {
+                Some(file) => rest.strip_prefix(file)?.strip_prefix(':')?,
+                None => {
+                    let colon = rest.match_indices(':').map(|(i, _)| i).find(|&i| {
+                        let (line, col) = rest[i + 1..].split_once(':').unwrap_or_default();
+                        let col = col.split([':', '-']).next().unwrap_or_default();
+                        is_number(line) && is_number(col)
+                    })?;
+                    &rest[colon + 1..]
+                }
+            };
+            let (pos, message) = rest.split_once(": ")?;
+            let (start, end) = match pos.split_once('-') {
+                Some((start, end)) => (start, Some(end)),
+                None => (pos, None),
+            };
+            let start = line_col(doc, start)?;
+            let end = match end.and_then(|e| line_col(doc, e)) {
+                Some(end) => end,
+                None => token_end(doc, start),
+            };
+            Some(Diagnostic {
+                range: Range { start: doc.position(start), end: doc.position(end) },
+                severity: Some(severity),
+                source: Some(source.into()),
+                message: message.trim().to_string(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Converts `line:col` (1-based, byte column) into a byte offset.
+fn line_col(doc: &Document, pos: &str) -> Option<usize> {
+    let (line, col) = pos.split_once(':')?;
+    let line: u32 = line.trim().parse().ok()?;
+    let col: usize = col.trim().parse().ok()?;
+    let line_start = doc.offset(Position { line: line.saturating_sub(1), character: 0 });
+    let line_end = doc.text[line_start..].find('\n').map_or(doc.text.len(), |i| line_start + i);
+    let mut offset = (line_start + col.saturating_sub(1)).min(line_end);
+    while !doc.text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    Some(offset)
+}
+
+/// Alloy only gives a start position; underline the word starting there.
+fn token_end(doc: &Document, start: usize) -> usize {
+    let rest = &doc.text[start..];
+    match rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == '"')) {
+        Some(0) => start + rest.chars().next().map_or(0, char::len_utf8),
+        Some(len) => start + len,
+        None => doc.text.len(),
+    }
+}
@@ -0,0 +1,84 @@
+//! Call hierarchy over the pipeline: a component's "incoming calls" are the
+//! components that send data into it, its "outgoing calls" are the ones it
+//! sends data to.
+
+use std::collections::BTreeMap;
+use tower_lsp::lsp_types::*;
+
+use crate::graph::{self, Flow};
+use crate::workspace::{ComponentDef, Workspace};
+
+pub fn prepare(uri: &Url, pos: Position, workspace: &Workspace) -> Option<Vec<CallHierarchyItem>> {
+    // Innermost component around the cursor, for components nested in a
+    // `declare` body.
+    let (_, component) = workspace
+        .components()
+        .filter(|(u, c)| *u == uri && c.range.start <= pos && pos <= c.range.end)
+        .max_by_key(|(_, c)| c.range.start)?;
+    Some(vec![item(uri, component, workspace)])
+}
+
+pub fn incoming(item: &CallHierarchyItem, workspace: &Workspace) -> Vec<CallHierarchyIncomingCall> {
+    let flows = graph::flows(workspace);
+    group(&flows, |f| (f.edge.to == item.name).then_some(&f.edge.from))
+        .into_iter()
+        .filter_map(|(from, flows)| {
+            let (uri, component) = lookup(workspace, from)?;
+            Some(CallHierarchyIncomingCall {
+                from_ranges: ranges_in(&flows, uri),
+                from: self::item(uri, component, workspace),
+            })
+        })
+        .collect()
+}
+
+pub fn outgoing(item: &CallHierarchyItem, workspace: &Workspace) -> Vec<CallHierarchyOutgoingCall> {
+    let flows = graph::flows(workspace);
+    group(&flows, |f| (f.edge.from == item.name).then_some(&f.edge.to))
+        .into_iter()
+        .filter_map(|(to, flows)| {
+            let (uri, component) = lookup(workspace, to)?;
+            Some(CallHierarchyOutgoingCall {
+                to: self::item(uri, component, workspace),
+                from_ranges: ranges_in(&flows, &item.uri),
+            })
+        })
+        .collect()
+}
+
+fn item(uri: &Url, component: &ComponentDef, workspace: &Workspace) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: component.id(),
+        kind: SymbolKind::OBJECT,
+        tags: None,
+        detail: Some(workspace.display_path(uri)),
+        uri: uri.clone(),
+        range: component.range,
+        selection_range: component.range,
+        data: None,
+    }
+}
+
+fn lookup<'a>(workspace: &'a Workspace, id: &str) -> Option<(&'a Url, &'a ComponentDef)> {
+    workspace.components().find(|(_, c)| c.id() == id)
+}
+
+/// Groups flows by the component `key` picks out of them, skipping those
+/// it returns `None` for.
+fn group<'f, 'a>(flows: &'f [Flow<'a>], key: impl Fn(&'f Flow<'a>) -> Option<&'f String>) -> BTreeMap<&'f str, Vec<&'f Flow<'a>>> {
+    let mut groups: BTreeMap<&str, Vec<&Flow>> = BTreeMap::new();
+    for flow in flows {
+        if let Some(k) = key(flow) {
+            groups.entry(k.as_str()).or_default().push(flow);
+        }
+    }
+    groups
+}
+
+/// The references making up a call, limited to those written in `uri`: the
+/// protocol wants ranges inside the caller's document. A reference may
+/// also live in the callee (components pull from the ones they reference),
+/// in which case the call is still listed, just without a range.
+fn ranges_in(flows: &[&Flow], uri: &Url) -> Vec<Range> {
+    flows.iter().filter(|f| f.uri == uri).map(|f| f.range).collect()
+}
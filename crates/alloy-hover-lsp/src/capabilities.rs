@@ -0,0 +1,94 @@
+//! What the connected client says it can handle, from `initialize`.
+//! Features fall back to what an older or minimal client can show rather
+//! than assuming everything a modern editor supports.
+
+use tower_lsp::lsp_types::{ClientCapabilities, MarkupKind, ResourceOperationKind};
+
+use crate::registration::Feature;
+
+#[derive(Debug, Clone, Default)]
+pub struct ClientSupport {
+    /// Hover contents may be markdown; otherwise they are sent as plain
+    /// text.
+    pub markdown_hover: bool,
+    /// Code actions may carry their own edits. Quick fixes are only offered
+    /// to clients that accept them, as every fix is such an edit.
+    pub code_action_literals: bool,
+    /// Completions may be snippets with placeholders.
+    pub snippets: bool,
+    /// Completion documentation may be markdown, as for hovers.
+    pub markdown_completion: bool,
+    /// `completionItem/resolve` may fill in `additionalTextEdits`, not
+    /// just documentation.
+    pub resolve_completion_edits: bool,
+    /// The server may create progress tokens (`window/workDoneProgress`),
+    /// used to report the initial workspace scan.
+    pub work_done_progress: bool,
+    /// The client can open a document on request (`window/showDocument`),
+    /// used to jump straight to a component's only consumer.
+    pub show_document: bool,
+    /// The client re-requests code lenses when asked to
+    /// (`workspace/codeLens/refresh`), used after refreshing health.
+    pub code_lens_refresh: bool,
+    /// Workspace edits may create files, used to write out converted
+    /// configs.
+    pub create_files: bool,
+    /// The client pulls diagnostics (`textDocument/diagnostic`) rather than
+    /// having them published.
+    pub pull_diagnostics: bool,
+    /// The client pulls again when asked to (`workspace/diagnostic/refresh`),
+    /// used when diagnostics change other than by an edit.
+    pub diagnostic_refresh: bool,
+    /// Features the client lets us register and unregister at runtime.
+    pub dynamic: Vec<Feature>,
+}
+
+impl ClientSupport {
+    pub fn new(capabilities: &ClientCapabilities) -> Self {
+        let text = capabilities.text_document.as_ref();
+        let markdown_hover = text
+            .and_then(|t| t.hover.as_ref())
+            .and_then(|h| h.content_format.as_ref())
+            .is_some_and(|formats| formats.contains(&MarkupKind::Markdown));
+        let code_action_literals = text
+            .and_then(|t| t.code_action.as_ref())
+            .is_some_and(|c| c.code_action_literal_support.is_some());
+        let completion_item = text.and_then(|t| t.completion.as_ref()).and_then(|c| c.completion_item.as_ref());
+        let snippets = completion_item.and_then(|i| i.snippet_support).unwrap_or(false);
+        let markdown_completion = completion_item
+            .and_then(|i| i.documentation_format.as_ref())
+            .is_some_and(|formats| formats.contains(&MarkupKind::Markdown));
+        let resolve_completion_edits = completion_item
+            .and_then(|i| i.resolve_support.as_ref())
+            .is_some_and(|r| r.properties.iter().any(|p| p == "additionalTextEdits"));
+        let pull_diagnostics = text.is_some_and(|t| t.diagnostic.is_some());
+        let window = capabilities.window.as_ref();
+        let work_done_progress = window.and_then(|w| w.work_done_progress).unwrap_or(false);
+        let show_document = window.and_then(|w| w.show_document.as_ref()).is_some_and(|s| s.support);
+        let workspace = capabilities.workspace.as_ref();
+        let code_lens_refresh =
+            workspace.and_then(|w| w.code_lens.as_ref()).and_then(|c| c.refresh_support).unwrap_or(false);
+        let diagnostic_refresh =
+            workspace.and_then(|w| w.diagnostic.as_ref()).and_then(|d| d.refresh_support).unwrap_or(false);
+        let edits = workspace.and_then(|w| w.workspace_edit.as_ref());
+        let create_files = edits.and_then(|e| e.document_changes).unwrap_or(false)
+            && edits
+                .and_then(|e| e.resource_operations.as_ref())
+                .is_some_and(|ops| ops.contains(&ResourceOperationKind::Create));
+        let dynamic = Feature::ALL.into_iter().filter(|f| f.is_dynamic(capabilities)).collect();
+        Self {
+            markdown_hover,
+            code_action_literals,
+            snippets,
+            markdown_completion,
+            resolve_completion_edits,
+            work_done_progress,
+            show_document,
+            code_lens_refresh,
+            create_files,
+            pull_diagnostics,
+            diagnostic_refresh,
+            dynamic,
+        }
+    }
+}
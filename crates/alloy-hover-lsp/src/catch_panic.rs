@@ -0,0 +1,106 @@
+//! Turns a panic while handling a message into an internal-error reply,
+//! so one bad edge case costs a single request instead of the whole
+//! server.
+//!
+//! Only panics unwind: a stack overflow aborts the process and cannot be
+//! caught here. Recursion over input is bounded where it happens instead,
+//! as with the parser's nesting limit.
+
+use std::{
+    backtrace::Backtrace,
+    cell::RefCell,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Once},
+    task::{Context, Poll},
+};
+use tower_lsp::jsonrpc::{Error, Request, Response};
+use tower_service::Service;
+
+/// What a caught panic said, and where it happened.
+pub struct PanicReport {
+    pub method: String,
+    pub message: String,
+    pub backtrace: String,
+}
+
+thread_local! {
+    /// Filled in by the panic hook, for the handler that catches it.
+    static LAST_PANIC: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+/// Wraps the service, calling `on_panic` with each panic it catches.
+#[derive(Clone)]
+pub struct CatchPanic<S> {
+    inner: S,
+    on_panic: Arc<dyn Fn(PanicReport) + Send + Sync>,
+}
+
+impl<S> CatchPanic<S> {
+    pub fn new(inner: S, on_panic: impl Fn(PanicReport) + Send + Sync + 'static) -> Self {
+        // The backtrace has to be taken where the panic happens; by the
+        // time it is caught the stack is gone.
+        static HOOK: Once = Once::new();
+        HOOK.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                let message = match (info.payload().downcast_ref::<&str>(), info.payload().downcast_ref::<String>()) {
+                    (Some(message), _) => message.to_string(),
+                    (_, Some(message)) => message.clone(),
+                    _ => "panicked".to_string(),
+                };
+                let message = match info.location() {
+                    Some(at) => format!("{message} at {}:{}", at.file(), at.line()),
+                    None => message,
+                };
+                LAST_PANIC.with(|last| *last.borrow_mut() = Some((message, Backtrace::force_capture().to_string())));
+                previous(info);
+            }));
+        });
+        Self { inner, on_panic: Arc::new(on_panic) }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S> Service<Request> for CatchPanic<S>
+where
+    S: Service<Request, Response = Option<Response>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Option<Response>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Option<Response>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let (method, id) = (request.method().to_string(), request.id().cloned());
+        let on_panic = self.on_panic.clone();
+        let mut future = match panic::catch_unwind(AssertUnwindSafe(|| self.inner.call(request))) {
+            Ok(future) => Box::pin(future),
+            Err(_) => return Box::pin(std::future::ready(Ok(caught(&method, id, &*on_panic)))),
+        };
+        Box::pin(std::future::poll_fn(move |cx| {
+            match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+                Ok(poll) => poll,
+                Err(_) => Poll::Ready(Ok(caught(&method, id.clone(), &*on_panic))),
+            }
+        }))
+    }
+}
+
+/// Reports the panic, and answers a request with an internal error; a
+/// notification gets no reply.
+fn caught(method: &str, id: Option<tower_lsp::jsonrpc::Id>, on_panic: &dyn Fn(PanicReport)) -> Option<Response> {
+    let (message, backtrace) = LAST_PANIC.with(|last| last.borrow_mut().take()).unwrap_or_default();
+    let error = Error { message: format!("{method} failed: {message}").into(), ..Error::internal_error() };
+    on_panic(PanicReport { method: method.to_string(), message, backtrace });
+    id.map(|id| Response::from_error(id, error))
+}
@@ -0,0 +1,66 @@
+//! Grafana Cloud endpoint presets, from `schema/cloud_endpoints.toml`:
+//! region-specific URLs to complete, and what a URL belongs to on hover.
+
+use serde::Deserialize;
+use std::{collections::BTreeMap, sync::OnceLock};
+
+const BUILTIN: &str = include_str!("../schema/cloud_endpoints.toml");
+
+#[derive(Deserialize)]
+struct Catalog {
+    products: BTreeMap<String, Product>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Product {
+    pub name: String,
+    /// Where the endpoint goes: an argument path within a component.
+    pub component: String,
+    pub argument: String,
+    regions: BTreeMap<String, String>,
+}
+
+/// One product's endpoint in one region.
+pub struct Preset {
+    pub product: &'static Product,
+    pub region: &'static str,
+    pub url: &'static str,
+}
+
+fn products() -> &'static BTreeMap<String, Product> {
+    static CATALOG: OnceLock<Catalog> = OnceLock::new();
+    &CATALOG.get_or_init(|| toml::from_str(BUILTIN).expect("bundled cloud endpoints are valid TOML")).products
+}
+
+fn presets() -> impl Iterator<Item = Preset> {
+    products().values().flat_map(|product| {
+        product.regions.iter().map(move |(region, url)| Preset { product, region, url })
+    })
+}
+
+/// The presets for `attribute` in the innermost of `blocks`, which run
+/// from a component inwards, possibly inside a module.
+pub fn for_argument(blocks: &[String], attribute: &str) -> Vec<Preset> {
+    presets()
+        .filter(|preset| {
+            let Some(owner) = blocks.iter().rposition(|b| *b == preset.product.component) else { return false };
+            let path: Vec<&str> = blocks[owner + 1..].iter().map(String::as_str).chain([attribute]).collect();
+            path.join(".") == preset.product.argument
+        })
+        .collect()
+}
+
+/// The preset on the same host as `url`, with or without a scheme, path
+/// or port.
+pub fn lookup(url: &str) -> Option<Preset> {
+    let host = host(url)?;
+    presets().find(|preset| self::host(preset.url) == Some(host))
+}
+
+fn host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit_once(':').map_or(authority, |(host, _)| host);
+    (!host.is_empty()).then_some(host)
+}
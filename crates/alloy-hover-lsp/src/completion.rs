@@ -0,0 +1,1162 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+use crate::cloud_endpoints::{self, Preset};
+use crate::config::HoverStyle;
+use crate::document::Document;
+use crate::hover;
+use crate::labels;
+use crate::lint;
+use crate::markdown;
+use crate::metrics_api::Catalog;
+use crate::relabel;
+use crate::releases::{Releases, TargetRelease};
+use crate::schema::Schema;
+use crate::snippets::{self, PIPELINES};
+use crate::stdlib;
+use crate::syntax::lexer::{tokenize, Token, TokenKind};
+use crate::syntax::unquote;
+use crate::syntax::Span;
+use crate::workspace::Workspace;
+
+/// Blocks whose string attributes name files on disk, and the attribute
+/// holding the path.
+const PATH_ATTRIBUTES: &[(&str, &str)] = &[
+    ("local.file", "filename"),
+    ("local.file_match", "path_targets"),
+    ("import.file", "filename"),
+];
+
+/// What the client supports and the settings turn on.
+#[derive(Clone, Copy, Default)]
+pub struct Options {
+    /// Insert snippets with placeholders rather than plain text.
+    pub snippets: bool,
+    /// Offer Grafana Cloud endpoints where they go.
+    pub cloud_endpoints: bool,
+    /// The client resolves `additionalTextEdits` too, so they can be left
+    /// to `completionItem/resolve` like documentation.
+    pub resolve_edits: bool,
+}
+
+/// What an item stands for, kept in its `data` so that
+/// `completionItem/resolve` can fill in what [`complete`] leaves out.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Resolve {
+    Component { name: String },
+    Argument { blocks: Vec<String>, name: String },
+    Block { blocks: Vec<String>, name: String },
+    /// A module component of `declared_in`, offered in `uri`, which does
+    /// not import it yet.
+    #[serde(rename_all = "camelCase")]
+    Import { uri: Url, declared_in: Url },
+}
+
+impl Resolve {
+    /// The document an [`Resolve::Import`] edits.
+    pub fn document(&self) -> Option<&Url> {
+        match self {
+            Resolve::Import { uri, .. } => Some(uri),
+            _ => None,
+        }
+    }
+}
+
+/// Fills in the documentation, or the import, that `resolve` says `item`
+/// lacks. `doc` is the document of an import. Documentation is what a
+/// full hover would show, markdown from the schema and the `docs` bundle,
+/// or its plain text for clients that cannot show `markdown`.
+pub fn resolve(
+    item: &mut CompletionItem,
+    resolve: Resolve,
+    doc: Option<&Document>,
+    releases: &Releases,
+    docs: impl Fn(&str) -> Option<Arc<str>>,
+    markdown: bool,
+) {
+    let schema = Schema::builtin();
+    let render = |md: String| match markdown {
+        true => Documentation::MarkupContent(MarkupContent { kind: MarkupKind::Markdown, value: md }),
+        false => Documentation::String(markdown::to_plain_text(&md)),
+    };
+    match resolve {
+        Resolve::Component { name } => {
+            item.documentation = hover::word(&name, docs(&name), HoverStyle::Full, releases).map(render);
+        }
+        Resolve::Argument { blocks, name } => {
+            let Some(arg) = schema.body_in(&blocks).and_then(|body| body.arguments.get(&name)) else { return };
+            let mut md = hover::argument(&name, arg, HoverStyle::Full, releases.argument_under(&blocks, &name));
+            if let Some(extra) = docs(&name) {
+                md.push_str(&format!("---\n\n{extra}"));
+            }
+            item.documentation = Some(render(md.trim_end().to_string()));
+        }
+        Resolve::Block { blocks, name } => {
+            let Some(block) = schema.body_in(&blocks).and_then(|body| body.blocks.get(&name)) else { return };
+            let owner = blocks.iter().rposition(|b| schema.component(b).is_some()).unwrap_or_default();
+            let md = hover::block_docs(&blocks[owner..], &name, block, HoverStyle::Full, docs(&name));
+            item.documentation = Some(render(md));
+        }
+        Resolve::Import { uri, declared_in } => {
+            let typed = item.text_edit.as_ref().map(|edit| match edit {
+                CompletionTextEdit::Edit(edit) => edit.range,
+                CompletionTextEdit::InsertAndReplace(edit) => edit.replace,
+            });
+            if let (Some(doc), Some(typed)) = (doc, typed) {
+                item.additional_text_edits = import_edits(doc, &uri, &declared_in, typed);
+            }
+        }
+    }
+}
+
+/// `item`, tagged deprecated if it is, and flagged so for clients that
+/// only strike through on the older field.
+#[allow(deprecated)]
+fn deprecate(item: CompletionItem, deprecated: bool) -> CompletionItem {
+    match deprecated {
+        true => CompletionItem { tags: Some(vec![CompletionItemTag::DEPRECATED]), deprecated: Some(true), ..item },
+        false => item,
+    }
+}
+
+/// `item`, with `resolve` in its `data`.
+fn lazy(item: CompletionItem, resolve: Resolve) -> CompletionItem {
+    CompletionItem { data: serde_json::to_value(resolve).ok(), ..item }
+}
+
+/// What the cursor is sitting in, as far as completion cares.
+enum Context {
+    /// Inside the string argument of `env("…")` / `sys.env("…")`; `prefix`
+    /// covers what has been typed between the quote and the cursor.
+    EnvName { prefix: Span },
+    /// Inside the path string of a file-reading block such as `local.file`.
+    FilePath { prefix: Span },
+    /// After `component.label.` in an expression; `base` is the component
+    /// reference before the final dot.
+    Export { base: String, prefix: Span },
+    /// A bare identifier being typed in an expression.
+    Identifier { prefix: Span },
+    /// A string attribute of a rule of the relabel `component`; `sources`
+    /// are the discovery components whose targets the rule sees.
+    RuleString { component: String, attribute: String, sources: Vec<String>, prefix: Span },
+    /// Directly inside a `targets = […]` list, where a target map goes;
+    /// `prefix` is an identifier typed so far, if any.
+    TargetList { prefix: Span },
+    /// A key of a map in a `targets` list, quoted or not; `existing` are
+    /// the keys the map already has.
+    TargetKey { quoted: bool, existing: Vec<String>, prefix: Span },
+    /// A string argument that Grafana Cloud has endpoints for, such as
+    /// `url` in a `prometheus.remote_write` endpoint.
+    CloudEndpoint { presets: Vec<Preset>, prefix: Span },
+    /// An element of a list of receivers such as `forward_to`; `ty` is the
+    /// element type (`LogsReceiver`) when the schema knows it. `prefix` is
+    /// the reference typed so far.
+    Wiring { ty: Option<String>, prefix: Span },
+    /// A name being typed at the start of a line outside any block, where
+    /// a new component goes.
+    TopLevel { prefix: Span },
+    /// A name being typed at the start of a line in a block body, where an
+    /// argument or a nested block goes. `scope` is that of the cursor.
+    Statement { scope: Scope, prefix: Span },
+}
+
+pub fn complete(
+    doc: &Document,
+    uri: &Url,
+    pos: Position,
+    workspace: &Workspace,
+    catalog: Option<&Catalog>,
+    options: Options,
+    target: Option<TargetRelease>,
+) -> Option<CompletionResponse> {
+    let offset = doc.offset(pos);
+    let snippets = options.snippets;
+    let items = match context_at(&doc.text, offset)? {
+        Context::EnvName { prefix } => env_names(doc, prefix, workspace),
+        Context::FilePath { prefix } => file_paths(doc, uri, prefix, workspace),
+        Context::Export { base, prefix } if base == "argument" || base.starts_with("argument.") => {
+            module_arguments(doc, &base, prefix, &scope_at(&doc.text, offset))
+        }
+        Context::Export { base, prefix } if stdlib::is_root(&base) => stdlib_members(doc, &base, prefix, snippets),
+        Context::Export { base, prefix } => exports(doc, uri, &base, prefix, workspace),
+        Context::Identifier { prefix } => {
+            let mut items = in_scope_symbols(doc, prefix, &scope_at(&doc.text, offset));
+            items.extend(stdlib_members(doc, "", prefix, snippets));
+            items
+        }
+        Context::TargetList { prefix } => {
+            let mut items = vec![target_template(doc, prefix, snippets)];
+            if prefix.start < prefix.end {
+                items.extend(in_scope_symbols(doc, prefix, &scope_at(&doc.text, offset)));
+                items.extend(stdlib_members(doc, "", prefix, snippets));
+            }
+            items
+        }
+        Context::TargetKey { quoted, existing, prefix } => target_keys(doc, quoted, &existing, prefix),
+        Context::RuleString { attribute, prefix, .. } if attribute == "action" => actions(doc, prefix),
+        Context::RuleString { component, attribute, sources, prefix } => {
+            rule_strings(doc, &component, &attribute, &sources, prefix, catalog)
+        }
+        Context::CloudEndpoint { presets, prefix } if options.cloud_endpoints => endpoints(doc, presets, prefix),
+        Context::CloudEndpoint { .. } => Vec::new(),
+        Context::Wiring { prefix, .. } if is_component_prefix(&doc.text[prefix.start..prefix.end], workspace) => {
+            // `loki.write.default.` lists that component's exports, as
+            // anywhere else.
+            let (base, partial) = doc.text[prefix.start..prefix.end].rsplit_once('.')?;
+            exports(doc, uri, base, Span::new(prefix.end - partial.len(), prefix.end), workspace)
+        }
+        Context::Wiring { ty, prefix } => wiring_targets(doc, uri, ty.as_deref(), prefix, workspace),
+        Context::TopLevel { prefix } => {
+            let mut items = pipelines(doc, prefix, snippets);
+            items.extend(component_names(doc, uri, prefix, workspace, options, target));
+            items
+        }
+        Context::Statement { scope, prefix } if scope.holds_components() => {
+            component_names(doc, uri, prefix, workspace, options, target)
+        }
+        Context::Statement { scope, prefix } => body_items(doc, &scope, prefix, snippets),
+    };
+    Some(CompletionResponse::Array(items))
+}
+
+fn context_at(text: &str, offset: usize) -> Option<Context> {
+    if in_raw_string_or_comment(text, offset) {
+        return None;
+    }
+    let Some(quote) = open_string_start(text, offset) else {
+        return target_context(text, offset)
+            .or_else(|| wiring_context(text, offset))
+            .or_else(|| expression_context(text, offset))
+            .or_else(|| statement_context(text, offset));
+    };
+    let prefix = Span::new(quote + 1, offset);
+
+    if let Some(before) = text[..quote].trim_end().strip_suffix('(') {
+        let before = before.trim_end();
+        if matches!(&before[chain_start(before, before.len())..], "env" | "sys.env") {
+            return Some(Context::EnvName { prefix });
+        }
+    }
+
+    let scope = scope_at(text, quote);
+    if let Some(existing) = scope.target_map.clone().filter(|_| is_map_key(text, quote)) {
+        return Some(Context::TargetKey { quoted: true, existing, prefix });
+    }
+    let block = scope.blocks.last()?;
+    let attribute = scope.attribute.as_deref()?;
+    let rule_string = matches!(attribute, "source_labels" | "regex" | "target_label" | "action");
+    if let (Some(sources), true) = (&scope.relabel_sources, rule_string) {
+        let component = scope.blocks[scope.blocks.len() - 2].clone();
+        let (attribute, sources) = (attribute.to_string(), sources.clone());
+        return Some(Context::RuleString { component, attribute, sources, prefix });
+    }
+    let presets = cloud_endpoints::for_argument(&scope.blocks, attribute);
+    if !presets.is_empty() {
+        return Some(Context::CloudEndpoint { presets, prefix });
+    }
+    // `path_targets` maps take paths as values; their keys are labels.
+    let path = PATH_ATTRIBUTES.iter().any(|&(b, a)| b == block && a == attribute);
+    (path && !is_map_key(text, quote)).then_some(Context::FilePath { prefix })
+}
+
+fn expression_context(text: &str, offset: usize) -> Option<Context> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    if text[line_start..offset].contains("//") {
+        return None;
+    }
+    let chain_start = chain_start(text, offset);
+    let chain = &text[chain_start..offset];
+    // Only the right-hand side of `=` (or inside a list/call) is an
+    // expression; a dotted name at the start of a line is a block header.
+    let before = text[line_start..chain_start].trim_end();
+    let Some((base, partial)) = chain.rsplit_once('.') else {
+        let after_operator = before.ends_with(|c: char| "=([{,+-*/%!<>&|:".contains(c));
+        return (!chain.is_empty() && after_operator).then(|| Context::Identifier {
+            prefix: Span::new(chain_start, offset),
+        });
+    };
+    if before.is_empty() || base.is_empty() {
+        return None;
+    }
+    Some(Context::Export {
+        base: base.to_string(),
+        prefix: Span::new(offset - partial.len(), offset),
+    })
+}
+
+/// Where the dotted name ending at `offset` starts.
+fn chain_start(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+fn statement_context(text: &str, offset: usize) -> Option<Context> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let typed = text[line_start..offset].trim_start();
+    if !typed.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        return None;
+    }
+    let prefix = Span::new(offset - typed.len(), offset);
+    let scope = scope_at(text, offset);
+    match scope.blocks.is_empty() {
+        true => scope.attribute.is_none().then_some(Context::TopLevel { prefix }),
+        false => scope.in_body.then_some(Context::Statement { scope, prefix }),
+    }
+}
+
+/// In a `targets` list: where a target map goes, or at a key of one.
+/// Dotted names are references to other components' targets and are left
+/// to the expression context.
+fn target_context(text: &str, offset: usize) -> Option<Context> {
+    let start = chain_start(text, offset);
+    if text[start..offset].contains('.') {
+        return None;
+    }
+    let prefix = Span::new(start, offset);
+    let scope = scope_at(text, offset);
+    if let Some(existing) = scope.target_map.filter(|_| is_map_key(text, start)) {
+        return Some(Context::TargetKey { quoted: false, existing, prefix });
+    }
+    let before = text[..start].trim_end();
+    let element_start = before.ends_with('[') || before.ends_with(',');
+    (scope.in_list && scope.attribute.as_deref() == Some("targets") && element_start)
+        .then_some(Context::TargetList { prefix })
+}
+
+/// Whether what starts at `start` is where a key of an object goes.
+fn is_map_key(text: &str, start: usize) -> bool {
+    let before = text[..start].trim_end();
+    before.ends_with('{') || before.ends_with(',')
+}
+
+/// Directly inside the list assigned to `forward_to`, or to any argument
+/// the schema types as a list of some component export type.
+fn wiring_context(text: &str, offset: usize) -> Option<Context> {
+    let chain_start = chain_start(text, offset);
+    let scope = scope_at(text, offset);
+    let attribute = scope.attribute.as_deref()?;
+    if !scope.in_list {
+        return None;
+    }
+    let ty = list_element_type(&scope.blocks, attribute);
+    if ty.is_none() && attribute != "forward_to" {
+        return None;
+    }
+    Some(Context::Wiring { ty, prefix: Span::new(chain_start, offset) })
+}
+
+/// `T` for an attribute the schema types `list(T)`, where `T` is the type
+/// some component exports.
+fn list_element_type(blocks: &[String], attribute: &str) -> Option<String> {
+    let schema = Schema::builtin();
+    let argument = schema.body_in(blocks)?.arguments.get(attribute)?;
+    let ty = argument.ty.strip_prefix("list(")?.strip_suffix(')')?;
+    let exported = schema.components.values().any(|c| c.exports.values().any(|e| e.ty == ty));
+    exported.then(|| ty.to_string())
+}
+
+/// Whether `typed` is `component.label.` plus a partial export, for a
+/// component in the workspace.
+fn is_component_prefix(typed: &str, workspace: &Workspace) -> bool {
+    typed.rsplit_once('.').is_some_and(|(base, _)| workspace.components().any(|(_, c)| c.id() == base))
+}
+
+/// Every export of the workspace's components that can go in the list:
+/// those of type `ty`, or any receiver when the type is not known.
+/// Components in the same file sort first.
+fn wiring_targets(
+    doc: &Document,
+    uri: &Url,
+    ty: Option<&str>,
+    prefix: Span,
+    workspace: &Workspace,
+) -> Vec<CompletionItem> {
+    let schema = Schema::builtin();
+    let range = doc.range(prefix);
+    let mut targets = BTreeMap::new();
+    for (file, component) in workspace.components() {
+        let Some(exports) = schema.component(&component.name).map(|c| &c.exports) else { continue };
+        for (export, e) in exports {
+            let fits = match ty {
+                Some(ty) => e.ty == ty,
+                None => e.ty.ends_with("Receiver"),
+            };
+            if fits {
+                let reference = format!("{}.{export}", component.id());
+                let local = file == uri;
+                let entry = targets.entry(reference).or_insert((local, e.ty.as_str(), file));
+                entry.0 |= local;
+            }
+        }
+    }
+    targets
+        .into_iter()
+        .map(|(reference, (local, ty, file))| CompletionItem {
+            sort_text: Some(format!("{}{reference}", if local { 0 } else { 1 })),
+            documentation: (!local)
+                .then(|| Documentation::String(format!("Defined in {}", workspace.display_path(file)))),
+            ..symbol(&reference, CompletionItemKind::REFERENCE, ty, range)
+        })
+        .collect()
+}
+
+/// The pipeline snippets, expanded as snippets when the client supports
+/// them and as their default text otherwise.
+fn pipelines(doc: &Document, prefix: Span, snippets: bool) -> Vec<CompletionItem> {
+    let range = doc.range(prefix);
+    PIPELINES
+        .iter()
+        .map(|snippet| {
+            let (new_text, format) = match snippets {
+                true => (snippet.body.to_string(), InsertTextFormat::SNIPPET),
+                false => (snippets::plain(snippet.body), InsertTextFormat::PLAIN_TEXT),
+            };
+            CompletionItem {
+                label: snippet.label.to_string(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some(snippet.detail.to_string()),
+                // Typing any of the component names finds the pipeline.
+                filter_text: Some(format!("{} {}", snippet.label, snippet.detail)),
+                insert_text_format: Some(format),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Components and modules to start a block with. Those the workspace
+/// already uses come first, most used first, since edits mostly extend an
+/// existing pipeline; deprecated ones come after those still current, and
+/// those the `target` release does not have come last and say why.
+/// Modules of files not imported yet are offered under the namespace an
+/// import would give them, and bring the import along.
+fn component_names(
+    doc: &Document,
+    uri: &Url,
+    prefix: Span,
+    workspace: &Workspace,
+    options: Options,
+    target: Option<TargetRelease>,
+) -> Vec<CompletionItem> {
+    let range = doc.range(prefix);
+    let mut uses: BTreeMap<&str, usize> = BTreeMap::new();
+    for (_, component) in workspace.components() {
+        *uses.entry(component.name.as_str()).or_default() += 1;
+    }
+    let schema = Schema::builtin();
+    let builtin = schema.components.keys().cloned();
+    let names: BTreeSet<String> = workspace.custom_components(uri).into_iter().chain(builtin).collect();
+
+    let mut items: Vec<CompletionItem> = names
+        .iter()
+        .map(|name| {
+            let name = name.clone();
+            let used = uses.get(name.as_str()).copied().unwrap_or(0);
+            let missing = target.and_then(|t| Some((t.releases.component(&name).missing_from(t.version)?, t.version)));
+            let deprecated = schema.component(&name).is_some_and(|c| c.deprecated.is_some())
+                || target.is_some_and(|t| t.releases.component(&name).deprecated.is_some_and(|v| t.version >= v));
+            let (detail, sort_text) = match (&missing, used) {
+                (Some((why, version)), _) => (Some(format!("{why}; targeting {version}")), format!("3{name}")),
+                (None, 0) if deprecated => (None, format!("2{name}")),
+                (None, 0) => (None, format!("1{name}")),
+                (None, n) => {
+                    let detail = format!("used {n} time{} in the workspace", if n == 1 { "" } else { "s" });
+                    let sort_text = match deprecated {
+                        true => format!("2{name}"),
+                        false => format!("0{:06}{name}", 999_999 - n.min(999_999)),
+                    };
+                    (Some(detail), sort_text)
+                }
+            };
+            let item = CompletionItem {
+                detail,
+                sort_text: Some(sort_text),
+                ..symbol(&name, CompletionItemKind::MODULE, "", range)
+            };
+            lazy(deprecate(item, deprecated), Resolve::Component { name })
+        })
+        .collect();
+
+    let mut importable = BTreeMap::new();
+    for (file, declare) in workspace.declares().filter(|(file, _)| *file != uri) {
+        let Some((namespace, _)) = lint::import_of(doc, uri, file) else { continue };
+        let name = format!("{namespace}.{}", declare.name);
+        if !names.contains(&name) {
+            importable.entry(name).or_insert(file);
+        }
+    }
+    let imports = importable.into_iter().map(|(name, file)| {
+        let item = CompletionItem {
+            detail: Some(format!("imports {}", workspace.display_path(file))),
+            sort_text: Some(format!("1{name}")),
+            ..symbol(&name, CompletionItemKind::MODULE, "", range)
+        };
+        match options.resolve_edits {
+            true => lazy(item, Resolve::Import { uri: uri.clone(), declared_in: file.clone() }),
+            false => CompletionItem { additional_text_edits: import_edits(doc, uri, file, range), ..item },
+        }
+    });
+    items.extend(imports);
+    items
+}
+
+/// The edit adding an `import.file` for `declared_in` to `doc`, unless it
+/// has one. It goes before the first statement, or at the end when that
+/// is what is being `typed`, as the edits of an item may not touch.
+fn import_edits(doc: &Document, uri: &Url, declared_in: &Url, typed: Range) -> Option<Vec<TextEdit>> {
+    let (_, (at, import)) = lint::import_of(doc, uri, declared_in).and_then(|(ns, import)| Some((ns, import?)))?;
+    let edit = match at >= doc.offset(typed.start) {
+        true => {
+            let end = doc.position(doc.text.len());
+            let gap = if doc.text.ends_with('\n') { "\n" } else { "\n\n" };
+            TextEdit { range: Range::new(end, end), new_text: format!("{gap}{}\n", import.trim_end()) }
+        }
+        false => TextEdit { range: doc.range(Span::new(at, at)), new_text: import },
+    };
+    Some(vec![edit])
+}
+
+/// The arguments a block body has not set yet, required ones first and
+/// deprecated ones last, then the blocks it can contain, from the schema at
+/// any depth. Blocks expand to an empty body on the lines below.
+fn body_items(doc: &Document, scope: &Scope, prefix: Span, snippets: bool) -> Vec<CompletionItem> {
+    let Some(body) = Schema::builtin().body_in(&scope.blocks) else { return Vec::new() };
+    let range = doc.range(prefix);
+    let line_start = doc.text[..prefix.start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &doc.text[line_start..prefix.start];
+    let unset = body.arguments.iter().filter(|(name, _)| !scope.assigned.contains(name));
+    let arguments = unset.map(|(name, arg)| {
+        let rank = match (arg.required, &arg.deprecated) {
+            (_, Some(_)) => 2,
+            (true, None) => 0,
+            (false, None) => 1,
+        };
+        let item = CompletionItem {
+            sort_text: Some(format!("{rank}{name}")),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text: format!("{name} = ") })),
+            ..symbol(name, CompletionItemKind::FIELD, &arg.ty, range)
+        };
+        let item = deprecate(item, arg.deprecated.is_some());
+        lazy(item, Resolve::Argument { blocks: scope.blocks.clone(), name: name.clone() })
+    });
+    let blocks = body.blocks.iter().map(|(name, block)| {
+        let (new_text, format) = match snippets {
+            true => (format!("{name} {{\n{indent}  $0\n{indent}}}"), InsertTextFormat::SNIPPET),
+            false => (format!("{name} {{\n{indent}}}"), InsertTextFormat::PLAIN_TEXT),
+        };
+        let item = CompletionItem {
+            sort_text: Some(format!("{}{name}", if block.deprecated.is_some() { 4 } else { 3 })),
+            insert_text_format: Some(format),
+            // The indentation is already in the text.
+            insert_text_mode: Some(InsertTextMode::AS_IS),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+            ..symbol(name, CompletionItemKind::STRUCT, "block", range)
+        };
+        let item = deprecate(item, block.deprecated.is_some());
+        lazy(item, Resolve::Block { blocks: scope.blocks.clone(), name: name.clone() })
+    });
+    arguments.chain(blocks).collect()
+}
+
+/// Where the cursor sits structurally, recovered from the tokens before it
+/// so that it works on half-typed, unparseable text.
+#[derive(Debug, Default)]
+struct Scope {
+    /// Names of the enclosing blocks, outermost first.
+    blocks: Vec<String>,
+    /// Attribute being assigned in the innermost block, if any.
+    attribute: Option<String>,
+    /// Arguments of the innermost enclosing `declare`; `None` outside one.
+    arguments: Option<Vec<String>>,
+    /// Loop variables of enclosing `foreach` blocks, inside their `template`.
+    loop_vars: Vec<String>,
+    /// Inside a `rule` of a `*.relabel` component: the discovery components
+    /// feeding its `targets`.
+    relabel_sources: Option<Vec<String>>,
+    /// Directly inside a `[…]` list that is the value of `attribute`.
+    in_list: bool,
+    /// Directly inside a map in the `targets` list: the keys it has,
+    /// before the cursor or after it.
+    target_map: Option<Vec<String>>,
+    /// Directly in the body of the innermost block, not in a value.
+    in_body: bool,
+    /// Attributes the innermost block's body assigns, before the cursor
+    /// or after it.
+    assigned: Vec<String>,
+}
+
+impl Scope {
+    /// Module and loop bodies hold components rather than arguments.
+    fn holds_components(&self) -> bool {
+        matches!(self.blocks.last().map(String::as_str), Some("declare" | "template"))
+    }
+}
+
+fn scope_at(text: &str, offset: usize) -> Scope {
+    struct Frame {
+        /// `Some` for block bodies, `None` for brackets, parens and objects.
+        block: Option<String>,
+        /// Opened by `[`.
+        list: bool,
+        /// Opened by `{` in a value: an object.
+        object: bool,
+        attribute: Option<String>,
+        /// Offset of the opening brace.
+        open: usize,
+    }
+
+    let tokens: Vec<_> = tokenize(&text[..offset])
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect();
+    let slice = |i: usize| &text[tokens[i].span.start..tokens[i].span.end];
+    let mut frames = vec![Frame { block: Some(String::new()), list: false, object: false, attribute: None, open: 0 }];
+
+    for i in 0..tokens.len() {
+        let next = tokens.get(i + 1).map(|t| t.kind);
+        let frame = frames.last_mut().expect("root frame is never popped");
+        match tokens[i].kind {
+            TokenKind::Ident if next == Some(TokenKind::Assign) => {
+                frame.attribute = Some(slice(i).to_string());
+            }
+            TokenKind::LBrace => {
+                let prev = i.checked_sub(1).map(|p| tokens[p].kind);
+                if frame.block.is_some() && prev != Some(TokenKind::Assign) {
+                    // Block header: `name.parts "label" {`.
+                    let mut j = i;
+                    if prev == Some(TokenKind::String) {
+                        j -= 1;
+                    }
+                    let mut parts = Vec::new();
+                    while j > 0 && tokens[j - 1].kind == TokenKind::Ident {
+                        parts.push(slice(j - 1));
+                        if j < 2 || tokens[j - 2].kind != TokenKind::Dot {
+                            break;
+                        }
+                        j -= 2;
+                    }
+                    parts.reverse();
+                    frame.attribute = None;
+                    let open = tokens[i].span.start;
+                    let block = Some(parts.join("."));
+                    frames.push(Frame { block, list: false, object: false, attribute: None, open });
+                } else {
+                    let open = tokens[i].span.start;
+                    frames.push(Frame { block: None, list: false, object: true, attribute: None, open });
+                }
+            }
+            TokenKind::LBracket | TokenKind::LParen => {
+                let list = tokens[i].kind == TokenKind::LBracket;
+                frames.push(Frame { block: None, list, object: false, attribute: None, open: tokens[i].span.start });
+            }
+            TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen if frames.len() > 1 => {
+                frames.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let mut scope = Scope::default();
+    let blocks: Vec<&Frame> = frames.iter().skip(1).filter(|f| f.block.is_some()).collect();
+    for (i, frame) in blocks.iter().enumerate() {
+        let name = frame.block.as_deref().unwrap_or_default();
+        scope.blocks.push(name.to_string());
+        // The rest of the body may follow the cursor, so read it from the
+        // whole text rather than the tokens seen so far.
+        let children = || body_tokens(text, frame.open);
+        match name {
+            "declare" => scope.arguments = Some(labels(text, &children(), "argument")),
+            "foreach" if blocks.get(i + 1).is_some_and(|f| f.block.as_deref() == Some("template")) => {
+                scope.loop_vars.extend(string_attribute(text, &children(), "var"));
+            }
+            _ if name.ends_with(".relabel") && i + 2 == blocks.len() && blocks[i + 1].block.as_deref() == Some("rule") => {
+                scope.relabel_sources = Some(target_sources(text, frame.open, 0));
+            }
+            _ => {}
+        }
+    }
+    scope.attribute = frames.iter().rev().find(|f| f.block.is_some()).and_then(|f| f.attribute.clone());
+    scope.in_list = match frames.as_slice() {
+        [.., parent, last] => last.list && parent.block.is_some(),
+        _ => false,
+    };
+    if let [.., block, list, map] = frames.as_slice() {
+        if map.object && list.list && block.attribute.as_deref() == Some("targets") {
+            let tokens = body_tokens(text, map.open);
+            let keys = tokens
+                .windows(2)
+                .filter(|w| matches!(w[0].kind, TokenKind::Ident | TokenKind::String) && w[1].kind == TokenKind::Assign)
+                .map(|w| unquote(&text[w[0].span.start..w[0].span.end]));
+            scope.target_map = Some(keys.collect());
+        }
+    }
+    if let [_, .., last] = frames.as_slice() {
+        scope.in_body = last.block.is_some();
+        let tokens = body_tokens(text, last.open);
+        scope.assigned = tokens
+            .windows(2)
+            .filter(|w| w[0].kind == TokenKind::Ident && w[1].kind == TokenKind::Assign)
+            .map(|w| text[w[0].span.start..w[0].span.end].to_string())
+            .collect();
+    }
+    scope
+}
+
+/// Tokens directly inside the body opened by the brace at `open`: nested
+/// bodies contribute only their brackets.
+fn body_tokens(text: &str, open: usize) -> Vec<Token> {
+    let mut out = Vec::new();
+    let mut depth = 0usize;
+    let tokens = tokenize(text).into_iter().filter(|t| t.kind != TokenKind::Comment);
+    for token in tokens.skip_while(|t| t.span.start != open).skip(1) {
+        match token.kind {
+            TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen if depth == 0 => break,
+            TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen => depth -= 1,
+            TokenKind::LBrace | TokenKind::LBracket | TokenKind::LParen => {
+                if depth == 0 {
+                    out.push(token);
+                }
+                depth += 1;
+                continue;
+            }
+            TokenKind::Eof => break,
+            _ => {}
+        }
+        if depth == 0 {
+            out.push(token);
+        }
+    }
+    out
+}
+
+/// Discovery components whose targets reach the component whose body opens
+/// at `open`, following `discovery.relabel` chains in the same file.
+fn target_sources(text: &str, open: usize, hops: usize) -> Vec<String> {
+    let tokens: Vec<Token> = tokenize(text).into_iter().filter(|t| t.kind != TokenKind::Comment).collect();
+    let slice = |t: &Token| &text[t.span.start..t.span.end];
+    let Some(start) = tokens.iter().position(|t| t.span.start == open) else { return Vec::new() };
+
+    // Reference chains in the value of the body's `targets` attribute.
+    let mut chains: Vec<Vec<&str>> = Vec::new();
+    let (mut depth, mut in_targets) = (0usize, false);
+    for (i, token) in tokens.iter().enumerate().skip(start + 1) {
+        let next = tokens.get(i + 1).map(|t| t.kind);
+        match token.kind {
+            TokenKind::LBrace | TokenKind::LBracket | TokenKind::LParen => depth += 1,
+            TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen if depth == 0 => break,
+            TokenKind::RBrace | TokenKind::RBracket | TokenKind::RParen => depth -= 1,
+            TokenKind::Ident if depth == 0 && next == Some(TokenKind::Assign) => in_targets = slice(token) == "targets",
+            TokenKind::Ident if in_targets => {
+                let continues = i > 0 && tokens[i - 1].kind == TokenKind::Dot;
+                match chains.last_mut() {
+                    Some(chain) if continues => chain.push(slice(token)),
+                    _ => chains.push(vec![slice(token)]),
+                }
+            }
+            TokenKind::Eof => break,
+            _ => {}
+        }
+    }
+
+    let mut sources = Vec::new();
+    for chain in chains {
+        match chain.as_slice() {
+            ["discovery", "relabel", label, ..] if hops < 8 => {
+                let header = tokens.windows(5).find(|w| {
+                    slice(&w[0]) == "discovery"
+                        && slice(&w[2]) == "relabel"
+                        && w[3].kind == TokenKind::String
+                        && unquote(slice(&w[3])) == *label
+                        && w[4].kind == TokenKind::LBrace
+                });
+                if let Some(w) = header {
+                    sources.extend(target_sources(text, w[4].span.start, hops + 1));
+                }
+            }
+            ["discovery", name, ..] => sources.push(format!("discovery.{name}")),
+            _ => {}
+        }
+    }
+    sources
+}
+
+/// Labels of `block "label" {` headers among `tokens`.
+fn labels(text: &str, tokens: &[Token], block: &str) -> Vec<String> {
+    tokens
+        .windows(3)
+        .filter(|w| {
+            w[0].kind == TokenKind::Ident
+                && &text[w[0].span.start..w[0].span.end] == block
+                && w[1].kind == TokenKind::String
+                && w[2].kind == TokenKind::LBrace
+        })
+        .map(|w| unquote(&text[w[1].span.start..w[1].span.end]))
+        .collect()
+}
+
+/// Value of a `name = "string"` attribute among `tokens`.
+fn string_attribute(text: &str, tokens: &[Token], name: &str) -> Option<String> {
+    tokens.windows(3).find_map(|w| {
+        let matches = w[0].kind == TokenKind::Ident
+            && &text[w[0].span.start..w[0].span.end] == name
+            && w[1].kind == TokenKind::Assign
+            && w[2].kind == TokenKind::String;
+        matches.then(|| unquote(&text[w[2].span.start..w[2].span.end]))
+    })
+}
+
+/// Raw strings and block comments can span lines, which the line-based
+/// scans below cannot see, so check the tokens first.
+fn in_raw_string_or_comment(text: &str, offset: usize) -> bool {
+    tokenize(text).iter().any(|t| {
+        let span = t.span;
+        match t.kind {
+            // A `//` comment runs up to the newline, so its end counts too.
+            TokenKind::Comment if text[span.start..].starts_with("//") => span.start < offset && offset <= span.end,
+            TokenKind::Comment | TokenKind::RawString => span.start < offset && offset < span.end,
+            TokenKind::Unterminated => span.start < offset && !text[span.start..].starts_with('"'),
+            _ => false,
+        }
+    })
+}
+
+/// If `offset` lies inside a double-quoted string on its line, returns the
+/// offset of the opening quote.
+fn open_string_start(text: &str, offset: usize) -> Option<usize> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let mut open = None;
+    let mut escaped = false;
+    for (i, ch) in text[line_start..offset].char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if open.is_some() => escaped = true,
+            '"' => open = if open.is_some() { None } else { Some(line_start + i) },
+            '/' if open.is_none() && text[line_start + i..].starts_with("//") => return None,
+            _ => {}
+        }
+    }
+    open
+}
+
+fn env_names(doc: &Document, prefix: Span, workspace: &Workspace) -> Vec<CompletionItem> {
+    // Names already used in the workspace win over the server's own
+    // environment: they are what the deployment is expected to provide.
+    let mut names: BTreeMap<String, bool> = std::env::vars_os()
+        .filter_map(|(k, _)| k.into_string().ok())
+        .filter(|k| is_env_name(k))
+        .map(|k| (k, false))
+        .collect();
+    for name in workspace.env_names() {
+        names.insert(name.to_string(), true);
+    }
+
+    let range = doc.range(prefix);
+    names
+        .into_iter()
+        .map(|(name, in_workspace)| CompletionItem {
+            label: name.clone(),
+            kind: Some(CompletionItemKind::VARIABLE),
+            detail: Some(
+                if in_workspace { "used in workspace" } else { "environment variable" }.to_string(),
+            ),
+            sort_text: Some(format!("{}{name}", if in_workspace { 0 } else { 1 })),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text: name })),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn is_env_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Lists directory entries matching the partially typed path. Relative
+/// paths resolve against the config file's directory; no path may lead
+/// outside the workspace root.
+fn file_paths(doc: &Document, uri: &Url, prefix: Span, workspace: &Workspace) -> Vec<CompletionItem> {
+    let typed = &doc.text[prefix.start..prefix.end];
+    let (dir_part, partial) = match typed.rsplit_once('/') {
+        Some((dir, partial)) => (format!("{dir}/"), partial),
+        None => (String::new(), typed),
+    };
+
+    // Joining an absolute path replaces the base, so it is checked the same way.
+    let base = uri
+        .to_file_path()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .or_else(|| workspace.root.clone());
+    let dir = match base {
+        Some(base) => base.join(&dir_part),
+        None if Path::new(&dir_part).is_absolute() => PathBuf::from(&dir_part),
+        None => return Vec::new(),
+    };
+    if let Some(root) = &workspace.root {
+        let inside = match (dir.canonicalize(), root.canonicalize()) {
+            (Ok(dir), Ok(root)) => dir.starts_with(root),
+            _ => false,
+        };
+        if !inside {
+            return Vec::new();
+        }
+    }
+
+    let Ok(entries) = fs::read_dir(&dir) else { return Vec::new() };
+    let range = doc.range(Span::new(prefix.end - partial.len(), prefix.end));
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(partial) || (name.starts_with('.') && !partial.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.file_type().ok()?.is_dir();
+            let new_text = if is_dir { format!("{name}/") } else { name.clone() };
+            Some(CompletionItem {
+                label: new_text.clone(),
+                kind: Some(if is_dir { CompletionItemKind::FOLDER } else { CompletionItemKind::FILE }),
+                sort_text: Some(format!("{}{name}", if is_dir { 0 } else { 1 })),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Lists the exports of the component referenced by `base`, e.g.
+/// `prometheus.remote_write.default`.
+fn exports(doc: &Document, uri: &Url, base: &str, prefix: Span, workspace: &Workspace) -> Vec<CompletionItem> {
+    let name = workspace
+        .components()
+        .find(|(_, c)| c.id() == base)
+        .map(|(_, c)| c.name.clone())
+        .or_else(|| base.rsplit_once('.').map(|(name, _)| name.to_string()));
+    let Some(name) = name else { return Vec::new() };
+
+    let range = doc.range(prefix);
+    let item = |label: &str, detail: &str, doc: Option<&str>| CompletionItem {
+        label: label.to_string(),
+        kind: Some(CompletionItemKind::FIELD),
+        detail: Some(detail.to_string()),
+        documentation: doc.filter(|d| !d.is_empty()).map(|d| Documentation::String(d.to_string())),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text: label.to_string() })),
+        ..Default::default()
+    };
+
+    if let Some(component) = Schema::builtin().component(&name) {
+        return component
+            .exports
+            .iter()
+            .map(|(export, e)| item(export, &e.ty, Some(&e.doc)))
+            .collect();
+    }
+    workspace
+        .declare_from(uri, &name)
+        .map(|(_, d)| d.exports.iter().map(|e| item(e, "module export", d.doc.as_deref())).collect())
+        .unwrap_or_default()
+}
+
+/// `argument.` offers the enclosing module's arguments, `argument.name.`
+/// offers `value`.
+fn module_arguments(doc: &Document, base: &str, prefix: Span, scope: &Scope) -> Vec<CompletionItem> {
+    let Some(arguments) = &scope.arguments else { return Vec::new() };
+    let range = doc.range(prefix);
+    let item = |label: &str, kind, detail: &str| symbol(label, kind, detail, range);
+    match base.strip_prefix("argument.") {
+        None => arguments.iter().map(|a| item(a, CompletionItemKind::VARIABLE, "module argument")).collect(),
+        Some(name) if arguments.iter().any(|a| a == name) => {
+            vec![item("value", CompletionItemKind::FIELD, "value passed for the argument")]
+        }
+        Some(_) => Vec::new(),
+    }
+}
+
+/// Names that are in scope because of the enclosing blocks: loop variables
+/// and, inside a `declare`, `argument`.
+fn in_scope_symbols(doc: &Document, prefix: Span, scope: &Scope) -> Vec<CompletionItem> {
+    let range = doc.range(prefix);
+    let item = |label: &str, kind, detail: &str| symbol(label, kind, detail, range);
+    let mut items: Vec<_> = scope
+        .loop_vars
+        .iter()
+        .map(|v| item(v, CompletionItemKind::VARIABLE, "foreach loop variable"))
+        .collect();
+    if scope.arguments.is_some() {
+        items.push(item("argument", CompletionItemKind::MODULE, "arguments of this module"));
+    }
+    items
+}
+
+/// The standard library under `namespace`, or at the top level for `""`.
+/// Functions insert their parentheses when the client takes snippets.
+fn stdlib_members(doc: &Document, namespace: &str, prefix: Span, snippets: bool) -> Vec<CompletionItem> {
+    let range = doc.range(prefix);
+    let (members, namespaces) = stdlib::under(namespace);
+    let namespaces = namespaces.into_iter().map(|name| symbol(name, CompletionItemKind::MODULE, "namespace", range));
+    let members = members.into_iter().map(|(name, member)| {
+        let documentation = Some(Documentation::String(member.doc.to_string()));
+        let Some(signature) = member.signature else {
+            return CompletionItem { documentation, ..symbol(name, CompletionItemKind::CONSTANT, "constant", range) };
+        };
+        let detail = format!("{}{signature}", member.name);
+        let mut item = CompletionItem { documentation, ..symbol(name, CompletionItemKind::FUNCTION, &detail, range) };
+        if snippets {
+            item.insert_text_format = Some(InsertTextFormat::SNIPPET);
+            item.text_edit = Some(CompletionTextEdit::Edit(TextEdit { range, new_text: format!("{name}($1)") }));
+        }
+        item
+    });
+    namespaces.chain(members).collect()
+}
+
+/// The values of a rule's `action`.
+fn actions(doc: &Document, prefix: Span) -> Vec<CompletionItem> {
+    let range = doc.range(prefix);
+    relabel::ACTIONS
+        .iter()
+        .map(|action| CompletionItem {
+            documentation: Some(Documentation::String(action.summary.to_string())),
+            ..symbol(action.name, CompletionItemKind::ENUM_MEMBER, "relabel action", range)
+        })
+        .collect()
+}
+
+/// Label names for `source_labels` and `target_label`: the well-known
+/// labels of what `component` relabels and, for `source_labels`, the ones
+/// discovery sets. For `regex`, which often matches `__name__`, metric
+/// names as well. Names from the metrics API are only there when one is
+/// configured.
+fn rule_strings(
+    doc: &Document,
+    component: &str,
+    attribute: &str,
+    sources: &[String],
+    prefix: Span,
+    catalog: Option<&Catalog>,
+) -> Vec<CompletionItem> {
+    let range = doc.range(prefix);
+    let mut items = if attribute == "target_label" { Vec::new() } else { meta_labels(sources, range) };
+    if attribute != "regex" {
+        items.extend(labels::relabeled_by(component).map(|label| CompletionItem {
+            documentation: Some(Documentation::String(label.doc.to_string())),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: label.name.split('<').next().unwrap_or(label.name).to_string(),
+            })),
+            ..symbol(label.name, CompletionItemKind::CONSTANT, "well-known label", range)
+        }));
+    }
+    let Some(catalog) = catalog else { return items };
+    let known: BTreeSet<String> = items.iter().map(|i| i.label.clone()).collect();
+    items.extend(
+        catalog
+            .labels
+            .iter()
+            .filter(|l| !known.contains(*l))
+            .map(|l| symbol(l, CompletionItemKind::CONSTANT, "label", range)),
+    );
+    if attribute == "regex" {
+        items.extend(catalog.metrics.iter().map(|(name, help)| CompletionItem {
+            documentation: (!help.is_empty()).then(|| Documentation::String(help.clone())),
+            ..symbol(name, CompletionItemKind::VALUE, "metric", range)
+        }));
+    }
+    items
+}
+
+/// A map for a static target, its address to fill in.
+fn target_template(doc: &Document, prefix: Span, snippets: bool) -> CompletionItem {
+    const BODY: &str = "{ \"__address__\" = \"${1:host:port}\"$0 }";
+    let (new_text, format) = match snippets {
+        true => (BODY.to_string(), InsertTextFormat::SNIPPET),
+        false => (snippets::plain(BODY), InsertTextFormat::PLAIN_TEXT),
+    };
+    let range = doc.range(prefix);
+    CompletionItem {
+        insert_text_format: Some(format),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+        ..symbol("{ \"__address__\" = … }", CompletionItemKind::SNIPPET, "target", range)
+    }
+}
+
+/// Well-known keys the target map does not have yet. Unquoted, a key is
+/// inserted quoted and followed by `=`; templated keys such as
+/// `__param_<name>` insert only the fixed part.
+fn target_keys(doc: &Document, quoted: bool, existing: &[String], prefix: Span) -> Vec<CompletionItem> {
+    let range = doc.range(prefix);
+    labels::WELL_KNOWN
+        .iter()
+        .filter(|label| label.on_targets && !existing.iter().any(|key| key == label.name))
+        .map(|label| {
+            let name = label.name.split('<').next().unwrap_or(label.name);
+            let new_text = if quoted { name.to_string() } else { format!("\"{name}\" = ") };
+            CompletionItem {
+                documentation: Some(Documentation::String(label.doc.to_string())),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+                ..symbol(label.name, CompletionItemKind::CONSTANT, "well-known label", range)
+            }
+        })
+        .collect()
+}
+
+/// Grafana Cloud endpoints, described by their product and region.
+fn endpoints(doc: &Document, presets: Vec<Preset>, prefix: Span) -> Vec<CompletionItem> {
+    let range = doc.range(prefix);
+    presets
+        .into_iter()
+        .map(|preset| {
+            let detail = format!("{} ({})", preset.product.name, preset.region);
+            CompletionItem {
+                // Typing the region finds the URL as well as typing the URL.
+                filter_text: Some(format!("{} {}", preset.url, preset.region)),
+                ..symbol(preset.url, CompletionItemKind::VALUE, &detail, range)
+            }
+        })
+        .collect()
+}
+
+/// `__meta_*` labels of the discovery components feeding a relabel rule.
+/// Templated labels such as `__meta_kubernetes_pod_label_<labelname>`
+/// insert only the fixed part.
+fn meta_labels(sources: &[String], range: Range) -> Vec<CompletionItem> {
+    let mut labels = BTreeMap::new();
+    for source in sources {
+        if let Some(component) = Schema::builtin().component(source) {
+            for (label, description) in &component.meta_labels {
+                labels.entry(label.as_str()).or_insert((source.as_str(), description.as_str()));
+            }
+        }
+    }
+    labels
+        .into_iter()
+        .map(|(label, (source, description))| CompletionItem {
+            documentation: Some(Documentation::String(description.to_string())),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: label.split('<').next().unwrap_or(label).to_string(),
+            })),
+            ..symbol(label, CompletionItemKind::CONSTANT, source, range)
+        })
+        .collect()
+}
+
+fn symbol(label: &str, kind: CompletionItemKind, detail: &str, range: Range) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        detail: Some(detail.to_string()),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text: label.to_string() })),
+        ..Default::default()
+    }
+}
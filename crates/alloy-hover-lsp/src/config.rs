@@ -0,0 +1,265 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Server settings, read from `initializationOptions` and refreshed from
+/// `workspace/didChangeConfiguration`. Unknown keys are ignored and missing
+/// ones fall back to defaults, so a partial object is always valid. A
+/// section that does not parse falls back to its defaults on its own.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    pub alloy: AlloyCli,
+    pub instance: Instance,
+    pub modules: Modules,
+    pub metrics_api: MetricsApi,
+    pub diagnostics: Diagnostics,
+    pub features: Features,
+    pub hover: HoverSettings,
+    pub memory: Memory,
+    pub grafana_cloud: GrafanaCloud,
+    /// Language for hover docs, e.g. `de`; defaults to the client's locale,
+    /// then `$LC_ALL`, `$LC_MESSAGES` and `$LANG`. Read at startup only.
+    pub locale: Option<String>,
+    /// What was wrong with the settings, and so ignored.
+    #[serde(skip)]
+    pub problems: Vec<String>,
+}
+
+/// How to reach the `alloy` binary for authoritative validation.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AlloyCli {
+    /// Explicit path to `alloy`; when unset the binary is looked up on PATH.
+    pub path: Option<PathBuf>,
+    /// Run `alloy fmt` and `alloy validate` whenever a file is saved. Off
+    /// by default, since it starts two processes per save.
+    pub validate_on_save: bool,
+    /// The release the configs have to load on, e.g. `"1.5"`. Components
+    /// and arguments added after it are reported, and listed last when
+    /// completing. Unset means the latest.
+    pub target_version: Option<String>,
+    /// A newer copy of the release database (`schema/releases.toml`) to use
+    /// instead of the bundled one.
+    pub releases_path: Option<PathBuf>,
+}
+
+/// A running Alloy instance the server may talk to. Everything that
+/// contacts it is opt-in.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Instance {
+    /// Base URL, e.g. `http://localhost:12345`. Hovers over components
+    /// show what it reports about them.
+    pub url: Option<String>,
+    /// Extra request headers (auth tokens and the like).
+    pub headers: BTreeMap<String, String>,
+    /// Post the buffer to `validate_path` on save.
+    pub live_validation: bool,
+    /// A code lens on each component with its health on the instance.
+    pub health_lens: bool,
+    /// On save, report the components the instance fails to run, such as
+    /// one that cannot be built, on their blocks.
+    pub runtime_diagnostics: bool,
+    /// Endpoint that evaluates a config sent as the request body without
+    /// applying it, answering non-2xx with `<file>:<line>:<col>: <msg>` lines.
+    pub validate_path: String,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            url: None,
+            headers: BTreeMap::new(),
+            live_validation: false,
+            health_lens: false,
+            runtime_diagnostics: false,
+            validate_path: "/-/validate".into(),
+        }
+    }
+}
+
+/// Remote modules (`import.git`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Modules {
+    /// Where checkouts, and the workspace index between runs, are kept;
+    /// defaults to the user cache directory.
+    pub cache_dir: Option<PathBuf>,
+    /// Clone repositories that are not cached yet when they are needed.
+    /// Off by default, since the repositories come from whatever config is
+    /// open.
+    pub fetch: bool,
+}
+
+/// A Prometheus-compatible HTTP API (Prometheus, Mimir) whose metric and
+/// label names are offered when completing relabel rules. Opt-in: nothing
+/// is queried until `url` is set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MetricsApi {
+    /// Base URL, e.g. `https://prometheus-prod-01.grafana.net/api/prom`.
+    pub url: Option<String>,
+    /// Basic auth, as used by Grafana Cloud and most Mimir gateways.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Extra request headers, e.g. `X-Scope-OrgID` or `Authorization`.
+    pub headers: BTreeMap<String, String>,
+}
+
+/// When our own lints run, and how loudly.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "RawDiagnostics")]
+pub struct Diagnostics {
+    /// How long to wait after the last edit before linting and publishing,
+    /// in milliseconds. Opening and saving a file lint right away; `0`
+    /// lints on every change.
+    pub debounce_ms: u64,
+    /// Every other key names a lint by its code in camelCase and overrides
+    /// its severity: `"unusedComponent": "off"`, `"plaintextSecret": "error"`.
+    pub rules: BTreeMap<String, Severity>,
+    /// Keys whose value is not a severity; they are left out of `rules`.
+    pub rejected: Vec<String>,
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self { debounce_ms: 300, rules: BTreeMap::new(), rejected: Vec::new() }
+    }
+}
+
+/// [`Diagnostics`] as written, before the rules are checked one by one.
+#[derive(Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct RawDiagnostics {
+    debounce_ms: u64,
+    #[serde(flatten)]
+    rules: BTreeMap<String, serde_json::Value>,
+}
+
+impl Default for RawDiagnostics {
+    fn default() -> Self {
+        Self { debounce_ms: Diagnostics::default().debounce_ms, rules: BTreeMap::new() }
+    }
+}
+
+impl From<RawDiagnostics> for Diagnostics {
+    fn from(raw: RawDiagnostics) -> Self {
+        let (mut rules, mut rejected) = (BTreeMap::new(), Vec::new());
+        for (rule, value) in raw.rules {
+            match Severity::deserialize(value) {
+                Ok(severity) => {
+                    rules.insert(rule, severity);
+                }
+                Err(_) => rejected.push(rule),
+            }
+        }
+        Self { debounce_ms: raw.debounce_ms, rules, rejected }
+    }
+}
+
+/// A lint's configured severity; `off` disables it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Hint,
+    #[serde(alias = "information")]
+    Info,
+    #[serde(alias = "warn")]
+    Warning,
+    Error,
+}
+
+/// Editor features that can be switched on and off. Clients that support
+/// dynamic registration drop or pick them up without a restart.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Features {
+    /// The "Preview pipeline graph" lens at the top of a config.
+    pub code_lens: bool,
+    /// Semantic highlighting, on top of the extension's tree-sitter grammar.
+    pub semantic_tokens: bool,
+    /// The block's name after the `}` closing a long block. Off by default.
+    pub closing_brace_hints: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self { code_lens: true, semantic_tokens: true, closing_brace_hints: false }
+    }
+}
+
+/// Bounds on what the server keeps around for huge workspaces.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Memory {
+    /// MiB the documents of files closed in the editor may hold. Beyond it
+    /// the least recently closed are dropped, keeping only their entries in
+    /// the workspace index; they are read again when reopened.
+    pub max_mb: usize,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self { max_mb: 256 }
+    }
+}
+
+/// Help with Grafana Cloud as the destination.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GrafanaCloud {
+    /// Complete endpoint URLs for each region, and name the product and
+    /// region of one on hover. Off by default.
+    pub presets: bool,
+}
+
+/// How much a hover shows.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HoverSettings {
+    pub style: HoverStyle,
+    /// Show what `env("NAME")` resolves to in the server's environment,
+    /// masked for names that look like secrets. Off by default.
+    pub env_values: bool,
+}
+
+/// `concise` hovers are a signature and a line of description; `full`
+/// ones, the default, add argument tables, examples and reference links.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HoverStyle {
+    Concise,
+    #[default]
+    Full,
+}
+
+impl Config {
+    /// Parses a settings object, tolerating `null` and the settings being
+    /// nested under a top-level `"alloy-hover"` key. Sections that do not
+    /// parse are left at their defaults and listed in `problems`.
+    pub fn from_value(value: Option<serde_json::Value>) -> Self {
+        let Some(mut value) = value else { return Self::default() };
+        if let Some(inner) = value.get_mut("alloy-hover") {
+            value = inner.take();
+        }
+        let mut problems = Vec::new();
+        if let serde_json::Value::Object(sections) = &mut value {
+            sections.retain(|key, section| {
+                let single = serde_json::json!({ key.as_str(): section.clone() });
+                match serde_json::from_value::<Config>(single) {
+                    Ok(_) => true,
+                    Err(err) => {
+                        problems.push(format!("`{key}` settings ignored: {err}"));
+                        false
+                    }
+                }
+            });
+        }
+        let mut config: Config = serde_json::from_value(value).unwrap_or_default();
+        let rejected = config.diagnostics.rejected.iter().map(|rule| format!("`diagnostics.{rule}` is not a severity"));
+        problems.extend(rejected);
+        config.problems = problems;
+        config
+    }
+}
@@ -0,0 +1,79 @@
+//! `alloy convert`: migrating Prometheus, Promtail, static-mode Grafana Agent
+//! and OpenTelemetry Collector configs to Alloy.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tower_lsp::lsp_types::Url;
+
+/// What `alloy convert --source-format` accepts.
+pub const FORMATS: [&str; 4] = ["prometheus", "promtail", "static", "otelcol"];
+
+/// Converted config, and the `.alloy` file it is meant for.
+#[derive(Debug, Serialize)]
+pub struct Converted {
+    pub uri: Url,
+    pub text: String,
+}
+
+/// Guesses the format of a YAML config from its top-level keys, and the
+/// keys nested under them. Only signatures specific to each format count:
+/// Helm values and other YAML often have a `metrics:` or `global:` key.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let keys = keys(text);
+    let has = |key: &str| keys.iter().any(|(parent, k)| parent.is_none() && *k == key);
+    let has_under = |parent: &str, key: &str| keys.iter().any(|(p, k)| *p == Some(parent) && *k == key);
+    if has("receivers") && has("exporters") && has("service") {
+        Some("otelcol")
+    } else if has("integrations") || ["metrics", "logs", "traces"].into_iter().any(|p| has_under(p, "configs")) {
+        Some("static")
+    } else if has("clients") && (has("server") || has("positions")) {
+        Some("promtail")
+    } else if has("scrape_configs") {
+        Some("prometheus")
+    } else {
+        None
+    }
+}
+
+/// The mapping keys of a YAML document, each with the top-level key it is
+/// nested under, if any. Keys inside lists are left out.
+fn keys(text: &str) -> Vec<(Option<&str>, &str)> {
+    let mut keys = Vec::new();
+    let mut parent = None;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with(['-', '#']) {
+            continue;
+        }
+        let Some((key, _)) = trimmed.split_once(':') else { continue };
+        let key = key.trim();
+        if trimmed.len() == line.len() {
+            parent = Some(key);
+            keys.push((None, key));
+        } else {
+            keys.push((parent, key));
+        }
+    }
+    keys
+}
+
+/// Where the converted config of `source` goes: beside it, as `.alloy`.
+pub fn target(source: &Path) -> PathBuf {
+    source.with_extension("alloy")
+}
+
+/// Runs `alloy convert` on the file on disk. Errors carry what it printed.
+pub async fn run(alloy: &Path, source: &Path, format: &str) -> Result<String, String> {
+    let out = Command::new(alloy)
+        .arg("convert")
+        .arg(format!("--source-format={format}"))
+        .arg(source)
+        .output()
+        .await
+        .map_err(|err| format!("running {}: {err}", alloy.display()))?;
+    if !out.status.success() {
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+    }
+    String::from_utf8(out.stdout).map_err(|err| err.to_string())
+}
@@ -0,0 +1,82 @@
+//! `textDocument/definition`: component references, module instances and
+//! remote modules.
+
+use std::{fs, path::Path};
+use tower_lsp::lsp_types::*;
+
+use crate::document::Document;
+use crate::modules::{self, Import};
+use crate::syntax::{Block, Stmt};
+use crate::workspace::Workspace;
+
+/// Where a definition lives. Remote modules may need fetching first, which
+/// is left to the caller.
+pub enum Target {
+    Location(Location),
+    /// A remote module; `declare` is set when the cursor was on an instance
+    /// of one of its components rather than the import itself.
+    Module { import: Import, declare: Option<String> },
+}
+
+pub fn target(doc: &Document, uri: &Url, offset: usize, workspace: &Workspace) -> Option<Target> {
+    let file = doc.syntax();
+    let imports = modules::imports(&file.body);
+    let mut chain = Vec::new();
+    let block = block_at(&file.body, offset, &mut chain);
+
+    // References in attribute values.
+    let attr = chain.last().map_or(&file.body[..], |b| &b.body[..]).iter().find_map(|s| match s {
+        Stmt::Attribute(a) if a.value.span.contains(offset) => Some(a),
+        _ => None,
+    });
+    if let Some(expr) = attr.and_then(|a| a.value.references().into_iter().find(|e| e.span.contains(offset))) {
+        let path = expr.path()?.join(".");
+        let (target_uri, component) = workspace.resolve(&path)?;
+        return Some(Target::Location(Location::new(target_uri.clone(), component.range)));
+    }
+
+    let block = block?;
+    if let Some(import) = Import::from_block(block) {
+        return Some(Target::Module { import, declare: None });
+    }
+    if !block.name_span().contains(offset) {
+        return None;
+    }
+    if let [namespace, name] = block.name.as_slice() {
+        if let Some(import) = imports.iter().find(|i| i.label() == namespace.name) {
+            return Some(Target::Module { import: import.clone(), declare: Some(name.name.clone()) });
+        }
+    }
+    let (target_uri, declare) = workspace.declare_from(uri, &block.full_name())?;
+    Some(Target::Location(Location::new(target_uri.clone(), declare.range)))
+}
+
+/// Resolves a target inside a fetched module: the `declare` when one was
+/// asked for, the top of the module file otherwise.
+pub fn in_module(path: &Path, declare: Option<&str>) -> Option<Location> {
+    let files = modules::module_files(path);
+    let Some(name) = declare else {
+        let first = files.first()?;
+        return Some(Location::new(Url::from_file_path(first).ok()?, Range::default()));
+    };
+    files.iter().find_map(|file| {
+        let doc = Document::new(fs::read_to_string(file).ok()?);
+        let parsed = doc.syntax();
+        let block = parsed.body.iter().find_map(|s| match s {
+            Stmt::Block(b) if b.full_name() == "declare" && b.label.as_ref().is_some_and(|l| l.value == name) => Some(b),
+            _ => None,
+        })?;
+        Some(Location::new(Url::from_file_path(file).ok()?, doc.range(block.span)))
+    })
+}
+
+/// Innermost block containing `offset`, recording its ancestors (and
+/// itself) in `chain`.
+fn block_at<'a>(body: &'a [Stmt], offset: usize, chain: &mut Vec<&'a Block>) -> Option<&'a Block> {
+    let block = body.iter().find_map(|s| match s {
+        Stmt::Block(b) if b.span.contains(offset) => Some(b),
+        _ => None,
+    })?;
+    chain.push(block);
+    Some(block_at(&block.body, offset, chain).unwrap_or(block))
+}
@@ -0,0 +1,201 @@
+//! Extra hover markdown keyed by component or attribute name, from
+//! `docs/alloy-hover.toml` or a docset the user points the server at.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::status::DocsStatus;
+
+/// The docset shipped with the extension, used when no other one loads.
+const BUILTIN: &str = include_str!("../../../docs/alloy-hover.toml");
+
+/// Entries are shared, so handing one out does not copy it.
+#[derive(Default)]
+pub struct Docs {
+    map: HashMap<String, Arc<str>>,
+    /// Entries from a translated docset, looked up before `map` so keys it
+    /// does not translate stay in English.
+    translated: HashMap<String, Arc<str>>,
+    /// The file the docset came from; translations sit next to it.
+    source: Option<PathBuf>,
+    /// The locale last passed to [`Docs::localize`], kept for reloads.
+    locale: Option<String>,
+    /// The file `translated` came from.
+    translation: Option<PathBuf>,
+    /// What was wrong with the file, for showing the user once.
+    problems: Vec<String>,
+}
+
+impl Docs {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("bundled docs parse")
+    }
+
+    /// Loads a docset, failing on any problem.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let docs = Self::parse(&text).with_context(|| format!("parsing {}", path.display()))?;
+        Ok(Self { source: Some(path.to_path_buf()), ..docs })
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let map: HashMap<String, String> = toml::from_str(text)?;
+        Ok(Self { map: map.into_iter().map(|(k, v)| (k, v.into())).collect(), ..Self::default() })
+    }
+
+    /// Loads a docset, keeping the entries that parse when others do not.
+    /// If the file cannot be read or nothing in it parses, the built-in
+    /// docset is used instead. Problems are kept for [`Docs::problems`].
+    pub fn load_lenient(path: &Path) -> Self {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => return Self::builtin_because(format!("reading {}: {err}", path.display())),
+        };
+        let (map, problems) = parse_lenient(path, &text);
+        if map.is_empty() {
+            let problems = problems.join("; ");
+            return Self::builtin_because(format!("no usable entries in {}: {problems}", path.display()));
+        }
+        Self { map, source: Some(path.to_path_buf()), problems, ..Self::default() }
+    }
+
+    /// Layers the translation for `locale` (`de`, `pt_BR`, `de_DE.UTF-8`)
+    /// over the docset: `alloy-hover.de.toml` next to `alloy-hover.toml`,
+    /// trying the region-specific file first. English locales, and docsets
+    /// with no file behind them, have nothing to layer. Returns the file
+    /// that was used.
+    pub fn localize(&mut self, locale: &str) -> Option<PathBuf> {
+        self.locale = Some(locale.to_string());
+        let source = self.source.as_ref()?;
+        let (stem, dir) = (source.file_stem()?.to_str()?, source.parent()?);
+        let path = locale_candidates(locale)
+            .into_iter()
+            .map(|locale| dir.join(format!("{stem}.{locale}.toml")))
+            .find(|path| path.is_file())?;
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                let (map, problems) = parse_lenient(&path, &text);
+                self.translated = map;
+                self.translation = Some(path.clone());
+                self.problems.extend(problems);
+            }
+            Err(err) => self.problems.push(format!("reading {}: {err}", path.display())),
+        }
+        Some(path)
+    }
+
+    /// The docset read again from `path`, or from the file it came from,
+    /// in the same locale. One with no file behind it is the built-in one.
+    pub fn reload(&self, path: Option<&Path>) -> Self {
+        let mut docs = match path.or(self.source.as_deref()) {
+            Some(path) => Self::load_lenient(path),
+            None => Self::builtin(),
+        };
+        if let Some(locale) = &self.locale {
+            docs.localize(locale);
+        }
+        docs
+    }
+
+    fn builtin_because(problem: String) -> Self {
+        let mut docs = Self::builtin();
+        docs.problems.push(format!("{problem}; using the built-in docs"));
+        docs
+    }
+
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    pub fn status(&self) -> DocsStatus {
+        DocsStatus {
+            entries: self.map.len(),
+            translated_entries: self.translated.len(),
+            source: self.source.clone(),
+            translation: self.translation.clone(),
+            locale: self.locale.clone(),
+            problems: self.problems.clone(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<str>> {
+        self.translated.get(key).or_else(|| self.map.get(key)).cloned()
+    }
+}
+
+/// The params of the `alloy/reloadDocs` notification: `{ "path": … }` to
+/// switch to another docset, or nothing to re-read the current one.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ReloadDocsParams {
+    pub path: Option<PathBuf>,
+}
+
+/// Parses what it can of a docs file, with a `path:line: message` problem
+/// for each entry that does not parse.
+fn parse_lenient(path: &Path, text: &str) -> (HashMap<String, Arc<str>>, Vec<String>) {
+    if let Ok(docs) = Docs::parse(text) {
+        return (docs.map, Vec::new());
+    }
+    let mut map = HashMap::new();
+    let mut problems = Vec::new();
+    for (line, entry) in entries(text) {
+        match toml::from_str::<HashMap<String, String>>(entry) {
+            Ok(entry) => map.extend(entry.into_iter().map(|(k, v)| (k, v.into()))),
+            Err(err) => {
+                let line = line + error_line(err.span(), entry);
+                problems.push(format!("{}:{line}: {}", path.display(), err.message()));
+            }
+        }
+    }
+    (map, problems)
+}
+
+/// File suffixes to try for a locale, most specific first: `pt_BR.UTF-8`
+/// gives `pt_BR` then `pt`. Empty for English and the `C` locale.
+fn locale_candidates(locale: &str) -> Vec<String> {
+    let locale = locale.split(['.', '@']).next().unwrap_or_default().replace('-', "_");
+    let language = locale.split('_').next().unwrap_or_default().to_ascii_lowercase();
+    if language.is_empty() || language == "en" || language == "c" || language == "posix" {
+        return Vec::new();
+    }
+    let mut candidates = vec![locale.clone()];
+    if language != locale {
+        candidates.push(language);
+    }
+    candidates
+}
+
+/// Splits a docs file into its top-level entries, each with the 1-based
+/// line it starts on, so one bad entry does not take the others with it.
+/// An entry runs until the next line that starts a key outside a
+/// multi-line string; comments before a key go with the previous entry.
+pub(crate) fn entries(text: &str) -> Vec<(usize, &str)> {
+    let mut starts = Vec::new();
+    let mut in_string = false;
+    let mut offset = 0;
+    for (n, line) in text.split_inclusive('\n').enumerate() {
+        let starts_key = !in_string && line.starts_with(|c: char| c == '"' || c == '\'' || c.is_ascii_alphanumeric());
+        if starts_key {
+            starts.push((n + 1, offset));
+        }
+        if (line.matches("\"\"\"").count() + line.matches("'''").count()) % 2 == 1 {
+            in_string = !in_string;
+        }
+        offset += line.len();
+    }
+    let ends = starts.iter().skip(1).map(|&(_, o)| o).chain([text.len()]);
+    starts.iter().zip(ends).map(|(&(line, start), end)| (line, &text[start..end])).collect()
+}
+
+/// The 0-based line within `entry` of a TOML error's span.
+pub(crate) fn error_line(span: Option<Range<usize>>, entry: &str) -> usize {
+    span.map_or(0, |s| entry[..s.start.min(entry.len())].matches('\n').count())
+}
@@ -0,0 +1,247 @@
+//! `check-docs`: validates a hover docset against the component schema, for
+//! the extension's tests and for docset authors.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::docs;
+use crate::schema::{BlockSchema, Component, Schema};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Problem {
+    pub file: PathBuf,
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}:{}: {severity}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+/// Labels after which docs entries list a component's attributes, blocks or
+/// exports as backticked names, e.g. `**Key attrs:** \`url\`, \`headers\``.
+const ATTRIBUTE_LABELS: &[&str] = &[
+    "**Key attr:**",
+    "**Key attrs:**",
+    "**Key block:**",
+    "**Attr:**",
+    "**Attrs:**",
+    "**Arguments:**",
+    "**Common args:**",
+    "**Typical attr:**",
+    "**Endpoint attrs:**",
+];
+const EXPORT_LABELS: &[&str] = &["**Exports:**"];
+
+/// Checks a docs file, or every `.toml` file below a directory. Keys
+/// defined in more than one of them are reported too, except between a
+/// docset and its translations (`alloy-hover.de.toml`).
+pub fn check(path: &Path) -> Vec<Problem> {
+    let mut files = Vec::new();
+    collect_toml(path, &mut files);
+    let mut problems = Vec::new();
+    if files.is_empty() {
+        problems.push(problem(path, 0, Severity::Error, "no docs files found".into()));
+    }
+    let mut seen: HashMap<(Option<String>, String), (PathBuf, usize)> = HashMap::new();
+    for file in files {
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(err) => {
+                problems.push(problem(&file, 0, Severity::Error, format!("cannot read: {err}")));
+                continue;
+            }
+        };
+        let locale = locale(&file);
+        for (line, entry, key, value) in check_syntax(&file, &text, &mut problems) {
+            let seen_key = (locale.clone(), key.clone());
+            if let Some((first_file, first_line)) = seen.get(&seen_key) {
+                let message = format!("duplicate key `{key}`, first defined at {}:{first_line}", first_file.display());
+                problems.push(problem(&file, line, Severity::Error, message));
+                continue;
+            }
+            seen.insert(seen_key, (file.clone(), line));
+            check_links(&file, line, entry, &mut problems);
+            check_key(&file, line, &key, &value, &mut problems);
+        }
+    }
+    problems
+}
+
+fn collect_toml(path: &Path, out: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        out.push(path.to_path_buf());
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else { return };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() || path.extension().is_some_and(|e| e == "toml") {
+            collect_toml(&path, out);
+        }
+    }
+}
+
+/// The locale of a translated docs file: `de` for `alloy-hover.de.toml`.
+fn locale(file: &Path) -> Option<String> {
+    let stem = file.file_stem()?.to_str()?;
+    stem.rsplit_once('.').map(|(_, locale)| locale.to_string())
+}
+
+fn problem(file: &Path, line: usize, severity: Severity, message: String) -> Problem {
+    Problem { file: file.to_path_buf(), line, severity, message }
+}
+
+/// Parses each entry on its own, so every broken one is reported, and
+/// returns the string entries with the line they start on and their text.
+fn check_syntax<'a>(
+    file: &Path,
+    text: &'a str,
+    problems: &mut Vec<Problem>,
+) -> Vec<(usize, &'a str, String, String)> {
+    let mut out = Vec::new();
+    for (line, entry) in docs::entries(text) {
+        let table = match toml::from_str::<toml::Table>(entry) {
+            Ok(table) => table,
+            Err(err) => {
+                let line = line + docs::error_line(err.span(), entry);
+                problems.push(problem(file, line, Severity::Error, err.message().to_string()));
+                continue;
+            }
+        };
+        for (key, value) in table {
+            match value {
+                toml::Value::String(value) => out.push((line, entry, key, value)),
+                other => {
+                    let message = format!("`{key}` must be a markdown string, not {}", other.type_str());
+                    problems.push(problem(file, line, Severity::Error, message));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Flags `[text](target)` links with no target, spaces in the target or
+/// a missing `)`, in an entry starting at `line`.
+fn check_links(file: &Path, line: usize, entry: &str, problems: &mut Vec<Problem>) {
+    for (n, text) in entry.lines().enumerate() {
+        let line = line + n;
+        let mut rest = text;
+        while let Some(i) = rest.find("](") {
+            let after = &rest[i + 2..];
+            let message = match after.find(')') {
+                None => Some("link is missing its closing `)`"),
+                Some(0) => Some("link has an empty target"),
+                Some(end) if after[..end].contains(char::is_whitespace) => Some("link target contains spaces"),
+                Some(_) => None,
+            };
+            if let Some(message) = message {
+                problems.push(problem(file, line, Severity::Error, message.into()));
+            }
+            rest = after;
+        }
+    }
+}
+
+/// Checks that a key names something in the schema, and that the names a
+/// component's entry lists as its attributes and exports exist.
+fn check_key(file: &Path, line: usize, key: &str, value: &str, problems: &mut Vec<Problem>) {
+    let schema = Schema::builtin();
+    // Components whose arguments or exports the schema does not describe
+    // yet cannot be checked for them.
+    if let Some(component) = schema.component(key) {
+        let mut names = BTreeSet::new();
+        attribute_names(&component.arguments, &component.blocks, &mut names);
+        for name in listed(value, ATTRIBUTE_LABELS) {
+            if !names.is_empty() && !names.contains(name.as_str()) {
+                let message = format!("`{key}` has no attribute or block `{name}`");
+                problems.push(problem(file, line, Severity::Error, message));
+            }
+        }
+        for name in listed(value, EXPORT_LABELS) {
+            if !component.exports.is_empty() && !component.exports.contains_key(&name) {
+                let message = format!("`{key}` does not export `{name}`");
+                problems.push(problem(file, line, Severity::Error, message));
+            }
+        }
+        return;
+    }
+    // `component.attribute`: the longest dotted prefix that is a component.
+    let mut prefix = key;
+    while let Some((head, _)) = prefix.rsplit_once('.') {
+        if let Some(component) = schema.component(head) {
+            let name = &key[head.len() + 1..];
+            let mut names = BTreeSet::new();
+            attribute_names(&component.arguments, &component.blocks, &mut names);
+            if !names.is_empty() && !names.contains(name) {
+                let message = format!("`{head}` has no attribute or block `{name}`");
+                problems.push(problem(file, line, Severity::Error, message));
+            }
+            return;
+        }
+        prefix = head;
+    }
+    if !known_anywhere(schema, key) {
+        let message = format!("`{key}` is not a component, attribute, block or export in the schema");
+        problems.push(problem(file, line, Severity::Warning, message));
+    }
+}
+
+fn attribute_names<'a>(
+    arguments: &'a BTreeMap<String, crate::schema::Argument>,
+    blocks: &'a BTreeMap<String, BlockSchema>,
+    out: &mut BTreeSet<&'a str>,
+) {
+    out.extend(arguments.keys().map(String::as_str));
+    for (name, block) in blocks {
+        out.insert(name);
+        attribute_names(&block.arguments, &block.blocks, out);
+    }
+}
+
+fn known_anywhere(schema: &Schema, name: &str) -> bool {
+    let in_component = |c: &Component| {
+        let mut names = BTreeSet::new();
+        attribute_names(&c.arguments, &c.blocks, &mut names);
+        names.contains(name) || c.exports.contains_key(name)
+    };
+    schema.components.values().any(in_component)
+}
+
+/// Backticked names after any of `labels`, up to the end of the line, the
+/// next bold label or a parenthesized aside. Dotted names and anything that
+/// is not an identifier (values, paths) are skipped.
+fn listed(value: &str, labels: &[&str]) -> Vec<String> {
+    let mut out = Vec::new();
+    for label in labels {
+        for (i, _) in value.match_indices(label) {
+            let rest = &value[i + label.len()..];
+            let end = [rest.find('\n'), rest.find("**"), rest.find('(')].into_iter().flatten().min().unwrap_or(rest.len());
+            let names = rest[..end].split('`').skip(1).step_by(2);
+            out.extend(
+                names
+                    .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+                    .map(str::to_string),
+            );
+        }
+    }
+    out
+}
@@ -0,0 +1,90 @@
+//! `docs-coverage`: what a tree of configs uses that the docset does not
+//! document yet, so docset authors know what to write next.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::docs::Docs;
+use crate::schema::Schema;
+use crate::syntax::{self, Block, Stmt};
+use crate::workspace;
+
+/// A component or attribute and how often the configs use it.
+#[derive(Debug)]
+pub struct Usage {
+    /// `prometheus.scrape`, or `prometheus.scrape.scrape_interval` with any
+    /// nested block names in between.
+    pub name: String,
+    pub uses: usize,
+    pub documented: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Coverage {
+    pub files: usize,
+    pub components: Vec<Usage>,
+    pub attributes: Vec<Usage>,
+}
+
+impl Coverage {
+    pub fn undocumented_components(&self) -> impl Iterator<Item = &Usage> {
+        self.components.iter().filter(|u| !u.documented)
+    }
+
+    pub fn undocumented_attributes(&self) -> impl Iterator<Item = &Usage> {
+        self.attributes.iter().filter(|u| !u.documented)
+    }
+}
+
+/// Scans every config file below `dir`. Only components the schema knows
+/// are counted; `declare`d and module components have no docs entries.
+/// An attribute counts as documented if the docset has an entry for its
+/// full name or, as hovers fall back to, for the bare attribute name.
+pub fn scan(dir: &Path, docs: &Docs) -> Coverage {
+    let schema = Schema::builtin();
+    let mut components = BTreeMap::new();
+    let mut attributes = BTreeMap::new();
+    let paths = workspace::config_files(dir);
+    for path in &paths {
+        let Ok(text) = fs::read_to_string(path) else { continue };
+        for stmt in &syntax::parse(&text).body {
+            let Stmt::Block(block) = stmt else { continue };
+            let name = block.full_name();
+            if schema.component(&name).is_some() {
+                *components.entry(name.clone()).or_insert(0) += 1;
+                count_attributes(block, &name, &mut attributes);
+            }
+        }
+    }
+
+    let usage = |map: BTreeMap<String, (usize, bool)>| {
+        let mut usage: Vec<Usage> =
+            map.into_iter().map(|(name, (uses, documented))| Usage { name, uses, documented }).collect();
+        usage.sort_by(|a, b| b.uses.cmp(&a.uses).then_with(|| a.name.cmp(&b.name)));
+        usage
+    };
+    let components = components.into_iter().map(|(name, uses)| {
+        let documented = docs.get(&name).is_some();
+        (name, (uses, documented))
+    });
+    let attributes = attributes.into_iter().map(|(name, (uses, attr))| {
+        let documented = docs.get(&name).is_some() || docs.get(&attr).is_some();
+        (name, (uses, documented))
+    });
+    Coverage { files: paths.len(), components: usage(components.collect()), attributes: usage(attributes.collect()) }
+}
+
+/// Counts the attributes and nested blocks in `block` under `prefix`,
+/// keeping the bare name alongside each full one.
+fn count_attributes(block: &Block, prefix: &str, out: &mut BTreeMap<String, (usize, String)>) {
+    for stmt in &block.body {
+        let (name, nested) = match stmt {
+            Stmt::Attribute(attr) => (attr.name.name.clone(), None),
+            Stmt::Block(nested) => (nested.full_name(), Some(nested)),
+        };
+        let full = format!("{prefix}.{name}");
+        out.entry(full.clone()).or_insert((0, name)).0 += 1;
+        if let Some(nested) = nested {
+            count_attributes(nested, &full, out);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::{Arc, OnceLock};
+use tower_lsp::lsp_types::{Position, Range};
+
+use crate::syntax::{self, lexer::BOM, Span};
+
+/// Text of one file plus the bookkeeping needed to translate between byte
+/// offsets and LSP (UTF-16) positions.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub text: String,
+    /// The editor's version of the text; 0 for files read from disk.
+    pub version: i32,
+    line_starts: Vec<usize>,
+    /// Parsed on first use and shared by clones, so every request against
+    /// the same version reuses one tree.
+    syntax: OnceLock<Arc<syntax::File>>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, version: 0, line_starts, syntax: OnceLock::new() }
+    }
+
+    pub fn with_version(self, version: i32) -> Self {
+        Self { version, ..self }
+    }
+
+    pub fn syntax(&self) -> &syntax::File {
+        self.syntax.get_or_init(|| Arc::new(syntax::parse(&self.text)))
+    }
+
+    /// Roughly the bytes the document holds: its text and line table, and
+    /// once parsed its tree, taken to be a few times the size of the text.
+    pub fn footprint(&self) -> usize {
+        let tree = if self.syntax.get().is_some() { 4 * self.text.len() } else { 0 };
+        self.text.len() + self.line_starts.len() * std::mem::size_of::<usize>() + tree
+    }
+
+    /// Where `line` starts, after the BOM on the first.
+    fn line_start(&self, line: usize) -> usize {
+        match line {
+            0 if self.text.starts_with(BOM) => BOM.len_utf8(),
+            _ => self.line_starts[line],
+        }
+    }
+
+    /// The text of `line`, its line ending included.
+    pub(crate) fn line_text(&self, line: usize) -> &str {
+        let start = self.line_start(line);
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.text.len());
+        &self.text[start..end]
+    }
+
+    pub fn offset(&self, pos: Position) -> usize {
+        let line = pos.line as usize;
+        if line >= self.line_starts.len() {
+            return self.text.len();
+        }
+        let start = self.line_start(line);
+        // Past the end of a line is its end, before the `\n` or `\r\n`.
+        let text = self.line_text(line);
+        let text = text.strip_suffix('\n').map_or(text, |t| t.strip_suffix('\r').unwrap_or(t));
+        let mut units = 0;
+        for (i, ch) in text.char_indices() {
+            if units >= pos.character as usize {
+                return start + i;
+            }
+            units += ch.len_utf16();
+        }
+        start + text.len()
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let mut offset = offset.min(self.text.len());
+        // Inside a `\r\n` is still the end of the line.
+        let (before, after) = (self.text.get(..offset), self.text.get(offset..));
+        if after.is_some_and(|t| t.starts_with('\n')) && before.is_some_and(|t| t.ends_with('\r')) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|&s| s <= offset) - 1;
+        let start = self.line_start(line);
+        // Inside the BOM is the start of the line.
+        let text = self.text.get(start..offset).unwrap_or_default();
+        let character = text.chars().map(char::len_utf16).sum::<usize>();
+        Position { line: line as u32, character: character as u32 }
+    }
+
+    /// The line of [`Document::position`], without counting out the column
+    /// along a long line.
+    pub fn line(&self, offset: usize) -> u32 {
+        let offset = offset.min(self.text.len());
+        (self.line_starts.partition_point(|&s| s <= offset) - 1) as u32
+    }
+
+    pub fn range(&self, span: Span) -> Range {
+        Range { start: self.position(span.start), end: self.position(span.end) }
+    }
+
+    /// `\r\n` if the file's first line ends with one, else `\n`.
+    pub fn line_ending(&self) -> &'static str {
+        match self.text.find('\n') {
+            Some(i) if self.text[..i].ends_with('\r') => "\r\n",
+            _ => "\n",
+        }
+    }
+
+    /// `text`, which is about to be inserted, with the file's line endings.
+    pub fn with_line_endings(&self, text: String) -> String {
+        match self.line_ending() {
+            "\r\n" if text.contains('\n') => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+            _ => text,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! `textDocument/foldingRange`: blocks, multi-line arrays and objects,
+//! raw strings and comments.
+
+use tower_lsp::lsp_types::*;
+
+use crate::document::Document;
+use crate::syntax::lexer::{tokenize, TokenKind};
+use crate::syntax::{self, ExprKind, Span, Stmt};
+
+pub fn ranges(doc: &Document) -> Vec<FoldingRange> {
+    let file = doc.syntax();
+    let mut out = Vec::new();
+
+    // Bracketed regions keep their closing line visible.
+    let bracketed = |span: Span, out: &mut Vec<FoldingRange>| {
+        let (start, end) = (doc.line(span.start), doc.line(span.end));
+        if end > start + 1 {
+            out.push(fold(start, end - 1, None));
+        }
+    };
+    fn blocks(body: &[Stmt], f: &mut impl FnMut(Span)) {
+        for stmt in body {
+            if let Stmt::Block(b) = stmt {
+                f(b.span);
+                blocks(&b.body, f);
+            }
+        }
+    }
+    blocks(&file.body, &mut |span| bracketed(span, &mut out));
+    syntax::walk_exprs(&file.body, &mut |expr| {
+        if matches!(expr.kind, ExprKind::Array(_) | ExprKind::Object(_)) {
+            bracketed(expr.span, &mut out);
+        }
+    });
+
+    // Raw strings and block comments fold whole; runs of `//` lines fold
+    // into one region.
+    let mut comment_run: Option<(u32, u32)> = None;
+    for token in tokenize(&doc.text) {
+        let (start, end) = (doc.line(token.span.start), doc.line(token.span.end));
+        let line_comment = token.kind == TokenKind::Comment && doc.text[token.span.start..].starts_with("//");
+        if line_comment {
+            match &mut comment_run {
+                Some((_, last)) if *last + 1 == start => *last = start,
+                _ => {
+                    flush_comments(comment_run.take(), &mut out);
+                    comment_run = Some((start, start));
+                }
+            }
+            continue;
+        }
+        flush_comments(comment_run.take(), &mut out);
+        match token.kind {
+            TokenKind::Comment if end > start => out.push(fold(start, end, Some(FoldingRangeKind::Comment))),
+            TokenKind::RawString if end > start => out.push(fold(start, end, None)),
+            _ => {}
+        }
+    }
+    flush_comments(comment_run, &mut out);
+
+    out.sort_by_key(|r| (r.start_line, std::cmp::Reverse(r.end_line)));
+    out
+}
+
+fn flush_comments(run: Option<(u32, u32)>, out: &mut Vec<FoldingRange>) {
+    if let Some((start, end)) = run.filter(|(start, end)| end > start) {
+        out.push(fold(start, end, Some(FoldingRangeKind::Comment)));
+    }
+}
+
+fn fold(start_line: u32, end_line: u32, kind: Option<FoldingRangeKind>) -> FoldingRange {
+    FoldingRange { start_line, end_line, kind, ..Default::default() }
+}
@@ -0,0 +1,146 @@
+//! The component dependency graph: which component feeds data into which.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use tower_lsp::lsp_types::{Range, Url};
+
+use crate::schema::Schema;
+use crate::workspace::Workspace;
+
+/// Export types that accept data. Referencing one means the referencing
+/// component pushes data into the exporting one; any other export is
+/// pulled by the component that references it.
+const RECEIVER_TYPES: &[&str] = &["MetricsReceiver", "LogsReceiver", "ProfilesReceiver", "otelcol.Consumer"];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentGraphParams {
+    /// Restrict the graph to components defined in this file.
+    pub uri: Option<Url>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    /// The same graph in Graphviz DOT syntax.
+    pub dot: String,
+    /// The same graph as a Mermaid flowchart.
+    pub mermaid: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Node {
+    pub id: String,
+    pub component: String,
+    pub label: String,
+    pub uri: Url,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Edge {
+    /// Component the data comes from.
+    pub from: String,
+    /// Component the data flows into.
+    pub to: String,
+    /// The export carrying it, e.g. `receiver` or `targets`.
+    pub export: String,
+}
+
+pub fn build(workspace: &Workspace, only: Option<&Url>) -> ComponentGraph {
+    let in_scope = |uri: &Url| only.is_none_or(|o| o == uri);
+
+    let mut nodes: Vec<Node> = workspace
+        .components()
+        .filter(|(uri, _)| in_scope(uri))
+        .map(|(uri, c)| Node {
+            id: c.id(),
+            component: c.name.clone(),
+            label: c.label.clone(),
+            uri: uri.clone(),
+            range: c.range,
+        })
+        .collect();
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    let ids: BTreeSet<&str> = nodes.iter().map(|n| n.id.as_str()).collect();
+
+    let edges: BTreeSet<Edge> = flows(workspace)
+        .into_iter()
+        .map(|f| f.edge)
+        .filter(|e| ids.contains(e.from.as_str()) || ids.contains(e.to.as_str()))
+        .collect();
+    let edges: Vec<Edge> = edges.into_iter().collect();
+
+    let dot = to_dot(&nodes, &edges);
+    let mermaid = to_mermaid(&nodes, &edges);
+    ComponentGraph { nodes, edges, dot, mermaid }
+}
+
+/// A single reference that moves data between two components.
+pub struct Flow<'a> {
+    pub edge: Edge,
+    /// Where the reference is written.
+    pub uri: &'a Url,
+    pub range: Range,
+}
+
+/// Every component-to-component reference in the workspace, with the
+/// direction data travels along it.
+pub fn flows(workspace: &Workspace) -> Vec<Flow<'_>> {
+    let mut flows = Vec::new();
+    for (uri, reference) in workspace.references() {
+        let Some(owner) = &reference.owner else { continue };
+        let Some((_, target)) = workspace.resolve(&reference.path) else { continue };
+        let target_id = target.id();
+        if *owner == target_id {
+            continue;
+        }
+        let export = reference.path[target_id.len()..].trim_start_matches('.');
+        let export = export.split('.').next().unwrap_or_default().to_string();
+        let pushes = Schema::builtin()
+            .component(&target.name)
+            .and_then(|c| c.exports.get(&export))
+            .is_some_and(|e| RECEIVER_TYPES.contains(&e.ty.as_str()));
+        let (from, to) = if pushes { (owner.clone(), target_id) } else { (target_id, owner.clone()) };
+        flows.push(Flow { edge: Edge { from, to, export }, uri, range: reference.range });
+    }
+    flows
+}
+
+fn to_dot(nodes: &[Node], edges: &[Edge]) -> String {
+    let mut dot = String::from("digraph alloy {\n  rankdir=LR;\n  node [shape=box];\n");
+    for node in nodes {
+        dot.push_str(&format!("  \"{}\";\n", node.id));
+    }
+    for edge in edges {
+        dot.push_str(&format!("  \"{}\" -> \"{}\" [label=\"{}\"];\n", edge.from, edge.to, edge.export));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn to_mermaid(nodes: &[Node], edges: &[Edge]) -> String {
+    // Mermaid IDs can't contain dots, so number the nodes and label them.
+    let mut ids: Vec<&str> = nodes.iter().map(|n| n.id.as_str()).collect();
+    for edge in edges {
+        for id in [&edge.from, &edge.to] {
+            if !ids.contains(&id.as_str()) {
+                ids.push(id);
+            }
+        }
+    }
+    let key = |id: &str| ids.iter().position(|i| *i == id).unwrap_or_default();
+
+    let mut out = String::from("flowchart LR\n");
+    for (i, id) in ids.iter().enumerate() {
+        out.push_str(&format!("  n{i}[\"{id}\"]\n"));
+    }
+    for edge in edges {
+        out.push_str(&format!("  n{} -->|{}| n{}\n", key(&edge.from), edge.export, key(&edge.to)));
+    }
+    out
+}
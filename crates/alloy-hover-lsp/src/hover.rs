@@ -0,0 +1,678 @@
+use std::sync::Arc;
+use tower_lsp::lsp_types::*;
+
+use crate::cloud_endpoints;
+use crate::config::HoverStyle;
+use crate::document::Document;
+use crate::labels;
+use crate::instance::ScrapeStatus;
+use crate::relabel;
+use crate::releases::{Availability, Releases};
+use crate::schema::{Argument, BlockSchema, Body, Schema};
+use crate::syntax::{Attribute, Block, Expr, ExprKind, Span, Stmt};
+use crate::units;
+use crate::workspace::{self, ArgumentDef, Workspace};
+
+/// Shown in place of string literals assigned to `secret` attributes.
+const MASK: &str = "••••";
+
+/// Parts of environment variable names whose values are masked.
+const SECRET_NAME_PARTS: &[&str] = &["PASSWORD", "PASSWD", "SECRET", "TOKEN", "KEY", "CREDENTIAL", "AUTH", "PRIVATE"];
+
+/// Hover for a component's label string: lists the expressions across the
+/// workspace that consume the component's exports.
+pub fn label_references(doc: &Document, offset: usize, workspace: &Workspace) -> Option<Hover> {
+    let file = doc.syntax();
+    let block = block_with_label_at(&file.body, offset)?;
+    let label = block.label.as_ref()?;
+    let id = format!("{}.{}", block.full_name(), label.value);
+
+    let mut refs: Vec<_> = workspace.references_to(&id).collect();
+    refs.sort_by_key(|(uri, r)| (uri.as_str(), r.range.start.line, r.range.start.character));
+
+    let value = if refs.is_empty() {
+        format!("`{id}` is not referenced anywhere in the workspace.")
+    } else {
+        let mut md = format!(
+            "`{id}` is referenced {} time{}:\n",
+            refs.len(),
+            if refs.len() == 1 { "" } else { "s" }
+        );
+        for (uri, r) in refs {
+            let line = r.range.start.line + 1;
+            md.push_str(&format!(
+                "- [{}:{line}]({uri}#L{line}) `{}`\n",
+                workspace.display_path(uri),
+                r.path
+            ));
+        }
+        md
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(doc.range(label.span)),
+    })
+}
+
+/// Hover for an attribute name inside a component: its schema type, default
+/// and documentation, plus the value currently assigned to it. Values of
+/// `secret` attributes are masked so hovers never echo credentials.
+/// `docs` supplies extra markdown for the attribute name, if any.
+pub fn attribute(
+    doc: &Document,
+    offset: usize,
+    style: HoverStyle,
+    releases: &Releases,
+    docs: impl Fn(&str) -> Option<Arc<str>>,
+) -> Option<Hover> {
+    let file = doc.syntax();
+    let mut chain = Vec::new();
+    let attr = attribute_at(&file.body, offset, &mut chain)?;
+    let arg = Schema::builtin().argument_in(&chain, &attr.name.name)?;
+
+    let availability = releases.argument_in(&chain, &attr.name.name);
+    let mut md = argument(&attr.name.name, arg, style, availability);
+    if style == HoverStyle::Concise {
+        return Some(Hover {
+            contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: md }),
+            range: Some(doc.range(attr.name.span)),
+        });
+    }
+    let value = render_value(&doc.text, &attr.value, arg.is_secret());
+    md.push_str(&format!("```alloy\n{} = {value}\n```\n", attr.name.name));
+    if arg.is_secret() {
+        md.push_str("\nThis attribute is secret-typed; literal values are masked.\n");
+    }
+    if let Some(extra) = docs(&attr.name.name) {
+        md.push_str(&format!("\n---\n\n{extra}"));
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: md }),
+        range: Some(doc.range(attr.name.span)),
+    })
+}
+
+/// An argument's type and whether it is required, then its documentation:
+/// the first line of it when concise, and all of it, when it came and
+/// went and its default when full.
+pub fn argument(name: &str, arg: &Argument, style: HoverStyle, availability: Availability) -> String {
+    let mut md = format!("`{name}`: `{}`", arg.ty);
+    if arg.required {
+        md.push_str(" (required)");
+    }
+    md.push_str("\n\n");
+    if style == HoverStyle::Concise {
+        md.push_str(arg.doc.lines().next().unwrap_or_default());
+        return md;
+    }
+    if !arg.doc.is_empty() {
+        md.push_str(&format!("{}\n\n", arg.doc));
+    }
+    for line in lifecycle(arg.deprecated.as_deref(), availability) {
+        md.push_str(&format!("{line}\n\n"));
+    }
+    if let Some(default) = &arg.default {
+        md.push_str(&format!("Default: `{default}`\n\n"));
+    }
+    md
+}
+
+/// Hover for a well-known label: a key of a map in a `targets` list, or a
+/// label a relabel rule reads or writes.
+pub fn label(doc: &Document, offset: usize) -> Option<Hover> {
+    let file = doc.syntax();
+    let mut chain = Vec::new();
+    let attr = attribute_around(&file.body, offset, &mut chain)?;
+    if !attr.value.span.contains(offset) {
+        return None;
+    }
+    let elements = match &attr.value.kind {
+        ExprKind::Array(elements) => elements.as_slice(),
+        _ => std::slice::from_ref(&attr.value),
+    };
+    let element = elements.iter().find(|e| e.span.contains(offset))?;
+    let (name, span) = match (attr.name.name.as_str(), &element.kind) {
+        ("targets", ExprKind::Object(fields)) => {
+            let field = fields.iter().find(|f| f.key_span.contains(offset))?;
+            (field.key.as_str(), field.key_span)
+        }
+        ("source_labels" | "target_label", ExprKind::String(name)) if relabel::is_rule(&chain) => {
+            (name.as_str(), element.span)
+        }
+        _ => return None,
+    };
+    let label = labels::lookup(name)?;
+    let value = format!("`{}` label\n\n{}", label.name, label.doc);
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(doc.range(span)),
+    })
+}
+
+/// Hover for a string that is a Grafana Cloud endpoint: the product and
+/// region it belongs to.
+pub fn cloud_endpoint(doc: &Document, offset: usize) -> Option<Hover> {
+    let file = doc.syntax();
+    let attr = attribute_around(&file.body, offset, &mut Vec::new())?;
+    if !attr.value.span.contains(offset) {
+        return None;
+    }
+    let ExprKind::String(url) = &attr.value.kind else { return None };
+    let preset = cloud_endpoints::lookup(url)?;
+    let value = format!("**{}**, region `{}`", preset.product.name, preset.region);
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(doc.range(attr.value.span)),
+    })
+}
+
+/// Hover for a string assigned to a `duration` attribute: its normalized
+/// form, and how it compares to the attribute's default.
+pub fn duration(doc: &Document, offset: usize) -> Option<Hover> {
+    let file = doc.syntax();
+    let mut chain = Vec::new();
+    let attr = attribute_around(&file.body, offset, &mut chain)?;
+    if !attr.value.span.contains(offset) {
+        return None;
+    }
+    let ExprKind::String(text) = &attr.value.kind else { return None };
+    let arg = Schema::builtin().argument_in(&chain, &attr.name.name).filter(|a| a.ty == "duration")?;
+    let ns = units::parse_duration(text).ok()?;
+    let mut value = format!("`{}`", units::format_duration(ns));
+    let default = arg.default.as_deref().and_then(|d| units::parse_duration(d.trim_matches('"')).ok());
+    if let Some(default) = default.filter(|d| *d > 0.0) {
+        let name = &attr.name.name;
+        let default_text = units::format_duration(default);
+        let ratio = ns / default;
+        let relation = if (ratio - 1.0).abs() < 1e-9 {
+            "the default".to_string()
+        } else {
+            let ratio = format!("{:.2}", ratio);
+            format!("{}× the default", ratio.trim_end_matches('0').trim_end_matches('.'))
+        };
+        value.push_str(&format!("\n\n{relation} `{name}` (`{default_text}`)"));
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(doc.range(attr.value.span)),
+    })
+}
+
+/// The ID of the `prometheus.scrape` component whose name is under
+/// `offset`, for showing what the running instance says about it.
+pub fn scrape_at(doc: &Document, offset: usize) -> Option<String> {
+    let file = doc.syntax();
+    let block = block_with_name_at(&file.body, offset).filter(|b| b.full_name() == "prometheus.scrape")?;
+    workspace::component_id(block)
+}
+
+/// A hover section on how a running scrape component's targets are doing.
+pub fn live_scrape(status: &ScrapeStatus) -> String {
+    const SHOWN_ERRORS: usize = 5;
+    let up = status.targets.iter().filter(|t| t.health == "up").count();
+    let down = status.targets.iter().filter(|t| t.health == "down").count();
+    let count = status.targets.len();
+    let mut md = format!(
+        "\n---\n\n**Live:** {count} target{} discovered, {up} up, {down} down\n",
+        if count == 1 { "" } else { "s" }
+    );
+    let errors: Vec<_> = status.targets.iter().filter(|t| !t.last_error.is_empty()).collect();
+    if !errors.is_empty() {
+        md.push_str("\nLast scrape errors:\n");
+        for target in errors.iter().take(SHOWN_ERRORS) {
+            md.push_str(&format!("- `{}`: {}\n", target.url, target.last_error));
+        }
+        if errors.len() > SHOWN_ERRORS {
+            md.push_str(&format!("- and {} more\n", errors.len() - SHOWN_ERRORS));
+        }
+    }
+    md
+}
+
+/// Hover for an `env("NAME")` call: the value `NAME` has in the server's
+/// environment, masked if the name or the attribute looks secret.
+pub fn env_call(doc: &Document, offset: usize) -> Option<Hover> {
+    fn call_at(expr: &Expr, offset: usize) -> Option<&Expr> {
+        if !expr.span.contains(offset) {
+            return None;
+        }
+        if let ExprKind::Call(callee, _) = &expr.kind {
+            if workspace::is_env_call(callee) {
+                return Some(expr);
+            }
+        }
+        let mut found = None;
+        expr.for_each_child(&mut |child| found = found.or_else(|| call_at(child, offset)));
+        found
+    }
+
+    let file = doc.syntax();
+    let mut chain = Vec::new();
+    let attr = attribute_around(&file.body, offset, &mut chain)?;
+    let call = call_at(&attr.value, offset)?;
+    let ExprKind::Call(_, args) = &call.kind else { return None };
+    let name = args.first()?.as_str()?;
+    let secret = Schema::builtin().argument_in(&chain, &attr.name.name).is_some_and(|a| a.is_secret())
+        || SECRET_NAME_PARTS.iter().any(|part| name.to_ascii_uppercase().contains(part));
+    let value = match std::env::var(name) {
+        Ok(_) if secret => format!("`{name}` is set (value masked)."),
+        Ok(value) => format!("`{name}` = `{value}`"),
+        Err(_) => format!("`{name}` is not set; `env()` returns an empty string."),
+    };
+    let value = format!("{value}\n\nAs the language server sees it; Alloy may run with another environment.");
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(doc.range(call.span)),
+    })
+}
+
+/// Hover markdown for a word with a docs entry, or a component the schema
+/// knows. Concise hovers are a signature and the entry's first line; full
+/// ones for components add an example, an arguments table and a link to
+/// the reference.
+pub fn word(name: &str, entry: Option<Arc<str>>, style: HoverStyle, releases: &Releases) -> Option<String> {
+    let component = Schema::builtin().component(name);
+    if entry.is_none() && component.is_none() {
+        return None;
+    }
+    let entry = entry.map_or_else(|| format!("**{name}**"), |e| e.trim_end().to_string());
+    let Some(component) = component else {
+        return Some(match style {
+            HoverStyle::Concise => entry.lines().take(2).collect::<Vec<_>>().join("\n"),
+            HoverStyle::Full => entry,
+        });
+    };
+    let required: Vec<(&String, &Argument)> =
+        component.arguments.iter().filter(|(_, arg)| arg.required).collect();
+
+    if style == HoverStyle::Concise {
+        let names: Vec<&str> = required.iter().map(|(name, _)| name.as_str()).collect();
+        let args = if names.is_empty() { String::new() } else { format!(" {{ {} }}", names.join(", ")) };
+        let summary = entry.lines().find(|l| !l.trim().is_empty() && l.trim() != format!("**{name}**"));
+        return Some(format!("`{name} \"LABEL\"{args}`\n\n{}", summary.unwrap_or_default()).trim_end().to_string());
+    }
+
+    let mut md = format!("{entry}\n\n");
+    for line in lifecycle(component.deprecated.as_deref(), releases.component(name)) {
+        md.push_str(&format!("{line}\n\n"));
+    }
+    md.push_str(&format!("```alloy\n{name} \"default\" {{\n"));
+    let width = required.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (arg, schema) in &required {
+        md.push_str(&format!("  {arg:width$} = {}\n", placeholder(&schema.ty)));
+    }
+    md.push_str("}\n```\n");
+    md.push_str(&contents(Body { arguments: &component.arguments, blocks: &component.blocks }));
+    let namespace = name.split('.').next().unwrap_or(name);
+    md.push_str(&format!(
+        "\n[Reference](https://grafana.com/docs/alloy/latest/reference/components/{namespace}/{name}/)\n"
+    ));
+    Some(md)
+}
+
+/// The arguments table and the list of blocks of a body, for hovers.
+fn contents(body: Body) -> String {
+    let mut md = String::new();
+    if !body.arguments.is_empty() {
+        md.push_str("\n| Argument | Type | |\n|---|---|---|\n");
+        for (arg, schema) in body.arguments {
+            let note = match (&schema.default, schema.required) {
+                (_, true) => "required".to_string(),
+                (Some(default), false) => format!("default `{default}`"),
+                (None, false) => String::new(),
+            };
+            md.push_str(&format!("| `{arg}` | `{}` | {note} |\n", schema.ty));
+        }
+    }
+    if !body.blocks.is_empty() {
+        let blocks: Vec<String> = body.blocks.keys().map(|b| format!("`{b}`")).collect();
+        md.push_str(&format!("\nBlocks: {}\n", blocks.join(", ")));
+    }
+    md
+}
+
+/// Hover for the name of a block nested in a component, at any depth: its
+/// path from the component, documentation and what it can contain.
+pub fn nested_block(
+    doc: &Document,
+    offset: usize,
+    style: HoverStyle,
+    docs: impl Fn(&str) -> Option<Arc<str>>,
+) -> Option<Hover> {
+    let mut chain = Vec::new();
+    if !block_chain_at(&doc.syntax().body, offset, &mut chain) {
+        return None;
+    }
+    let (block, parents) = chain.split_last()?;
+    let owner = parents.iter().rposition(|b| workspace::component_id(b).is_some())?;
+    let names: Vec<String> = parents.iter().map(|b| b.full_name()).collect();
+    let name = block.full_name();
+    let schema = Schema::builtin().body_in(&names)?.blocks.get(&name)?;
+
+    let md = block_docs(&names[owner..], &name, schema, style, docs(&name));
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: md }),
+        range: Some(doc.range(block.name_span())),
+    })
+}
+
+/// A block's path from the component owning it, `names[0]`, through the
+/// other `names` it is in, then its documentation: the first line when
+/// concise; all of it, what the block can contain and the docs `entry` for
+/// its name when full.
+pub fn block_docs(
+    names: &[String],
+    name: &str,
+    schema: &BlockSchema,
+    style: HoverStyle,
+    entry: Option<Arc<str>>,
+) -> String {
+    let path: Vec<&str> = names[1..].iter().map(String::as_str).chain([name]).collect();
+    let mut md = format!("`{}` block in `{}`\n\n", path.join("."), names[0]);
+    match style {
+        HoverStyle::Concise => md.push_str(schema.doc.lines().next().unwrap_or_default()),
+        HoverStyle::Full => {
+            md.push_str(&format!("{}\n", schema.doc));
+            if let Some(reason) = &schema.deprecated {
+                md.push_str(&format!("\n**Deprecated:** {reason}\n"));
+            }
+            md.push_str(&contents(Body { arguments: &schema.arguments, blocks: &schema.blocks }));
+            if let Some(extra) = entry {
+                md.push_str(&format!("\n---\n\n{extra}"));
+            }
+        }
+    }
+    md.trim_end().to_string()
+}
+
+/// When something was introduced, deprecated and removed, one line each,
+/// with the schema's reason for deprecating it.
+fn lifecycle(reason: Option<&str>, availability: Availability) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(since) = availability.since {
+        lines.push(format!("Introduced in Alloy {since}."));
+    }
+    match (reason, availability.deprecated) {
+        (Some(reason), Some(version)) => lines.push(format!("**Deprecated in Alloy {version}:** {reason}")),
+        (Some(reason), None) => lines.push(format!("**Deprecated:** {reason}")),
+        (None, Some(version)) => lines.push(format!("**Deprecated in Alloy {version}.**")),
+        (None, None) => {}
+    }
+    if let Some(removed) = availability.removed {
+        lines.push(format!("Removed in Alloy {removed}."));
+    }
+    lines
+}
+
+/// A value of type `ty` to show in an example.
+fn placeholder(ty: &str) -> &'static str {
+    match ty {
+        _ if ty.starts_with("list(") => "[]",
+        _ if ty.starts_with("map(") => "{}",
+        "duration" => "\"1m\"",
+        "bool" => "false",
+        "number" | "int" => "0",
+        _ if ty.contains("string") || ty.contains("secret") => "\"\"",
+        _ => "null",
+    }
+}
+
+/// Hover for the value of a relabel rule's `action`: what the action does,
+/// the rule fields it reads and an example.
+pub fn relabel_action(doc: &Document, offset: usize, style: HoverStyle) -> Option<Hover> {
+    let file = doc.syntax();
+    let mut chain = Vec::new();
+    let attr = attribute_around(&file.body, offset, &mut chain)?;
+    if attr.name.name != "action" || !attr.value.span.contains(offset) || !relabel::is_rule(&chain) {
+        return None;
+    }
+    let action = relabel::action(attr.value.as_str()?)?;
+
+    let fields: Vec<String> = action.fields.iter().map(|f| format!("`{f}`")).collect();
+    let value = match style {
+        HoverStyle::Concise => format!("`{}` relabel action\n\n{}", action.name, action.summary),
+        HoverStyle::Full => format!(
+            "`{}` relabel action\n\n{}\n\nUses: {}\n\n```alloy\n{}\n```\n",
+            action.name,
+            action.summary,
+            fields.join(", "),
+            action.example
+        ),
+    };
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(doc.range(attr.value.span)),
+    })
+}
+
+/// Hover for a reference to another component's export, such as an entry
+/// of `forward_to`: what the target is, where it is defined and the type of
+/// the value that flows across the edge.
+pub fn component_reference(doc: &Document, uri: &Url, offset: usize, workspace: &Workspace) -> Option<Hover> {
+    let file = doc.syntax();
+    let mut chain = Vec::new();
+    let attr = attribute_around(&file.body, offset, &mut chain)?;
+    let expr = attr.value.references().into_iter().find(|e| e.span.contains(offset))?;
+    let reference = expr.path()?.join(".");
+    let (target_uri, component) = workspace.resolve(&reference)?;
+    let export = reference[component.id().len()..].trim_start_matches('.').split('.').next().unwrap_or_default();
+
+    let schema = Schema::builtin();
+    let line = component.range.start.line + 1;
+    let mut md = format!(
+        "`{}` component `\"{}\"`, defined at [{}:{line}]({target_uri}#L{line})\n\n",
+        component.name,
+        component.label,
+        workspace.display_path(target_uri)
+    );
+    if !export.is_empty() {
+        match schema.component(&component.name).and_then(|c| c.exports.get(export)) {
+            Some(e) => {
+                let ty = schema.capsule(&e.ty).unwrap_or(&e.ty);
+                md.push_str(&format!("`{export}`: `{ty}`\n\n"));
+                if let Some(capsule) = schema.capsules.get(ty).filter(|c| !c.doc.is_empty()) {
+                    md.push_str(&format!("{}\n\n", capsule.doc));
+                }
+                if !e.doc.is_empty() {
+                    md.push_str(&format!("{}\n", e.doc));
+                }
+            }
+            None if workspace.declare_from(uri, &component.name).is_some() => {
+                md.push_str(&format!("`{export}`: module export\n"));
+            }
+            None => {}
+        }
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: md }),
+        range: Some(doc.range(expr.span)),
+    })
+}
+
+/// Hover for the name of a module instance (`math.add "x" { … }`): where
+/// the module is declared, its doc comment and its arguments.
+pub fn module_instance(
+    doc: &Document,
+    uri: &Url,
+    offset: usize,
+    workspace: &Workspace,
+    style: HoverStyle,
+) -> Option<Hover> {
+    let block = block_with_name_at(&doc.syntax().body, offset)?;
+    let (declared_in, declare) = workspace.declare_from(uri, &block.full_name())?;
+
+    let line = declare.range.start.line + 1;
+    let mut md = format!(
+        "`{}`: module `{}`, declared at [{}:{line}]({declared_in}#L{line})\n\n",
+        block.full_name(),
+        declare.name,
+        workspace.display_path(declared_in)
+    );
+    match (&declare.doc, style) {
+        (Some(text), HoverStyle::Concise) => md.push_str(text.lines().next().unwrap_or_default()),
+        (Some(text), HoverStyle::Full) => md.push_str(&format!("{text}\n\n")),
+        (None, _) => {}
+    }
+    if style == HoverStyle::Full && !declare.arguments.is_empty() {
+        md.push_str("Arguments:\n");
+        for arg in &declare.arguments {
+            md.push_str(&format!("- `{}`", arg.name));
+            if arg.optional {
+                md.push_str(" (optional)");
+            }
+            if let Some(summary) = arg.doc.as_deref().and_then(|d| d.lines().next()) {
+                md.push_str(&format!(": {summary}"));
+            }
+            md.push('\n');
+        }
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: md }),
+        range: Some(doc.range(block.name_span())),
+    })
+}
+
+/// Hover for a module argument, either where it is used inside its
+/// `declare` (`argument.region.value`) or where a module instance sets it.
+pub fn module_argument(doc: &Document, uri: &Url, offset: usize, workspace: &Workspace) -> Option<Hover> {
+    let file = doc.syntax();
+    let mut chain = Vec::new();
+    let attr = attribute_around(&file.body, offset, &mut chain)?;
+
+    let (module, arg, span) = if attr.name.span.contains(offset) {
+        let instance = chain.last()?;
+        let (_, declare) = workspace.declare_from(uri, &instance.full_name())?;
+        let arg = declare.arguments.iter().find(|a| a.name == attr.name.name)?.clone();
+        (declare.name.clone(), arg, attr.name.span)
+    } else {
+        let expr = attr.value.references().into_iter().find(|e| e.span.contains(offset))?;
+        let name = match expr.path()?.as_slice() {
+            ["argument", name, ..] => name.to_string(),
+            _ => return None,
+        };
+        let declare = chain.iter().rev().find(|b| b.full_name() == "declare")?;
+        let block = declare
+            .blocks()
+            .find(|b| b.full_name() == "argument" && b.label.as_ref().is_some_and(|l| l.value == name))?;
+        let module = declare.label.as_ref()?.value.clone();
+        (module, ArgumentDef::from_block(doc, file, block)?, expr.span)
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: render_argument(&module, &arg),
+        }),
+        range: Some(doc.range(span)),
+    })
+}
+
+fn render_argument(module: &str, arg: &ArgumentDef) -> String {
+    let mut md = format!(
+        "`{}`: argument of module `{module}` ({})\n\n",
+        arg.name,
+        if arg.optional { "optional" } else { "required" }
+    );
+    if let Some(default) = &arg.default {
+        md.push_str(&format!("Default: `{default}`\n\n"));
+    }
+    if let Some(doc) = &arg.doc {
+        md.push_str(doc);
+        md.push('\n');
+    }
+    md
+}
+
+/// Finds the attribute (name or value) under `offset`, recording the blocks
+/// enclosing it in `chain`.
+fn attribute_around<'a>(body: &'a [Stmt], offset: usize, chain: &mut Vec<&'a Block>) -> Option<&'a Attribute> {
+    body.iter().find_map(|stmt| match stmt {
+        Stmt::Attribute(a) if a.span.contains(offset) => Some(a),
+        Stmt::Block(b) if b.span.contains(offset) => {
+            chain.push(b);
+            let found = attribute_around(&b.body, offset, chain);
+            if found.is_none() {
+                chain.pop();
+            }
+            found
+        }
+        _ => None,
+    })
+}
+
+/// Finds the attribute whose name is under `offset`, recording the blocks
+/// enclosing it (outermost first) in `chain`.
+fn attribute_at<'a>(body: &'a [Stmt], offset: usize, chain: &mut Vec<&'a Block>) -> Option<&'a Attribute> {
+    attribute_around(body, offset, chain).filter(|a| a.name.span.contains(offset))
+}
+
+/// Source text of `value`, with string literals masked when `secret`.
+/// Arguments to `env()`/`sys.env()` are variable names rather than secrets
+/// and are left alone.
+fn render_value(text: &str, value: &Expr, secret: bool) -> String {
+    fn literals(expr: &Expr, out: &mut Vec<Span>) {
+        match &expr.kind {
+            ExprKind::String(_) => out.push(expr.span),
+            ExprKind::Call(callee, _) if workspace::is_env_call(callee) => {}
+            _ => expr.for_each_child(&mut |child| literals(child, out)),
+        }
+    }
+
+    let source = &text[value.span.start..value.span.end];
+    if !secret {
+        return source.to_string();
+    }
+    let mut spans = Vec::new();
+    literals(value, &mut spans);
+    let mut out = String::new();
+    let mut pos = value.span.start;
+    for span in spans {
+        out.push_str(&text[pos..span.start]);
+        out.push_str(&format!("\"{MASK}\""));
+        pos = span.end;
+    }
+    out.push_str(&text[pos..value.span.end]);
+    out
+}
+
+fn block_with_label_at(body: &[Stmt], offset: usize) -> Option<&Block> {
+    body.iter().find_map(|stmt| {
+        let Stmt::Block(block) = stmt else { return None };
+        if !block.span.contains(offset) {
+            return None;
+        }
+        match &block.label {
+            Some(label) if label.span.contains(offset) => Some(block),
+            _ => block_with_label_at(&block.body, offset),
+        }
+    })
+}
+
+/// Records in `chain` the blocks down to the one whose name is under
+/// `offset`, outermost first; returns whether there is one.
+fn block_chain_at<'a>(body: &'a [Stmt], offset: usize, chain: &mut Vec<&'a Block>) -> bool {
+    let Some(block) = body.iter().find_map(|stmt| match stmt {
+        Stmt::Block(b) if b.span.contains(offset) => Some(b),
+        _ => None,
+    }) else {
+        return false;
+    };
+    chain.push(block);
+    block.name_span().contains(offset) || block_chain_at(&block.body, offset, chain)
+}
+
+fn block_with_name_at(body: &[Stmt], offset: usize) -> Option<&Block> {
+    body.iter().find_map(|stmt| {
+        let Stmt::Block(block) = stmt else { return None };
+        if !block.span.contains(offset) {
+            return None;
+        }
+        if block.name_span().contains(offset) {
+            Some(block)
+        } else {
+            block_with_name_at(&block.body, offset)
+        }
+    })
+}
@@ -0,0 +1,56 @@
+//! `textDocument/inlayHint`: the name of a block after its closing brace,
+//! for finding your way around long, deeply nested configs.
+
+use tower_lsp::lsp_types::*;
+
+use crate::document::Document;
+use crate::syntax::{Block, Stmt};
+use crate::workspace;
+
+/// Blocks shorter than this many lines get no hint; their header is in
+/// view anyway.
+const MIN_LINES: u32 = 5;
+
+/// `// prometheus.scrape.default` after the `}` of every block in `range`
+/// that spans at least [`MIN_LINES`] lines. Nested blocks show their own
+/// name, e.g. `// endpoint`.
+pub fn closing_braces(doc: &Document, range: Range) -> Vec<InlayHint> {
+    fn visit(doc: &Document, body: &[Stmt], range: Range, out: &mut Vec<InlayHint>) {
+        for stmt in body {
+            let Stmt::Block(block) = stmt else { continue };
+            let (start, end) = (doc.position(block.span.start), doc.position(block.span.end));
+            if end.line < range.start.line || start.line > range.end.line {
+                continue;
+            }
+            let closed = doc.text[..block.span.end].ends_with('}');
+            if closed && end.line + 1 >= start.line + MIN_LINES && end.line <= range.end.line {
+                out.push(InlayHint {
+                    position: end,
+                    label: InlayHintLabel::String(format!("// {}", name(block))),
+                    kind: None,
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: Some(true),
+                    padding_right: None,
+                    data: None,
+                });
+            }
+            visit(doc, &block.body, range, out);
+        }
+    }
+
+    let mut out = Vec::new();
+    visit(doc, &doc.syntax().body, range, &mut out);
+    out.sort_by_key(|h| (h.position.line, h.position.character));
+    out
+}
+
+/// The component ID for components, the block name (and label, if any)
+/// for everything else.
+fn name(block: &Block) -> String {
+    match (workspace::component_id(block), &block.label) {
+        (Some(id), _) => id,
+        (None, Some(label)) => format!("{} \"{}\"", block.full_name(), label.value),
+        (None, None) => block.full_name(),
+    }
+}
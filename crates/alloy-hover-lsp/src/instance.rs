@@ -0,0 +1,144 @@
+//! Opt-in integration with a running Alloy instance over its HTTP API.
+//! Calls are blocking; run them on a blocking task.
+
+use serde::Deserialize;
+use std::time::Duration;
+use tower_lsp::lsp_types::*;
+
+use crate::alloy_cli;
+use crate::config::Instance;
+use crate::document::Document;
+use crate::syntax::Stmt;
+use crate::workspace;
+
+pub const SOURCE: &str = "alloy-instance";
+/// The source of diagnostics for components the instance failed to run.
+pub const RUNTIME_SOURCE: &str = "alloy-runtime";
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn request(settings: &Instance, method: &str, path: &str) -> Option<ureq::Request> {
+    let base = settings.url.as_deref()?.trim_end_matches('/');
+    let mut req = ureq::request(method, &format!("{base}{path}")).timeout(TIMEOUT);
+    for (name, value) in &settings.headers {
+        req = req.set(name, value);
+    }
+    Some(req)
+}
+
+/// Sends `doc` to the instance for a dry-run evaluation. Returns `None`
+/// when live validation is off or the instance can't be reached, so stale
+/// results aren't replaced by an empty set just because the network hiccuped.
+pub fn validate(settings: &Instance, doc: &Document) -> Option<Vec<Diagnostic>> {
+    if !settings.live_validation {
+        return None;
+    }
+    let req = request(settings, "POST", &settings.validate_path)?;
+    let body = match req.set("Content-Type", "text/plain").send_string(&doc.text) {
+        Ok(_) => return Some(Vec::new()),
+        Err(ureq::Error::Status(_, resp)) => resp.into_string().ok()?,
+        Err(ureq::Error::Transport(_)) => return None,
+    };
+
+    let mut diagnostics = alloy_cli::parse_output(&body, None, SOURCE, doc);
+    if diagnostics.is_empty() && !body.trim().is_empty() {
+        // Not positional; still worth surfacing.
+        diagnostics.push(Diagnostic {
+            range: Range::default(),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some(SOURCE.into()),
+            message: body.trim().to_string(),
+            ..Default::default()
+        });
+    }
+    Some(diagnostics)
+}
+
+/// A component as the instance's components API reports it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Component {
+    #[serde(rename = "localID")]
+    pub local_id: String,
+    /// The module the component runs in; empty for the root config.
+    #[serde(rename = "moduleID", default)]
+    pub module_id: String,
+    #[serde(default)]
+    pub health: Health,
+    /// What the component chooses to expose about its state; its shape
+    /// depends on the component.
+    #[serde(default)]
+    pub debug_info: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Health {
+    /// `healthy`, `unhealthy`, `unknown` or `exited`.
+    pub state: String,
+    pub message: String,
+}
+
+/// What `prometheus.scrape` reports about its targets.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ScrapeStatus {
+    pub targets: Vec<TargetStatus>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TargetStatus {
+    pub url: String,
+    /// `up`, `down` or `unknown`.
+    pub health: String,
+    pub last_error: String,
+}
+
+impl Component {
+    pub fn scrape_status(&self) -> Option<ScrapeStatus> {
+        serde_json::from_value(self.debug_info.clone()?).ok()
+    }
+}
+
+/// The component with the ID `id`, if an instance is configured, knows it
+/// and answers.
+pub fn component(settings: &Instance, id: &str) -> Option<Component> {
+    let response = request(settings, "GET", &format!("/api/v0/web/components/{id}"))?.call().ok()?;
+    serde_json::from_str(&response.into_string().ok()?).ok()
+}
+
+/// Every component the instance runs.
+pub fn components(settings: &Instance) -> Option<Vec<Component>> {
+    let response = request(settings, "GET", "/api/v0/web/components")?.call().ok()?;
+    serde_json::from_str(&response.into_string().ok()?).ok()
+}
+
+/// A diagnostic on each component of `doc` that the instance reports as
+/// unhealthy or exited with a message, such as `failed to build component:
+/// invalid URL`. Components are matched by ID, so this assumes `doc` is the
+/// config the instance runs.
+pub fn runtime_errors(components: &[Component], doc: &Document) -> Vec<Diagnostic> {
+    let file = doc.syntax();
+    file.body
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Block(block) => Some((block, workspace::component_id(block)?)),
+            _ => None,
+        })
+        .filter_map(|(block, id)| {
+            let component = components.iter().find(|c| c.module_id.is_empty() && c.local_id == id)?;
+            let severity = match component.health.state.as_str() {
+                "unhealthy" => DiagnosticSeverity::ERROR,
+                "exited" if !component.health.message.is_empty() => DiagnosticSeverity::WARNING,
+                _ => return None,
+            };
+            Some(Diagnostic {
+                range: doc.range(block.name_span()),
+                severity: Some(severity),
+                source: Some(RUNTIME_SOURCE.into()),
+                message: format!("{id} is {}: {}", component.health.state, component.health.message),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,104 @@
+//! The component schema as a JSON Schema document, for tools outside the
+//! editor (CI linters, other editors) that want the same data.
+//!
+//! Alloy configs are not JSON, so the document describes the obvious JSON
+//! shape of one: component names at the top level, each mapping labels to
+//! an object of arguments, with nested blocks as arrays of objects.
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::schema::{Argument, BlockSchema, Schema};
+use crate::units;
+
+pub fn export(schema: &Schema) -> Value {
+    let components: Map<String, Value> = schema
+        .components
+        .iter()
+        .map(|(name, component)| {
+            let mut body = object(&component.arguments, &component.blocks);
+            if let Some(reason) = &component.deprecated {
+                body["deprecated"] = json!(true);
+                body["description"] = json!(format!("Deprecated: {reason}"));
+            }
+            if !component.exports.is_empty() {
+                let exports: Map<String, Value> = component
+                    .exports
+                    .iter()
+                    .map(|(name, e)| (name.clone(), json!({ "type": e.ty, "description": e.doc })))
+                    .collect();
+                body["x-alloy-exports"] = Value::Object(exports);
+            }
+            let instances = json!({
+                "type": "object",
+                "description": format!("`{name}` components, keyed by label."),
+                "additionalProperties": body,
+            });
+            (name.clone(), instances)
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Grafana Alloy components",
+        "type": "object",
+        "properties": components,
+    })
+}
+
+fn object(arguments: &BTreeMap<String, Argument>, blocks: &BTreeMap<String, BlockSchema>) -> Value {
+    let mut properties: Map<String, Value> = arguments.iter().map(|(name, arg)| (name.clone(), argument(arg))).collect();
+    for (name, block) in blocks {
+        let mut item = object(&block.arguments, &block.blocks);
+        if !block.doc.is_empty() {
+            item["description"] = json!(block.doc);
+        }
+        if block.deprecated.is_some() {
+            item["deprecated"] = json!(true);
+        }
+        properties.insert(name.clone(), json!({ "type": "array", "items": item }));
+    }
+    let required: Vec<&String> = arguments.iter().filter(|(_, a)| a.required).map(|(n, _)| n).collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn argument(arg: &Argument) -> Value {
+    let mut value = value_type(&arg.ty);
+    value["x-alloy-type"] = json!(arg.ty);
+    if !arg.doc.is_empty() {
+        value["description"] = json!(arg.doc);
+    }
+    if arg.deprecated.is_some() {
+        value["deprecated"] = json!(true);
+    }
+    // Defaults are Alloy syntax; the literal ones read the same as JSON.
+    if let Some(default) = arg.default.as_deref().and_then(|d| serde_json::from_str::<Value>(d).ok()) {
+        value["default"] = default;
+    }
+    value
+}
+
+/// Maps an Alloy type to JSON Schema. Capsules (receivers, consumers,
+/// handlers) have no JSON form and accept anything.
+fn value_type(ty: &str) -> Value {
+    if let Some(inner) = ty.strip_prefix("list(").and_then(|t| t.strip_suffix(')')) {
+        return json!({ "type": "array", "items": value_type(inner) });
+    }
+    if let Some(inner) = ty.strip_prefix("map(").and_then(|t| t.strip_suffix(')')) {
+        return json!({ "type": "object", "additionalProperties": value_type(inner) });
+    }
+    match ty {
+        "string" | "secret" | "string or secret" => json!({ "type": "string" }),
+        "duration" => json!({ "type": "string", "pattern": units::DURATION_PATTERN }),
+        "bytes" => json!({ "type": "string", "pattern": units::BYTES_PATTERN }),
+        "number" => json!({ "type": "number" }),
+        "int" => json!({ "type": "integer" }),
+        "bool" => json!({ "type": "boolean" }),
+        _ => json!({}),
+    }
+}
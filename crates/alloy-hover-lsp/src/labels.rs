@@ -0,0 +1,90 @@
+//! Labels that mean something to scraping: the keys of target maps that
+//! `prometheus.scrape` reads, and the ones it sets on what it scrapes.
+
+pub struct Label {
+    /// `<name>` stands for a part filled in per use, as in `__param_<name>`.
+    pub name: &'static str,
+    pub doc: &'static str,
+    /// Whether targets have the label; `__name__` is only on series.
+    pub on_targets: bool,
+    /// Whether scraped series have it; `__address__` and the like are
+    /// dropped once the target is scraped.
+    pub on_series: bool,
+}
+
+pub const WELL_KNOWN: &[Label] = &[
+    Label {
+        name: "__address__",
+        doc: "The `host:port` to scrape. Every target needs one; `instance` defaults to it.",
+        on_targets: true,
+        on_series: false,
+    },
+    Label {
+        name: "__metrics_path__",
+        doc: "The HTTP path to scrape, overriding the component's `metrics_path` (`/metrics`).",
+        on_targets: true,
+        on_series: false,
+    },
+    Label {
+        name: "__scheme__",
+        doc: "`http` or `https`, overriding the component's `scheme`.",
+        on_targets: true,
+        on_series: false,
+    },
+    Label {
+        name: "__scrape_interval__",
+        doc: "How often to scrape this target, overriding the component's `scrape_interval`.",
+        on_targets: true,
+        on_series: false,
+    },
+    Label {
+        name: "__scrape_timeout__",
+        doc: "The scrape timeout for this target, overriding the component's `scrape_timeout`.",
+        on_targets: true,
+        on_series: false,
+    },
+    Label {
+        name: "__param_<name>",
+        doc: "Sets the URL parameter `<name>` of the scrape request, e.g. `__param_module` for an exporter that probes.",
+        on_targets: true,
+        on_series: false,
+    },
+    Label {
+        name: "job",
+        doc: "The job the series belong to. Defaults to the scrape component's `job_name`, or its ID.",
+        on_targets: true,
+        on_series: true,
+    },
+    Label {
+        name: "instance",
+        doc: "The instance the series come from. Defaults to `__address__` once relabeling is done.",
+        on_targets: true,
+        on_series: true,
+    },
+    Label {
+        name: "__name__",
+        doc: "The metric name of a series. Only series have it, so only metric relabeling sees it.",
+        on_targets: false,
+        on_series: true,
+    },
+];
+
+
+/// The well-known label `name` is, templated ones matching any suffix.
+pub fn lookup(name: &str) -> Option<&'static Label> {
+    WELL_KNOWN.iter().find(|label| match label.name.split_once('<') {
+        Some((fixed, _)) => name.len() > fixed.len() && name.starts_with(fixed),
+        None => label.name == name,
+    })
+}
+
+/// The well-known labels of what the `component` relabels: targets for
+/// `discovery.relabel`, series for `prometheus.relabel`.
+pub fn relabeled_by(component: &str) -> impl Iterator<Item = &'static Label> {
+    let (targets, series) = match component {
+        "discovery.relabel" => (true, false),
+        "prometheus.relabel" => (false, true),
+        _ => (false, false),
+    };
+    WELL_KNOWN.iter().filter(move |label| (targets && label.on_targets) || (series && label.on_series))
+}
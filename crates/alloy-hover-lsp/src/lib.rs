@@ -0,0 +1,45 @@
+//! Language analysis for Alloy configuration files and the language server
+//! built on it; the `alloy-hover-lsp` binary wires [`server`] to stdio.
+
+pub mod alloy_cli;
+pub mod call_hierarchy;
+pub mod capabilities;
+pub mod catch_panic;
+pub mod cloud_endpoints;
+pub mod completion;
+pub mod config;
+pub mod convert;
+pub mod definition;
+pub mod docs;
+pub mod docs_check;
+pub mod docs_coverage;
+pub mod document;
+pub mod folding;
+pub mod graph;
+pub mod hover;
+pub mod inlay_hints;
+pub mod instance;
+pub mod json_schema;
+pub mod labels;
+pub mod linked_editing;
+pub mod lint;
+pub mod lint_config;
+pub mod markdown;
+pub mod metrics_api;
+pub mod modules;
+pub mod registration;
+pub mod relabel;
+pub mod releases;
+pub mod schema;
+pub mod semantic_tokens;
+pub mod server;
+pub mod snippets;
+pub mod status;
+pub mod stdlib;
+pub mod style;
+pub mod suppress;
+pub mod symbols;
+pub mod syntax;
+pub mod trace;
+pub mod units;
+pub mod workspace;
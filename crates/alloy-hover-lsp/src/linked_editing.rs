@@ -0,0 +1,64 @@
+//! `textDocument/linkedEditingRange`: editing a component label also edits
+//! the label inside every same-file reference to that component.
+
+use tower_lsp::lsp_types::*;
+
+use crate::document::Document;
+use crate::syntax::{Block, Expr, Span, Stmt};
+use crate::workspace;
+
+/// Labels are plain identifiers; anything else ends the linked edit.
+const LABEL_PATTERN: &str = "[A-Za-z0-9_]+";
+
+pub fn ranges(doc: &Document, offset: usize) -> Option<LinkedEditingRanges> {
+    let file = doc.syntax();
+    let mut components = Vec::new();
+    collect_components(&file.body, &mut components);
+    let mut paths = Vec::new();
+    for stmt in &file.body {
+        collect_paths_in(stmt, &mut paths);
+    }
+
+    components.into_iter().find_map(|block| {
+        let label = block.label.as_ref()?;
+        let name: Vec<&str> = block.name.iter().map(|i| i.name.as_str()).collect();
+        // Just the text between the quotes.
+        let inner = Span::new(label.span.start + 1, label.span.end - 1);
+
+        let mut spans = vec![inner];
+        for path in &paths {
+            let n = name.len();
+            let matches = path.len() > n
+                && path[..n].iter().map(|(s, _)| *s).eq(name.iter().copied())
+                && path[n].0 == label.value;
+            if matches {
+                spans.push(path[n].1);
+            }
+        }
+        if spans.len() < 2 || !spans.iter().any(|s| s.contains(offset)) {
+            return None;
+        }
+        Some(LinkedEditingRanges {
+            ranges: spans.into_iter().map(|s| doc.range(s)).collect(),
+            word_pattern: Some(LABEL_PATTERN.into()),
+        })
+    })
+}
+
+fn collect_components<'a>(body: &'a [Stmt], out: &mut Vec<&'a Block>) {
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        if workspace::component_id(block).is_some() {
+            out.push(block);
+        } else if block.full_name() == "declare" {
+            collect_components(&block.body, out);
+        }
+    }
+}
+
+fn collect_paths_in<'a>(stmt: &'a Stmt, out: &mut Vec<Vec<(&'a str, Span)>>) {
+    match stmt {
+        Stmt::Attribute(a) => out.extend(a.value.references().into_iter().filter_map(Expr::path_spans)),
+        Stmt::Block(b) => b.body.iter().for_each(|s| collect_paths_in(s, out)),
+    }
+}
@@ -0,0 +1,942 @@
+//! Checks the server runs itself on every change, as opposed to the
+//! external validators in `alloy_cli` and `instance`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use tower_lsp::lsp_types::*;
+
+use crate::config::Severity;
+use crate::document::Document;
+use crate::relabel::{self, RegexIssue};
+use crate::releases::TargetRelease;
+use crate::schema::Schema;
+use crate::stdlib;
+use crate::style;
+use crate::suppress;
+use crate::syntax::lexer::{tokenize, TokenKind};
+use crate::syntax::{self, Attribute, Block, ExprKind, Stmt};
+use crate::units;
+use crate::workspace::{self, Workspace};
+
+pub const SOURCE: &str = "alloy-hover";
+
+pub const SYNTAX_ERROR: &str = "syntax-error";
+pub const PLAINTEXT_SECRET: &str = "plaintext-secret";
+pub const UNKNOWN_REFERENCE: &str = "unknown-reference";
+pub const UNKNOWN_COMPONENT: &str = "unknown-component";
+pub const UNKNOWN_EXPORT: &str = "unknown-export";
+pub const UNKNOWN_BLOCK: &str = "unknown-block";
+pub const MISSING_BLOCK: &str = "missing-block";
+pub const REPEATED_BLOCK: &str = "repeated-block";
+pub const INVALID_DURATION: &str = "invalid-duration";
+pub const INVALID_BYTE_SIZE: &str = "invalid-byte-size";
+pub const INVALID_REGEX: &str = "invalid-regex";
+pub const INVALID_ESCAPE: &str = "invalid-escape";
+pub const UNESCAPED_DOT: &str = "unescaped-dot";
+pub const INCOMPATIBLE_TYPE: &str = "incompatible-type";
+pub const UNWIRED_PRODUCER: &str = "unwired-producer";
+pub const UNUSED_COMPONENT: &str = "unused-component";
+pub const UNUSED_MODULE: &str = "unused-module";
+pub const NO_PRODUCERS: &str = "no-producers";
+pub const UNUSED_SUPPRESSION: &str = "unused-suppression";
+pub const UNAVAILABLE: &str = "unavailable";
+pub const NOT_IMPORTED: &str = "not-imported";
+pub const DUPLICATE_KEY: &str = "duplicate-key";
+pub const MISSING_FINAL_NEWLINE: &str = "missing-final-newline";
+pub const ASSIGNMENT_SPACING: &str = "assignment-spacing";
+pub const BLANK_LINES: &str = "blank-lines";
+pub const MISSING_TRAILING_COMMA: &str = "missing-trailing-comma";
+
+/// A diagnostic plus, when there is an obvious one, the edit that fixes it.
+pub struct Finding {
+    pub diagnostic: Diagnostic,
+    /// Quick fixes, the preferred one first.
+    pub fixes: Vec<Fix>,
+}
+
+pub struct Fix {
+    pub title: String,
+    /// Edits to the file the finding is in.
+    pub edits: Vec<TextEdit>,
+    /// Edits to other files, such as references to a component the fix
+    /// renames.
+    pub elsewhere: HashMap<Url, Vec<TextEdit>>,
+}
+
+impl Fix {
+    pub fn new(title: impl Into<String>, edits: Vec<TextEdit>) -> Self {
+        Self { title: title.into(), edits, elsewhere: HashMap::new() }
+    }
+}
+
+/// Runs every check. `target` is the Alloy release the file has to load
+/// on, if one is configured.
+pub fn check(doc: &Document, uri: &Url, workspace: &Workspace, target: Option<TargetRelease>) -> Vec<Finding> {
+    let file = doc.syntax();
+    let local = Local::collect(&file.body);
+    let cx = Context { doc, uri, workspace, local: &local, target };
+    let mut findings: Vec<Finding> =
+        file.errors.iter().map(|err| error(doc, err.span, SYNTAX_ERROR, err.message.clone())).collect();
+    visit(&file.body, &mut Vec::new(), &mut |chain, attr| {
+        plaintext_secret(doc, chain, attr, &mut findings);
+        malformed_units(doc, chain, attr, &mut findings);
+        relabel_regex(doc, chain, attr, &mut findings);
+        unknown_reference(&cx, chain, attr, &mut findings);
+        capsule_types(&cx, chain, attr, &mut findings);
+        unavailable_argument(&cx, chain, attr, &mut findings);
+        duplicate_keys(&cx, &attr.value, &mut findings);
+    });
+    unknown_module_component(&cx, &file.body, &mut findings);
+    misspelled_component(&cx, &file.body, &mut findings);
+    unknown_block(doc, &file.body, &mut Vec::new(), &mut findings);
+    block_count(doc, &file.body, &mut Vec::new(), &mut findings);
+    unwired_producer(&cx, &file.body, &mut findings);
+    unused_component(&cx, &file.body, &mut findings);
+    unused_module(&cx, &file.body, &mut findings);
+    unavailable_component(&cx, &file.body, &mut findings);
+    not_imported(&cx, &file.body, &mut findings);
+    invalid_escapes(doc, &mut findings);
+    style::check(doc, &mut findings);
+    suppress::apply(doc, findings)
+}
+
+/// Applies configured severities, keyed by rule name (see [`rule_name`]):
+/// findings of rules that are `off` are dropped, the others re-levelled.
+pub fn configure(findings: Vec<Finding>, rules: &BTreeMap<String, Severity>) -> Vec<Finding> {
+    if rules.is_empty() {
+        return findings;
+    }
+    findings
+        .into_iter()
+        .filter_map(|mut finding| {
+            let code = match &finding.diagnostic.code {
+                Some(NumberOrString::String(code)) => code,
+                _ => return Some(finding),
+            };
+            let severity = match rules.get(&rule_name(code)).or_else(|| rules.get(code)) {
+                None => return Some(finding),
+                Some(Severity::Off) => return None,
+                Some(Severity::Hint) => DiagnosticSeverity::HINT,
+                Some(Severity::Info) => DiagnosticSeverity::INFORMATION,
+                Some(Severity::Warning) => DiagnosticSeverity::WARNING,
+                Some(Severity::Error) => DiagnosticSeverity::ERROR,
+            };
+            finding.diagnostic.severity = Some(severity);
+            Some(finding)
+        })
+        .collect()
+}
+
+/// The name a rule is configured by: its code in camelCase, e.g.
+/// `plaintextSecret` for `plaintext-secret`.
+pub fn rule_name(code: &str) -> String {
+    let mut words = code.split('-');
+    let first = words.next().unwrap_or_default().to_string();
+    words.fold(first, |mut name, word| {
+        let mut chars = word.chars();
+        name.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        name.push_str(chars.as_str());
+        name
+    })
+}
+
+/// What the checks need to know beyond the statement at hand.
+struct Context<'a> {
+    doc: &'a Document,
+    uri: &'a Url,
+    workspace: &'a Workspace,
+    local: &'a Local,
+    target: Option<TargetRelease<'a>>,
+}
+
+/// Names defined in the file being checked, wherever they are nested.
+#[derive(Default)]
+struct Local {
+    /// Component IDs, including those inside `declare` and `foreach` bodies
+    /// that the workspace index does not expose.
+    components: BTreeSet<String>,
+    /// Labels of `import.*` blocks: their modules are not indexed, so
+    /// anything under these namespaces can't be checked.
+    imports: BTreeSet<String>,
+    /// Names of `declare` blocks, at any depth.
+    declares: BTreeSet<String>,
+}
+
+impl Local {
+    fn collect(body: &[Stmt]) -> Self {
+        fn walk(body: &[Stmt], out: &mut Local) {
+            for block in body.iter().filter_map(|s| match s {
+                Stmt::Block(b) => Some(b),
+                Stmt::Attribute(_) => None,
+            }) {
+                if let Some(id) = workspace::component_id(block) {
+                    out.components.insert(id);
+                }
+                if let (true, Some(label)) = (block.full_name().starts_with("import."), &block.label) {
+                    out.imports.insert(label.value.clone());
+                }
+                if let ("declare", Some(label)) = (block.full_name().as_str(), &block.label) {
+                    out.declares.insert(label.value.clone());
+                }
+                walk(&block.body, out);
+            }
+        }
+        let mut local = Local::default();
+        walk(body, &mut local);
+        local
+    }
+}
+
+/// Symbols that are in scope because of the blocks enclosing a statement
+/// rather than because a component defines them.
+#[derive(Debug, Default)]
+pub struct BlockScope {
+    /// Arguments of the innermost enclosing `declare`; `None` outside one.
+    pub arguments: Option<Vec<String>>,
+    /// Loop variables of enclosing `foreach` blocks, visible inside their
+    /// `template`.
+    pub loop_vars: Vec<String>,
+}
+
+impl BlockScope {
+    pub fn of(chain: &[&Block]) -> Self {
+        let mut scope = BlockScope::default();
+        for (i, block) in chain.iter().enumerate() {
+            match block.full_name().as_str() {
+                "declare" => {
+                    let args = block.blocks().filter(|b| b.full_name() == "argument");
+                    scope.arguments = Some(args.filter_map(|b| b.label.as_ref().map(|l| l.value.clone())).collect());
+                }
+                "foreach" if chain.get(i + 1).is_some_and(|b| b.full_name() == "template") => {
+                    if let Some(var) = block.attribute("var").and_then(|a| a.value.as_str()) {
+                        scope.loop_vars.push(var.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        scope
+    }
+}
+
+/// Calls `f` for every attribute along with the blocks enclosing it.
+fn visit<'a>(body: &'a [Stmt], chain: &mut Vec<&'a Block>, f: &mut impl FnMut(&[&'a Block], &'a Attribute)) {
+    for stmt in body {
+        match stmt {
+            Stmt::Attribute(attr) => f(chain, attr),
+            Stmt::Block(block) => {
+                chain.push(block);
+                visit(&block.body, chain, f);
+                chain.pop();
+            }
+        }
+    }
+}
+
+/// Secret-typed attributes should be loaded from somewhere (`env()`,
+/// `local.file`, `remote.*`) rather than committed as a string literal.
+fn plaintext_secret(doc: &Document, chain: &[&Block], attr: &Attribute, out: &mut Vec<Finding>) {
+    if !matches!(attr.value.kind, ExprKind::String(_)) {
+        return;
+    }
+    if !Schema::builtin().argument_in(chain, &attr.name.name).is_some_and(|a| a.is_secret()) {
+        return;
+    }
+
+    let range = doc.range(attr.value.span);
+    let label = chain.iter().rev().find_map(|b| b.label.as_ref()).map(|l| l.value.as_str());
+    let var = env_var_name(label, &attr.name.name);
+    out.push(Finding {
+        diagnostic: Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(PLAINTEXT_SECRET.into())),
+            source: Some(SOURCE.into()),
+            message: format!(
+                "`{}` is a secret but is set to a plaintext string; load it with `env()`, `local.file` or a `remote.*` component instead",
+                attr.name.name
+            ),
+            ..Default::default()
+        },
+        fixes: vec![Fix::new(
+            format!("Read `{}` from env(\"{var}\")", attr.name.name),
+            vec![TextEdit { range, new_text: format!("env(\"{var}\")") }],
+        )],
+    });
+}
+
+/// `duration` and `bytes` strings are only parsed when Alloy loads the
+/// config; catch typos like `"15x"` or `"1MiBs"` while editing.
+fn malformed_units(doc: &Document, chain: &[&Block], attr: &Attribute, out: &mut Vec<Finding>) {
+    let ExprKind::String(value) = &attr.value.kind else { return };
+    let Some(arg) = Schema::builtin().argument_in(chain, &attr.name.name) else { return };
+    let (code, result) = match arg.ty.as_str() {
+        "duration" => (INVALID_DURATION, units::parse_duration(value)),
+        "bytes" => (INVALID_BYTE_SIZE, units::parse_bytes(value)),
+        _ => return,
+    };
+    if let Err(reason) = result {
+        out.push(error(doc, attr.value.span, code, format!("invalid `{}` value \"{value}\": {reason}", attr.name.name)));
+    }
+}
+
+/// A key repeated within one object literal: the later value silently
+/// wins, so it is almost always a copy-paste slip. The fix drops the
+/// earlier entry, which changes nothing about what Alloy loads.
+fn duplicate_keys(cx: &Context, value: &syntax::Expr, out: &mut Vec<Finding>) {
+    value.for_each_child(&mut |child| duplicate_keys(cx, child, out));
+    let ExprKind::Object(fields) = &value.kind else { return };
+    for (i, field) in fields.iter().enumerate() {
+        let Some(earlier) = fields[..i].iter().rposition(|f| f.key == field.key) else { continue };
+        let message = format!("`{}` is set more than once in this object; the last value wins", field.key);
+        let mut finding = warning(cx.doc, field.key_span, DUPLICATE_KEY, message);
+        let first = cx.doc.range(fields[earlier].key_span);
+        finding.diagnostic.related_information = Some(vec![DiagnosticRelatedInformation {
+            location: Location::new(cx.uri.clone(), first),
+            message: "earlier value".into(),
+        }]);
+        let removed = Range::new(first.start, cx.doc.position(fields[earlier + 1].key_span.start));
+        let edits = vec![TextEdit { range: removed, new_text: String::new() }];
+        finding.fixes.push(Fix::new(format!("Remove the earlier `{}`", field.key), edits));
+        out.push(finding);
+    }
+}
+
+/// Escape sequences Alloy refuses to load, in any quoted string. A
+/// backslash escaping nothing is usually a regex's, as in `"\d+"`; the fix
+/// doubles it.
+fn invalid_escapes(doc: &Document, out: &mut Vec<Finding>) {
+    for token in tokenize(&doc.text).into_iter().filter(|t| t.kind == TokenKind::String) {
+        let raw = &doc.text[token.span.start..token.span.end];
+        for escape in syntax::bad_escapes(raw) {
+            let span = syntax::Span::new(token.span.start + escape.span.start, token.span.start + escape.span.end);
+            let mut finding = error(doc, span, INVALID_ESCAPE, escape.message);
+            if escape.unknown {
+                let at = doc.position(span.start);
+                let edits = vec![TextEdit { range: Range::new(at, at), new_text: "\\".into() }];
+                finding.fixes.push(Fix::new("Escape the backslash", edits));
+            }
+            out.push(finding);
+        }
+    }
+}
+
+/// Relabel rule regexes are compiled when the component starts; report
+/// syntax errors now, and dots that were probably meant literally.
+fn relabel_regex(doc: &Document, chain: &[&Block], attr: &Attribute, out: &mut Vec<Finding>) {
+    if attr.name.name != "regex" || !relabel::is_rule(chain) {
+        return;
+    }
+    let ExprKind::String(pattern) = &attr.value.kind else { return };
+    let raw = &doc.text[attr.value.span.start..attr.value.span.end];
+    let at = |offset| attr.value.span.start + syntax::source_offset(raw, offset);
+    for issue in relabel::check_regex(pattern) {
+        match issue {
+            RegexIssue::Syntax { range, message } => {
+                let (start, end) = (at(range.start), at(range.end));
+                // Errors at a single point still get a character to underline.
+                let end = if end > start { end } else { start + doc.text[start..].chars().next().map_or(0, char::len_utf8) };
+                let span = syntax::Span::new(start, end);
+                out.push(error(doc, span, INVALID_REGEX, format!("invalid regex: {message}")));
+            }
+            RegexIssue::UnescapedDot { offset } => {
+                let start = at(offset);
+                let range = doc.range(syntax::Span::new(start, start + 1));
+                // The regex needs `\.`; inside a quoted string that is `\\.`.
+                let escape = if raw.starts_with('`') { "\\" } else { "\\\\" };
+                out.push(Finding {
+                    diagnostic: Diagnostic {
+                        range,
+                        severity: Some(DiagnosticSeverity::WARNING),
+                        code: Some(NumberOrString::String(UNESCAPED_DOT.into())),
+                        source: Some(SOURCE.into()),
+                        message: "`.` matches any character; escape it to match a literal dot".into(),
+                        ..Default::default()
+                    },
+                    fixes: vec![Fix::new(
+                        "Escape the dot",
+                        vec![TextEdit { range: Range::new(range.start, range.start), new_text: escape.into() }],
+                    )],
+                });
+            }
+        }
+    }
+}
+
+/// Every reference must name a component, a module argument, a loop
+/// variable or a standard library value. References into module instances
+/// must also name one of the module's exports.
+fn unknown_reference(cx: &Context, chain: &[&Block], attr: &Attribute, out: &mut Vec<Finding>) {
+    let scope = BlockScope::of(chain);
+    for expr in attr.value.references() {
+        let path = expr.path().unwrap_or_default();
+        let (code, message) = match path.as_slice() {
+            [] => continue,
+            ["argument", rest @ ..] => match (&scope.arguments, rest.first()) {
+                (None, _) => (UNKNOWN_REFERENCE, "`argument` is only available inside a `declare` block".to_string()),
+                (Some(args), Some(name)) if !args.iter().any(|a| a == name) => {
+                    (UNKNOWN_REFERENCE, format!("the enclosing `declare` has no argument `{name}`"))
+                }
+                _ => continue,
+            },
+            [first, ..] if scope.loop_vars.iter().any(|v| v == first) => continue,
+            [first, ..] if stdlib::is_root(first) => continue,
+            // Modules we could not load can't be checked.
+            [first, ..] if cx.local.imports.contains(*first) && cx.workspace.module(cx.uri, first).is_none() => continue,
+            _ => {
+                let joined = path.join(".");
+                let is_prefix = |id: &str| {
+                    joined.strip_prefix(id).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+                };
+                if let Some((_, component)) = cx.workspace.resolve(&joined) {
+                    let export = joined[component.id().len()..].trim_start_matches('.').split('.').next();
+                    let declare = cx.workspace.declare_from(cx.uri, &component.name);
+                    match (export, declare) {
+                        (Some(export), Some((_, d))) if !export.is_empty() && !d.exports.iter().any(|e| e == export) => (
+                            UNKNOWN_EXPORT,
+                            format!("module component `{}` has no export `{export}`", component.name),
+                        ),
+                        _ => continue,
+                    }
+                } else if cx.local.components.iter().any(|id| is_prefix(id)) {
+                    continue;
+                } else {
+                    (
+                        UNKNOWN_REFERENCE,
+                        format!("unknown reference `{joined}`: no component, argument or loop variable by that name is in scope"),
+                    )
+                }
+            }
+        };
+        out.push(error(cx.doc, expr.span, code, message));
+    }
+}
+
+/// References assigned to an argument of a capsule type, or to a list of
+/// one, must be exports of that type: logs cannot be forwarded into a
+/// metrics receiver. Exports whose type the schema does not know pass.
+fn capsule_types(cx: &Context, chain: &[&Block], attr: &Attribute, out: &mut Vec<Finding>) {
+    let schema = Schema::builtin();
+    let Some(argument) = schema.argument_in(chain, &attr.name.name) else { return };
+    let (expected, values) = match argument.ty.strip_prefix("list(").and_then(|t| t.strip_suffix(')')) {
+        Some(element) => match &attr.value.kind {
+            ExprKind::Array(items) => (element, items.iter().collect()),
+            _ => return,
+        },
+        None => (argument.ty.as_str(), vec![&attr.value]),
+    };
+    let Some(expected) = schema.capsule(expected) else { return };
+    for value in values {
+        let Some(path) = value.path() else { continue };
+        let joined = path.join(".");
+        let Some((_, component)) = cx.workspace.resolve(&joined) else { continue };
+        let export = joined[component.id().len()..].trim_start_matches('.');
+        let Some(found) = schema.component(&component.name).and_then(|c| c.exports.get(export)) else { continue };
+        let found = schema.capsule(&found.ty).unwrap_or(&found.ty);
+        if found != expected {
+            let message = format!("`{joined}` is a `{found}`, but `{}` takes `{expected}`", attr.name.name);
+            out.push(error(cx.doc, value.span, INCOMPATIBLE_TYPE, message));
+        }
+    }
+}
+
+/// Instances of `namespace.name` for a loaded remote module must name one
+/// of its `declare` blocks.
+fn unknown_module_component(cx: &Context, body: &[Stmt], out: &mut Vec<Finding>) {
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        if let [namespace, name] = block.name.as_slice() {
+            if let Some(declares) = cx.workspace.module(cx.uri, &namespace.name) {
+                if !declares.iter().any(|(_, d)| d.name == name.name) {
+                    let message = format!("module `{}` has no component `{}`", namespace.name, name.name);
+                    let suggestion = closest(&name.name, declares.iter().map(|(_, d)| d.name.as_str()))
+                        .map(|d| format!("{}.{d}", namespace.name));
+                    out.push(did_you_mean(error(cx.doc, block.name_span(), UNKNOWN_COMPONENT, message), suggestion));
+                }
+            }
+        }
+        unknown_module_component(cx, &block.body, out);
+    }
+}
+
+/// Component blocks whose name is not in the schema but is a likely typo
+/// of one that is. The schema does not cover every component, so names
+/// that are not close to a known one are left alone.
+fn misspelled_component(cx: &Context, body: &[Stmt], out: &mut Vec<Finding>) {
+    let schema = Schema::builtin();
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        let name = block.full_name();
+        let first = block.name.first().map_or("", |i| i.name.as_str());
+        let known = schema.component(&name).is_some()
+            || workspace::component_id(block).is_none()
+            || cx.local.imports.contains(first)
+            || cx.workspace.declare_from(cx.uri, &name).is_some()
+            || cx.workspace.declare(&name).is_some();
+        if !known {
+            if let Some(suggestion) = closest(&name, schema.components.keys().map(String::as_str)) {
+                let message = format!("unknown component `{name}`");
+                let finding = error(cx.doc, block.name_span(), UNKNOWN_COMPONENT, message);
+                let mut finding = did_you_mean(finding, Some(suggestion.to_string()));
+                if let (Some(label), Some(fix)) = (&block.label, finding.fixes.last_mut()) {
+                    let (old, new) = (format!("{name}.{}", label.value), format!("{suggestion}.{}", label.value));
+                    rename_references(cx, &old, &new, fix);
+                }
+                out.push(finding);
+            }
+        }
+        misspelled_component(cx, &block.body, out);
+    }
+}
+
+/// Blocks nested somewhere their component does not define them. Only
+/// bodies the schema lists blocks for are checked, as a component or block
+/// without any may just not be described yet.
+fn unknown_block(doc: &Document, body: &[Stmt], chain: &mut Vec<String>, out: &mut Vec<Finding>) {
+    let schema = Schema::builtin();
+    let allowed = schema.body_in(chain).map(|b| b.blocks).filter(|blocks| !blocks.is_empty());
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        let name = block.full_name();
+        if let Some(allowed) = allowed.filter(|allowed| !allowed.contains_key(&name)) {
+            let message = format!("`{name}` is not a block of {}", block_path(chain));
+            let finding = error(doc, block.name_span(), UNKNOWN_BLOCK, message);
+            out.push(did_you_mean(finding, closest(&name, allowed.keys().map(String::as_str)).map(str::to_string)));
+            continue;
+        }
+        chain.push(name);
+        unknown_block(doc, &block.body, chain, out);
+        chain.pop();
+    }
+}
+
+/// Nested blocks given fewer or more times than their schema allows,
+/// reported on the block they are missing from or repeated in.
+fn block_count(doc: &Document, body: &[Stmt], chain: &mut Vec<String>, out: &mut Vec<Finding>) {
+    let schema = Schema::builtin();
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        chain.push(block.full_name());
+        for (name, nested) in schema.body_in(chain).map(|b| b.blocks).into_iter().flatten() {
+            let count = block.blocks().filter(|b| b.full_name() == *name).count();
+            if count == 0 && nested.required {
+                let message = format!("{} is missing its required `{name}` block", block_path(chain));
+                let mut finding = error(doc, block.name_span(), MISSING_BLOCK, message);
+                // An unclosed block has no `}` to add the new one before.
+                if doc.text[..block.span.end].ends_with('}') {
+                    let edit = insert_statement(doc, block, &format!("{name} {{\n}}"));
+                    finding.fixes.push(Fix::new(format!("Add the `{name}` block"), vec![edit]));
+                }
+                out.push(finding);
+            } else if count > 1 && !nested.repeatable {
+                let message = format!("{} has {count} `{name}` blocks; only one is allowed", block_path(chain));
+                out.push(error(doc, block.name_span(), REPEATED_BLOCK, message));
+            }
+        }
+        block_count(doc, &block.body, chain, out);
+        chain.pop();
+    }
+}
+
+/// Names the body at the end of `chain` for a message: "`grpc` in
+/// `otelcol.receiver.otlp`", or just the component for its own body.
+fn block_path(chain: &[String]) -> String {
+    let schema = Schema::builtin();
+    let owner = chain.iter().rposition(|b| schema.component(b).is_some()).unwrap_or_default();
+    match &chain[owner + 1..] {
+        [] => format!("`{}`", chain[owner]),
+        path => format!("`{}` in `{}`", path.join("."), chain[owner]),
+    }
+}
+
+/// Components the target release does not have, being newer or already
+/// removed; a config using them would not load on it.
+fn unavailable_component(cx: &Context, body: &[Stmt], out: &mut Vec<Finding>) {
+    let Some(target) = cx.target else { return };
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        let name = block.full_name();
+        if workspace::component_id(block).is_some() {
+            if let Some(why) = target.releases.component(&name).missing_from(target.version) {
+                let message = format!("`{name}` {why}, but the target version is {}", target.version);
+                out.push(error(cx.doc, block.name_span(), UNAVAILABLE, message));
+            }
+        }
+        unavailable_component(cx, &block.body, out);
+    }
+}
+
+/// Arguments the target release does not have.
+fn unavailable_argument(cx: &Context, chain: &[&Block], attr: &Attribute, out: &mut Vec<Finding>) {
+    let Some(target) = cx.target else { return };
+    if let Some(why) = target.releases.argument_in(chain, &attr.name.name).missing_from(target.version) {
+        let message = format!("`{}` {why}, but the target version is {}", attr.name.name, target.version);
+        out.push(error(cx.doc, attr.name.span, UNAVAILABLE, message));
+    }
+}
+
+/// Module components used by their bare name while declared in another
+/// file; Alloy only sees them through an import. The fix adds an
+/// `import.file` for that file, or reuses one, and qualifies the name.
+fn not_imported(cx: &Context, body: &[Stmt], out: &mut Vec<Finding>) {
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        not_imported(cx, &block.body, out);
+        let name = block.full_name();
+        if name.contains('.') || block.label.is_none() || cx.local.declares.contains(&name) {
+            continue;
+        }
+        let Some((declared_in, _)) = cx.workspace.declare_from(cx.uri, &name) else { continue };
+        if declared_in == cx.uri {
+            continue;
+        }
+        let shown = cx.workspace.display_path(declared_in);
+        let message = format!("`{name}` is declared in `{shown}`, which this file does not import");
+        let mut finding = warning(cx.doc, block.name_span(), NOT_IMPORTED, message);
+        let Some((namespace, import)) = import_of(cx.doc, cx.uri, declared_in) else {
+            out.push(finding);
+            continue;
+        };
+        let qualified = format!("{namespace}.{name}");
+        let edits = match import {
+            // The block is the first statement: one edit, as an insertion
+            // and a replacement at the same spot would be ambiguous.
+            Some((at, import)) if at == block.name_span().start => {
+                vec![TextEdit { range: cx.doc.range(block.name_span()), new_text: format!("{import}{qualified}") }]
+            }
+            Some((at, import)) => vec![
+                TextEdit { range: cx.doc.range(syntax::Span::new(at, at)), new_text: import },
+                TextEdit { range: cx.doc.range(block.name_span()), new_text: qualified },
+            ],
+            None => vec![TextEdit { range: cx.doc.range(block.name_span()), new_text: qualified }],
+        };
+        finding.fixes.push(Fix::new(format!("Import `{name}` from `{shown}`"), edits));
+        out.push(finding);
+    }
+}
+
+/// How the document at `uri` gets at the modules declared in
+/// `declared_in`: the label of the `import.file` already reading it, or a
+/// new one along with where it goes and its text. `None` for an unsaved
+/// buffer, which has no directory to import relative to.
+pub(crate) fn import_of(doc: &Document, uri: &Url, declared_in: &Url) -> Option<(String, Option<(usize, String)>)> {
+    let (Ok(from), Ok(to)) = (uri.to_file_path(), declared_in.to_file_path()) else { return None };
+    let relative = workspace::relative_path(from.parent()?, &to);
+    if let Some(label) = existing_import(doc, &relative) {
+        return Some((label, None));
+    }
+    let namespace = module_namespace(&to);
+    let at = first_statement(&doc.syntax().body).unwrap_or(doc.text.len());
+    let import = format!(
+        "import.file \"{namespace}\" {{\n  filename = file.path_join(module_path, {})\n}}\n\n",
+        syntax::quote(&relative)
+    );
+    Some((namespace, Some((at, import))))
+}
+
+/// The label of a top-level `import.file` already reading `relative`.
+fn existing_import(doc: &Document, relative: &str) -> Option<String> {
+    doc.syntax().body.iter().find_map(|stmt| {
+        let Stmt::Block(block) = stmt else { return None };
+        let filename = block.attribute("filename")?;
+        let mut reads = false;
+        filename.value.walk(&mut |expr| reads |= expr.as_str() == Some(relative));
+        (block.full_name() == "import.file" && reads).then(|| block.label.as_ref().map(|l| l.value.clone()))?
+    })
+}
+
+fn first_statement(body: &[Stmt]) -> Option<usize> {
+    body.first().map(|stmt| match stmt {
+        Stmt::Attribute(a) => a.span.start,
+        Stmt::Block(b) => b.span.start,
+    })
+}
+
+/// An import label for a module file: its stem, made an identifier.
+fn module_namespace(path: &Path) -> String {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("module");
+    let mut namespace: String = stem.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if !namespace.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        namespace.insert(0, '_');
+    }
+    namespace
+}
+
+/// Components added by the "stub a receiver" fix, per receiver type, with
+/// the body they get.
+const STUB_RECEIVERS: &[(&str, &str, &str)] = &[
+    (
+        "MetricsReceiver",
+        "prometheus.remote_write",
+        "endpoint {\n  url = \"https://prometheus.example.com/api/v1/write\"\n}",
+    ),
+    ("LogsReceiver", "loki.write", "endpoint {\n  url = \"https://loki.example.com/loki/api/v1/push\"\n}"),
+    ("ProfilesReceiver", "pyroscope.write", "endpoint {\n  url = \"https://pyroscope.example.com\"\n}"),
+];
+
+/// Producers whose `forward_to` is missing or empty send their data
+/// nowhere. The fixes wire them to a receiver of the right type the
+/// workspace already has, or add a stub receiver next to them.
+fn unwired_producer(cx: &Context, body: &[Stmt], out: &mut Vec<Finding>) {
+    let schema = Schema::builtin();
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        unwired_producer(cx, &block.body, out);
+        let Some(id) = workspace::component_id(block) else { continue };
+        let Some(argument) = schema.argument(&block.full_name(), &[], "forward_to") else { continue };
+        let Some(ty) = argument.ty.strip_prefix("list(").and_then(|t| t.strip_suffix(')')) else { continue };
+        let (span, message) = match block.attribute("forward_to") {
+            Some(attr) if matches!(&attr.value.kind, ExprKind::Array(items) if items.is_empty()) => {
+                (attr.value.span, format!("`{id}` forwards to nothing: `forward_to` is empty"))
+            }
+            Some(_) => continue,
+            None => (block.name_span(), format!("`{id}` forwards to nothing: `forward_to` is not set")),
+        };
+        if !cx.doc.text[..block.span.end].ends_with('}') {
+            continue;
+        }
+        let wire = |reference: &str| match block.attribute("forward_to") {
+            Some(attr) => TextEdit { range: cx.doc.range(attr.value.span), new_text: format!("[{reference}]") },
+            None => insert_statement(cx.doc, block, &format!("forward_to = [{reference}]")),
+        };
+
+        let mut finding = warning(cx.doc, span, UNWIRED_PRODUCER, message);
+        let mut receivers: Vec<_> = cx
+            .workspace
+            .components()
+            .filter(|(_, c)| c.id() != id)
+            .flat_map(|(uri, c)| {
+                let exports = schema.component(&c.name).map(|s| &s.exports);
+                exports.into_iter().flatten().filter(|(_, e)| e.ty == ty).map(move |(export, _)| {
+                    (uri != cx.uri, format!("{}.{export}", c.id()))
+                })
+            })
+            .collect();
+        receivers.sort();
+        for (_, reference) in receivers.into_iter().take(3) {
+            finding.fixes.push(Fix::new(format!("Forward to `{reference}`"), vec![wire(&reference)]));
+        }
+        if let Some((_, component, stub)) = STUB_RECEIVERS.iter().find(|(t, ..)| *t == ty) {
+            let label = (1..)
+                .map(|n| if n == 1 { "default".to_string() } else { format!("default_{n}") })
+                .find(|label| !cx.local.components.contains(&format!("{component}.{label}")))
+                .unwrap_or_default();
+            let indent = line_indent(&cx.doc.text, block.span.start);
+            let stub = format!("{component} \"{label}\" {{\n{}\n}}", indent_lines(stub, "  "));
+            let end = cx.doc.position(block.span.end);
+            finding.fixes.push(Fix::new(
+                format!("Add a `{component} \"{label}\"` and forward to it"),
+                vec![
+                    wire(&format!("{component}.{label}.receiver")),
+                    TextEdit {
+                        range: Range::new(end, end),
+                        new_text: format!("\n\n{}", indent_lines(&stub, indent)),
+                    },
+                ],
+            ));
+        }
+        out.push(finding);
+    }
+}
+
+/// Components at the end of a pipeline, which send what they receive out
+/// of Alloy; `otelcol.exporter.*` are too.
+const SINKS: &[&str] = &["prometheus.remote_write", "loki.write", "pyroscope.write"];
+
+/// Top-level components that only exist to be referenced (a receiver, a
+/// file's contents, discovered targets) but that nothing in the workspace
+/// references. Components without exports, like `prometheus.scrape`, act
+/// on their own and are never unused; neither are unknown components.
+/// A sink nothing forwards to is reported on its own: it is more often
+/// wiring lost in a refactor than dead code, so it gets no removal fix.
+fn unused_component(cx: &Context, body: &[Stmt], out: &mut Vec<Finding>) {
+    let schema = Schema::builtin();
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        let Some(id) = workspace::component_id(block) else { continue };
+        if schema.component(&block.full_name()).is_none_or(|c| c.exports.is_empty()) {
+            continue;
+        }
+        if cx.workspace.references_to(&id).next().is_some() {
+            continue;
+        }
+        let name = block.full_name();
+        if SINKS.contains(&name.as_str()) || name.starts_with("otelcol.exporter.") {
+            let message = format!("nothing forwards data to `{id}`, so it never sends anything");
+            out.push(warning(cx.doc, block.name_span(), NO_PRODUCERS, message));
+            continue;
+        }
+        let message = format!("`{id}` is never used: nothing references its exports");
+        let mut finding = warning(cx.doc, block.name_span(), UNUSED_COMPONENT, message);
+        finding.diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+        finding.fixes.push(Fix::new(format!("Remove `{id}`"), vec![remove_block(cx.doc, block)]));
+        out.push(finding);
+    }
+}
+
+/// Top-level `declare`s that nothing in the workspace instantiates, by
+/// their own name or under the namespace of an import. Modules other
+/// repositories import look unused here; the rule can be turned off for
+/// those.
+fn unused_module(cx: &Context, body: &[Stmt], out: &mut Vec<Finding>) {
+    let schema = Schema::builtin();
+    for stmt in body {
+        let Stmt::Block(block) = stmt else { continue };
+        let Some(label) = block.label.as_ref().filter(|_| block.full_name() == "declare") else { continue };
+        let name = &label.value;
+        let used = cx.workspace.components().any(|(_, c)| {
+            c.name == *name
+                || c.name.split_once('.').is_some_and(|(_, rest)| rest == name)
+                    && schema.component(&c.name).is_none()
+        });
+        if used {
+            continue;
+        }
+        let message = format!("module `{name}` is never used: nothing in the workspace instantiates it");
+        let mut finding = warning(cx.doc, label.span, UNUSED_MODULE, message);
+        finding.diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+        out.push(finding);
+    }
+}
+
+/// An edit deleting `block` with the rest of its last line, and one of the
+/// blank lines around it so that no double gap is left behind.
+fn remove_block(doc: &Document, block: &Block) -> TextEdit {
+    let text = &doc.text;
+    let line_start = text[..block.span.start].rfind('\n').map_or(0, |i| i + 1);
+    let mut start = if text[line_start..block.span.start].trim().is_empty() { line_start } else { block.span.start };
+    let mut end = text[block.span.end..].find('\n').map_or(text.len(), |i| block.span.end + i + 1);
+    let gap_before = start == 0 || text[..start].ends_with("\n\n");
+    if gap_before && text[end..].starts_with('\n') {
+        end += 1;
+    } else if gap_before && end == text.len() && start > 0 {
+        start -= 1;
+    }
+    TextEdit { range: doc.range(syntax::Span::new(start, end)), new_text: String::new() }
+}
+
+/// An edit adding `stmt` as the last statement of `block`, its lines
+/// indented to match.
+fn insert_statement(doc: &Document, block: &Block, stmt: &str) -> TextEdit {
+    let indent = line_indent(&doc.text, block.span.start);
+    let line = stmt.replace('\n', &format!("\n{indent}  "));
+    let close = block.span.end - 1;
+    let close_line = doc.text[..close].rfind('\n').map_or(0, |i| i + 1);
+    if doc.text[close_line..close].trim().is_empty() && close_line > block.span.start {
+        let at = doc.position(close_line);
+        TextEdit { range: Range::new(at, at), new_text: format!("{indent}  {line}\n") }
+    } else {
+        // `name "label" { }` on one line.
+        let (from, to) = (doc.text[..close].trim_end().len(), close);
+        TextEdit { range: doc.range(syntax::Span::new(from, to)), new_text: format!("\n{indent}  {line}\n{indent}") }
+    }
+}
+
+/// The whitespace at the start of the line containing `offset`.
+fn line_indent(text: &str, offset: usize) -> &str {
+    let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[start..];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn indent_lines(text: &str, indent: &str) -> String {
+    text.lines().map(|l| format!("{indent}{l}")).collect::<Vec<_>>().join("\n")
+}
+
+/// Adds edits to `fix` renaming the references to component `old`, in
+/// every file, to `new`.
+fn rename_references(cx: &Context, old: &str, new: &str, fix: &mut Fix) {
+    for (uri, reference) in cx.workspace.references_to(old) {
+        let edit = TextEdit { range: reference.range, new_text: reference.path.replacen(old, new, 1) };
+        match uri == cx.uri {
+            true => fix.edits.push(edit),
+            false => fix.elsewhere.entry(uri.clone()).or_default().push(edit),
+        }
+    }
+}
+
+/// Adds "did you mean" and a quick fix renaming the block to `suggestion`.
+fn did_you_mean(mut finding: Finding, suggestion: Option<String>) -> Finding {
+    if let Some(suggestion) = suggestion {
+        finding.diagnostic.message.push_str(&format!("; did you mean `{suggestion}`?"));
+        finding.fixes.push(Fix::new(
+            format!("Change to `{suggestion}`"),
+            vec![TextEdit { range: finding.diagnostic.range, new_text: suggestion }],
+        ));
+    }
+    finding
+}
+
+/// The candidate closest to `name` by edit distance, if it is close enough
+/// to be a typo: at most two edits, and fewer for short names.
+pub(crate) fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 4).clamp(1, 2);
+    candidates
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|&(d, _)| d > 0 && d <= limit)
+        .min()
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance counting a swap of adjacent characters as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+fn error(doc: &Document, span: syntax::Span, code: &str, message: String) -> Finding {
+    Finding {
+        diagnostic: Diagnostic {
+            range: doc.range(span),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(code.into())),
+            source: Some(SOURCE.into()),
+            message,
+            ..Default::default()
+        },
+        fixes: Vec::new(),
+    }
+}
+
+fn warning(doc: &Document, span: syntax::Span, code: &str, message: String) -> Finding {
+    let mut finding = error(doc, span, code, message);
+    finding.diagnostic.severity = Some(DiagnosticSeverity::WARNING);
+    finding
+}
+
+pub(crate) fn info(doc: &Document, span: syntax::Span, code: &str, message: String) -> Finding {
+    let mut finding = error(doc, span, code, message);
+    finding.diagnostic.severity = Some(DiagnosticSeverity::INFORMATION);
+    finding
+}
+
+pub(crate) fn hint(doc: &Document, span: syntax::Span, code: &str, message: String) -> Finding {
+    let mut finding = error(doc, span, code, message);
+    finding.diagnostic.severity = Some(DiagnosticSeverity::HINT);
+    finding
+}
+
+/// `prometheus.remote_write "grafana_cloud"`'s `password` becomes
+/// `GRAFANA_CLOUD_PASSWORD`.
+fn env_var_name(label: Option<&str>, attribute: &str) -> String {
+    label
+        .into_iter()
+        .chain([attribute])
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
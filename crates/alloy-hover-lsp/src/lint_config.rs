@@ -0,0 +1,94 @@
+//! `.alloylint.toml`: lint policy checked in at the workspace root, so it
+//! is versioned with the configs instead of living in editor settings.
+//!
+//! ```toml
+//! # Files that are not linted at all, relative to the workspace root.
+//! exclude = ["vendor/**"]
+//!
+//! [rules]
+//! unused-component = "off"
+//! plaintext-secret = "error"
+//!
+//! # Severities for some paths only, applied in order after `[rules]`.
+//! [[overrides]]
+//! paths = ["modules/**"]
+//! rules = { no-producers = "off" }
+//! ```
+
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::config::Severity;
+
+pub const FILE_NAME: &str = ".alloylint.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Severities by rule, named by code (`unused-component`) or in camelCase
+    /// as in the editor settings (`unusedComponent`).
+    pub rules: BTreeMap<String, Severity>,
+    /// Globs of files not to lint; `*` stays within a directory, `**`
+    /// crosses any number of them.
+    pub exclude: Vec<String>,
+    pub overrides: Vec<Override>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Override {
+    /// Globs, as for `exclude`.
+    pub paths: Vec<String>,
+    pub rules: BTreeMap<String, Severity>,
+}
+
+impl LintConfig {
+    /// Reads `dir/.alloylint.toml`; `Ok(None)` when there is none.
+    pub fn load(dir: &Path) -> Result<Option<Self>, String> {
+        let path = dir.join(FILE_NAME);
+        let Ok(text) = fs::read_to_string(&path) else { return Ok(None) };
+        toml::from_str(&text).map(Some).map_err(|err| format!("{}: {}", path.display(), err.message()))
+    }
+
+    /// The severities for the file at `path` (relative to the workspace
+    /// root, `/`-separated), or `None` if it is excluded.
+    pub fn rules_for(&self, path: &str) -> Option<BTreeMap<String, Severity>> {
+        if self.exclude.iter().any(|glob| glob_match(glob, path)) {
+            return None;
+        }
+        let mut rules = self.rules.clone();
+        for o in self.overrides.iter().filter(|o| o.paths.iter().any(|glob| glob_match(glob, path))) {
+            rules.extend(o.rules.iter().map(|(rule, severity)| (rule.clone(), *severity)));
+        }
+        Some(rules)
+    }
+}
+
+/// Matches `path` against `pattern`, both `/`-separated: `**` is any number
+/// of directories, `*` any run of characters within one, `?` one character.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn segments(pattern: &[&str], path: &[&str]) -> bool {
+        match (pattern.split_first(), path.split_first()) {
+            (None, None) => true,
+            (Some((&"**", rest)), _) => {
+                segments(rest, path) || path.split_first().is_some_and(|(_, tail)| segments(pattern, tail))
+            }
+            (Some((p, rest)), Some((s, tail))) => segment(p.as_bytes(), s.as_bytes()) && segments(rest, tail),
+            _ => false,
+        }
+    }
+    fn segment(pattern: &[u8], name: &[u8]) -> bool {
+        match (pattern.split_first(), name.split_first()) {
+            (None, None) => true,
+            (Some((b'*', rest)), _) => {
+                segment(rest, name) || name.split_first().is_some_and(|(_, tail)| segment(pattern, tail))
+            }
+            (Some((b'?', rest)), Some((_, tail))) => segment(rest, tail),
+            (Some((p, rest)), Some((c, tail))) => p == c && segment(rest, tail),
+            _ => false,
+        }
+    }
+    let pattern: Vec<&str> = pattern.trim_start_matches("./").split('/').filter(|s| !s.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    segments(&pattern, &path)
+}
@@ -1,143 +1,128 @@
-use anyhow::{Context, Result};
-use std::{
-    collections::HashMap,
-    fs,
-    path::PathBuf,
-    sync::{Arc, RwLock},
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use tower_lsp::Server;
+
+use alloy_hover_lsp::{
+    docs::Docs,
+    docs_check::{self, Severity},
+    docs_coverage,
+    json_schema,
+    schema::Schema,
+    server,
+    trace::Trace,
 };
-use tower_lsp::lsp_types::*;
-use tower_lsp::{LanguageServer, LspService, Server};
 
-#[derive(Default)]
-struct Docs {
-    map: HashMap<String, String>,
+/// Used when `ALLOY_HOVER_DOCS` is unset and the file exists.
+const DEFAULT_DOCS: &str = "docs/alloy-hover.toml";
+
+/// Threads without `--threads`: enough to keep the server responsive
+/// while it indexes, without taking over a shared machine.
+const DEFAULT_THREADS: usize = 2;
+
+fn main() -> Result<()> {
+    let threads = match flag("--threads") {
+        Some(value) => match value.parse::<usize>() {
+            Ok(threads) if threads > 0 => threads,
+            _ => bail!("--threads takes a positive number, not `{value}`"),
+        },
+        None => DEFAULT_THREADS,
+    };
+    // The blocking pool is left at its default: its threads mostly wait on
+    // stdio, files and the network rather than use a core.
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(threads)
+        .enable_all()
+        .build()
+        .context("starting the async runtime")?
+        .block_on(run(threads))
 }
-impl Docs {
-    fn load(path: PathBuf) -> Result<Self> {
-        let text = fs::read_to_string(&path)
-            .with_context(|| format!("reading {}", path.display()))?;
-        let map: HashMap<String, String> =
-            toml::from_str(&text).context("parsing alloy-hover.toml")?;
-        Ok(Self { map })
-    }
-    fn get(&self, key: &str) -> Option<String> {
-        self.map.get(key).cloned()
-    }
-}
-
-struct Backend {
-    files: Arc<RwLock<HashMap<Url, String>>>,
-    docs: Docs,
-}
-
-#[tower_lsp::async_trait]
-impl LanguageServer for Backend {
-    async fn initialize(
-        &self,
-        _params: InitializeParams,
-    ) -> tower_lsp::jsonrpc::Result<InitializeResult> {
-        Ok(InitializeResult {
-            capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
-                )),
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
-                ..Default::default()
-            },
-            server_info: Some(ServerInfo {
-                name: "alloy-hover-lsp".into(),
-                version: Some("0.1.0".into()),
-            }),
-        })
-    }
-
-    async fn initialized(&self, _params: InitializedParams) {}
 
-    async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.files
-            .write()
-            .unwrap()
-            .insert(params.text_document.uri, params.text_document.text);
-    }
-
-    async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        if let Some(change) = params.content_changes.into_iter().last() {
-            self.files
-                .write()
-                .unwrap()
-                .insert(params.text_document.uri, change.text);
+async fn run(threads: usize) -> Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("export-json-schema") => {
+            let schema = json_schema::export(Schema::builtin());
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            return Ok(());
         }
+        Some("check-docs") => check_docs(std::env::args().skip(2).collect()),
+        Some("docs-coverage") => report_coverage(std::env::args().nth(2).unwrap_or_else(|| ".".into())),
+        _ => {}
     }
 
-    async fn hover(
-        &self,
-        params: HoverParams,
-    ) -> tower_lsp::jsonrpc::Result<Option<Hover>> {
-        let uri = params.text_document_position_params.text_document.uri;
-        let pos = params.text_document_position_params.position;
-
-        let text = {
-            let guard = self.files.read().unwrap();
-            guard.get(&uri).cloned()
-        };
-        let Some(text) = text else { return Ok(None) };
-
-        let line = text.lines().nth(pos.line as usize).unwrap_or_default();
+    // A broken docset must not keep the server from starting; problems are
+    // logged here and shown to the user once the client is initialized.
+    let docs = load_docs();
 
-        let mut start = pos.character as usize;
-        let mut end = start;
-        let is_word = |ch: char| ch.is_alphanumeric() || ch == '_' || ch == '.';
+    // Requires tokio feature: io-std
+    let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
 
-        while start > 0 && line.chars().nth(start - 1).map(is_word).unwrap_or(false) {
-            start -= 1;
-        }
-        while end < line.len() && line.chars().nth(end).map(is_word).unwrap_or(false) {
-            end += 1;
+    let (service, socket) = server::service(docs, threads);
+    match flag("--trace") {
+        Some(path) => {
+            let trace = Trace::create(Path::new(&path)).with_context(|| format!("creating trace file {path}"))?;
+            Server::new(trace.incoming(stdin), trace.outgoing(stdout), socket).serve(service).await;
         }
+        None => Server::new(stdin, stdout, socket).serve(service).await,
+    }
+    Ok(())
+}
 
-        let word = line.get(start..end).unwrap_or("").trim_matches('"');
-        if word.is_empty() {
-            return Ok(None);
+/// The value given for `--name value` or `--name=value`.
+fn flag(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
         }
-
-        if let Some(md) = self.docs.get(word) {
-            return Ok(Some(Hover {
-                contents: HoverContents::Markup(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value: md,
-                }),
-                range: Some(Range {
-                    start: Position {
-                        line: pos.line,
-                        character: start as u32,
-                    },
-                    end: Position {
-                        line: pos.line,
-                        character: end as u32,
-                    },
-                }),
-            }));
+        if let Some(value) = arg.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.to_string());
         }
-
-        Ok(None)
     }
+    None
+}
 
-    async fn shutdown(&self) -> tower_lsp::jsonrpc::Result<()> {
-        Ok(())
+/// The docset the server would use, logging what was wrong with it.
+fn load_docs() -> Docs {
+    let docs = match std::env::var_os("ALLOY_HOVER_DOCS") {
+        Some(path) => Docs::load_lenient(Path::new(&path)),
+        None if Path::new(DEFAULT_DOCS).exists() => Docs::load_lenient(Path::new(DEFAULT_DOCS)),
+        None => Docs::builtin(),
+    };
+    for problem in docs.problems() {
+        eprintln!("alloy-hover-lsp: {problem}");
     }
+    docs
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let docs_path =
-        std::env::var("ALLOY_HOVER_DOCS").unwrap_or_else(|_| "docs/alloy-hover.toml".into());
-    let docs = Docs::load(PathBuf::from(docs_path))?;
-    let files = Arc::new(RwLock::new(HashMap::new()));
-
-    // Requires tokio feature: io-std
-    let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
+/// `docs-coverage [DIR]`: lists the components and attributes the configs
+/// below `DIR` use that the docset has no entry for, most used first.
+fn report_coverage(dir: String) -> ! {
+    let coverage = docs_coverage::scan(Path::new(&dir), &load_docs());
+    println!("{} config files in {dir}", coverage.files);
+    let sections = [
+        ("components", &coverage.components, coverage.undocumented_components().collect::<Vec<_>>()),
+        ("attributes", &coverage.attributes, coverage.undocumented_attributes().collect()),
+    ];
+    for (kind, all, missing) in sections {
+        println!("\n{} of {} {kind} documented", all.len() - missing.len(), all.len());
+        for usage in missing {
+            println!("{:>6}  {}", usage.uses, usage.name);
+        }
+    }
+    std::process::exit(0)
+}
 
-    let (service, socket) = LspService::new(|_client| Backend { files: files.clone(), docs });
-    Server::new(stdin, stdout, socket).serve(service).await;
-    Ok(())
+/// `check-docs [PATH…]`: reports problems in docs files or directories
+/// (the default docset if none are given) and exits non-zero on errors.
+fn check_docs(mut paths: Vec<String>) -> ! {
+    if paths.is_empty() {
+        paths.push(DEFAULT_DOCS.into());
+    }
+    let problems: Vec<_> = paths.iter().flat_map(|p| docs_check::check(Path::new(p))).collect();
+    for problem in &problems {
+        println!("{problem}");
+    }
+    let errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+    println!("{errors} errors, {} warnings", problems.len() - errors);
+    std::process::exit(if errors > 0 { 1 } else { 0 })
 }
@@ -0,0 +1,115 @@
+//! Rendering of the markdown the server produces as plain text, for clients
+//! that cannot display markdown. Line structure is kept: code blocks are
+//! indented, list items and paragraphs stay on their own lines.
+
+/// Strips emphasis, code spans, headings, fences and link syntax, keeping
+/// the text they wrap. Links keep their target in parentheses.
+pub fn to_plain_text(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            out.push_str("    ");
+            out.push_str(line);
+        } else if is_rule(trimmed) {
+            out.push_str("────────");
+        } else {
+            let indent = &line[..line.len() - trimmed.len()];
+            let (marker, rest) = match trimmed.split_once(' ') {
+                Some(("-" | "*" | "+", rest)) => ("- ", rest),
+                Some((hashes, rest)) if (1..=6).contains(&hashes.len()) && hashes.bytes().all(|b| b == b'#') => {
+                    ("", rest)
+                }
+                Some((">", rest)) => ("", rest),
+                _ => ("", trimmed),
+            };
+            out.push_str(indent);
+            out.push_str(marker);
+            inline(rest, &mut out);
+        }
+        out.push('\n');
+    }
+    if !markdown.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+/// `---`, `***` or `___`, possibly spaced out.
+fn is_rule(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3 && ["-", "*", "_"].iter().any(|r| chars.iter().all(|c| r.starts_with(*c)))
+}
+
+fn inline(text: &str, out: &mut String) {
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '\\' if rest[1..].starts_with(|c: char| c.is_ascii_punctuation()) => {
+                out.push_str(&rest[1..2]);
+                rest = &rest[2..];
+            }
+            '`' => {
+                let ticks = rest.len() - rest.trim_start_matches('`').len();
+                let body = &rest[ticks..];
+                match body.find(&rest[..ticks]) {
+                    Some(end) => {
+                        out.push_str(body[..end].trim());
+                        rest = &body[end + ticks..];
+                    }
+                    None => {
+                        out.push_str(&rest[..ticks]);
+                        rest = body;
+                    }
+                }
+            }
+            '[' => match link(rest) {
+                Some((label, target, len)) => {
+                    inline(label, out);
+                    if target != label {
+                        out.push_str(&format!(" ({target})"));
+                    }
+                    rest = &rest[len..];
+                }
+                None => {
+                    out.push('[');
+                    rest = &rest[1..];
+                }
+            },
+            // Emphasis, unless it stands alone (`2 * 3`) or, for `_`, sits
+            // inside a word (`scrape_interval`).
+            '*' | '_' => {
+                let after = rest.trim_start_matches(c);
+                let before_word = out.ends_with(char::is_alphanumeric);
+                let after_word = after.starts_with(char::is_alphanumeric);
+                let alone = out.ends_with(char::is_whitespace) && after.starts_with(char::is_whitespace);
+                if alone || (c == '_' && before_word && after_word) {
+                    out.push_str(&rest[..rest.len() - after.len()]);
+                }
+                rest = after;
+            }
+            _ => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+}
+
+/// `[label](target)` at the start of `text`: the label, the target and the
+/// length of the whole link.
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let close = text.find("](")?;
+    let label = &text[1..close];
+    if label.contains('[') {
+        return None;
+    }
+    let after = &text[close + 2..];
+    let end = after.find(')')?;
+    Some((label, after[..end].trim(), close + 2 + end + 1))
+}
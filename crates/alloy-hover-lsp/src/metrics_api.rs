@@ -0,0 +1,79 @@
+//! Opt-in lookup of the metric and label names a Prometheus-compatible API
+//! knows about, for completion. Calls are blocking; run them on a blocking
+//! task.
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use crate::config::MetricsApi;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long fetched names are used before asking the API again.
+pub const MAX_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Names from the user's environment.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    /// Metric names, with their help text where the API has metadata.
+    pub metrics: BTreeMap<String, String>,
+    pub labels: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    status: String,
+    data: Option<T>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    #[serde(default)]
+    help: String,
+}
+
+/// Fetches metric and label names. `None` when no API is configured.
+/// Metadata (help texts) is best effort: not every backend serves it.
+pub fn fetch(settings: &MetricsApi) -> Result<Option<Catalog>> {
+    if settings.url.is_none() {
+        return Ok(None);
+    }
+    let names: Vec<String> = get(settings, "/api/v1/label/__name__/values")?;
+    let labels: Vec<String> = get(settings, "/api/v1/labels")?;
+    let metadata: HashMap<String, Vec<Metadata>> = get(settings, "/api/v1/metadata").unwrap_or_default();
+
+    let metrics = names
+        .into_iter()
+        .map(|name| {
+            let help = metadata.get(&name).and_then(|m| m.first()).map(|m| m.help.clone()).unwrap_or_default();
+            (name, help)
+        })
+        .collect();
+    Ok(Some(Catalog { metrics, labels }))
+}
+
+fn get<T: DeserializeOwned>(settings: &MetricsApi, path: &str) -> Result<T> {
+    let base = settings.url.as_deref().unwrap_or_default().trim_end_matches('/');
+    let url = format!("{base}{path}");
+    let mut req = ureq::get(&url).timeout(TIMEOUT);
+    if let Some(user) = &settings.username {
+        let credentials = format!("{user}:{}", settings.password.as_deref().unwrap_or_default());
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+        req = req.set("Authorization", &format!("Basic {encoded}"));
+    }
+    for (name, value) in &settings.headers {
+        req = req.set(name, value);
+    }
+    let body = req.call()?.into_string()?;
+    let response: Response<T> = serde_json::from_str(&body).with_context(|| format!("parsing response from {url}"))?;
+    match response.data {
+        Some(data) if response.status == "success" => Ok(data),
+        _ => bail!("{url}: {}", response.error.unwrap_or(response.status)),
+    }
+}
@@ -0,0 +1,312 @@
+//! Remote modules pulled in with `import.git` and `import.http`: finding
+//! them in a config and keeping a local copy under the cache directory.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use tokio::process::Command;
+use tower_lsp::lsp_types::Url;
+
+use crate::config::Modules;
+use crate::syntax::{Block, Span, Stmt};
+use crate::workspace::{DeclareDef, FileIndex, CONFIG_EXTENSIONS};
+
+/// An `import.git "label" { … }` block.
+#[derive(Debug, Clone)]
+pub struct GitImport {
+    pub label: String,
+    pub repository: String,
+    /// Branch, tag or commit; Alloy defaults to `HEAD`.
+    pub revision: String,
+    /// File or directory inside the repository.
+    pub path: String,
+    pub span: Span,
+}
+
+impl GitImport {
+    pub fn from_block(block: &Block) -> Option<Self> {
+        if block.full_name() != "import.git" {
+            return None;
+        }
+        let string = |name| block.attribute(name).and_then(|a| a.value.as_str()).map(str::to_string);
+        Some(GitImport {
+            label: block.label.as_ref()?.value.clone(),
+            repository: string("repository")?,
+            revision: string("revision").unwrap_or_else(|| "HEAD".into()),
+            path: string("path")?,
+            span: block.span,
+        })
+    }
+
+    /// Where this revision is checked out. Each repository/revision pair
+    /// gets its own directory, so a pinned module never changes under us.
+    pub fn checkout_dir(&self, cache: &Path) -> PathBuf {
+        let name: String = self
+            .repository
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(".git")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let key = fnv1a(format!("{}\0{}", self.repository, self.revision).as_bytes());
+        cache.join("git").join(format!("{name}-{key:016x}"))
+    }
+
+    /// The module source inside an existing checkout, if there is one.
+    /// Paths leading out of the checkout have none.
+    pub fn cached_path(&self, cache: &Path) -> Option<PathBuf> {
+        if !self.path_is_inside() {
+            return None;
+        }
+        let dir = self.checkout_dir(cache);
+        let path = dir.join(&self.path);
+        // Symlinks in the repository may point out of it too.
+        path.canonicalize().ok()?.starts_with(dir.canonicalize().ok()?).then_some(path)
+    }
+
+    /// Whether `path` is relative and never steps up a directory.
+    fn path_is_inside(&self) -> bool {
+        Path::new(&self.path).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    }
+}
+
+/// An `import.http "label" { url = … }` block.
+#[derive(Debug, Clone)]
+pub struct HttpImport {
+    pub label: String,
+    pub url: String,
+    pub span: Span,
+}
+
+impl HttpImport {
+    pub fn from_block(block: &Block) -> Option<Self> {
+        if block.full_name() != "import.http" {
+            return None;
+        }
+        Some(HttpImport {
+            label: block.label.as_ref()?.value.clone(),
+            url: block.attribute("url")?.value.as_str()?.to_string(),
+            span: block.span,
+        })
+    }
+
+    /// Where the module at `url` is downloaded to.
+    pub fn cache_file(&self, cache: &Path) -> PathBuf {
+        cache.join("http").join(format!("{:016x}.alloy", fnv1a(self.url.as_bytes())))
+    }
+
+    pub fn cached_path(&self, cache: &Path) -> Option<PathBuf> {
+        let path = self.cache_file(cache);
+        path.is_file().then_some(path)
+    }
+}
+
+/// A module imported from outside the workspace.
+#[derive(Debug, Clone)]
+pub enum Import {
+    Git(GitImport),
+    Http(HttpImport),
+}
+
+impl Import {
+    pub fn from_block(block: &Block) -> Option<Self> {
+        GitImport::from_block(block)
+            .map(Import::Git)
+            .or_else(|| HttpImport::from_block(block).map(Import::Http))
+    }
+
+    /// The namespace the module's components are used under.
+    pub fn label(&self) -> &str {
+        match self {
+            Import::Git(i) => &i.label,
+            Import::Http(i) => &i.label,
+        }
+    }
+
+    pub fn cached_path(&self, cache: &Path) -> Option<PathBuf> {
+        match self {
+            Import::Git(i) => i.cached_path(cache),
+            Import::Http(i) => i.cached_path(cache),
+        }
+    }
+
+    /// The module source, downloading it first if it is not cached yet.
+    pub async fn fetch(&self, cache: &Path) -> Result<PathBuf> {
+        match self {
+            Import::Git(i) => fetch(i, cache).await,
+            Import::Http(i) => {
+                let (import, cache) = (i.clone(), cache.to_path_buf());
+                tokio::task::spawn_blocking(move || fetch_http(&import, &cache)).await?
+            }
+        }
+    }
+
+    /// The cached module source, downloading it only when `settings` allow.
+    pub async fn load(&self, settings: &Modules) -> Result<Option<PathBuf>> {
+        let Some(cache) = cache_dir(settings) else { return Ok(None) };
+        match self.cached_path(&cache) {
+            Some(path) => Ok(Some(path)),
+            None if settings.fetch => self.fetch(&cache).await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Top-level `import.git` and `import.http` blocks of a file.
+pub fn imports(body: &[Stmt]) -> Vec<Import> {
+    body.iter()
+        .filter_map(|s| match s {
+            Stmt::Block(b) => Import::from_block(b),
+            Stmt::Attribute(_) => None,
+        })
+        .collect()
+}
+
+/// The configured cache directory, or the platform's user cache directory.
+pub fn cache_dir(settings: &Modules) -> Option<PathBuf> {
+    if let Some(dir) = &settings.cache_dir {
+        return Some(dir.clone());
+    }
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("alloy-hover-lsp"))
+}
+
+/// Returns the module source for `import`, cloning the pinned revision first
+/// if it is not cached yet. Checkouts are made read-only: they mirror the
+/// remote and edits would be silently lost.
+pub async fn fetch(import: &GitImport, cache: &Path) -> Result<PathBuf> {
+    if let Some(path) = import.cached_path(cache) {
+        return Ok(path);
+    }
+    // Git reads arguments starting with `-` as options, and some of those
+    // (`--upload-pack`) run commands.
+    for value in [&import.repository, &import.revision] {
+        if value.starts_with('-') {
+            bail!("`{value}` is not a repository or revision");
+        }
+    }
+    if !import.path_is_inside() {
+        bail!("`{}` is not a path inside the repository", import.path);
+    }
+    let dir = import.checkout_dir(cache);
+    let staging = dir.with_extension("partial");
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging).with_context(|| format!("creating {}", staging.display()))?;
+
+    git(&staging, &["init", "-q"]).await?;
+    git(&staging, &["fetch", "-q", "--depth", "1", "--", &import.repository, &import.revision]).await?;
+    git(&staging, &["checkout", "-q", "FETCH_HEAD"]).await?;
+    let _ = fs::remove_dir_all(staging.join(".git"));
+    set_readonly(&staging);
+
+    // Another request may have finished the same checkout meanwhile.
+    if fs::rename(&staging, &dir).is_err() {
+        let _ = remove_checkout(&staging);
+    }
+    import
+        .cached_path(cache)
+        .with_context(|| format!("`{}` does not exist in {}", import.path, import.repository))
+}
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Blocking; run it on a blocking task.
+fn fetch_http(import: &HttpImport, cache: &Path) -> Result<PathBuf> {
+    let body = ureq::get(&import.url)
+        .timeout(HTTP_TIMEOUT)
+        .call()
+        .with_context(|| format!("fetching {}", import.url))?
+        .into_string()?;
+    let path = import.cache_file(cache);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    fs::write(&path, body).with_context(|| format!("writing {}", path.display()))?;
+    Ok(path)
+}
+
+async fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await
+        .context("running git")?;
+    if !out.status.success() {
+        bail!("git {}: {}", args[0], String::from_utf8_lossy(&out.stderr).trim());
+    }
+    Ok(())
+}
+
+fn set_readonly(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            set_readonly(&path);
+        } else if let Ok(meta) = fs::metadata(&path) {
+            let mut perms = meta.permissions();
+            perms.set_readonly(true);
+            let _ = fs::set_permissions(&path, perms);
+        }
+    }
+}
+
+fn remove_checkout(dir: &Path) -> std::io::Result<()> {
+    // Read-only files can't be deleted on Windows.
+    if cfg!(windows) {
+        for entry in fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                remove_checkout(&path)?;
+            } else {
+                let mut perms = fs::metadata(&path)?.permissions();
+                #[allow(clippy::permissions_set_readonly_false)]
+                perms.set_readonly(false);
+                fs::set_permissions(&path, perms)?;
+            }
+        }
+    }
+    fs::remove_dir_all(dir)
+}
+
+/// Config files making up a module: the file itself, or every config file
+/// directly inside a module directory.
+pub fn module_files(path: &Path) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path.to_path_buf()];
+    }
+    let Ok(entries) = fs::read_dir(path) else { return Vec::new() };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()).is_some_and(|e| CONFIG_EXTENSIONS.contains(&e)))
+        .collect();
+    files.sort();
+    files
+}
+
+/// The `declare` blocks a module provides, with the file each is in.
+pub fn declares(path: &Path) -> Vec<(Url, DeclareDef)> {
+    let mut out = Vec::new();
+    for file in module_files(path) {
+        let (Ok(text), Ok(uri)) = (fs::read_to_string(&file), Url::from_file_path(&file)) else { continue };
+        out.extend(FileIndex::build(&text).declares.into_iter().map(|d| (uri.clone(), d)));
+    }
+    out
+}
+
+/// Stable across builds, unlike `DefaultHasher`, so cache paths survive
+/// upgrades.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
@@ -0,0 +1,99 @@
+//! Features the user can switch on and off in settings. Clients that support
+//! dynamic registration get them registered after `initialize` and
+//! (un)registered as settings change; others get them advertised up front
+//! and the handlers answer nothing while a feature is off.
+
+use serde_json::{json, Value};
+use tower_lsp::lsp_types::*;
+
+use crate::config::Features;
+use crate::semantic_tokens;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    CodeLens,
+    SemanticTokens,
+    InlayHints,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::CodeLens, Feature::SemanticTokens, Feature::InlayHints];
+
+    pub fn method(self) -> &'static str {
+        match self {
+            Feature::CodeLens => "textDocument/codeLens",
+            Feature::SemanticTokens => "textDocument/semanticTokens",
+            Feature::InlayHints => "textDocument/inlayHint",
+        }
+    }
+
+    pub fn enabled(self, features: &Features) -> bool {
+        match self {
+            Feature::CodeLens => features.code_lens,
+            Feature::SemanticTokens => features.semantic_tokens,
+            Feature::InlayHints => features.closing_brace_hints,
+        }
+    }
+
+    /// Whether the client lets the server register this feature at runtime.
+    pub fn is_dynamic(self, capabilities: &ClientCapabilities) -> bool {
+        let Some(text) = &capabilities.text_document else { return false };
+        let dynamic = match self {
+            Feature::CodeLens => text.code_lens.as_ref().and_then(|c| c.dynamic_registration),
+            Feature::SemanticTokens => text.semantic_tokens.as_ref().and_then(|c| c.dynamic_registration),
+            Feature::InlayHints => text.inlay_hint.as_ref().and_then(|c| c.dynamic_registration),
+        };
+        dynamic.unwrap_or(false)
+    }
+
+    /// Registered under its method name, which doubles as the ID. A `null`
+    /// document selector means the documents the client starts us for.
+    pub fn registration(self) -> Registration {
+        let mut options = match self {
+            Feature::CodeLens => json!(code_lens_options()),
+            Feature::SemanticTokens => json!(semantic_tokens_options()),
+            Feature::InlayHints => json!(inlay_hint_options()),
+        };
+        options["documentSelector"] = Value::Null;
+        Registration {
+            id: self.method().into(),
+            method: self.method().into(),
+            register_options: Some(options),
+        }
+    }
+
+    pub fn unregistration(self) -> Unregistration {
+        Unregistration { id: self.method().into(), method: self.method().into() }
+    }
+}
+
+pub fn code_lens_options() -> CodeLensOptions {
+    CodeLensOptions { resolve_provider: Some(false) }
+}
+
+pub fn inlay_hint_options() -> InlayHintOptions {
+    InlayHintOptions { resolve_provider: Some(false), ..Default::default() }
+}
+
+pub fn semantic_tokens_options() -> SemanticTokensOptions {
+    SemanticTokensOptions {
+        legend: semantic_tokens::legend(),
+        full: Some(SemanticTokensFullOptions::Bool(true)),
+        ..Default::default()
+    }
+}
+
+/// What to register and unregister when settings go from `before` to
+/// `after`, among the features the client registers dynamically.
+pub fn changes(before: &Features, after: &Features, dynamic: &[Feature]) -> (Vec<Registration>, Vec<Unregistration>) {
+    let mut register = Vec::new();
+    let mut unregister = Vec::new();
+    for &feature in dynamic {
+        match (feature.enabled(before), feature.enabled(after)) {
+            (false, true) => register.push(feature.registration()),
+            (true, false) => unregister.push(feature.unregistration()),
+            _ => {}
+        }
+    }
+    (register, unregister)
+}
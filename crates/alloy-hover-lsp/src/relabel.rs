@@ -0,0 +1,145 @@
+//! Relabeling rules (`rule` blocks of the `*.relabel` components).
+
+use std::ops::Range;
+
+use regex_syntax::ast::{self, Ast};
+
+use crate::syntax::Block;
+
+/// Whether `chain` ends in a `rule` block of a `*.relabel` component.
+pub fn is_rule(chain: &[&Block]) -> bool {
+    match chain {
+        [.., parent, rule] => rule.full_name() == "rule" && parent.full_name().ends_with(".relabel"),
+        _ => false,
+    }
+}
+
+/// A value of a rule's `action` attribute.
+pub struct Action {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// Rule fields the action reads; the others are ignored.
+    pub fields: &'static [&'static str],
+    pub example: &'static str,
+}
+
+pub const ACTIONS: &[Action] = &[
+    Action {
+        name: "replace",
+        summary: "Matches `regex` against the joined `source_labels` and, if it matches, sets `target_label` to `replacement` with `$1`-style capture groups expanded.",
+        fields: &["source_labels", "separator", "regex", "target_label", "replacement"],
+        example: "rule {\n  source_labels = [\"__meta_kubernetes_pod_name\"]\n  target_label  = \"pod\"\n}",
+    },
+    Action {
+        name: "keep",
+        summary: "Drops targets or series for which `regex` does not match the joined `source_labels`.",
+        fields: &["source_labels", "separator", "regex"],
+        example: "rule {\n  source_labels = [\"namespace\"]\n  regex         = \"prod|staging\"\n  action        = \"keep\"\n}",
+    },
+    Action {
+        name: "drop",
+        summary: "Drops targets or series for which `regex` matches the joined `source_labels`.",
+        fields: &["source_labels", "separator", "regex"],
+        example: "rule {\n  source_labels = [\"__name__\"]\n  regex         = \"go_gc_.*\"\n  action        = \"drop\"\n}",
+    },
+    Action {
+        name: "keepequal",
+        summary: "Drops targets or series for which the joined `source_labels` do not equal `target_label`.",
+        fields: &["source_labels", "separator", "target_label"],
+        example: "rule {\n  source_labels = [\"__meta_port\"]\n  target_label  = \"port\"\n  action        = \"keepequal\"\n}",
+    },
+    Action {
+        name: "dropequal",
+        summary: "Drops targets or series for which the joined `source_labels` equal `target_label`.",
+        fields: &["source_labels", "separator", "target_label"],
+        example: "rule {\n  source_labels = [\"__meta_port\"]\n  target_label  = \"port\"\n  action        = \"dropequal\"\n}",
+    },
+    Action {
+        name: "hashmod",
+        summary: "Sets `target_label` to the hash of the joined `source_labels` modulo `modulus`; combine with `keep` to shard targets.",
+        fields: &["source_labels", "separator", "modulus", "target_label"],
+        example: "rule {\n  source_labels = [\"__address__\"]\n  modulus       = 4\n  target_label  = \"__tmp_hash\"\n  action        = \"hashmod\"\n}",
+    },
+    Action {
+        name: "labelmap",
+        summary: "Matches `regex` against every label name and copies matching labels to the name given by `replacement`, with capture groups expanded.",
+        fields: &["regex", "replacement"],
+        example: "rule {\n  regex       = \"__meta_kubernetes_pod_label_(.+)\"\n  replacement = \"$1\"\n  action      = \"labelmap\"\n}",
+    },
+    Action {
+        name: "labeldrop",
+        summary: "Removes every label whose name matches `regex`.",
+        fields: &["regex"],
+        example: "rule {\n  regex  = \"__tmp_.*\"\n  action = \"labeldrop\"\n}",
+    },
+    Action {
+        name: "labelkeep",
+        summary: "Removes every label whose name does not match `regex`.",
+        fields: &["regex"],
+        example: "rule {\n  regex  = \"__name__|job|instance\"\n  action = \"labelkeep\"\n}",
+    },
+    Action {
+        name: "lowercase",
+        summary: "Sets `target_label` to the joined `source_labels`, lowercased.",
+        fields: &["source_labels", "separator", "target_label"],
+        example: "rule {\n  source_labels = [\"env\"]\n  target_label  = \"env\"\n  action        = \"lowercase\"\n}",
+    },
+    Action {
+        name: "uppercase",
+        summary: "Sets `target_label` to the joined `source_labels`, uppercased.",
+        fields: &["source_labels", "separator", "target_label"],
+        example: "rule {\n  source_labels = [\"env\"]\n  target_label  = \"env\"\n  action        = \"uppercase\"\n}",
+    },
+];
+
+pub fn action(name: &str) -> Option<&'static Action> {
+    ACTIONS.iter().find(|a| a.name == name)
+}
+
+/// A problem in a rule's `regex`, with byte ranges into the pattern.
+pub enum RegexIssue {
+    Syntax { range: Range<usize>, message: String },
+    /// A `.` between literal characters, as in `example.com`, which most
+    /// likely meant a literal dot.
+    UnescapedDot { offset: usize },
+}
+
+/// Checks `pattern` the way Prometheus compiles it. Rust's regex syntax is
+/// RE2's: no backreferences or look-around, the same classes and flags.
+pub fn check_regex(pattern: &str) -> Vec<RegexIssue> {
+    if let Err(err) = regex_syntax::Parser::new().parse(pattern) {
+        let (span, message) = match &err {
+            regex_syntax::Error::Parse(e) => (e.span(), e.kind().to_string()),
+            regex_syntax::Error::Translate(e) => (e.span(), e.kind().to_string()),
+            _ => return vec![RegexIssue::Syntax { range: 0..pattern.len(), message: err.to_string() }],
+        };
+        return vec![RegexIssue::Syntax { range: span.start.offset..span.end.offset, message }];
+    }
+    let mut issues = Vec::new();
+    if let Ok(ast) = ast::parse::Parser::new().parse(pattern) {
+        unescaped_dots(&ast, &mut issues);
+    }
+    issues
+}
+
+fn unescaped_dots(ast: &Ast, out: &mut Vec<RegexIssue>) {
+    match ast {
+        Ast::Concat(concat) => {
+            let is_word = |a: Option<&Ast>| {
+                matches!(a, Some(Ast::Literal(l)) if l.kind == ast::LiteralKind::Verbatim && l.c.is_alphanumeric())
+            };
+            for (i, item) in concat.asts.iter().enumerate() {
+                match item {
+                    Ast::Dot(span) if i > 0 && is_word(concat.asts.get(i - 1)) && is_word(concat.asts.get(i + 1)) => {
+                        out.push(RegexIssue::UnescapedDot { offset: span.start.offset });
+                    }
+                    other => unescaped_dots(other, out),
+                }
+            }
+        }
+        Ast::Alternation(alt) => alt.asts.iter().for_each(|a| unescaped_dots(a, out)),
+        Ast::Group(group) => unescaped_dots(&group.ast, out),
+        Ast::Repetition(rep) => unescaped_dots(&rep.ast, out),
+        _ => {}
+    }
+}
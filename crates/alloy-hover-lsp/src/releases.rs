@@ -0,0 +1,125 @@
+//! Which Alloy releases have each component and argument: what version
+//! gating, deprecation notes and "introduced in" hover lines are based on.
+//! The database is `schema/releases.toml`, or a newer copy named by the
+//! `alloy.releasesPath` setting.
+
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use crate::syntax::Block;
+use crate::workspace;
+
+const BUILTIN: &str = include_str!("../schema/releases.toml");
+
+/// An Alloy release. Written `"1.5"`; a leading `v` and a patch number are
+/// allowed and ignored, since patch releases add nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.trim().trim_start_matches('v').split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map_or(Some(0), |minor| minor.parse().ok())?;
+        Some(Self { major, minor })
+    }
+}
+
+impl TryFrom<String> for Version {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        Self::parse(&text).ok_or_else(|| format!("`{text}` is not a version like \"1.5\""))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// The releases a component or argument exists in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Availability {
+    pub since: Option<Version>,
+    pub deprecated: Option<Version>,
+    pub removed: Option<Version>,
+}
+
+impl Availability {
+    /// Why `version` does not have it, or `None` if it does.
+    pub fn missing_from(&self, version: Version) -> Option<String> {
+        match (self.since, self.removed) {
+            (Some(since), _) if version < since => Some(format!("needs Alloy {since} or later")),
+            (_, Some(removed)) if version >= removed => Some(format!("was removed in Alloy {removed}")),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Releases {
+    pub components: BTreeMap<String, Availability>,
+    /// By component, then by the argument's `block.…argument` path.
+    pub arguments: BTreeMap<String, BTreeMap<String, Availability>>,
+}
+
+/// The release database together with the release a workspace targets,
+/// for the checks and completions gated on it.
+#[derive(Clone, Copy)]
+pub struct TargetRelease<'a> {
+    pub releases: &'a Releases,
+    pub version: Version,
+}
+
+impl Releases {
+    /// The database compiled into the binary.
+    pub fn builtin() -> Arc<Releases> {
+        static RELEASES: OnceLock<Arc<Releases>> = OnceLock::new();
+        RELEASES.get_or_init(|| Arc::new(toml::from_str(BUILTIN).expect("bundled releases are valid TOML"))).clone()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("reading {}: {err}", path.display()))?;
+        toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err.message()))
+    }
+
+    pub fn component(&self, name: &str) -> Availability {
+        self.components.get(name).copied().unwrap_or_default()
+    }
+
+    /// The availability of an attribute, resolved from the blocks enclosing
+    /// it as in [`Schema::argument_in`](crate::schema::Schema::argument_in).
+    pub fn argument_in(&self, chain: &[&Block], name: &str) -> Availability {
+        let Some(owner) = chain.iter().rposition(|b| workspace::component_id(b).is_some()) else {
+            return Availability::default();
+        };
+        let path: Vec<String> =
+            chain[owner + 1..].iter().map(|b| b.full_name()).chain([name.to_string()]).collect();
+        let arguments = self.arguments.get(&chain[owner].full_name());
+        arguments.and_then(|a| a.get(&path.join("."))).copied().unwrap_or_default()
+    }
+
+    /// Like [`Releases::argument_in`], for blocks known by name only; the
+    /// innermost component among them owns the attribute.
+    pub fn argument_under(&self, blocks: &[String], name: &str) -> Availability {
+        let Some(owner) = blocks.iter().rposition(|b| self.components.contains_key(b)) else {
+            return Availability::default();
+        };
+        let path: Vec<&str> = blocks[owner + 1..].iter().map(String::as_str).chain([name]).collect();
+        let arguments = self.arguments.get(&blocks[owner]);
+        arguments.and_then(|a| a.get(&path.join("."))).copied().unwrap_or_default()
+    }
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use crate::syntax::Block;
+use crate::workspace;
+
+const BUILTIN: &str = include_str!("../schema/components.toml");
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Schema {
+    #[serde(default)]
+    pub components: BTreeMap<String, Component>,
+    /// Opaque values components hand each other, such as the receiver logs
+    /// are forwarded to. Only an export of the same capsule type can be
+    /// assigned where one is expected.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub capsules: BTreeMap<String, Capsule>,
+    /// Block definitions shared between components, pulled in with `use`.
+    /// Already inlined after loading, so consumers never need to look here.
+    #[serde(default, skip_serializing)]
+    shared_blocks: BTreeMap<String, BlockSchema>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Component {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub arguments: BTreeMap<String, Argument>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub blocks: BTreeMap<String, BlockSchema>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub exports: BTreeMap<String, Export>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    /// `__meta_*` labels on the targets of a discovery component, with
+    /// their descriptions.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta_labels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct BlockSchema {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub arguments: BTreeMap<String, Argument>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub blocks: BTreeMap<String, BlockSchema>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    /// Whether the body it is in needs one.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+    /// Whether it can be given more than once; otherwise at most once.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub repeatable: bool,
+    #[serde(default, rename = "use", skip_serializing)]
+    shared: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Argument {
+    #[serde(rename = "type")]
+    pub ty: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+    /// Default value as Alloy syntax, e.g. `"\"60s\""` or `"true"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+}
+
+/// What a component's body, or a block nested in one at any depth, can
+/// contain.
+#[derive(Debug, Clone, Copy)]
+pub struct Body<'a> {
+    pub arguments: &'a BTreeMap<String, Argument>,
+    pub blocks: &'a BTreeMap<String, BlockSchema>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Capsule {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Export {
+    #[serde(rename = "type")]
+    pub ty: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub doc: String,
+}
+
+impl Schema {
+    /// The schema compiled into the binary.
+    pub fn builtin() -> &'static Schema {
+        static SCHEMA: OnceLock<Schema> = OnceLock::new();
+        SCHEMA.get_or_init(|| {
+            let mut schema: Schema = toml::from_str(BUILTIN).expect("bundled schema is valid TOML");
+            schema.inline_shared_blocks();
+            schema
+        })
+    }
+
+    pub fn component(&self, name: &str) -> Option<&Component> {
+        self.components.get(name)
+    }
+
+    /// The capsule type `ty` names, written either bare (`LogsReceiver`) or
+    /// as `capsule(otelcol.Handler)`.
+    pub fn capsule<'a>(&self, ty: &'a str) -> Option<&'a str> {
+        let name = ty.strip_prefix("capsule(").and_then(|t| t.strip_suffix(')')).unwrap_or(ty);
+        self.capsules.contains_key(name).then_some(name)
+    }
+
+    /// The body of `component`, or of the nested block reached by following
+    /// `blocks` from it.
+    pub fn body(&self, component: &str, blocks: &[&str]) -> Option<Body<'_>> {
+        let component = self.component(component)?;
+        let mut body = Body { arguments: &component.arguments, blocks: &component.blocks };
+        for name in blocks {
+            let block = body.blocks.get(*name)?;
+            body = Body { arguments: &block.arguments, blocks: &block.blocks };
+        }
+        Some(body)
+    }
+
+    /// Looks up an argument of `component`, or of the nested block reached
+    /// by following `blocks` from it.
+    pub fn argument(&self, component: &str, blocks: &[&str], name: &str) -> Option<&Argument> {
+        self.body(component, blocks)?.arguments.get(name)
+    }
+
+    /// The body of the innermost of `chain`, the names of the blocks around
+    /// a statement (outermost first): the innermost component block owns
+    /// it, anything below that is a nested block path into its schema.
+    pub fn body_in<S: AsRef<str>>(&self, chain: &[S]) -> Option<Body<'_>> {
+        let owner = chain.iter().rposition(|b| self.component(b.as_ref()).is_some())?;
+        let nested: Vec<&str> = chain[owner + 1..].iter().map(AsRef::as_ref).collect();
+        self.body(chain[owner].as_ref(), &nested)
+    }
+
+    /// Resolves an attribute from the blocks enclosing it, as in
+    /// [`Schema::body_in`].
+    pub fn argument_in(&self, chain: &[&Block], name: &str) -> Option<&Argument> {
+        let owner = chain.iter().rposition(|b| workspace::component_id(b).is_some())?;
+        let nested: Vec<String> = chain[owner + 1..].iter().map(|b| b.full_name()).collect();
+        let nested: Vec<&str> = nested.iter().map(String::as_str).collect();
+        self.argument(&chain[owner].full_name(), &nested, name)
+    }
+
+    fn inline_shared_blocks(&mut self) {
+        let shared = std::mem::take(&mut self.shared_blocks);
+        for component in self.components.values_mut() {
+            for block in component.blocks.values_mut() {
+                block.inline(&shared);
+            }
+        }
+    }
+}
+
+impl Argument {
+    pub fn is_secret(&self) -> bool {
+        self.ty == "secret"
+    }
+}
+
+impl BlockSchema {
+    /// Replaces a `use = "…"` reference with the shared definition, keeping
+    /// anything set locally on top of it.
+    fn inline(&mut self, shared: &BTreeMap<String, BlockSchema>) {
+        if let Some(base) = self.shared.take().and_then(|name| shared.get(&name)) {
+            let mut merged = base.clone();
+            merged.arguments.extend(std::mem::take(&mut self.arguments));
+            merged.blocks.extend(std::mem::take(&mut self.blocks));
+            if !self.doc.is_empty() {
+                merged.doc = std::mem::take(&mut self.doc);
+            }
+            merged.deprecated = self.deprecated.take().or(merged.deprecated);
+            merged.required |= self.required;
+            merged.repeatable |= self.repeatable;
+            *self = merged;
+        }
+        for block in self.blocks.values_mut() {
+            block.inline(shared);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! `textDocument/semanticTokens/full`: classifies the AST so themes can
+//! colour block names, attributes and values, and flag sensitive values.
+
+use tower_lsp::lsp_types::*;
+
+use crate::document::Document;
+use crate::schema::Schema;
+use crate::syntax::{Block, Expr, ExprKind, Span, Stmt};
+use crate::workspace;
+
+/// Order matters: a token's type is its index in this list.
+pub const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::TYPE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::STRING,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::COMMENT,
+];
+
+/// Set on attributes whose schema type is `secret`, and on the values
+/// assigned to them.
+pub const SECRET: SemanticTokenModifier = SemanticTokenModifier::new("secret");
+
+/// Order matters: bit `i` of a token's modifier set is this list's entry `i`.
+pub const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[SECRET];
+
+const TYPE: u32 = 0;
+const PROPERTY: u32 = 1;
+const STRING: u32 = 2;
+const NUMBER: u32 = 3;
+const KEYWORD: u32 = 4;
+const FUNCTION: u32 = 5;
+const VARIABLE: u32 = 6;
+const COMMENT: u32 = 7;
+
+const SECRET_BIT: u32 = 1 << 0;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend { token_types: TOKEN_TYPES.to_vec(), token_modifiers: TOKEN_MODIFIERS.to_vec() }
+}
+
+struct Raw {
+    span: Span,
+    ty: u32,
+    modifiers: u32,
+}
+
+pub fn tokens(doc: &Document) -> SemanticTokens {
+    let file = doc.syntax();
+    let mut raw: Vec<Raw> = file.comments.iter().map(|&span| Raw { span, ty: COMMENT, modifiers: 0 }).collect();
+    collect_body(&file.body, &mut Vec::new(), &mut raw);
+    raw.sort_by_key(|t| t.span.start);
+    SemanticTokens { result_id: None, data: encode(doc, &raw) }
+}
+
+fn collect_body<'a>(body: &'a [Stmt], chain: &mut Vec<&'a Block>, out: &mut Vec<Raw>) {
+    for stmt in body {
+        match stmt {
+            Stmt::Block(block) => {
+                out.push(Raw { span: block.name_span(), ty: TYPE, modifiers: 0 });
+                if let Some(label) = &block.label {
+                    out.push(Raw { span: label.span, ty: STRING, modifiers: 0 });
+                }
+                chain.push(block);
+                collect_body(&block.body, chain, out);
+                chain.pop();
+            }
+            Stmt::Attribute(attr) => {
+                let secret = Schema::builtin()
+                    .argument_in(chain, &attr.name.name)
+                    .is_some_and(|arg| arg.is_secret());
+                let modifiers = if secret { SECRET_BIT } else { 0 };
+                out.push(Raw { span: attr.name.span, ty: PROPERTY, modifiers });
+                collect_expr(&attr.value, modifiers, out);
+            }
+        }
+    }
+}
+
+fn collect_expr(expr: &Expr, modifiers: u32, out: &mut Vec<Raw>) {
+    let ty = match &expr.kind {
+        ExprKind::String(_) => STRING,
+        ExprKind::Number(_) => NUMBER,
+        ExprKind::Null | ExprKind::Bool(_) => KEYWORD,
+        _ if expr.path().is_some() => VARIABLE,
+        ExprKind::Call(callee, args) => {
+            if callee.path().is_some() {
+                out.push(Raw { span: callee.span, ty: FUNCTION, modifiers: 0 });
+            } else {
+                collect_expr(callee, modifiers, out);
+            }
+            // The variable name passed to `env()` is not itself sensitive.
+            let modifiers = if workspace::is_env_call(callee) { 0 } else { modifiers };
+            args.iter().for_each(|a| collect_expr(a, modifiers, out));
+            return;
+        }
+        ExprKind::Object(fields) => {
+            for field in fields {
+                out.push(Raw { span: field.key_span, ty: PROPERTY, modifiers: 0 });
+                collect_expr(&field.value, modifiers, out);
+            }
+            return;
+        }
+        _ => {
+            expr.for_each_child(&mut |child| collect_expr(child, modifiers, out));
+            return;
+        }
+    };
+    out.push(Raw { span: expr.span, ty, modifiers });
+}
+
+/// Delta-encodes tokens per the LSP spec. Tokens spanning several lines
+/// (block comments, raw strings) are split into one token per line, since
+/// not every client supports multiline tokens. Tokens overlapping an
+/// earlier one (a block name broken up by a comment) are dropped: deltas
+/// can't go backwards.
+fn encode(doc: &Document, raw: &[Raw]) -> Vec<SemanticToken> {
+    let mut data = Vec::new();
+    let (mut prev_line, mut prev_start) = (0, 0);
+    let mut covered = 0;
+    for token in raw {
+        if token.span.start < covered {
+            continue;
+        }
+        covered = token.span.end;
+        let text = &doc.text[token.span.start..token.span.end];
+        let mut offset = token.span.start;
+        for line in text.split('\n') {
+            let piece = line.strip_suffix('\r').unwrap_or(line);
+            if !piece.is_empty() {
+                let start = doc.position(offset);
+                let length = piece.chars().map(char::len_utf16).sum::<usize>() as u32;
+                let delta_line = start.line - prev_line;
+                let delta_start = if delta_line == 0 { start.character - prev_start } else { start.character };
+                data.push(SemanticToken {
+                    delta_line,
+                    delta_start,
+                    length,
+                    token_type: token.ty,
+                    token_modifiers_bitset: token.modifiers,
+                });
+                (prev_line, prev_start) = (start.line, start.character);
+            }
+            offset += line.len() + 1;
+        }
+    }
+    data
+}
@@ -0,0 +1,1488 @@
+//! The language server: LSP request handlers over the analyses in the
+//! other modules.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+use tower_lsp::lsp_types::*;
+use tower_lsp::{Client, ClientSocket, LanguageServer, LspService};
+
+use crate::{
+    alloy_cli, call_hierarchy,
+    capabilities::ClientSupport,
+    catch_panic::{CatchPanic, PanicReport},
+    completion::{self, Resolve},
+    config::Config,
+    convert,
+    definition::{self, Target},
+    docs::{Docs, ReloadDocsParams},
+    document::Document,
+    folding,
+    graph::{self, ComponentGraph, ComponentGraphParams},
+    hover, inlay_hints, instance, lint,
+    lint_config::{self, LintConfig},
+    linked_editing, markdown,
+    metrics_api::{self, Catalog},
+    modules,
+    registration::{self, Feature},
+    releases::{Releases, TargetRelease, Version},
+    schema::Schema,
+    semantic_tokens,
+    status::{CacheStatus, DocumentsStatus, PendingStatus, Status, WorkspaceStatus},
+    suppress, symbols,
+    syntax::lexer::BOM,
+    workspace::{self, Workspace},
+};
+
+const PREVIEW_GRAPH_COMMAND: &str = "alloy.previewGraph";
+const FMT_COMMAND: &str = "alloy.fmt";
+const VALIDATE_COMMAND: &str = "alloy.validate";
+const GRAPH_COMMAND: &str = "alloy.graph";
+const CONSUMERS_COMMAND: &str = "alloy.goToConsumers";
+const REFRESH_HEALTH_COMMAND: &str = "alloy.refreshHealth";
+const CONVERT_COMMAND: &str = "alloy.convert";
+const LIST_MODULES_COMMAND: &str = "alloy.listModules";
+
+const NO_ALLOY: &str = "no `alloy` binary found; set `alloy.path` in the settings";
+
+type DiagnosticSets = HashMap<&'static str, Vec<Diagnostic>>;
+type HoverResult = Option<Hover>;
+type ComponentHealth = Arc<HashMap<String, instance::Health>>;
+
+/// What the last hover was computed against. Any edit to the file, or to
+/// the workspace index it may draw on, changes the key; a change of
+/// settings drops the cached hover instead.
+#[derive(PartialEq)]
+struct HoverKey {
+    uri: Url,
+    version: i32,
+    revision: u64,
+    position: Position,
+}
+
+/// Names from the configured metrics API, and when they were last asked for.
+#[derive(Default)]
+struct MetricsCache {
+    catalog: Option<Arc<Catalog>>,
+    requested: Option<Instant>,
+}
+
+/// The workspace's `.alloylint.toml` as of its last modification time, so
+/// edits to it apply from the next lint on.
+#[derive(Default)]
+struct ProjectLints {
+    modified: Option<SystemTime>,
+    config: Arc<LintConfig>,
+}
+
+#[derive(Clone)]
+pub struct Backend {
+    client: Client,
+    client_support: Arc<RwLock<ClientSupport>>,
+    config: Arc<RwLock<Config>>,
+    files: Arc<RwLock<HashMap<Url, Document>>>,
+    /// Files closed in the editor whose documents are still in `files`,
+    /// least recently closed first.
+    closed: Arc<RwLock<VecDeque<Url>>>,
+    workspace: Arc<RwLock<Workspace>>,
+    /// Diagnostics per file, keyed by their `source`: our own lints plus
+    /// external tools (the `alloy` CLI, a running instance).
+    diagnostics: Arc<RwLock<HashMap<Url, DiagnosticSets>>>,
+    metrics: Arc<RwLock<MetricsCache>>,
+    /// Bumped on every lint; a debounced run only goes ahead if no other
+    /// lint was scheduled or ran while it waited.
+    lint_generations: Arc<RwLock<HashMap<Url, u64>>>,
+    /// Files with a debounced lint still waiting to run.
+    pending_lints: Arc<RwLock<HashSet<Url>>>,
+    /// Hovering repeatedly over the same spot while reading is common.
+    last_hover: Arc<RwLock<Option<(HoverKey, HoverResult)>>>,
+    docs: Arc<RwLock<Docs>>,
+    project_lints: Arc<RwLock<ProjectLints>>,
+    releases: Arc<RwLock<Arc<Releases>>>,
+    /// What the instance last said about each component's health, by ID;
+    /// kept until the user asks for it again.
+    health: Arc<RwLock<Option<ComponentHealth>>>,
+    /// How many threads indexing and linting many files at once may use.
+    threads: usize,
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(
+        &self,
+        params: InitializeParams,
+    ) -> tower_lsp::jsonrpc::Result<InitializeResult> {
+        #[allow(deprecated)]
+        let root = params
+            .workspace_folders
+            .as_ref()
+            .and_then(|folders| folders.first())
+            .map(|f| &f.uri)
+            .or(params.root_uri.as_ref())
+            .and_then(|uri| uri.to_file_path().ok())
+            // A lone file opened on its own comes with no root, or with the
+            // file itself as the root. Either way there is no workspace to
+            // index; every feature works from the open documents alone.
+            .filter(|path| path.is_dir());
+        *self.workspace.write().unwrap() = Workspace::new(root);
+        *self.config.write().unwrap() = Config::from_value(params.initialization_options);
+        let locale = self.config.read().unwrap().locale.clone();
+        if let Some(locale) = locale.or(params.locale).or_else(system_locale) {
+            self.docs.write().unwrap().localize(&locale);
+        }
+        let support = ClientSupport::new(&params.capabilities);
+        *self.client_support.write().unwrap() = support.clone();
+        // Dynamically registered features are registered in `initialized`.
+        let features = self.config.read().unwrap().features.clone();
+        let advertise = |feature: Feature| feature.enabled(&features) && !support.dynamic.contains(&feature);
+
+        Ok(InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::FULL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
+                )),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec!["\"".into(), ".".into(), "/".into()]),
+                    resolve_provider: Some(true),
+                    ..Default::default()
+                }),
+                code_action_provider: support.code_action_literals.then(|| {
+                    CodeActionProviderCapability::Options(CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        ..Default::default()
+                    })
+                }),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                diagnostic_provider: support.pull_diagnostics.then(|| {
+                    DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                        identifier: Some(lint::SOURCE.into()),
+                        inter_file_dependencies: true,
+                        workspace_diagnostics: true,
+                        ..Default::default()
+                    })
+                }),
+                code_lens_provider: advertise(Feature::CodeLens).then(registration::code_lens_options),
+                inlay_hint_provider: advertise(Feature::InlayHints)
+                    .then(|| OneOf::Right(InlayHintServerCapabilities::Options(registration::inlay_hint_options()))),
+                semantic_tokens_provider: advertise(Feature::SemanticTokens).then(|| {
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(registration::semantic_tokens_options())
+                }),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: None,
+                    file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                        will_rename: Some(rename_filters()),
+                        did_rename: Some(rename_filters()),
+                        ..Default::default()
+                    }),
+                }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: [
+                        PREVIEW_GRAPH_COMMAND,
+                        FMT_COMMAND,
+                        VALIDATE_COMMAND,
+                        GRAPH_COMMAND,
+                        CONSUMERS_COMMAND,
+                        REFRESH_HEALTH_COMMAND,
+                        CONVERT_COMMAND,
+                        LIST_MODULES_COMMAND,
+                    ]
+                        .map(String::from)
+                        .into(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            server_info: Some(ServerInfo {
+                name: "alloy-hover-lsp".into(),
+                version: Some("0.1.0".into()),
+            }),
+        })
+    }
+
+    async fn initialized(&self, _params: InitializedParams) {
+        if let Err(err) = self.load_releases() {
+            self.client.show_message(MessageType::WARNING, format!("Alloy release database: {err}")).await;
+        }
+        let progress = self.begin_progress("index", "Indexing Alloy configs").await;
+        let (root, threads) = (self.workspace.read().unwrap().root.clone(), self.threads);
+        let cache = modules::cache_dir(&self.config.read().unwrap().modules);
+        // Walking a large repo is blocking IO; keep it off the LSP loop, and
+        // off the workspace lock so files can be opened meanwhile.
+        let scanned = tokio::task::spawn_blocking(move || {
+            let mut scanned = Workspace::new(root);
+            scanned.scan(cache.as_deref(), threads);
+            scanned
+        });
+        if let Ok(scanned) = scanned.await {
+            self.workspace.write().unwrap().merge(scanned);
+            // Files opened during the scan were linted against part of it.
+            let open = self.open_uris();
+            workspace::parallel_map(&open, self.threads, |uri| self.lint(uri));
+            for uri in open {
+                self.publish_diagnostics(uri).await;
+            }
+        }
+        if let Some(token) = progress {
+            self.end_progress(token).await;
+        }
+        self.show_docs_problems().await;
+        self.show_config_problems().await;
+        self.refresh_metrics(false);
+
+        let registrations: Vec<_> = {
+            let (support, config) = (self.client_support.read().unwrap(), self.config.read().unwrap());
+            support.dynamic.iter().filter(|f| f.enabled(&config.features)).map(|f| f.registration()).collect()
+        };
+        self.update_registrations(registrations, Vec::new()).await;
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.closed.write().unwrap().retain(|closed| *closed != uri);
+        let dependents = self.set_text(uri.clone(), params.text_document.version, params.text_document.text);
+        self.lint(&uri);
+        self.publish_diagnostics(uri.clone()).await;
+        for dependent in dependents {
+            self.lint(&dependent);
+            self.publish_diagnostics(dependent).await;
+        }
+        if self.load_modules(&uri).await {
+            self.publish_diagnostics(uri).await;
+        }
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        if let Some(change) = params.content_changes.into_iter().last() {
+            let uri = params.text_document.uri;
+            let dependents = self.set_text(uri.clone(), params.text_document.version, change.text);
+            self.schedule_lint(uri).await;
+            for dependent in dependents {
+                self.schedule_lint(dependent).await;
+            }
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        let mut closed = self.closed.write().unwrap();
+        closed.retain(|closed| *closed != uri);
+        closed.push_back(uri);
+        drop(closed);
+        self.evict_closed();
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.validate(&uri).await;
+        self.publish_diagnostics(uri).await;
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        let config = Config::from_value(Some(params.settings));
+        let before = std::mem::replace(&mut *self.config.write().unwrap(), config.clone());
+        self.show_config_problems().await;
+        self.refresh_metrics(true);
+        self.evict_closed();
+
+        let dynamic = self.client_support.read().unwrap().dynamic.clone();
+        let (register, unregister) = registration::changes(&before.features, &config.features, &dynamic);
+        self.update_registrations(register, unregister).await;
+
+        // Hovers draw on many settings (style, env values, cloud presets).
+        *self.last_hover.write().unwrap() = None;
+        let releases_changed = before.alloy.releases_path != config.alloy.releases_path;
+        if releases_changed {
+            if let Err(err) = self.load_releases() {
+                self.client.show_message(MessageType::WARNING, format!("Alloy release database: {err}")).await;
+            }
+        }
+        let relint = before.diagnostics.rules != config.diagnostics.rules
+            || before.alloy.target_version != config.alloy.target_version
+            || releases_changed;
+        if relint {
+            let open = self.open_uris();
+            workspace::parallel_map(&open, self.threads, |uri| self.lint(uri));
+            for uri in open {
+                self.publish_diagnostics(uri).await;
+            }
+        }
+    }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> tower_lsp::jsonrpc::Result<DocumentDiagnosticReportResult> {
+        let report = self.diagnostic_report(&params.text_document.uri, params.previous_result_id.as_deref());
+        Ok(DocumentDiagnosticReportResult::Report(report))
+    }
+
+    /// Lints of the workspace's files that are not open, read from disk;
+    /// open ones are pulled one by one. Files whose lints are what the
+    /// client already has are reported unchanged.
+    async fn workspace_diagnostic(
+        &self,
+        params: WorkspaceDiagnosticParams,
+    ) -> tower_lsp::jsonrpc::Result<WorkspaceDiagnosticReportResult> {
+        let previous: HashMap<Url, String> =
+            params.previous_result_ids.into_iter().map(|p| (p.uri, p.value)).collect();
+        // Reading and linting every file is blocking work; keep it off the LSP loop.
+        let this = self.clone();
+        let items = tokio::task::spawn_blocking(move || {
+            let unopened: Vec<Url> = {
+                let (files, closed) = (this.files.read().unwrap(), this.closed.read().unwrap());
+                let closed: HashSet<&Url> = closed.iter().collect();
+                let workspace = this.workspace.read().unwrap();
+                workspace.uris().filter(|uri| !files.contains_key(uri) || closed.contains(uri)).cloned().collect()
+            };
+            workspace::parallel_map(&unopened, this.threads, |uri| {
+                let text = std::fs::read_to_string(uri.to_file_path().ok()?).ok()?;
+                let items: Vec<Diagnostic> =
+                    this.findings(&Document::new(text), uri).into_iter().map(|f| f.diagnostic).collect();
+                let result_id = result_id(&items);
+                let report = if previous.get(uri) == Some(&result_id) {
+                    WorkspaceDocumentDiagnosticReport::Unchanged(WorkspaceUnchangedDocumentDiagnosticReport {
+                        uri: uri.clone(),
+                        version: None,
+                        unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport { result_id },
+                    })
+                } else {
+                    WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                        uri: uri.clone(),
+                        version: None,
+                        full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id: Some(result_id), items },
+                    })
+                };
+                Some(report)
+            })
+        })
+        .await
+        // A panic there is the request's, as it would be run inline.
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+        let items = items.into_iter().flatten().collect();
+        Ok(WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport { items }))
+    }
+
+    async fn will_rename_files(&self, params: RenameFilesParams) -> tower_lsp::jsonrpc::Result<Option<WorkspaceEdit>> {
+        let changes = self.workspace.read().unwrap().import_edits(&renamed_paths(&params));
+        Ok((!changes.is_empty()).then(|| WorkspaceEdit { changes: Some(changes), ..Default::default() }))
+    }
+
+    /// The index, and the documents kept of closed files, follow the files;
+    /// the client reopens any that were open under their new URI. Open
+    /// files are linted again, as what they import may now be somewhere else.
+    async fn did_rename_files(&self, params: RenameFilesParams) {
+        let renames = renamed_paths(&params);
+        self.workspace.write().unwrap().rename(&renames);
+        {
+            let (mut files, mut closed) = (self.files.write().unwrap(), self.closed.write().unwrap());
+            for uri in closed.iter_mut() {
+                let Some(new) = workspace::moved(&renames, uri) else { continue };
+                if let Some(doc) = files.remove(uri) {
+                    files.insert(new.clone(), doc);
+                }
+                *uri = new;
+            }
+        }
+        let open = self.open_uris();
+        workspace::parallel_map(&open, self.threads, |uri| self.lint(uri));
+        for uri in open {
+            self.publish_diagnostics(uri).await;
+        }
+    }
+
+    async fn completion(
+        &self,
+        params: CompletionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        let uri = params.text_document_position.text_document.uri;
+        let pos = params.text_document_position.position;
+
+        self.refresh_metrics(false);
+        let catalog = self.metrics.read().unwrap().catalog.clone();
+        let files = self.files.read().unwrap();
+        let Some(doc) = files.get(&uri) else { return Ok(None) };
+        let workspace = self.workspace.read().unwrap();
+        let support = self.client_support.read().unwrap().clone();
+        let options = completion::Options {
+            snippets: support.snippets,
+            cloud_endpoints: self.config.read().unwrap().grafana_cloud.presets,
+            resolve_edits: support.resolve_completion_edits,
+        };
+        let releases = self.releases.read().unwrap().clone();
+        let target = self.target_version().map(|version| TargetRelease { releases: &releases, version });
+        let mut response = completion::complete(doc, &uri, pos, &workspace, catalog.as_deref(), options, target);
+        let items = match &mut response {
+            Some(CompletionResponse::Array(items)) => items.as_mut_slice(),
+            Some(CompletionResponse::List(list)) => list.items.as_mut_slice(),
+            None => &mut [],
+        };
+        items.iter_mut().for_each(|item| with_line_endings(doc, item));
+        Ok(response)
+    }
+
+    /// Documentation, and the imports of modules, are only worked out for
+    /// the item the client is about to show or insert.
+    async fn completion_resolve(&self, mut item: CompletionItem) -> tower_lsp::jsonrpc::Result<CompletionItem> {
+        let Some(resolve) = item.data.clone().and_then(|data| serde_json::from_value::<Resolve>(data).ok()) else {
+            return Ok(item);
+        };
+        let releases = self.releases.read().unwrap().clone();
+        let files = self.files.read().unwrap();
+        let doc = resolve.document().and_then(|uri| files.get(uri));
+        let docs = |name: &str| self.docs.read().unwrap().get(name);
+        let markdown = self.client_support.read().unwrap().markdown_completion;
+        completion::resolve(&mut item, resolve, doc, &releases, docs, markdown);
+        if let Some(doc) = doc {
+            with_line_endings(doc, &mut item);
+        }
+        Ok(item)
+    }
+
+    async fn hover(
+        &self,
+        params: HoverParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Hover>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let pos = params.text_document_position_params.position;
+
+        let (mut hover, scrape) = self.static_hover(&uri, pos);
+        // Live data is fetched afresh each time, never cached.
+        if let (Some(Hover { contents: HoverContents::Markup(markup), .. }), Some(id)) = (&mut hover, scrape) {
+            if let Some(live) = self.live_scrape(id).await {
+                match markup.kind {
+                    MarkupKind::Markdown => markup.value.push_str(&live),
+                    MarkupKind::PlainText => markup.value.push_str(&markdown::to_plain_text(&live)),
+                }
+            }
+        }
+        Ok(hover)
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> tower_lsp::jsonrpc::Result<Option<Vec<FoldingRange>>> {
+        let files = self.files.read().unwrap();
+        Ok(files.get(&params.text_document.uri).map(folding::ranges))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<DocumentSymbolResponse>> {
+        let files = self.files.read().unwrap();
+        Ok(files.get(&params.text_document.uri).map(|doc| DocumentSymbolResponse::Nested(symbols::outline(doc))))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<GotoDefinitionResponse>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let pos = params.text_document_position_params.position;
+        let target = {
+            let files = self.files.read().unwrap();
+            let Some(doc) = files.get(&uri) else { return Ok(None) };
+            definition::target(doc, &uri, doc.offset(pos), &self.workspace.read().unwrap())
+        };
+
+        let location = match target {
+            None => None,
+            Some(Target::Location(location)) => Some(location),
+            Some(Target::Module { import, declare }) => {
+                let settings = self.config.read().unwrap().modules.clone();
+                match import.load(&settings).await {
+                    Ok(path) => path.and_then(|p| definition::in_module(&p, declare.as_deref())),
+                    Err(err) => {
+                        let message = format!("fetching module `{}`: {err:#}", import.label());
+                        self.client.log_message(MessageType::WARNING, message).await;
+                        None
+                    }
+                }
+            }
+        };
+        Ok(location.map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let pos = params.text_document_position_params.position;
+        Ok(call_hierarchy::prepare(&uri, pos, &self.workspace.read().unwrap()))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        Ok(Some(call_hierarchy::incoming(&params.item, &self.workspace.read().unwrap())))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        Ok(Some(call_hierarchy::outgoing(&params.item, &self.workspace.read().unwrap())))
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<LinkedEditingRanges>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let pos = params.text_document_position_params.position;
+        let files = self.files.read().unwrap();
+        Ok(files.get(&uri).and_then(|doc| linked_editing::ranges(doc, doc.offset(pos))))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<SemanticTokensResult>> {
+        if !self.config.read().unwrap().features.semantic_tokens {
+            return Ok(None);
+        }
+        let files = self.files.read().unwrap();
+        let Some(doc) = files.get(&params.text_document.uri) else { return Ok(None) };
+        Ok(Some(SemanticTokensResult::Tokens(semantic_tokens::tokens(doc))))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> tower_lsp::jsonrpc::Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let files = self.files.read().unwrap();
+        let Some(doc) = files.get(&uri) else { return Ok(None) };
+
+        let overlaps = |r: &Range| r.start <= params.range.end && params.range.start <= r.end;
+        let actions = self
+            .findings(doc, &uri)
+            .into_iter()
+            .filter(|f| overlaps(&f.diagnostic.range))
+            .flat_map(|f| {
+                let diagnostic = f.diagnostic;
+                let (uri, files) = (&uri, &files);
+                // Suppressing is always possible, but never the preferred fix.
+                let suppress = suppress::fix(doc, &diagnostic).map(|fix| (false, fix));
+                let fixes = f.fixes.into_iter().enumerate().map(|(i, fix)| (i == 0, fix));
+                fixes.chain(suppress).map(move |(preferred, fix)| {
+                    let mut changes = fix.elsewhere;
+                    changes.entry(uri.clone()).or_default().extend(fix.edits);
+                    for (uri, edits) in &mut changes {
+                        // Only open files are known to use CRLF.
+                        let Some(doc) = files.get(uri) else { continue };
+                        for edit in edits {
+                            edit.new_text = doc.with_line_endings(std::mem::take(&mut edit.new_text));
+                        }
+                    }
+                    CodeActionOrCommand::CodeAction(CodeAction {
+                        title: fix.title,
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit { changes: Some(changes), ..Default::default() }),
+                        is_preferred: Some(preferred),
+                        ..Default::default()
+                    })
+                })
+            })
+            .collect();
+        Ok(Some(actions))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> tower_lsp::jsonrpc::Result<Option<Vec<InlayHint>>> {
+        if !self.config.read().unwrap().features.closing_brace_hints {
+            return Ok(None);
+        }
+        let files = self.files.read().unwrap();
+        Ok(files.get(&params.text_document.uri).map(|doc| inlay_hints::closing_braces(doc, params.range)))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> tower_lsp::jsonrpc::Result<Option<Vec<CodeLens>>> {
+        if !self.config.read().unwrap().features.code_lens {
+            return Ok(None);
+        }
+        let uri = params.text_document.uri;
+        let health = self.component_health().await;
+        let workspace = self.workspace.read().unwrap();
+        let components: Vec<_> = workspace.components().filter(|(u, _)| **u == uri).map(|(_, c)| c).collect();
+        if components.is_empty() {
+            return Ok(None);
+        }
+        let mut lenses = vec![CodeLens {
+            range: Range::default(),
+            command: Some(Command {
+                title: "Preview pipeline graph".into(),
+                command: PREVIEW_GRAPH_COMMAND.into(),
+                arguments: Some(vec![serde_json::json!(uri)]),
+            }),
+            data: None,
+        }];
+        // One lens per component whose exports are used somewhere, above
+        // its header.
+        for component in components {
+            let id = component.id();
+            let start = component.range.start;
+            if let Some(health) = health.as_ref().and_then(|h| h.get(&id)) {
+                let title = match (health.state.as_str(), health.message.as_str()) {
+                    (state, "") | (state @ "healthy", _) => state.to_string(),
+                    (state, message) => format!("{state}: {message}"),
+                };
+                lenses.push(CodeLens {
+                    range: Range::new(start, start),
+                    command: Some(Command { title, command: REFRESH_HEALTH_COMMAND.into(), arguments: None }),
+                    data: None,
+                });
+            }
+            let consumers = workspace.references_to(&id).count();
+            if consumers == 0 {
+                continue;
+            }
+            lenses.push(CodeLens {
+                range: Range::new(start, start),
+                command: Some(Command {
+                    title: format!("{consumers} consumer{}", if consumers == 1 { "" } else { "s" }),
+                    command: CONSUMERS_COMMAND.into(),
+                    arguments: Some(vec![serde_json::json!(id)]),
+                }),
+                data: None,
+            });
+        }
+        Ok(Some(lenses))
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<serde_json::Value>> {
+        let mut args = params.arguments.into_iter();
+        match params.command.as_str() {
+            // Arguments: `[uri]`. Applies `alloy fmt`'s output as an edit.
+            FMT_COMMAND => {
+                let uri = command_uri(args.next())?;
+                self.format(uri).await.map_err(tower_lsp::jsonrpc::Error::invalid_params)?;
+                Ok(None)
+            }
+            // Arguments: `[uri]`. Runs every check a save does, publishes the
+            // results and returns them.
+            VALIDATE_COMMAND => {
+                let uri = command_uri(args.next())?;
+                if !self.files.read().unwrap().contains_key(&uri) {
+                    return Err(tower_lsp::jsonrpc::Error::invalid_params(format!("`{uri}` is not open")));
+                }
+                self.validate(&uri).await;
+                self.publish_diagnostics(uri.clone()).await;
+                Ok(Some(serde_json::json!(self.current_diagnostics(&uri))))
+            }
+            // Arguments: `[uri?, "mermaid" | "dot"]`; renders Mermaid by default.
+            PREVIEW_GRAPH_COMMAND | GRAPH_COMMAND => {
+                let uri = args.next().and_then(|v| serde_json::from_value::<Url>(v).ok());
+                let format = args.next();
+                let graph = graph::build(&self.workspace.read().unwrap(), uri.as_ref());
+                let rendered = match format.as_ref().and_then(|f| f.as_str()) {
+                    Some("dot") => graph.dot,
+                    _ => graph.mermaid,
+                };
+                Ok(Some(serde_json::Value::String(rendered)))
+            }
+            // Arguments: `[component ID]`. Returns where the component's
+            // exports are used; with a single consumer, also opens it.
+            CONSUMERS_COMMAND => {
+                let id = args.next().and_then(|v| v.as_str().map(str::to_string)).ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params("expected a component ID as the first argument")
+                })?;
+                let locations = self.consumers(&id);
+                let show_document = self.client_support.read().unwrap().show_document;
+                if let ([location], true) = (&locations[..], show_document) {
+                    let params = ShowDocumentParams {
+                        uri: location.uri.clone(),
+                        external: None,
+                        take_focus: Some(true),
+                        selection: Some(location.range),
+                    };
+                    let _ = self.client.show_document(params).await;
+                }
+                Ok(Some(serde_json::json!(locations)))
+            }
+            // No arguments. Asks the instance for component health again
+            // and has the client redraw the lenses showing it.
+            REFRESH_HEALTH_COMMAND => {
+                *self.health.write().unwrap() = None;
+                self.component_health().await;
+                if self.client_support.read().unwrap().code_lens_refresh {
+                    let _ = self.client.code_lens_refresh().await;
+                }
+                Ok(None)
+            }
+            // Arguments: `[uri, format?]`, the format one of
+            // `convert::FORMATS` and guessed when left out. Converts the
+            // file on disk and returns `{ uri, text }`, the `.alloy` file
+            // beside it and its contents, also creating and opening it
+            // when the client can and there is no such file yet.
+            CONVERT_COMMAND => {
+                let uri = command_uri(args.next())?;
+                let format = args.next().and_then(|v| v.as_str().map(str::to_string));
+                let converted = self.convert(uri, format).await.map_err(tower_lsp::jsonrpc::Error::invalid_params)?;
+                Ok(Some(serde_json::json!(converted)))
+            }
+            // No arguments. Returns every `declare` in the workspace, by
+            // file and name, with its arguments and exports and the `uri`
+            // and `path` of the file it is in.
+            LIST_MODULES_COMMAND => {
+                let workspace = self.workspace.read().unwrap();
+                let mut declares: Vec<_> =
+                    workspace.declares().map(|(uri, declare)| (workspace.display_path(uri), declare, uri)).collect();
+                declares.sort_by(|(a, x, _), (b, y, _)| (a, &x.name).cmp(&(b, &y.name)));
+                let modules = declares.into_iter().map(|(path, declare, uri)| {
+                    let mut module = serde_json::json!(declare);
+                    module["uri"] = serde_json::json!(uri);
+                    module["path"] = serde_json::json!(path);
+                    module
+                });
+                Ok(Some(serde_json::Value::Array(modules.collect())))
+            }
+            other => Err(tower_lsp::jsonrpc::Error::invalid_params(format!("unknown command `{other}`"))),
+        }
+    }
+
+    async fn shutdown(&self) -> tower_lsp::jsonrpc::Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend {
+    /// Gets going again after a handler panicked: the locks it held are
+    /// usable again (what it was changing may be half done, but that beats
+    /// every later request failing), and the client's log gets the details.
+    fn recover(&self, report: PanicReport) {
+        self.client_support.clear_poison();
+        self.config.clear_poison();
+        self.files.clear_poison();
+        self.closed.clear_poison();
+        self.workspace.clear_poison();
+        self.diagnostics.clear_poison();
+        self.metrics.clear_poison();
+        self.lint_generations.clear_poison();
+        self.pending_lints.clear_poison();
+        self.last_hover.clear_poison();
+        self.docs.clear_poison();
+        self.project_lints.clear_poison();
+        self.releases.clear_poison();
+        self.health.clear_poison();
+        let client = self.client.clone();
+        let message = format!("{} panicked: {}\n{}", report.method, report.message, report.backtrace);
+        tokio::spawn(async move { client.log_message(MessageType::ERROR, message).await });
+    }
+
+    /// Reindexes just this file, returning the other open files whose
+    /// diagnostics the change can affect and so have to be linted again.
+    fn set_text(&self, uri: Url, version: i32, text: String) -> Vec<Url> {
+        let doc = Document::new(text).with_version(version);
+        let mut workspace = self.workspace.write().unwrap();
+        let change = workspace.update_document(uri.clone(), &doc);
+        let dependents = workspace.dependents(&uri, &change);
+        drop(workspace);
+        self.files.write().unwrap().insert(uri, doc);
+        let (files, closed) = (self.files.read().unwrap(), self.closed.read().unwrap());
+        let closed: HashSet<&Url> = closed.iter().collect();
+        dependents.into_iter().filter(|u| files.contains_key(u) && !closed.contains(u)).collect()
+    }
+
+    /// The files open in the editor, leaving out closed ones whose documents
+    /// are only kept around.
+    fn open_uris(&self) -> Vec<Url> {
+        let (files, closed) = (self.files.read().unwrap(), self.closed.read().unwrap());
+        let closed: HashSet<&Url> = closed.iter().collect();
+        files.keys().filter(|uri| !closed.contains(uri)).cloned().collect()
+    }
+
+    /// Drops the documents of closed files, least recently closed first,
+    /// until they fit in `memory.maxMb`. The workspace index keeps what it
+    /// learned from them, so references into them still resolve.
+    fn evict_closed(&self) {
+        let limit = self.config.read().unwrap().memory.max_mb.saturating_mul(1 << 20);
+        let mut closed = self.closed.write().unwrap();
+        let mut files = self.files.write().unwrap();
+        let mut held: usize = closed.iter().filter_map(|uri| files.get(uri)).map(Document::footprint).sum();
+        while held > limit {
+            let Some(uri) = closed.pop_front() else { break };
+            held -= files.remove(&uri).map_or(0, |doc| doc.footprint());
+        }
+    }
+
+    /// The hover that only depends on the text and workspace, cached, and
+    /// the scrape component under the cursor if there is one.
+    fn static_hover(&self, uri: &Url, pos: Position) -> (HoverResult, Option<String>) {
+        let files = self.files.read().unwrap();
+        let Some(doc) = files.get(uri) else { return (None, None) };
+        let scrape = hover::scrape_at(doc, doc.offset(pos));
+        let workspace = self.workspace.read().unwrap();
+        let key = HoverKey { uri: uri.clone(), version: doc.version, revision: workspace.revision(), position: pos };
+        if let Some((last, hover)) = &*self.last_hover.read().unwrap() {
+            if *last == key {
+                return (hover.clone(), scrape);
+            }
+        }
+        let mut hover = self.compute_hover(doc, uri, pos, &workspace);
+        if !self.client_support.read().unwrap().markdown_hover {
+            if let Some(Hover { contents: HoverContents::Markup(markup), .. }) = &mut hover {
+                markup.kind = MarkupKind::PlainText;
+                markup.value = markdown::to_plain_text(&markup.value);
+            }
+        }
+        *self.last_hover.write().unwrap() = Some((key, hover.clone()));
+        (hover, scrape)
+    }
+
+    /// Component health for the lenses, from the instance the first time
+    /// it is needed after startup or a refresh. `None` with the lenses off
+    /// or the instance unreachable.
+    async fn component_health(&self) -> Option<ComponentHealth> {
+        let settings = self.config.read().unwrap().instance.clone();
+        if !settings.health_lens || settings.url.is_none() {
+            return None;
+        }
+        if let Some(health) = self.health.read().unwrap().clone() {
+            return Some(health);
+        }
+        let components = tokio::task::spawn_blocking(move || instance::components(&settings)).await.ok()??;
+        let health: ComponentHealth = Arc::new(components.into_iter().map(|c| (c.local_id, c.health)).collect());
+        *self.health.write().unwrap() = Some(health.clone());
+        Some(health)
+    }
+
+    /// What the configured instance says about the scrape component `id`.
+    async fn live_scrape(&self, id: String) -> Option<String> {
+        let settings = self.config.read().unwrap().instance.clone();
+        settings.url.as_ref()?;
+        let component = tokio::task::spawn_blocking(move || instance::component(&settings, &id)).await.ok()??;
+        Some(hover::live_scrape(&component.scrape_status()?))
+    }
+
+    fn compute_hover(&self, doc: &Document, uri: &Url, pos: Position, workspace: &Workspace) -> Option<Hover> {
+        let offset = doc.offset(pos);
+        let settings = self.config.read().unwrap().hover.clone();
+        let style = settings.style;
+        let releases = self.releases.read().unwrap().clone();
+        if let Some(hover) = hover::label_references(doc, offset, workspace) {
+            return Some(hover);
+        }
+        if let Some(hover) = hover::module_argument(doc, uri, offset, workspace) {
+            return Some(hover);
+        }
+        if let Some(hover) = hover::module_instance(doc, uri, offset, workspace, style) {
+            return Some(hover);
+        }
+        if let Some(hover) = hover::relabel_action(doc, offset, style) {
+            return Some(hover);
+        }
+        if let Some(hover) = hover::label(doc, offset) {
+            return Some(hover);
+        }
+        if let Some(hover) = hover::component_reference(doc, uri, offset, workspace) {
+            return Some(hover);
+        }
+        if settings.env_values {
+            if let Some(hover) = hover::env_call(doc, offset) {
+                return Some(hover);
+            }
+        }
+        if self.config.read().unwrap().grafana_cloud.presets {
+            if let Some(hover) = hover::cloud_endpoint(doc, offset) {
+                return Some(hover);
+            }
+        }
+        if let Some(hover) = hover::duration(doc, offset) {
+            return Some(hover);
+        }
+        let docs = |name: &str| self.docs.read().unwrap().get(name);
+        if let Some(hover) = hover::attribute(doc, offset, style, &releases, docs) {
+            return Some(hover);
+        }
+        if let Some(hover) = hover::nested_block(doc, offset, style, docs) {
+            return Some(hover);
+        }
+
+        let line = doc.text.lines().nth(pos.line as usize).unwrap_or_default();
+
+        let mut start = pos.character as usize;
+        let mut end = start;
+        let is_word = |ch: char| ch.is_alphanumeric() || ch == '_' || ch == '.';
+
+        while start > 0 && line.chars().nth(start - 1).map(is_word).unwrap_or(false) {
+            start -= 1;
+        }
+        while end < line.len() && line.chars().nth(end).map(is_word).unwrap_or(false) {
+            end += 1;
+        }
+
+        let word = line.get(start..end).unwrap_or("").trim_matches('"');
+        if word.is_empty() {
+            return None;
+        }
+
+        let md = hover::word(word, self.docs.read().unwrap().get(word), style, &releases)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: md,
+            }),
+            range: Some(Range {
+                start: Position {
+                    line: pos.line,
+                    character: start as u32,
+                },
+                end: Position {
+                    line: pos.line,
+                    character: end as u32,
+                },
+            }),
+        })
+    }
+
+    async fn update_registrations(&self, register: Vec<Registration>, unregister: Vec<Unregistration>) {
+        if !unregister.is_empty() {
+            if let Err(err) = self.client.unregister_capability(unregister).await {
+                self.client.log_message(MessageType::WARNING, format!("unregistering features: {err}")).await;
+            }
+        }
+        if !register.is_empty() {
+            if let Err(err) = self.client.register_capability(register).await {
+                self.client.log_message(MessageType::WARNING, format!("registering features: {err}")).await;
+            }
+        }
+    }
+
+    /// Starts a progress report, if the client shows them. `id` names the
+    /// token, so it must be unique among reports running at once.
+    async fn begin_progress(&self, id: &str, title: &str) -> Option<ProgressToken> {
+        if !self.client_support.read().unwrap().work_done_progress {
+            return None;
+        }
+        let token = ProgressToken::String(format!("alloy-hover/{id}"));
+        let create = WorkDoneProgressCreateParams { token: token.clone() };
+        self.client.send_request::<request::WorkDoneProgressCreate>(create).await.ok()?;
+        let begin = WorkDoneProgressBegin { title: title.into(), ..Default::default() };
+        self.send_progress(token.clone(), WorkDoneProgress::Begin(begin)).await;
+        Some(token)
+    }
+
+    async fn end_progress(&self, token: ProgressToken) {
+        self.send_progress(token, WorkDoneProgress::End(WorkDoneProgressEnd::default())).await;
+    }
+
+    async fn send_progress(&self, token: ProgressToken, progress: WorkDoneProgress) {
+        let params = ProgressParams { token, value: ProgressParamsValue::WorkDone(progress) };
+        self.client.send_notification::<notification::Progress>(params).await;
+    }
+
+    /// Lints and publishes once `uri` has gone `diagnostics.debounceMs`
+    /// without another edit, so a burst of keystrokes costs one run.
+    async fn schedule_lint(&self, uri: Url) {
+        let delay = Duration::from_millis(self.config.read().unwrap().diagnostics.debounce_ms);
+        if delay.is_zero() {
+            self.lint(&uri);
+            return self.publish_diagnostics(uri).await;
+        }
+        let generation = self.bump_lint_generation(&uri);
+        self.pending_lints.write().unwrap().insert(uri.clone());
+        let this = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if this.lint_generations.read().unwrap().get(&uri) == Some(&generation) {
+                this.lint(&uri);
+                this.publish_diagnostics(uri).await;
+            }
+        });
+    }
+
+    fn bump_lint_generation(&self, uri: &Url) -> u64 {
+        let mut generations = self.lint_generations.write().unwrap();
+        let generation = generations.entry(uri.clone()).or_default();
+        *generation += 1;
+        *generation
+    }
+
+    /// Our lints for `doc`, at their configured severities: those of the
+    /// project's `.alloylint.toml`, then the editor settings on top. Files
+    /// the project excludes get none.
+    fn findings(&self, doc: &Document, uri: &Url) -> Vec<lint::Finding> {
+        let project = self.project_lints();
+        let root = self.workspace.read().unwrap().root.clone();
+        let relative = root.zip(uri.to_file_path().ok()).and_then(|(root, path)| {
+            path.strip_prefix(root).ok().map(|p| p.to_string_lossy().replace('\\', "/"))
+        });
+        let project_rules = match relative {
+            Some(path) => match project.rules_for(&path) {
+                Some(rules) => rules,
+                None => return Vec::new(),
+            },
+            None => project.rules.clone(),
+        };
+        let settings = self.config.read().unwrap().diagnostics.rules.clone();
+        let rules: BTreeMap<_, _> = project_rules
+            .into_iter()
+            .chain(settings)
+            .map(|(rule, severity)| (lint::rule_name(&rule), severity))
+            .collect();
+        let releases = self.releases.read().unwrap().clone();
+        let target = self.target_version().map(|version| TargetRelease { releases: &releases, version });
+        let findings = lint::check(doc, uri, &self.workspace.read().unwrap(), target);
+        lint::configure(findings, &rules)
+    }
+
+    /// The configured `alloy.targetVersion`, if it is set and parses.
+    fn target_version(&self) -> Option<Version> {
+        self.config.read().unwrap().alloy.target_version.as_deref().and_then(Version::parse)
+    }
+
+    /// Switches to the release database `alloy.releasesPath` names, or back
+    /// to the bundled one; a file that does not load leaves the bundled one.
+    fn load_releases(&self) -> Result<(), String> {
+        let path = self.config.read().unwrap().alloy.releases_path.clone();
+        let (releases, result) = match path.map(|path| Releases::load(&path)) {
+            None => (Releases::builtin(), Ok(())),
+            Some(Ok(releases)) => (Arc::new(releases), Ok(())),
+            Some(Err(err)) => (Releases::builtin(), Err(err)),
+        };
+        *self.releases.write().unwrap() = releases;
+        result
+    }
+
+    /// The project's lint policy, re-read whenever the file has changed on
+    /// disk. A file that does not parse is reported and ignored.
+    fn project_lints(&self) -> Arc<LintConfig> {
+        let Some(root) = self.workspace.read().unwrap().root.clone() else { return Arc::default() };
+        let modified = std::fs::metadata(root.join(lint_config::FILE_NAME)).and_then(|m| m.modified()).ok();
+        {
+            let cached = self.project_lints.read().unwrap();
+            if cached.modified == modified {
+                return cached.config.clone();
+            }
+        }
+        let config = match LintConfig::load(&root) {
+            Ok(config) => config.unwrap_or_default(),
+            Err(err) => {
+                let client = self.client.clone();
+                tokio::spawn(async move { client.show_message(MessageType::WARNING, err).await });
+                LintConfig::default()
+            }
+        };
+        let config = Arc::new(config);
+        *self.project_lints.write().unwrap() = ProjectLints { modified, config: config.clone() };
+        config
+    }
+
+    /// Lints `uri` now, superseding any debounced run still waiting.
+    fn lint(&self, uri: &Url) {
+        self.bump_lint_generation(uri);
+        self.pending_lints.write().unwrap().remove(uri);
+        let Some(doc) = self.files.read().unwrap().get(uri).cloned() else { return };
+        let lints = self.findings(&doc, uri).into_iter().map(|f| f.diagnostic).collect();
+        self.diagnostics.write().unwrap().entry(uri.clone()).or_default().insert(lint::SOURCE, lints);
+    }
+
+    /// Everything a save checks: lints (after loading imported modules),
+    /// `alloy fmt`/`alloy validate` on the file on disk if enabled, and the
+    /// live instance. Results are stored but not published.
+    async fn validate(&self, uri: &Url) {
+        let config = self.config.read().unwrap().clone();
+        let Some(doc) = self.files.read().unwrap().get(uri).cloned() else { return };
+
+        let live = {
+            let (settings, doc) = (config.instance.clone(), doc.clone());
+            tokio::task::spawn_blocking(move || instance::validate(&settings, &doc))
+        };
+        let runtime = {
+            let (settings, doc) = (config.instance.clone(), doc.clone());
+            tokio::task::spawn_blocking(move || match settings.runtime_diagnostics {
+                true => Some(instance::runtime_errors(&instance::components(&settings)?, &doc)),
+                false => None,
+            })
+        };
+        let cli = match (config.alloy.validate_on_save, alloy_cli::binary(&config.alloy)) {
+            (true, Some(alloy)) => match uri.to_file_path() {
+                Ok(path) => Some(alloy_cli::validate(&alloy, &path, &doc).await),
+                Err(()) => None,
+            },
+            _ => None,
+        };
+        let live = live.await.ok().flatten();
+        let runtime = runtime.await.ok().flatten();
+        if !self.load_modules(uri).await {
+            self.lint(uri);
+        }
+
+        let mut all = self.diagnostics.write().unwrap();
+        let sets = all.entry(uri.clone()).or_default();
+        if let Some(diagnostics) = cli {
+            sets.insert(alloy_cli::SOURCE, diagnostics);
+        }
+        if let Some(diagnostics) = live {
+            sets.insert(instance::SOURCE, diagnostics);
+        }
+        if let Some(diagnostics) = runtime {
+            sets.insert(instance::RUNTIME_SOURCE, diagnostics);
+        }
+    }
+
+    /// Replaces the open document's text with `alloy fmt`'s output through
+    /// `workspace/applyEdit`. Nothing is sent if it is already formatted.
+    async fn format(&self, uri: Url) -> Result<(), String> {
+        let alloy = alloy_cli::binary(&self.config.read().unwrap().alloy).ok_or(NO_ALLOY)?;
+        let doc = self.files.read().unwrap().get(&uri).cloned().ok_or(format!("`{uri}` is not open"))?;
+        let formatted = doc.with_line_endings(alloy_cli::format(&alloy, &doc.text).await?);
+        // The edit starts after any BOM, which stays.
+        let formatted = formatted.strip_prefix(BOM).unwrap_or(&formatted).to_string();
+        if formatted == doc.text.trim_start_matches(BOM) {
+            return Ok(());
+        }
+        let edit = TextEdit {
+            range: Range { start: Position::default(), end: doc.position(doc.text.len()) },
+            new_text: formatted,
+        };
+        let edit = WorkspaceEdit { changes: Some(HashMap::from([(uri, vec![edit])])), ..Default::default() };
+        match self.client.apply_edit(edit).await {
+            Ok(response) if response.applied => Ok(()),
+            Ok(response) => Err(response.failure_reason.unwrap_or_else(|| "the client did not apply the edit".into())),
+            Err(err) => Err(err.message.into_owned()),
+        }
+    }
+
+    /// Runs `alloy convert` on the config at `uri`, writing the result to a
+    /// new `.alloy` file beside it through `workspace/applyEdit` if the
+    /// client can create files; an existing file is never overwritten. When
+    /// nothing is written, the user is told why.
+    async fn convert(&self, uri: Url, format: Option<String>) -> Result<convert::Converted, String> {
+        let alloy = alloy_cli::binary(&self.config.read().unwrap().alloy).ok_or(NO_ALLOY)?;
+        let source = uri.to_file_path().map_err(|()| format!("`{uri}` is not a file on disk"))?;
+        let format = match format {
+            Some(format) if convert::FORMATS.contains(&format.as_str()) => format,
+            Some(format) => return Err(format!("unknown format `{format}`; expected one of {:?}", convert::FORMATS)),
+            None => {
+                let path = source.clone();
+                let text = tokio::task::spawn_blocking(move || std::fs::read_to_string(path))
+                    .await
+                    .map_err(|err| err.to_string())?
+                    .map_err(|err| format!("reading {uri}: {err}"))?;
+                let format = convert::detect(&text).ok_or(format!("cannot tell what kind of config `{uri}` is"))?;
+                format.to_string()
+            }
+        };
+        let text = convert::run(&alloy, &source, &format).await?;
+        let target = convert::target(&source);
+        let target_uri = Url::from_file_path(&target).map_err(|()| format!("no URI for {}", target.display()))?;
+        let support = self.client_support.read().unwrap().clone();
+        let exists = {
+            let target = target.clone();
+            tokio::task::spawn_blocking(move || target.exists()).await.unwrap_or(true)
+        };
+        let not_written = if exists {
+            Some(format!("{} already exists", target.display()))
+        } else if !support.create_files {
+            Some("the editor cannot create files".to_string())
+        } else {
+            let create = ResourceOp::Create(CreateFile { uri: target_uri.clone(), options: None, annotation_id: None });
+            let fill = TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri: target_uri.clone(), version: None },
+                edits: vec![OneOf::Left(TextEdit { range: Range::default(), new_text: text.clone() })],
+            };
+            let edit = WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Operations(vec![
+                    DocumentChangeOperation::Op(create),
+                    DocumentChangeOperation::Edit(fill),
+                ])),
+                ..Default::default()
+            };
+            let applied = self.client.apply_edit(edit).await.is_ok_and(|response| response.applied);
+            if applied && support.show_document {
+                let uri = target_uri.clone();
+                let params = ShowDocumentParams { uri, external: None, take_focus: Some(true), selection: None };
+                let _ = self.client.show_document(params).await;
+            }
+            (!applied).then(|| "the editor did not apply the edit".to_string())
+        };
+        if let Some(reason) = not_written {
+            let message = format!("Converted {} but did not write it: {reason}", source.display());
+            self.client.show_message(MessageType::WARNING, message).await;
+        }
+        Ok(convert::Converted { uri: target_uri, text })
+    }
+
+    /// Indexes the remote modules `uri` imports (fetching those not cached
+    /// yet, if allowed) so their components can be checked and completed.
+    /// Returns whether there were any, i.e. whether lints were refreshed.
+    async fn load_modules(&self, uri: &Url) -> bool {
+        let imports = match self.files.read().unwrap().get(uri) {
+            Some(doc) => modules::imports(&doc.syntax().body),
+            None => return false,
+        };
+        if imports.is_empty() {
+            return false;
+        }
+        let settings = self.config.read().unwrap().modules.clone();
+        for import in imports {
+            match import.load(&settings).await {
+                Ok(Some(path)) => {
+                    let declares = modules::declares(&path);
+                    self.workspace.write().unwrap().set_module(uri.clone(), import.label().to_string(), declares);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    let message = format!("loading module `{}`: {err:#}", import.label());
+                    self.client.log_message(MessageType::WARNING, message).await;
+                }
+            }
+        }
+        self.lint(uri);
+        true
+    }
+
+    /// Refetches names from the metrics API in the background once they are
+    /// older than `metrics_api::MAX_AGE`, or right away when `force`d.
+    fn refresh_metrics(&self, force: bool) {
+        let settings = self.config.read().unwrap().metrics_api.clone();
+        if settings.url.is_none() {
+            *self.metrics.write().unwrap() = MetricsCache::default();
+            return;
+        }
+        {
+            let mut cache = self.metrics.write().unwrap();
+            if !force && cache.requested.is_some_and(|t| t.elapsed() < metrics_api::MAX_AGE) {
+                return;
+            }
+            cache.requested = Some(Instant::now());
+        }
+        let (cache, client) = (self.metrics.clone(), self.client.clone());
+        tokio::spawn(async move {
+            match tokio::task::spawn_blocking(move || metrics_api::fetch(&settings)).await {
+                Ok(Ok(catalog)) => cache.write().unwrap().catalog = catalog.map(Arc::new),
+                Ok(Err(err)) => client.log_message(MessageType::WARNING, format!("metrics API: {err:#}")).await,
+                Err(_) => {}
+            }
+        });
+    }
+
+    async fn component_graph(
+        &self,
+        params: ComponentGraphParams,
+    ) -> tower_lsp::jsonrpc::Result<ComponentGraph> {
+        let workspace = self.workspace.read().unwrap();
+        Ok(graph::build(&workspace, params.uri.as_ref()))
+    }
+
+    /// Takes no meaningful params; accepts (and ignores) `null` or `{}`.
+    async fn export_schema(
+        &self,
+        _params: Option<serde_json::Value>,
+    ) -> tower_lsp::jsonrpc::Result<serde_json::Value> {
+        serde_json::to_value(Schema::builtin()).map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Re-reads the docset, from a new path if one is given, so edited docs
+    /// show up without a restart. Takes `null`, `{}` or `{ "path": … }`.
+    async fn reload_docs(&self, params: Option<ReloadDocsParams>) {
+        let path = params.unwrap_or_default().path;
+        let docs = self.docs.read().unwrap().reload(path.as_deref());
+        *self.docs.write().unwrap() = docs;
+        *self.last_hover.write().unwrap() = None;
+        self.show_docs_problems().await;
+    }
+
+    /// What the server has loaded and has yet to do. Takes `null` or `{}`.
+    async fn status(&self, _params: Option<serde_json::Value>) -> tower_lsp::jsonrpc::Result<Status> {
+        let docs = self.docs.read().unwrap().status();
+        let workspace = {
+            let workspace = self.workspace.read().unwrap();
+            WorkspaceStatus {
+                root: workspace.root.clone(),
+                files_indexed: workspace.file_count(),
+                modules_loaded: workspace.module_count(),
+                revision: workspace.revision(),
+            }
+        };
+        let documents = {
+            let (files, closed) = (self.files.read().unwrap(), self.closed.read().unwrap());
+            DocumentsStatus {
+                open: files.len().saturating_sub(closed.len()),
+                closed: closed.len(),
+                bytes: files.values().map(Document::footprint).sum(),
+            }
+        };
+        let catalog = self.metrics.read().unwrap().catalog.clone();
+        let caches = CacheStatus {
+            diagnostic_files: self.diagnostics.read().unwrap().len(),
+            metric_names: catalog.as_ref().map(|c| c.metrics.len()),
+            metric_labels: catalog.as_ref().map(|c| c.labels.len()),
+            hover_cached: self.last_hover.read().unwrap().is_some(),
+        };
+        let pending = PendingStatus { lints: self.pending_lints.read().unwrap().len() };
+        Ok(Status { version: env!("CARGO_PKG_VERSION"), docs, workspace, documents, caches, pending })
+    }
+
+    async fn show_docs_problems(&self) {
+        let problems = self.docs.read().unwrap().problems().to_vec();
+        for problem in problems {
+            self.client.show_message(MessageType::WARNING, format!("Alloy hover docs: {problem}")).await;
+        }
+    }
+
+    async fn show_config_problems(&self) {
+        let problems = self.config.read().unwrap().problems.clone();
+        for problem in problems {
+            self.client.show_message(MessageType::WARNING, format!("Alloy hover settings: {problem}")).await;
+        }
+    }
+
+    /// Sends `uri`'s diagnostics to a client that has them published; one
+    /// that pulls them is asked to pull again instead.
+    async fn publish_diagnostics(&self, uri: Url) {
+        let support = self.client_support.read().unwrap().clone();
+        if support.pull_diagnostics {
+            if support.diagnostic_refresh {
+                let _ = self.client.workspace_diagnostic_refresh().await;
+            }
+            return;
+        }
+        let diagnostics = self.current_diagnostics(&uri);
+        self.client.publish_diagnostics(uri, diagnostics, None).await;
+    }
+
+    /// The expressions using `id`'s exports, ordered by file and position.
+    fn consumers(&self, id: &str) -> Vec<Location> {
+        let workspace = self.workspace.read().unwrap();
+        let mut refs: Vec<_> = workspace.references_to(id).collect();
+        refs.sort_by_key(|(uri, r)| (uri.as_str(), r.range.start.line, r.range.start.character));
+        refs.into_iter().map(|(uri, r)| Location::new(uri.clone(), r.range)).collect()
+    }
+
+    /// A full report of `uri`'s diagnostics, or an unchanged one if they are
+    /// what the client already has under `previous`. A debounced lint still
+    /// waiting runs now, so a pull right after an edit sees it.
+    fn diagnostic_report(&self, uri: &Url, previous: Option<&str>) -> DocumentDiagnosticReport {
+        if self.pending_lints.read().unwrap().contains(uri) {
+            self.lint(uri);
+        }
+        let items = self.current_diagnostics(uri);
+        let result_id = result_id(&items);
+        if previous == Some(result_id.as_str()) {
+            let unchanged_document_diagnostic_report = UnchangedDocumentDiagnosticReport { result_id };
+            return DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                related_documents: None,
+                unchanged_document_diagnostic_report,
+            });
+        }
+        DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+            related_documents: None,
+            full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id: Some(result_id), items },
+        })
+    }
+
+    fn current_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        self.diagnostics
+            .read()
+            .unwrap()
+            .get(uri)
+            .map(|sets| sets.values().flatten().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Identifies a set of diagnostics by its contents, so that the same ones
+/// get the same ID however many times they are computed.
+fn result_id(diagnostics: &[Diagnostic]) -> String {
+    format!("{:016x}", modules::fnv1a(serde_json::to_string(diagnostics).unwrap_or_default().as_bytes()))
+}
+
+/// The locale from the environment, in POSIX precedence order.
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()))
+}
+
+/// Gives the text a completion inserts the `\r\n`s of a file that uses them.
+fn with_line_endings(doc: &Document, item: &mut CompletionItem) {
+    if let Some(text) = &mut item.insert_text {
+        *text = doc.with_line_endings(std::mem::take(text));
+    }
+    if let Some(CompletionTextEdit::Edit(edit)) = &mut item.text_edit {
+        edit.new_text = doc.with_line_endings(std::mem::take(&mut edit.new_text));
+    }
+    for edit in item.additional_text_edits.iter_mut().flatten() {
+        edit.new_text = doc.with_line_endings(std::mem::take(&mut edit.new_text));
+    }
+}
+
+/// The config files, and any folders, whose renames move imports.
+fn rename_filters() -> FileOperationRegistrationOptions {
+    let filter = |glob: String, matches| FileOperationFilter {
+        scheme: Some("file".into()),
+        pattern: FileOperationPattern { glob, matches: Some(matches), options: None },
+    };
+    let extensions = workspace::CONFIG_EXTENSIONS.join(",");
+    FileOperationRegistrationOptions {
+        filters: vec![
+            filter(format!("**/*.{{{extensions}}}"), FileOperationPatternKind::File),
+            filter("**".into(), FileOperationPatternKind::Folder),
+        ],
+    }
+}
+
+/// The `(old, new)` paths of the renamed files or folders on disk.
+fn renamed_paths(params: &RenameFilesParams) -> Vec<(std::path::PathBuf, std::path::PathBuf)> {
+    params
+        .files
+        .iter()
+        .filter_map(|rename| {
+            let old = Url::parse(&rename.old_uri).ok()?.to_file_path().ok()?;
+            let new = Url::parse(&rename.new_uri).ok()?.to_file_path().ok()?;
+            Some((old, new))
+        })
+        .collect()
+}
+
+/// The document URI a command takes as its first argument.
+fn command_uri(arg: Option<serde_json::Value>) -> tower_lsp::jsonrpc::Result<Url> {
+    arg.and_then(|v| serde_json::from_value(v).ok())
+        .ok_or_else(|| tower_lsp::jsonrpc::Error::invalid_params("expected a document URI as the first argument"))
+}
+
+/// Builds the language server, with the custom `alloy/*` methods, using up
+/// to `threads` threads for work over many files. A panic in a handler
+/// fails just that message.
+pub fn service(docs: Docs, threads: usize) -> (CatchPanic<LspService<Backend>>, ClientSocket) {
+    let (service, socket) = LspService::build(|client| Backend {
+        client,
+        client_support: Arc::new(RwLock::new(ClientSupport::default())),
+        config: Arc::new(RwLock::new(Config::default())),
+        files: Arc::new(RwLock::new(HashMap::new())),
+        closed: Arc::new(RwLock::new(VecDeque::new())),
+        workspace: Arc::new(RwLock::new(Workspace::default())),
+        diagnostics: Arc::new(RwLock::new(HashMap::new())),
+        metrics: Arc::new(RwLock::new(MetricsCache::default())),
+        lint_generations: Arc::new(RwLock::new(HashMap::new())),
+        pending_lints: Arc::new(RwLock::new(HashSet::new())),
+        last_hover: Arc::new(RwLock::new(None)),
+        docs: Arc::new(RwLock::new(docs)),
+        project_lints: Arc::new(RwLock::new(ProjectLints::default())),
+        releases: Arc::new(RwLock::new(Releases::builtin())),
+        health: Arc::new(RwLock::new(None)),
+        threads,
+    })
+    .custom_method("alloy/componentGraph", Backend::component_graph)
+    .custom_method("alloy/exportSchema", Backend::export_schema)
+    .custom_method("alloy/reloadDocs", Backend::reload_docs)
+    .custom_method("alloy/status", Backend::status)
+    .finish();
+    let backend = service.inner().clone();
+    (CatchPanic::new(service, move |report| backend.recover(report)), socket)
+}
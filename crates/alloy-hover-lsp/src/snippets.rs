@@ -0,0 +1,106 @@
+//! Whole pipelines offered as completions at the top level of a file:
+//! several blocks, already wired to each other, with the labels and
+//! endpoints left as placeholders.
+
+/// A pipeline snippet. `body` uses LSP snippet syntax; a placeholder used
+/// more than once (a label, say) is edited in every place at the same time.
+pub struct Snippet {
+    pub label: &'static str,
+    pub detail: &'static str,
+    pub body: &'static str,
+}
+
+pub const PIPELINES: &[Snippet] = &[
+    Snippet {
+        label: "pipeline: Kubernetes pods → remote_write",
+        detail: "discovery.kubernetes → prometheus.scrape → prometheus.remote_write",
+        body: r#"discovery.kubernetes "${1:pods}" {
+  role = "pod"
+}
+
+prometheus.scrape "${1:pods}" {
+  targets    = discovery.kubernetes.${1:pods}.targets
+  forward_to = [prometheus.remote_write.${2:default}.receiver]
+}
+
+prometheus.remote_write "${2:default}" {
+  endpoint {
+    url = "${3:https://prometheus.example.com/api/v1/write}"
+  }
+}
+$0"#,
+    },
+    Snippet {
+        label: "pipeline: file logs → loki.process → loki.write",
+        detail: "local.file_match → loki.source.file → loki.process → loki.write",
+        body: r#"local.file_match "${1:logs}" {
+  path_targets = [{ "__path__" = "${2:/var/log/*.log}" }]
+}
+
+loki.source.file "${1:logs}" {
+  targets    = local.file_match.${1:logs}.targets
+  forward_to = [loki.process.${1:logs}.receiver]
+}
+
+loki.process "${1:logs}" {
+  forward_to = [loki.write.${3:default}.receiver]
+}
+
+loki.write "${3:default}" {
+  endpoint {
+    url = "${4:https://loki.example.com/loki/api/v1/push}"
+  }
+}
+$0"#,
+    },
+    Snippet {
+        label: "pipeline: OTLP receiver → batch → OTLP exporter",
+        detail: "otelcol.receiver.otlp → otelcol.processor.batch → otelcol.exporter.otlp",
+        body: r#"otelcol.receiver.otlp "${1:default}" {
+  grpc { }
+  http { }
+
+  output {
+    metrics = [otelcol.processor.batch.${1:default}.input]
+    logs    = [otelcol.processor.batch.${1:default}.input]
+    traces  = [otelcol.processor.batch.${1:default}.input]
+  }
+}
+
+otelcol.processor.batch "${1:default}" {
+  output {
+    metrics = [otelcol.exporter.otlp.${2:default}.input]
+    logs    = [otelcol.exporter.otlp.${2:default}.input]
+    traces  = [otelcol.exporter.otlp.${2:default}.input]
+  }
+}
+
+otelcol.exporter.otlp "${2:default}" {
+  client {
+    endpoint = "${3:otlp.example.com:4317}"
+  }
+}
+$0"#,
+    },
+];
+
+/// `body` with every placeholder replaced by its default text, for clients
+/// that cannot expand snippets.
+pub fn plain(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(inner) = rest.strip_prefix('{') {
+            let end = inner.find('}').unwrap_or(inner.len());
+            let placeholder = &inner[..end];
+            out.push_str(placeholder.split_once(':').map_or("", |(_, default)| default));
+            rest = inner.get(end + 1..).unwrap_or_default();
+        } else {
+            rest = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+        }
+    }
+    out.push_str(rest);
+    out
+}
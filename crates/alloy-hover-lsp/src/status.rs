@@ -0,0 +1,67 @@
+//! The `alloy/status` request: what the server has loaded and what it is
+//! still working on, to poll when hovers or diagnostics seem off.
+
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub version: &'static str,
+    pub docs: DocsStatus,
+    pub workspace: WorkspaceStatus,
+    pub documents: DocumentsStatus,
+    pub caches: CacheStatus,
+    pub pending: PendingStatus,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocsStatus {
+    pub entries: usize,
+    pub translated_entries: usize,
+    /// The docset file; `None` for the built-in docs.
+    pub source: Option<PathBuf>,
+    /// The translation layered over it, if one was found.
+    pub translation: Option<PathBuf>,
+    pub locale: Option<String>,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceStatus {
+    pub root: Option<PathBuf>,
+    pub files_indexed: usize,
+    /// Imports resolved to the modules they load.
+    pub modules_loaded: usize,
+    pub revision: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentsStatus {
+    pub open: usize,
+    /// Closed in the editor but still held, up to `memory.maxMb`.
+    pub closed: usize,
+    /// Roughly what all held documents take up.
+    pub bytes: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatus {
+    /// Files with diagnostics held for them.
+    pub diagnostic_files: usize,
+    /// Names from the metrics API; `None` until a fetch succeeds.
+    pub metric_names: Option<usize>,
+    pub metric_labels: Option<usize>,
+    pub hover_cached: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingStatus {
+    /// Files with a debounced lint waiting to run.
+    pub lints: usize,
+}
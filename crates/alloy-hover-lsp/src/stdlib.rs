@@ -0,0 +1,74 @@
+//! Alloy's standard library: the functions and constants every expression
+//! can use, offered as completions in expression position.
+
+/// A function or constant. `name` is the full path, like `sys.env`;
+/// functions have a `signature`, constants have none.
+pub struct Member {
+    pub name: &'static str,
+    pub signature: Option<&'static str>,
+    pub doc: &'static str,
+}
+
+const fn function(name: &'static str, signature: &'static str, doc: &'static str) -> Member {
+    Member { name, signature: Some(signature), doc }
+}
+
+const fn constant(name: &'static str, doc: &'static str) -> Member {
+    Member { name, signature: None, doc }
+}
+
+pub const MEMBERS: &[Member] = &[
+    function("array.combine_maps", "(left, right, keys)", "Joins two arrays of objects on the given keys."),
+    function("array.concat", "(list...)", "Concatenates arrays into one."),
+    function("coalesce", "(value...)", "The first argument that is not null or empty."),
+    constant("constants.arch", "The architecture Alloy runs on, like `amd64`."),
+    constant("constants.hostname", "The hostname of the machine Alloy runs on."),
+    constant("constants.os", "The operating system Alloy runs on, like `linux`."),
+    function("convert.nonsensitive", "(secret)", "A secret as a plain string; it is shown wherever the value is."),
+    function("encoding.from_base64", "(string)", "Decodes a base64 string."),
+    function("encoding.from_json", "(string)", "Decodes a JSON string into an object or array."),
+    function("encoding.from_URLsafe_base64", "(string)", "Decodes a URL-safe base64 string."),
+    function("encoding.from_yaml", "(string)", "Decodes a YAML string into an object or array."),
+    function("encoding.to_base64", "(string)", "Encodes a string as base64."),
+    function("encoding.to_URLsafe_base64", "(string)", "Encodes a string as URL-safe base64."),
+    function("file.path_join", "(elem...)", "Joins path elements with the OS's separator."),
+    function("json_path", "(json, path)", "The values a JSONPath expression selects from a JSON string."),
+    constant("module_path", "The directory of the module being evaluated."),
+    function("string.format", "(format, args...)", "Formats values with a `printf`-style template."),
+    function("string.join", "(list, separator)", "Joins a list of strings with a separator."),
+    function("string.replace", "(string, old, new)", "Replaces every `old` in a string with `new`."),
+    function("string.split", "(string, separator)", "Splits a string into a list at each separator."),
+    function("string.to_lower", "(string)", "A string in lower case."),
+    function("string.to_upper", "(string)", "A string in upper case."),
+    function("string.trim", "(string, cutset)", "A string without leading and trailing characters in `cutset`."),
+    function("string.trim_prefix", "(string, prefix)", "A string without a leading `prefix`."),
+    function("string.trim_space", "(string)", "A string without leading and trailing whitespace."),
+    function("string.trim_suffix", "(string, suffix)", "A string without a trailing `suffix`."),
+    function("sys.env", "(name)", "The value of an environment variable, or an empty string."),
+];
+
+/// The members directly under `namespace` (`""` for the top level), and
+/// the namespaces there, by the name they are written with after it.
+pub fn under(namespace: &str) -> (Vec<(&'static str, &'static Member)>, Vec<&'static str>) {
+    let (mut members, mut namespaces) = (Vec::new(), Vec::new());
+    for member in MEMBERS {
+        let rest = match namespace {
+            "" => member.name,
+            _ => match member.name.strip_prefix(namespace).and_then(|r| r.strip_prefix('.')) {
+                Some(rest) => rest,
+                None => continue,
+            },
+        };
+        match rest.split_once('.') {
+            Some((child, _)) if !namespaces.contains(&child) => namespaces.push(child),
+            Some(_) => {}
+            None => members.push((rest, member)),
+        }
+    }
+    (members, namespaces)
+}
+
+/// Whether an expression starting with `first` is the standard library's.
+pub fn is_root(first: &str) -> bool {
+    MEMBERS.iter().any(|m| m.name.split('.').next() == Some(first))
+}
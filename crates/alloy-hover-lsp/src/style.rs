@@ -0,0 +1,116 @@
+//! Formatting lints, for files that are not run through `alloy fmt`: each
+//! points at one thing it would change and fixes just that.
+
+use tower_lsp::lsp_types::*;
+
+use crate::document::Document;
+use crate::lint::{self, Finding, Fix};
+use crate::syntax::lexer::{tokenize, Token, TokenKind};
+use crate::syntax::{Expr, ExprKind, Span, Stmt};
+
+pub fn check(doc: &Document, out: &mut Vec<Finding>) {
+    let tokens = tokenize(&doc.text);
+    final_newline(doc, out);
+    assignment_spacing(doc, &tokens, out);
+    blank_lines(doc, &tokens, out);
+    trailing_commas(doc, &doc.syntax().body, out);
+}
+
+fn final_newline(doc: &Document, out: &mut Vec<Finding>) {
+    let end = doc.text.len();
+    if end == 0 || doc.text.ends_with('\n') {
+        return;
+    }
+    let message = "no newline at end of file".to_string();
+    let mut finding = lint::hint(doc, Span::new(end, end), lint::MISSING_FINAL_NEWLINE, message);
+    let at = doc.position(end);
+    let edits = vec![TextEdit { range: Range::new(at, at), new_text: "\n".into() }];
+    finding.fixes.push(Fix::new("Add a final newline", edits));
+    out.push(finding);
+}
+
+/// One space on either side of `=`. More before it are fine when they
+/// line it up with the `=` on the line above or below, comment lines in
+/// between aside, as `alloy fmt` does.
+fn assignment_spacing(doc: &Document, tokens: &[Token], out: &mut Vec<Finding>) {
+    let assigns: Vec<(usize, Position)> = (1..tokens.len().saturating_sub(1))
+        .filter(|&i| tokens[i].kind == TokenKind::Assign)
+        .map(|i| (i, doc.position(tokens[i].span.start)))
+        .collect();
+    for &(i, at) in &assigns {
+        let (prev, next) = (tokens[i - 1].span.end, tokens[i + 1].span.start);
+        let before = &doc.text[prev..tokens[i].span.start];
+        let after = &doc.text[tokens[i].span.end..next];
+        if before.contains('\n') || after.contains('\n') {
+            continue;
+        }
+        let aligned = || {
+            assigns.iter().any(|(_, other)| {
+                let between = at.line.min(other.line) + 1..at.line.max(other.line);
+                other.character == at.character
+                    && other.line != at.line
+                    && between.into_iter().all(|line| doc.line_text(line as usize).trim_start().starts_with("//"))
+            })
+        };
+        if before == " " && after == " " || before.len() > 1 && after == " " && aligned() {
+            continue;
+        }
+        let message = "`=` should have one space on either side".to_string();
+        let mut finding = lint::hint(doc, tokens[i].span, lint::ASSIGNMENT_SPACING, message);
+        let edits = vec![TextEdit { range: doc.range(Span::new(prev, next)), new_text: " = ".into() }];
+        finding.fixes.push(Fix::new("Put one space around `=`", edits));
+        out.push(finding);
+    }
+}
+
+/// At most one blank line between statements. The extra ones are what the
+/// finding covers and the fix removes.
+fn blank_lines(doc: &Document, tokens: &[Token], out: &mut Vec<Finding>) {
+    for pair in tokens.windows(2).filter(|w| w[1].kind != TokenKind::Eof) {
+        let gap = Span::new(pair[0].span.end, pair[1].span.start);
+        let newlines: Vec<usize> =
+            doc.text[gap.start..gap.end].match_indices('\n').map(|(i, _)| gap.start + i + 1).collect();
+        if newlines.len() < 3 {
+            continue;
+        }
+        let extra = Span::new(newlines[1], newlines[newlines.len() - 1]);
+        let message = format!("{} blank lines in a row; one is enough", newlines.len() - 1);
+        let mut finding = lint::hint(doc, extra, lint::BLANK_LINES, message);
+        let edits = vec![TextEdit { range: doc.range(extra), new_text: String::new() }];
+        finding.fixes.push(Fix::new("Remove the extra blank lines", edits));
+        out.push(finding);
+    }
+}
+
+/// Lists and objects split over several lines end with a comma after the
+/// last element, as `alloy fmt` writes them.
+fn trailing_commas(doc: &Document, body: &[Stmt], out: &mut Vec<Finding>) {
+    fn expr(doc: &Document, value: &Expr, out: &mut Vec<Finding>) {
+        value.for_each_child(&mut |child| expr(doc, child, out));
+        let last = match &value.kind {
+            ExprKind::Array(items) => items.last().map(|item| item.span.end),
+            ExprKind::Object(fields) => fields.last().map(|field| field.value.span.end),
+            _ => None,
+        };
+        let close = value.span.end - 1;
+        let Some(last) = last.filter(|_| doc.text[close..].starts_with([']', '}'])) else { return };
+        let multiline = doc.text[last..close].contains('\n');
+        if !multiline || doc.text[last..close].trim_start().starts_with(',') {
+            return;
+        }
+        let at = Span::new(last, last);
+        let message = "a list or object split over lines ends with a comma".to_string();
+        let mut finding = lint::hint(doc, at, lint::MISSING_TRAILING_COMMA, message);
+        let at = doc.position(last);
+        let edits = vec![TextEdit { range: Range::new(at, at), new_text: ",".into() }];
+        finding.fixes.push(Fix::new("Add a trailing comma", edits));
+        out.push(finding);
+    }
+
+    for stmt in body {
+        match stmt {
+            Stmt::Attribute(attr) => expr(doc, &attr.value, out),
+            Stmt::Block(block) => trailing_commas(doc, &block.body, out),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! `// alloy-lint:disable=rule,…` directives. After code on a line, a
+//! directive silences its rules on that line; on a line of its own, for
+//! the whole statement below it: an attribute, or a block and everything
+//! in it. Rules are named by code or in camelCase, as in the settings.
+
+use tower_lsp::lsp_types::*;
+
+use crate::document::Document;
+use crate::lint::{self, Finding, Fix};
+use crate::syntax::{Span, Stmt};
+
+pub const PREFIX: &str = "alloy-lint:disable=";
+
+pub struct Directive {
+    /// The whole comment.
+    pub span: Span,
+    pub rules: Vec<String>,
+    /// First and last line covered, zero-based.
+    pub lines: (u32, u32),
+}
+
+pub fn directives(doc: &Document) -> Vec<Directive> {
+    let file = doc.syntax();
+    file.comments
+        .iter()
+        .filter_map(|&span| {
+            let body = doc.text[span.start..span.end].strip_prefix("//")?.trim();
+            let rules = body.strip_prefix(PREFIX)?;
+            let rules: Vec<String> =
+                rules.split(',').map(str::trim).filter(|r| !r.is_empty()).map(str::to_string).collect();
+            let line = doc.position(span.start).line;
+            let line_start = doc.text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+            let lines = match doc.text[line_start..span.start].trim().is_empty() {
+                true => statement_after(&doc.text, &file.body, span.end)
+                    .map(|stmt| (doc.position(stmt.start).line, doc.position(stmt.end).line))?,
+                false => (line, line),
+            };
+            Some(Directive { span, rules, lines })
+        })
+        .collect()
+}
+
+/// Drops the findings a directive covers, and reports each rule in a
+/// directive that covered nothing.
+pub fn apply(doc: &Document, findings: Vec<Finding>) -> Vec<Finding> {
+    let directives = directives(doc);
+    if directives.is_empty() {
+        return findings;
+    }
+    let mut used = vec![Vec::new(); directives.len()];
+    let mut kept: Vec<Finding> = findings
+        .into_iter()
+        .filter(|finding| {
+            let Some(NumberOrString::String(code)) = &finding.diagnostic.code else { return true };
+            let line = finding.diagnostic.range.start.line;
+            let mut suppressed = false;
+            for (directive, used) in directives.iter().zip(&mut used) {
+                if line < directive.lines.0 || line > directive.lines.1 {
+                    continue;
+                }
+                if let Some(rule) = directive.rules.iter().find(|r| lint::rule_name(r) == lint::rule_name(code)) {
+                    used.push(rule.clone());
+                    suppressed = true;
+                }
+            }
+            !suppressed
+        })
+        .collect();
+
+    for (directive, used) in directives.iter().zip(used) {
+        let unused: Vec<&String> = directive.rules.iter().filter(|r| !used.contains(r)).collect();
+        for rule in unused {
+            let message = format!("`{rule}` is not reported here; the suppression can be removed");
+            let mut finding = lint::info(doc, directive.span, lint::UNUSED_SUPPRESSION, message);
+            finding.diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+            let title = format!("Remove the `{rule}` suppression");
+            finding.fixes.push(Fix::new(title, vec![without(doc, directive, rule)]));
+            kept.push(finding);
+        }
+    }
+    kept
+}
+
+/// A fix silencing `diagnostic` with a directive above the statement it is
+/// in, or by adding its rule to the directive already there.
+pub fn fix(doc: &Document, diagnostic: &Diagnostic) -> Option<Fix> {
+    let Some(NumberOrString::String(code)) = &diagnostic.code else { return None };
+    if code == lint::UNUSED_SUPPRESSION {
+        return None;
+    }
+    let stmt = statement_at(&doc.syntax().body, doc.offset(diagnostic.range.start))?;
+    let lines = (doc.position(stmt.start).line, doc.position(stmt.end).line);
+    let title = format!("Suppress `{code}` here");
+    if let Some(existing) = directives(doc).into_iter().find(|d| d.lines == lines && d.span.end <= stmt.start) {
+        let rules = existing.rules.join(",");
+        let edit = TextEdit { range: doc.range(existing.span), new_text: format!("// {PREFIX}{rules},{code}") };
+        return Some(Fix::new(title, vec![edit]));
+    }
+    let line_start = doc.text[..stmt.start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &doc.text[line_start..stmt.start];
+    let indent = &indent[..indent.len() - indent.trim_start().len()];
+    let at = doc.position(line_start);
+    let edit = TextEdit { range: Range::new(at, at), new_text: format!("{indent}// {PREFIX}{code}\n") };
+    Some(Fix::new(title, vec![edit]))
+}
+
+/// The innermost statement containing `offset`.
+fn statement_at(body: &[Stmt], offset: usize) -> Option<Span> {
+    body.iter().find_map(|stmt| match stmt {
+        Stmt::Attribute(a) => a.span.contains(offset).then_some(a.span),
+        Stmt::Block(b) if b.span.contains(offset) => statement_at(&b.body, offset).or(Some(b.span)),
+        Stmt::Block(_) => None,
+    })
+}
+
+/// An edit dropping `rule` from `directive`, or the whole comment if it is
+/// the only rule.
+fn without(doc: &Document, directive: &Directive, rule: &str) -> TextEdit {
+    let rest: Vec<&str> = directive.rules.iter().map(String::as_str).filter(|r| *r != rule).collect();
+    if !rest.is_empty() {
+        return TextEdit { range: doc.range(directive.span), new_text: format!("// {PREFIX}{}", rest.join(",")) };
+    }
+    let text = &doc.text;
+    let line_start = text[..directive.span.start].rfind('\n').map_or(0, |i| i + 1);
+    let (start, end) = if text[line_start..directive.span.start].trim().is_empty() {
+        // A line of its own goes, newline included.
+        (line_start, text[directive.span.end..].find('\n').map_or(text.len(), |i| directive.span.end + i + 1))
+    } else {
+        (text[..directive.span.start].trim_end().len(), directive.span.end)
+    };
+    TextEdit { range: doc.range(Span::new(start, end)), new_text: String::new() }
+}
+
+/// The statement starting right after `offset`, with only whitespace and
+/// other comment lines in between.
+fn statement_after(text: &str, body: &[Stmt], offset: usize) -> Option<Span> {
+    for stmt in body {
+        let span = match stmt {
+            Stmt::Attribute(a) => a.span,
+            Stmt::Block(b) => b.span,
+        };
+        if span.start >= offset {
+            let gap = &text[offset..span.start];
+            return gap.lines().all(|l| l.trim().is_empty() || l.trim().starts_with("//")).then_some(span);
+        }
+        if let (Stmt::Block(block), true) = (stmt, span.contains(offset)) {
+            return statement_after(text, &block.body, offset);
+        }
+    }
+    None
+}
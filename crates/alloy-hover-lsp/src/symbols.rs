@@ -0,0 +1,109 @@
+//! `textDocument/documentSymbol`: the outline of a file. Components,
+//! modules and their arguments each get a kind of their own, and a detail
+//! with their label and the capsule types they export or take.
+
+use tower_lsp::lsp_types::*;
+
+use crate::document::Document;
+use crate::schema::Schema;
+use crate::syntax::{Attribute, Block, ExprKind, Stmt};
+use crate::workspace;
+
+pub fn outline(doc: &Document) -> Vec<DocumentSymbol> {
+    symbols(doc, &doc.syntax().body, &mut Vec::new())
+}
+
+fn symbols<'a>(doc: &Document, body: &'a [Stmt], chain: &mut Vec<&'a Block>) -> Vec<DocumentSymbol> {
+    body.iter()
+        .map(|stmt| match stmt {
+            Stmt::Attribute(attr) => attribute(doc, attr, chain),
+            Stmt::Block(block) => {
+                chain.push(block);
+                let children = symbols(doc, &block.body, chain);
+                chain.pop();
+                self::block(doc, block, children)
+            }
+        })
+        .collect()
+}
+
+fn block(doc: &Document, block: &Block, children: Vec<DocumentSymbol>) -> DocumentSymbol {
+    let name = block.full_name();
+    let label = block.label.as_ref().map(|l| l.value.clone());
+    let (kind, symbol_name, detail) = match (name.as_str(), label) {
+        ("declare", Some(label)) => (SymbolKind::FUNCTION, label, Some(signature(block))),
+        ("argument", Some(label)) => {
+            let detail = if is_optional(block) { "optional argument" } else { "argument" };
+            (SymbolKind::VARIABLE, label, Some(detail.into()))
+        }
+        ("export", Some(label)) => (SymbolKind::PROPERTY, label, Some("export".into())),
+        (import, Some(label)) if import.starts_with("import.") => (SymbolKind::NAMESPACE, label, Some(name)),
+        (_, Some(label)) if workspace::component_id(block).is_some() => {
+            let detail = component_detail(&name, &label);
+            (SymbolKind::OBJECT, name, Some(detail))
+        }
+        // Unlabelled blocks: nested blocks, and top-level settings such as
+        // `logging`.
+        (_, _) => (SymbolKind::STRUCT, name, None),
+    };
+    // Symbols named by their label select it; components, by their name.
+    let selection = block.label.as_ref().filter(|_| kind != SymbolKind::OBJECT).map_or(block.name_span(), |l| l.span);
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name: symbol_name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: doc.range(block.span),
+        selection_range: doc.range(selection),
+        children: (!children.is_empty()).then_some(children),
+    }
+}
+
+/// `"label"`, and the capsule types of the component's exports:
+/// `"cloud" · receiver: MetricsReceiver`.
+fn component_detail(name: &str, label: &str) -> String {
+    let schema = Schema::builtin();
+    let exports = schema.component(name).into_iter().flat_map(|c| &c.exports);
+    let capsules: Vec<String> = exports
+        .filter_map(|(export, e)| schema.capsule(&e.ty).map(|capsule| format!("{export}: {capsule}")))
+        .collect();
+    match capsules.is_empty() {
+        true => format!("{label:?}"),
+        false => format!("{label:?} · {}", capsules.join(", ")),
+    }
+}
+
+/// The arguments of a `declare`, optional ones marked: `(dsn, timeout?)`.
+fn signature(declare: &Block) -> String {
+    let arguments: Vec<String> = declare
+        .blocks()
+        .filter(|b| b.full_name() == "argument")
+        .filter_map(|b| {
+            let name = &b.label.as_ref()?.value;
+            Some(if is_optional(b) { format!("{name}?") } else { name.clone() })
+        })
+        .collect();
+    format!("({})", arguments.join(", "))
+}
+
+fn is_optional(argument: &Block) -> bool {
+    argument.attribute("optional").is_some_and(|a| matches!(a.value.kind, ExprKind::Bool(true)))
+}
+
+/// An attribute, with its type from the schema when the component is known.
+fn attribute(doc: &Document, attr: &Attribute, chain: &[&Block]) -> DocumentSymbol {
+    let detail = Schema::builtin().argument_in(chain, &attr.name.name).map(|a| a.ty.clone());
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name: attr.name.name.clone(),
+        detail,
+        kind: SymbolKind::FIELD,
+        tags: None,
+        deprecated: None,
+        range: doc.range(attr.span),
+        selection_range: doc.range(attr.name.span),
+        children: None,
+    }
+}
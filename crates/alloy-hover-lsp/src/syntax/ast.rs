@@ -0,0 +1,254 @@
+use super::Span;
+
+#[derive(Debug, Clone, Default)]
+pub struct File {
+    pub body: Vec<Stmt>,
+    /// Spans of every `//` and `/* */` comment, in source order.
+    pub comments: Vec<Span>,
+    pub errors: Vec<SyntaxError>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Attribute(Attribute),
+    Block(Block),
+}
+
+#[derive(Debug, Clone)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Dotted name segments, e.g. `["prometheus", "scrape"]`.
+    pub name: Vec<Ident>,
+    pub label: Option<StringLit>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct StringLit {
+    pub value: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: Ident,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Ident(String),
+    Access(Box<Expr>, Ident),
+    Index(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Array(Vec<Expr>),
+    Object(Vec<ObjectField>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Paren(Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectField {
+    pub key: String,
+    pub key_span: Span,
+    pub value: Expr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+impl File {
+    /// The run of `//` comment lines directly above `start` (no blank line in
+    /// between), with the comment markers stripped.
+    pub fn doc_comment(&self, text: &str, start: usize) -> Option<String> {
+        let mut lines = Vec::new();
+        let mut next = start;
+        for comment in self.comments.iter().rev().skip_while(|c| c.end > start) {
+            let gap = &text[comment.end..next];
+            let line_start = text[..comment.start].rfind('\n').map_or(0, |i| i + 1);
+            let own_line = text[line_start..comment.start].trim().is_empty();
+            let body = &text[comment.start..comment.end];
+            if !gap.trim().is_empty() || gap.matches('\n').count() > 1 || !own_line || !body.starts_with("//") {
+                break;
+            }
+            let line = body.trim_start_matches('/');
+            lines.push(line.strip_prefix(' ').unwrap_or(line).trim_end().to_string());
+            next = line_start;
+        }
+        lines.reverse();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+}
+
+impl Block {
+    pub fn full_name(&self) -> String {
+        self.name.iter().map(|i| i.name.as_str()).collect::<Vec<_>>().join(".")
+    }
+
+    pub fn name_span(&self) -> Span {
+        let first = self.name.first().map_or(self.span, |i| i.span);
+        let last = self.name.last().map_or(self.span, |i| i.span);
+        first.to(last)
+    }
+
+    pub fn attributes(&self) -> impl Iterator<Item = &Attribute> {
+        self.body.iter().filter_map(|s| match s {
+            Stmt::Attribute(a) => Some(a),
+            Stmt::Block(_) => None,
+        })
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.body.iter().filter_map(|s| match s {
+            Stmt::Block(b) => Some(b),
+            Stmt::Attribute(_) => None,
+        })
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes().find(|a| a.name.name == name)
+    }
+}
+
+impl Expr {
+    /// Flattens an identifier/field-access chain such as
+    /// `prometheus.remote_write.default.receiver` into its segments.
+    pub fn path(&self) -> Option<Vec<&str>> {
+        match &self.kind {
+            ExprKind::Ident(name) => Some(vec![name.as_str()]),
+            ExprKind::Access(base, field) => {
+                let mut path = base.path()?;
+                path.push(field.name.as_str());
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+
+    /// Like [`Expr::path`], but with the span of each segment.
+    pub fn path_spans(&self) -> Option<Vec<(&str, Span)>> {
+        match &self.kind {
+            ExprKind::Ident(name) => Some(vec![(name.as_str(), self.span)]),
+            ExprKind::Access(base, field) => {
+                let mut path = base.path_spans()?;
+                path.push((field.name.as_str(), field.span));
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.kind {
+            ExprKind::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The identifier chains used as values inside this expression, outermost
+    /// only (`a.b.c` is not also reported as `a.b`), skipping the names of
+    /// called functions.
+    pub fn references(&self) -> Vec<&Expr> {
+        fn visit<'a>(expr: &'a Expr, out: &mut Vec<&'a Expr>) {
+            if expr.path().is_some() {
+                out.push(expr);
+                return;
+            }
+            match &expr.kind {
+                ExprKind::Call(_, args) => args.iter().for_each(|a| visit(a, out)),
+                _ => expr.for_each_child(&mut |child| visit(child, out)),
+            }
+        }
+        let mut out = Vec::new();
+        visit(self, &mut out);
+        out
+    }
+
+    /// Calls `f` on this expression and every expression nested inside it.
+    pub fn walk(&self, f: &mut impl FnMut(&Expr)) {
+        f(self);
+        self.for_each_child(&mut |child| child.walk(f));
+    }
+
+    /// Calls `f` on each direct sub-expression.
+    pub fn for_each_child<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        match &self.kind {
+            ExprKind::Access(base, _) => f(base),
+            ExprKind::Index(base, index) => {
+                f(base);
+                f(index);
+            }
+            ExprKind::Call(callee, args) => {
+                f(callee);
+                args.iter().for_each(f);
+            }
+            ExprKind::Array(items) => items.iter().for_each(f),
+            ExprKind::Object(fields) => fields.iter().for_each(|field| f(&field.value)),
+            ExprKind::Unary(_, e) | ExprKind::Paren(e) => f(e),
+            ExprKind::Binary(_, l, r) => {
+                f(l);
+                f(r);
+            }
+            ExprKind::Null
+            | ExprKind::Bool(_)
+            | ExprKind::Number(_)
+            | ExprKind::String(_)
+            | ExprKind::Ident(_) => {}
+        }
+    }
+}
+
+/// Calls `f` on every expression in `body`, including nested blocks.
+pub fn walk_exprs(body: &[Stmt], f: &mut impl FnMut(&Expr)) {
+    for stmt in body {
+        match stmt {
+            Stmt::Attribute(a) => a.value.walk(f),
+            Stmt::Block(b) => walk_exprs(&b.body, f),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use super::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident,
+    Number,
+    String,
+    RawString,
+    Comment,
+
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Comma,
+    Dot,
+    Assign,
+    Colon,
+
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    Bang,
+
+    /// A string or block comment that never got closed.
+    Unterminated,
+    Unknown,
+    Eof,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+pub fn is_ident_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_'
+}
+
+pub fn is_ident_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+/// The byte order mark some Windows tools start files with. It is not part
+/// of the text editors show: the lexer skips it, and positions on the first
+/// line don't count it.
+pub const BOM: char = '\u{feff}';
+
+/// Splits `text` into tokens. Comments are kept so that callers can look at
+/// them; the final token is always `Eof`.
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = if text.starts_with(BOM) { BOM.len_utf8() } else { 0 };
+
+    while i < bytes.len() {
+        let start = i;
+        let b = bytes[i];
+
+        if b.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let kind = match b {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                // The `\r` of a `\r\n` ends the line, not the comment.
+                if bytes.get(i) == Some(&b'\n') && bytes[i - 1] == b'\r' {
+                    i -= 1;
+                }
+                TokenKind::Comment
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => match text[i + 2..].find("*/") {
+                Some(end) => {
+                    i += 2 + end + 2;
+                    TokenKind::Comment
+                }
+                None => {
+                    i = bytes.len();
+                    TokenKind::Unterminated
+                }
+            },
+            b'"' => {
+                i += 1;
+                let mut closed = false;
+                while i < bytes.len() {
+                    match bytes[i] {
+                        b'\\' => i += 2,
+                        b'"' => {
+                            i += 1;
+                            closed = true;
+                            break;
+                        }
+                        b'\n' => break,
+                        _ => i += 1,
+                    }
+                }
+                i = i.min(bytes.len());
+                if closed {
+                    TokenKind::String
+                } else {
+                    TokenKind::Unterminated
+                }
+            }
+            b'`' => match text[i + 1..].find('`') {
+                Some(end) => {
+                    i += 1 + end + 1;
+                    TokenKind::RawString
+                }
+                None => {
+                    i = bytes.len();
+                    TokenKind::Unterminated
+                }
+            },
+            b'0'..=b'9' => {
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                if bytes.get(i) == Some(&b'.') && bytes.get(i + 1).is_some_and(u8::is_ascii_digit) {
+                    i += 1;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                if matches!(bytes.get(i), Some(b'e' | b'E')) {
+                    let mut j = i + 1;
+                    if matches!(bytes.get(j), Some(b'+' | b'-')) {
+                        j += 1;
+                    }
+                    if bytes.get(j).is_some_and(u8::is_ascii_digit) {
+                        i = j;
+                        while i < bytes.len() && bytes[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                TokenKind::Number
+            }
+            _ if is_ident_start(b as char) => {
+                while i < bytes.len() && is_ident_char(bytes[i] as char) {
+                    i += 1;
+                }
+                TokenKind::Ident
+            }
+            _ => {
+                let two = bytes.get(i + 1).copied();
+                let (kind, len) = match (b, two) {
+                    (b'|', Some(b'|')) => (TokenKind::Or, 2),
+                    (b'&', Some(b'&')) => (TokenKind::And, 2),
+                    (b'=', Some(b'=')) => (TokenKind::Eq, 2),
+                    (b'!', Some(b'=')) => (TokenKind::NotEq, 2),
+                    (b'<', Some(b'=')) => (TokenKind::LtEq, 2),
+                    (b'>', Some(b'=')) => (TokenKind::GtEq, 2),
+                    (b'{', _) => (TokenKind::LBrace, 1),
+                    (b'}', _) => (TokenKind::RBrace, 1),
+                    (b'[', _) => (TokenKind::LBracket, 1),
+                    (b']', _) => (TokenKind::RBracket, 1),
+                    (b'(', _) => (TokenKind::LParen, 1),
+                    (b')', _) => (TokenKind::RParen, 1),
+                    (b',', _) => (TokenKind::Comma, 1),
+                    (b'.', _) => (TokenKind::Dot, 1),
+                    (b'=', _) => (TokenKind::Assign, 1),
+                    (b':', _) => (TokenKind::Colon, 1),
+                    (b'<', _) => (TokenKind::Lt, 1),
+                    (b'>', _) => (TokenKind::Gt, 1),
+                    (b'+', _) => (TokenKind::Plus, 1),
+                    (b'-', _) => (TokenKind::Minus, 1),
+                    (b'*', _) => (TokenKind::Star, 1),
+                    (b'/', _) => (TokenKind::Slash, 1),
+                    (b'%', _) => (TokenKind::Percent, 1),
+                    (b'^', _) => (TokenKind::Caret, 1),
+                    (b'!', _) => (TokenKind::Bang, 1),
+                    _ => {
+                        // Swallow the whole (possibly multi-byte) character.
+                        let ch_len = text[i..].chars().next().map_or(1, char::len_utf8);
+                        (TokenKind::Unknown, ch_len)
+                    }
+                };
+                i += len;
+                kind
+            }
+        };
+
+        tokens.push(Token { kind, span: Span::new(start, i) });
+    }
+
+    tokens.push(Token { kind: TokenKind::Eof, span: Span::new(text.len(), text.len()) });
+    tokens
+}
@@ -0,0 +1,30 @@
+//! Lexer, AST and parser for the Alloy configuration syntax (River).
+
+pub mod ast;
+pub mod lexer;
+mod parser;
+
+pub use ast::*;
+pub use parser::{bad_escapes, parse, quote, source_offset, unquote, BadEscape};
+
+/// Byte range into the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+}
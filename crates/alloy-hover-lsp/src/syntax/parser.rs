@@ -0,0 +1,527 @@
+use super::ast::*;
+use super::lexer::{tokenize, Token, TokenKind};
+use super::Span;
+use std::iter::Peekable;
+
+type PResult<T> = Result<T, SyntaxError>;
+
+/// How deep blocks and expressions may nest. Parsing recurses once per
+/// level, so deeper input would overflow the stack and abort the process;
+/// real configs stay far below this.
+const MAX_DEPTH: usize = 256;
+
+pub fn parse(text: &str) -> File {
+    let mut comments = Vec::new();
+    let tokens: Vec<Token> = tokenize(text)
+        .into_iter()
+        .filter(|t| {
+            if t.kind == TokenKind::Comment {
+                comments.push(t.span);
+                false
+            } else {
+                true
+            }
+        })
+        .collect();
+
+    let mut parser = Parser { text, tokens, pos: 0, depth: 0, errors: Vec::new() };
+    let body = parser.body(false);
+    File { body, comments, errors: parser.errors }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Blocks and expressions currently being parsed inside one another.
+    depth: usize,
+    /// Every syntax error so far; parsing carries on after each.
+    errors: Vec<SyntaxError>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Token {
+        self.tokens[self.pos]
+    }
+
+    fn peek_kind(&self) -> TokenKind {
+        self.peek().kind
+    }
+
+    fn bump(&mut self) -> Token {
+        let tok = self.peek();
+        if tok.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn slice(&self, span: Span) -> &'a str {
+        &self.text[span.start..span.end]
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> PResult<T> {
+        let tok = self.peek();
+        let message = match tok.kind {
+            TokenKind::Unterminated => "unterminated string or comment".to_string(),
+            _ => message.into(),
+        };
+        Err(SyntaxError { message, span: tok.span })
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> PResult<Token> {
+        if self.peek_kind() == kind {
+            Ok(self.bump())
+        } else {
+            self.error(format!("expected {what}"))
+        }
+    }
+
+    /// Parses one level deeper with `parse`, or fails once nesting passes
+    /// [`MAX_DEPTH`]; the statement is then dropped like any broken one.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> PResult<T>) -> PResult<T> {
+        if self.depth == MAX_DEPTH {
+            return self.error(format!("nested more than {MAX_DEPTH} levels deep"));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn ident(&mut self) -> PResult<Ident> {
+        let tok = self.expect(TokenKind::Ident, "identifier")?;
+        Ok(Ident { name: self.slice(tok.span).to_string(), span: tok.span })
+    }
+
+    /// Parses statements until EOF (or `}` when `nested`). A statement with
+    /// a syntax error is dropped and parsing resumes at the next one, so a
+    /// half-typed line doesn't take the rest of the file with it.
+    fn body(&mut self, nested: bool) -> Vec<Stmt> {
+        let mut body = Vec::new();
+        loop {
+            let start = self.pos;
+            let result = match self.peek_kind() {
+                TokenKind::Eof => break,
+                TokenKind::RBrace if nested => break,
+                TokenKind::Ident => self.stmt(),
+                _ => self.error("expected attribute or block"),
+            };
+            match result {
+                Ok(stmt) => body.push(stmt),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.recover(start);
+                }
+            }
+        }
+        body
+    }
+
+    /// Skips past a broken statement that began at token `start`, up to a
+    /// line starting with an identifier or the `}` closing the body, not
+    /// counting those inside braces the statement opened.
+    fn recover(&mut self, start: usize) {
+        let mut depth = 0usize;
+        loop {
+            let tok = self.peek();
+            let moved = self.pos > start;
+            match tok.kind {
+                TokenKind::Eof => return,
+                TokenKind::RBrace if depth == 0 && moved => return,
+                TokenKind::Ident if depth == 0 && moved && self.starts_line(tok) => return,
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    fn starts_line(&self, tok: Token) -> bool {
+        self.text[..tok.span.start].trim_end_matches([' ', '\t']).ends_with('\n')
+    }
+
+    fn stmt(&mut self) -> PResult<Stmt> {
+        let first = self.ident()?;
+
+        if self.peek_kind() == TokenKind::Assign {
+            self.bump();
+            let value = self.expr()?;
+            let span = first.span.to(value.span);
+            return Ok(Stmt::Attribute(Attribute { name: first, value, span }));
+        }
+
+        let mut name = vec![first];
+        while self.peek_kind() == TokenKind::Dot {
+            self.bump();
+            name.push(self.ident()?);
+        }
+
+        let label = if self.peek_kind() == TokenKind::String {
+            let tok = self.bump();
+            Some(StringLit { value: unquote(self.slice(tok.span)), span: tok.span })
+        } else {
+            None
+        };
+
+        // Failing before the `{` lets recovery skip the whole block.
+        let body = self.nested(|parser| {
+            parser.expect(TokenKind::LBrace, "`{` or `=`")?;
+            Ok(parser.body(true))
+        })?;
+        // An unclosed block still has its body, running to the end.
+        let end = match self.expect(TokenKind::RBrace, "`}`") {
+            Ok(rbrace) => rbrace.span,
+            Err(err) => {
+                self.errors.push(err);
+                self.peek().span
+            }
+        };
+
+        let span = name[0].span.to(end);
+        Ok(Stmt::Block(Block { name, label, body, span }))
+    }
+
+    fn expr(&mut self) -> PResult<Expr> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, min_prec: u8) -> PResult<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some((op, prec)) = binary_op(self.peek_kind()) {
+            if prec < min_prec {
+                break;
+            }
+            self.bump();
+            // `^` is right-associative, everything else is left-associative.
+            let next = if op == BinaryOp::Pow { prec } else { prec + 1 };
+            let rhs = self.nested(|parser| parser.binary(next))?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr { kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), span };
+        }
+        Ok(lhs)
+    }
+
+    /// Every way an expression nests another, through brackets or unary
+    /// operators, comes through here; right operands nest in `binary`.
+    fn unary(&mut self) -> PResult<Expr> {
+        self.nested(|parser| {
+            let op = match parser.peek_kind() {
+                TokenKind::Bang => UnaryOp::Not,
+                TokenKind::Minus => UnaryOp::Neg,
+                _ => return parser.postfix(),
+            };
+            let tok = parser.bump();
+            let operand = parser.unary()?;
+            let span = tok.span.to(operand.span);
+            Ok(Expr { kind: ExprKind::Unary(op, Box::new(operand)), span })
+        })
+    }
+
+    fn postfix(&mut self) -> PResult<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            match self.peek_kind() {
+                TokenKind::Dot => {
+                    self.bump();
+                    let field = self.ident()?;
+                    let span = expr.span.to(field.span);
+                    expr = Expr { kind: ExprKind::Access(Box::new(expr), field), span };
+                }
+                TokenKind::LBracket => {
+                    self.bump();
+                    let index = self.expr()?;
+                    let close = self.expect(TokenKind::RBracket, "`]`")?;
+                    let span = expr.span.to(close.span);
+                    expr = Expr { kind: ExprKind::Index(Box::new(expr), Box::new(index)), span };
+                }
+                TokenKind::LParen => {
+                    self.bump();
+                    let (args, close) = self.list(TokenKind::RParen, "`)`", Self::expr)?;
+                    let span = expr.span.to(close.span);
+                    expr = Expr { kind: ExprKind::Call(Box::new(expr), args), span };
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> PResult<Expr> {
+        let tok = self.peek();
+        let kind = match tok.kind {
+            TokenKind::Ident => {
+                self.bump();
+                match self.slice(tok.span) {
+                    "null" => ExprKind::Null,
+                    "true" => ExprKind::Bool(true),
+                    "false" => ExprKind::Bool(false),
+                    name => ExprKind::Ident(name.to_string()),
+                }
+            }
+            TokenKind::Number => {
+                self.bump();
+                ExprKind::Number(self.slice(tok.span).to_string())
+            }
+            TokenKind::String | TokenKind::RawString => {
+                self.bump();
+                ExprKind::String(unquote(self.slice(tok.span)))
+            }
+            TokenKind::LParen => {
+                self.bump();
+                let inner = self.expr()?;
+                let close = self.expect(TokenKind::RParen, "`)`")?;
+                return Ok(Expr { kind: ExprKind::Paren(Box::new(inner)), span: tok.span.to(close.span) });
+            }
+            TokenKind::LBracket => {
+                self.bump();
+                let (items, close) = self.list(TokenKind::RBracket, "`]`", Self::expr)?;
+                return Ok(Expr { kind: ExprKind::Array(items), span: tok.span.to(close.span) });
+            }
+            TokenKind::LBrace => {
+                self.bump();
+                let (fields, close) = self.list(TokenKind::RBrace, "`}`", Self::object_field)?;
+                return Ok(Expr { kind: ExprKind::Object(fields), span: tok.span.to(close.span) });
+            }
+            _ => return self.error("expected expression"),
+        };
+        Ok(Expr { kind, span: tok.span })
+    }
+
+    fn object_field(&mut self) -> PResult<ObjectField> {
+        let tok = self.peek();
+        let key = match tok.kind {
+            TokenKind::Ident => self.slice(tok.span).to_string(),
+            TokenKind::String => unquote(self.slice(tok.span)),
+            _ => return self.error("expected object key"),
+        };
+        self.bump();
+        match self.peek_kind() {
+            TokenKind::Assign | TokenKind::Colon => {
+                self.bump();
+            }
+            _ => return self.error("expected `=` after object key"),
+        }
+        let value = self.expr()?;
+        Ok(ObjectField { key, key_span: tok.span, value })
+    }
+
+    /// Parses a comma-separated list (trailing comma allowed) up to and
+    /// including the closing token.
+    fn list<T>(
+        &mut self,
+        close: TokenKind,
+        what: &str,
+        mut item: impl FnMut(&mut Self) -> PResult<T>,
+    ) -> PResult<(Vec<T>, Token)> {
+        let mut items = Vec::new();
+        while self.peek_kind() != close {
+            items.push(item(self)?);
+            if self.peek_kind() == TokenKind::Comma {
+                self.bump();
+            } else if self.peek_kind() != close {
+                return self.error(format!("expected `,` or {what}"));
+            }
+        }
+        let tok = self.bump();
+        Ok((items, tok))
+    }
+}
+
+fn binary_op(kind: TokenKind) -> Option<(BinaryOp, u8)> {
+    Some(match kind {
+        TokenKind::Or => (BinaryOp::Or, 1),
+        TokenKind::And => (BinaryOp::And, 2),
+        TokenKind::Eq => (BinaryOp::Eq, 3),
+        TokenKind::NotEq => (BinaryOp::NotEq, 3),
+        TokenKind::Lt => (BinaryOp::Lt, 3),
+        TokenKind::LtEq => (BinaryOp::LtEq, 3),
+        TokenKind::Gt => (BinaryOp::Gt, 3),
+        TokenKind::GtEq => (BinaryOp::GtEq, 3),
+        TokenKind::Plus => (BinaryOp::Add, 4),
+        TokenKind::Minus => (BinaryOp::Sub, 4),
+        TokenKind::Star => (BinaryOp::Mul, 5),
+        TokenKind::Slash => (BinaryOp::Div, 5),
+        TokenKind::Percent => (BinaryOp::Mod, 5),
+        TokenKind::Caret => (BinaryOp::Pow, 6),
+        _ => return None,
+    })
+}
+
+/// Strips the quotes from a string token and resolves escape sequences.
+/// Raw (backtick) strings are returned verbatim.
+pub fn unquote(raw: &str) -> String {
+    if let Some(inner) = raw.strip_prefix('`') {
+        return inner.strip_suffix('`').unwrap_or(inner).to_string();
+    }
+    let inner = raw.strip_prefix('"').unwrap_or(raw);
+    let inner = inner.strip_suffix('"').unwrap_or(inner);
+
+    let mut out = Vec::with_capacity(inner.len());
+    let mut chars = inner.char_indices().peekable();
+    while let Some((_, ch)) = chars.next() {
+        match ch {
+            '\\' => escape(&mut chars).push_to(&mut out),
+            other => Escaped::Char(other).push_to(&mut out),
+        }
+    }
+    // Byte escapes can spell out invalid UTF-8, which a `String` cannot hold.
+    String::from_utf8(out).unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
+}
+
+/// `value` as a quoted Alloy string, escaped so [`unquote`] gives it back.
+pub fn quote(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if ch.is_control() => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// One decoded escape sequence: `\x` and octal escapes are single bytes, as
+/// in Go, and everything else a character.
+enum Escaped {
+    Byte(u8),
+    Char(char),
+}
+
+impl Escaped {
+    fn len(&self) -> usize {
+        match self {
+            Escaped::Byte(_) => 1,
+            Escaped::Char(ch) => ch.len_utf8(),
+        }
+    }
+
+    fn push_to(&self, out: &mut Vec<u8>) {
+        match *self {
+            Escaped::Byte(byte) => out.push(byte),
+            Escaped::Char(ch) => out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+}
+
+/// Decodes the escape sequence after a backslash. The malformed ones that
+/// [`bad_escapes`] reports decode to the escaped character, or to U+FFFD
+/// when digits are missing or out of range.
+fn escape<I: Iterator<Item = (usize, char)>>(chars: &mut Peekable<I>) -> Escaped {
+    let Some((_, escape)) = chars.next() else { return Escaped::Char('\\') };
+    let (digits, radix) = match escape {
+        'a' => return Escaped::Char('\u{7}'),
+        'b' => return Escaped::Char('\u{8}'),
+        'f' => return Escaped::Char('\u{c}'),
+        'n' => return Escaped::Char('\n'),
+        'r' => return Escaped::Char('\r'),
+        't' => return Escaped::Char('\t'),
+        'v' => return Escaped::Char('\u{b}'),
+        '0'..='7' => (2, 8),
+        'x' => (2, 16),
+        'u' => (4, 16),
+        'U' => (8, 16),
+        other => return Escaped::Char(other),
+    };
+    let mut value = escape.to_digit(8).unwrap_or(0);
+    for _ in 0..digits {
+        let Some(digit) = chars.peek().and_then(|&(_, ch)| ch.to_digit(radix)) else {
+            return Escaped::Char(char::REPLACEMENT_CHARACTER);
+        };
+        value = value.saturating_mul(radix) + digit;
+        chars.next();
+    }
+    match escape {
+        'x' | '0'..='7' => u8::try_from(value).map_or(Escaped::Char(char::REPLACEMENT_CHARACTER), Escaped::Byte),
+        _ => Escaped::Char(char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER)),
+    }
+}
+
+/// Maps a byte offset into the [`unquote`]d value of the string token `raw`
+/// back to the offset in `raw` it came from.
+pub fn source_offset(raw: &str, value_offset: usize) -> usize {
+    if raw.starts_with('`') {
+        return (value_offset + 1).min(raw.len());
+    }
+    let mut value_len = 0;
+    let mut chars = raw.char_indices().skip(1).peekable();
+    while let Some((i, ch)) = chars.next() {
+        if value_len >= value_offset || (ch == '"' && chars.peek().is_none()) {
+            return i;
+        }
+        let decoded = match ch {
+            '\\' => escape(&mut chars),
+            other => Escaped::Char(other),
+        };
+        value_len += decoded.len();
+    }
+    raw.len()
+}
+
+/// An escape sequence in a quoted string that Alloy rejects.
+pub struct BadEscape {
+    /// Where it is within the string token.
+    pub span: Span,
+    /// The backslash escapes nothing, as in `\d`, rather than starting a
+    /// malformed escape: the writer probably meant a literal backslash.
+    pub unknown: bool,
+    pub message: String,
+}
+
+/// Checks the escape sequences of the string token `raw` against Alloy's
+/// rules, which are Go's: `\a \b \f \n \r \t \v \\ \"`, three octal digits,
+/// and `\x`, `\u` and `\U` with two, four and eight hex digits.
+pub fn bad_escapes(raw: &str) -> Vec<BadEscape> {
+    if raw.starts_with('`') {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    let mut chars = raw.char_indices().skip(1).peekable();
+    while let Some((start, ch)) = chars.next() {
+        if ch != '\\' {
+            continue;
+        }
+        let Some((_, escape)) = chars.next() else { break };
+        let (digits, radix, max) = match escape {
+            'a' | 'b' | 'f' | 'n' | 'r' | 't' | 'v' | '\\' | '"' => continue,
+            '0'..='7' => (2, 8, 0xff),
+            'x' => (2, 16, 0xff),
+            'u' => (4, 16, 0x10ffff),
+            'U' => (8, 16, 0x10ffff),
+            _ => {
+                let span = Span::new(start, start + 1 + escape.len_utf8());
+                let message = format!("`\\{escape}` is not an escape sequence; a backslash is written `\\\\`");
+                out.push(BadEscape { span, unknown: true, message });
+                continue;
+            }
+        };
+        // An octal escape's first digit is the one after the backslash.
+        let mut value = escape.to_digit(radix).unwrap_or(0);
+        let (mut end, mut taken) = (start + 2, 0);
+        while let Some(digit) = chars.peek().and_then(|&(_, c)| c.to_digit(radix)).filter(|_| taken < digits) {
+            value = value * radix + digit;
+            (end, taken) = (end + 1, taken + 1);
+            chars.next();
+        }
+        let written = &raw[start..end];
+        let message = if taken < digits {
+            let (needs, kind) = if radix == 8 { (3, "octal") } else { (digits, "hex") };
+            Some(format!("`{written}` needs {needs} {kind} digits"))
+        } else if value > max || (0xd800..0xe000).contains(&value) {
+            Some(format!("`{written}` is not a valid {}", if max == 0xff { "byte" } else { "Unicode character" }))
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            out.push(BadEscape { span: Span::new(start, end), unknown: false, message });
+        }
+    }
+    out
+}
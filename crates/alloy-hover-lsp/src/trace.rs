@@ -0,0 +1,124 @@
+//! `--trace <path>`: a record of every JSON-RPC message between the client
+//! and the server, one JSON object per line with when it passed and which
+//! way it went, to attach to bug reports about protocol-level problems.
+
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    io::{self, LineWriter, Write},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// The trace file, shared by both directions.
+pub struct Trace {
+    out: Mutex<LineWriter<File>>,
+}
+
+impl Trace {
+    pub fn create(path: &Path) -> io::Result<Arc<Self>> {
+        Ok(Arc::new(Trace { out: Mutex::new(LineWriter::new(File::create(path)?)) }))
+    }
+
+    /// Wraps the stream messages arrive on from the client.
+    pub fn incoming<T>(self: &Arc<Self>, inner: T) -> Tap<T> {
+        Tap { inner, direction: "received", frames: Frames::default(), trace: self.clone() }
+    }
+
+    /// Wraps the stream the server sends its messages on.
+    pub fn outgoing<T>(self: &Arc<Self>, inner: T) -> Tap<T> {
+        Tap { inner, direction: "sent", frames: Frames::default(), trace: self.clone() }
+    }
+
+    fn record(&self, direction: &str, body: &[u8]) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let message =
+            serde_json::from_slice(body).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into()));
+        let line = json!({ "timeMs": time, "direction": direction, "message": message });
+        // Tracing is a diagnostic aid; failing to write must not stop the server.
+        let _ = writeln!(self.out.lock().unwrap(), "{line}");
+    }
+}
+
+/// A stream that passes everything through, recording each complete
+/// message seen on it.
+pub struct Tap<T> {
+    inner: T,
+    direction: &'static str,
+    frames: Frames,
+    trace: Arc<Trace>,
+}
+
+impl<T> Tap<T> {
+    fn saw(&mut self, bytes: &[u8]) {
+        self.frames.buffer.extend_from_slice(bytes);
+        while let Some(body) = self.frames.next() {
+            self.trace.record(self.direction, &body);
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Tap<T> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            this.saw(&buf.filled()[before..]);
+        }
+        poll
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Tap<T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            this.saw(&buf[..written]);
+        }
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Splits a byte stream into the bodies of its `Content-Length` frames.
+#[derive(Default)]
+struct Frames {
+    buffer: Vec<u8>,
+}
+
+impl Frames {
+    /// Takes the next complete message body off the buffer, if there is one.
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let end = self.buffer.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let headers = String::from_utf8_lossy(&self.buffer[..end]);
+        let length = headers.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim().eq_ignore_ascii_case("content-length").then(|| value.trim().parse::<usize>().ok())?
+        });
+        let start = end + 4;
+        let Some(length) = length else {
+            // Not a frame we understand; drop its headers and carry on.
+            self.buffer.drain(..start);
+            return self.next();
+        };
+        if self.buffer.len() < start + length {
+            return None;
+        }
+        let body = self.buffer[start..start + length].to_vec();
+        self.buffer.drain(..start + length);
+        Some(body)
+    }
+}
@@ -0,0 +1,152 @@
+//! Parsing of the unit strings Alloy accepts for `duration` and `bytes`
+//! values, with the same rules it applies when loading a config.
+
+const DURATION_UNITS: &[(&str, f64)] = &[
+    ("ns", 1.0),
+    ("us", 1e3),
+    ("µs", 1e3),
+    ("μs", 1e3),
+    ("ms", 1e6),
+    ("s", 1e9),
+    ("m", 60e9),
+    ("h", 3600e9),
+];
+
+/// Base-2 sizes: `KB` and `KiB` both mean 1024 bytes.
+const BYTE_UNITS: &[(&str, f64)] = &[
+    ("B", 1.0),
+    ("KB", 1024.0),
+    ("KiB", 1024.0),
+    ("MB", 1048576.0),
+    ("MiB", 1048576.0),
+    ("GB", 1073741824.0),
+    ("GiB", 1073741824.0),
+    ("TB", 1099511627776.0),
+    ("TiB", 1099511627776.0),
+    ("PB", 1125899906842624.0),
+    ("PiB", 1125899906842624.0),
+    ("EB", 1152921504606846976.0),
+    ("EiB", 1152921504606846976.0),
+];
+
+/// The same rules as regular expressions, for tools that only take those.
+pub const DURATION_PATTERN: &str = r"^[-+]?(0|(([0-9]+(\.[0-9]*)?|\.[0-9]+)(ns|us|µs|μs|ms|s|m|h))+)$";
+pub const BYTES_PATTERN: &str = r"^(([0-9]+(\.[0-9]*)?|\.[0-9]+)(B|KB|KiB|MB|MiB|GB|GiB|TB|TiB|PB|PiB|EB|EiB))+$";
+
+/// Parses a Go-style duration such as `"15s"` or `"2h30m"`, returning
+/// nanoseconds.
+pub fn parse_duration(s: &str) -> Result<f64, String> {
+    let (sign, rest) = match s.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if rest == "0" {
+        return Ok(0.0);
+    }
+    parse_segments(rest, DURATION_UNITS)
+        .map(|n| sign * n)
+        .map_err(|err| match err {
+            SegmentError::Unit(unit) => format!(
+                "unknown duration unit `{unit}`; use ns, us, ms, s, m or h{}",
+                if matches!(unit.as_str(), "d" | "w" | "y") { " (e.g. `24h` for a day)" } else { "" }
+            ),
+            other => other.describe("duration", "15s"),
+        })
+}
+
+/// Formats nanoseconds the way Go prints durations, leaving out zero
+/// segments: `1m30s`, `1h`, `1.5s`, `250ms`.
+pub fn format_duration(ns: f64) -> String {
+    let sign = if ns < 0.0 { "-" } else { "" };
+    let ns = ns.abs().round() as u128;
+    if ns == 0 {
+        return "0s".into();
+    }
+    if ns < 1_000_000_000 {
+        let (scale, unit) = if ns < 1_000 {
+            (1, "ns")
+        } else if ns < 1_000_000 {
+            (1_000, "us")
+        } else {
+            (1_000_000, "ms")
+        };
+        return format!("{sign}{}{unit}", decimal(ns, scale));
+    }
+    let (hours, minutes, seconds) = (ns / 3_600_000_000_000, ns / 60_000_000_000 % 60, ns % 60_000_000_000);
+    let mut out = sign.to_string();
+    if hours > 0 {
+        out.push_str(&format!("{hours}h"));
+    }
+    if minutes > 0 {
+        out.push_str(&format!("{minutes}m"));
+    }
+    if seconds > 0 {
+        out.push_str(&format!("{}s", decimal(seconds, 1_000_000_000)));
+    }
+    out
+}
+
+/// `n / scale` without trailing zeros: `1500, 1000` is `1.5`.
+fn decimal(n: u128, scale: u128) -> String {
+    let (whole, fraction) = (n / scale, n % scale);
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let digits = scale.ilog10() as usize;
+    let fraction = format!("{fraction:0digits$}");
+    format!("{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+/// Parses a byte size such as `"512MiB"`, returning bytes.
+pub fn parse_bytes(s: &str) -> Result<f64, String> {
+    parse_segments(s, BYTE_UNITS).map_err(|err| match err {
+        SegmentError::Unit(unit) => format!("unknown size unit `{unit}`; use B, KiB, MiB, GiB, TiB (or KB, MB, …)"),
+        other => other.describe("size", "512MiB"),
+    })
+}
+
+enum SegmentError {
+    Empty,
+    Number,
+    MissingUnit,
+    Unit(String),
+}
+
+impl SegmentError {
+    fn describe(self, what: &str, example: &str) -> String {
+        match self {
+            SegmentError::Empty => format!("empty {what}"),
+            SegmentError::Number => format!("malformed {what}: expected a number followed by a unit, e.g. `{example}`"),
+            SegmentError::MissingUnit => format!("{what} is missing a unit, e.g. `{example}`"),
+            SegmentError::Unit(unit) => format!("unknown unit `{unit}`"),
+        }
+    }
+}
+
+/// Sums a sequence of `<number><unit>` segments.
+fn parse_segments(s: &str, units: &[(&str, f64)]) -> Result<f64, SegmentError> {
+    if s.is_empty() {
+        return Err(SegmentError::Empty);
+    }
+    let mut total = 0.0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let number = &rest[..number_len];
+        if number.is_empty() || number == "." || number.matches('.').count() > 1 {
+            return Err(SegmentError::Number);
+        }
+        rest = &rest[number_len..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        if unit.is_empty() {
+            return Err(SegmentError::MissingUnit);
+        }
+        let Some((_, scale)) = units.iter().find(|(u, _)| *u == unit) else {
+            return Err(SegmentError::Unit(unit.to_string()));
+        };
+        total += number.parse::<f64>().map_err(|_| SegmentError::Number)? * scale;
+        rest = &rest[unit_len..];
+    }
+    Ok(total)
+}
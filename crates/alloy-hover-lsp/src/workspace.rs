@@ -0,0 +1,649 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+use tower_lsp::lsp_types::{Range, TextEdit, Url};
+
+use crate::document::Document;
+use crate::modules::fnv1a;
+use crate::syntax::{self, Block, ExprKind, Stmt};
+
+/// File extensions treated as Alloy configs (mirrors the extension's
+/// `path_suffixes`).
+pub const CONFIG_EXTENSIONS: &[&str] = &["alloy", "aconf"];
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor"];
+
+/// What we remember about a single config file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileIndex {
+    /// Names looked up via `env("…")` / `sys.env("…")`.
+    pub env_names: Vec<String>,
+    /// Labelled component blocks, e.g. `prometheus.scrape "default"`.
+    pub components: Vec<ComponentDef>,
+    pub declares: Vec<DeclareDef>,
+    /// Every identifier/field-access chain used as a value.
+    pub references: Vec<Reference>,
+    /// `import.file` blocks reading a literal path.
+    pub imports: Vec<FileImport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentDef {
+    pub name: String,
+    pub label: String,
+    pub range: Range,
+}
+
+impl ComponentDef {
+    /// The ID other components use to reference this one.
+    pub fn id(&self) -> String {
+        format!("{}.{}", self.name, self.label)
+    }
+}
+
+/// A custom component defined with `declare "name" { … }`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeclareDef {
+    pub name: String,
+    /// The `//` comment above the `declare` block.
+    pub doc: Option<String>,
+    pub arguments: Vec<ArgumentDef>,
+    pub exports: Vec<String>,
+    pub range: Range,
+}
+
+/// An `argument "name" { … }` block of a `declare`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArgumentDef {
+    pub name: String,
+    pub optional: bool,
+    /// Source text of the `default` expression.
+    pub default: Option<String>,
+    /// The `comment` attribute and/or the `//` comment above the block.
+    pub doc: Option<String>,
+    pub range: Range,
+}
+
+impl ArgumentDef {
+    pub fn from_block(doc: &Document, file: &syntax::File, block: &Block) -> Option<Self> {
+        let name = block.label.as_ref()?.value.clone();
+        let attribute = |name| block.attribute(name).map(|a| &a.value);
+        let optional = attribute("optional").is_some_and(|v| matches!(v.kind, ExprKind::Bool(true)));
+        let default = attribute("default").map(|v| doc.text[v.span.start..v.span.end].to_string());
+        let comment = attribute("comment").and_then(|v| v.as_str()).map(str::to_string);
+        let doc_comment = file.doc_comment(&doc.text, block.span.start);
+        let doc_text = match (comment, doc_comment) {
+            (Some(a), Some(b)) => Some(format!("{a}\n\n{b}")),
+            (a, b) => a.or(b),
+        };
+        Some(ArgumentDef { name, optional, default, doc: doc_text, range: doc.range(block.span) })
+    }
+}
+
+/// The path an `import.file` reads, given as a string literal or joined
+/// onto `module_path`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileImport {
+    /// As written, e.g. `modules/db.alloy`.
+    pub path: String,
+    /// The string literal holding it, quotes included.
+    pub range: Range,
+    /// Joined onto `module_path`, so relative to the importing file.
+    /// Otherwise a relative path is relative to where Alloy runs, taken to
+    /// be the workspace root.
+    pub from_module: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reference {
+    /// Dotted path, e.g. `prometheus.remote_write.default.receiver`.
+    pub path: String,
+    pub range: Range,
+    /// ID of the component whose body contains the reference.
+    pub owner: Option<String>,
+    /// Attribute the reference appears in, e.g. `forward_to`.
+    pub attribute: String,
+}
+
+impl FileIndex {
+    pub fn build(text: &str) -> Self {
+        Self::from_document(&Document::new(text.to_string()))
+    }
+
+    pub fn from_document(doc: &Document) -> Self {
+        let file = doc.syntax();
+        let mut index = FileIndex::default();
+        index.collect_blocks(doc, file, &file.body);
+        index.collect_references(doc, &file.body, None);
+        syntax::walk_exprs(&file.body, &mut |expr| {
+            if let ExprKind::Call(callee, args) = &expr.kind {
+                if is_env_call(callee) {
+                    if let Some(name) = args.first().and_then(|a| a.as_str()) {
+                        index.env_names.push(name.to_string());
+                    }
+                }
+            }
+        });
+        index
+    }
+
+    /// IDs of the components the file defines, and names of its `declare`s.
+    fn defined(&self) -> BTreeSet<String> {
+        self.components.iter().map(ComponentDef::id).chain(self.declares.iter().map(|d| d.name.clone())).collect()
+    }
+
+    fn referenced(&self) -> BTreeSet<String> {
+        self.references.iter().map(|r| r.path.clone()).collect()
+    }
+
+    fn collect_blocks(&mut self, doc: &Document, file: &syntax::File, body: &[Stmt]) {
+        for stmt in body {
+            let Stmt::Block(block) = stmt else { continue };
+            let Some(label) = &block.label else { continue };
+            if block.full_name() == "declare" {
+                self.declares.push(DeclareDef {
+                    name: label.value.clone(),
+                    doc: file.doc_comment(&doc.text, block.span.start),
+                    arguments: block
+                        .blocks()
+                        .filter(|b| b.full_name() == "argument")
+                        .filter_map(|b| ArgumentDef::from_block(doc, file, b))
+                        .collect(),
+                    exports: labels_of(block, "export"),
+                    range: doc.range(block.span),
+                });
+                // Components inside a module body are addressable from
+                // within that module.
+                self.collect_blocks(doc, file, &block.body);
+            } else if block.full_name() == "import.file" {
+                self.imports.extend(file_import(doc, block));
+            } else if component_id(block).is_some() {
+                self.components.push(ComponentDef {
+                    name: block.full_name(),
+                    label: label.value.clone(),
+                    range: doc.range(block.span),
+                });
+            }
+        }
+    }
+
+    fn collect_references(&mut self, doc: &Document, body: &[Stmt], owner: Option<&str>) {
+        for stmt in body {
+            match stmt {
+                Stmt::Attribute(a) => {
+                    for expr in a.value.references() {
+                        self.references.push(Reference {
+                            path: expr.path().unwrap_or_default().join("."),
+                            range: doc.range(expr.span),
+                            owner: owner.map(str::to_string),
+                            attribute: a.name.name.clone(),
+                        });
+                    }
+                }
+                Stmt::Block(b) => {
+                    let id = component_id(b);
+                    self.collect_references(doc, &b.body, id.as_deref().or(owner));
+                }
+            }
+        }
+    }
+}
+
+/// `name.label` for blocks that instantiate a component, `None` for
+/// declarations, module plumbing and plain nested blocks.
+pub fn component_id(block: &Block) -> Option<String> {
+    let label = block.label.as_ref()?;
+    match block.full_name().as_str() {
+        "declare" | "argument" | "export" => None,
+        name if name.starts_with("import.") => None,
+        name => Some(format!("{name}.{}", label.value)),
+    }
+}
+
+/// The literal path of an `import.file`: `"…"` or
+/// `file.path_join(module_path, "…")`.
+fn file_import(doc: &Document, block: &Block) -> Option<FileImport> {
+    let value = &block.attribute("filename")?.value;
+    let (literal, from_module) = match &value.kind {
+        ExprKind::String(_) => (value, false),
+        ExprKind::Call(callee, args) if callee.path().as_deref() == Some(&["file", "path_join"]) => match &args[..] {
+            [base, literal] if base.path().as_deref() == Some(&["module_path"]) => (literal, true),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(FileImport { path: literal.as_str()?.to_string(), range: doc.range(literal.span), from_module })
+}
+
+fn labels_of(block: &Block, child: &str) -> Vec<String> {
+    block
+        .blocks()
+        .filter(|b| b.full_name() == child)
+        .filter_map(|b| b.label.as_ref().map(|l| l.value.clone()))
+        .collect()
+}
+
+pub fn is_env_call(callee: &syntax::Expr) -> bool {
+    matches!(callee.path().as_deref(), Some(["env"] | ["sys", "env"]))
+}
+
+/// What updating a file's index changed that other files can depend on.
+#[derive(Debug, Default)]
+pub struct Change {
+    /// Component IDs and `declare` names the file gained or lost.
+    pub defined: BTreeSet<String>,
+    /// Reference paths the file gained or lost.
+    pub referenced: BTreeSet<String>,
+}
+
+fn symmetric_difference(a: BTreeSet<String>, b: BTreeSet<String>) -> BTreeSet<String> {
+    a.symmetric_difference(&b).cloned().collect()
+}
+
+#[derive(Debug, Default)]
+pub struct Workspace {
+    pub root: Option<PathBuf>,
+    files: HashMap<Url, FileIndex>,
+    /// Declarations of remote modules, per importing file and namespace,
+    /// each with the (cached) file it came from.
+    modules: HashMap<Url, HashMap<String, Vec<(Url, DeclareDef)>>>,
+    revision: u64,
+}
+
+impl Workspace {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self { root, files: HashMap::new(), modules: HashMap::new(), revision: 0 }
+    }
+
+    /// Changes whenever the index does, for caching results derived from it.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// How many imports have their module loaded.
+    pub fn module_count(&self) -> usize {
+        self.modules.values().map(HashMap::len).sum()
+    }
+
+    pub fn set_module(&mut self, importer: Url, namespace: String, declares: Vec<(Url, DeclareDef)>) {
+        self.revision += 1;
+        self.modules.entry(importer).or_default().insert(namespace, declares);
+    }
+
+    /// The declarations `importer` can use under `namespace`, if that
+    /// module has been loaded.
+    pub fn module(&self, importer: &Url, namespace: &str) -> Option<&[(Url, DeclareDef)]> {
+        self.modules.get(importer)?.get(namespace).map(Vec::as_slice)
+    }
+
+    /// Indexes every config file below the workspace root. With a `cache`
+    /// directory, files whose content is unchanged since the last scan
+    /// are not parsed again, their index being read back from there. The
+    /// rest are parsed on up to `threads` threads.
+    pub fn scan(&mut self, cache: Option<&Path>, threads: usize) {
+        let Some(root) = self.root.clone() else { return };
+        self.revision += 1;
+        let cache_file = cache.map(|dir| IndexCache::file(dir, &root));
+        let mut cached = cache_file.as_deref().map(IndexCache::load).unwrap_or_default();
+        let mut fresh = IndexCache::default();
+        let mut unparsed = Vec::new();
+        for path in config_files(&root) {
+            let Ok(text) = fs::read_to_string(&path) else { continue };
+            let Ok(uri) = Url::from_file_path(&path) else { continue };
+            let key = IndexCache::key(&text);
+            match fresh.files.get(&key).cloned().or_else(|| cached.files.remove(&key)) {
+                Some(index) => {
+                    fresh.files.insert(key, index.clone());
+                    self.files.insert(uri, index);
+                }
+                None => unparsed.push((uri, key, text)),
+            }
+        }
+        let parsed = unparsed.len();
+        let indexes = parallel_map(&unparsed, threads, |(_, _, text)| FileIndex::build(text));
+        for ((uri, key, _), index) in unparsed.into_iter().zip(indexes) {
+            fresh.files.insert(key, index.clone());
+            self.files.insert(uri, index);
+        }
+        if let Some(file) = cache_file.filter(|_| parsed > 0 || !cached.files.is_empty()) {
+            fresh.save(&file);
+        }
+    }
+
+    /// Adds the files of a workspace scanned on the side, keeping the index
+    /// of those updated here in the meantime.
+    pub fn merge(&mut self, scanned: Workspace) {
+        self.revision += 1;
+        for (uri, index) in scanned.files {
+            self.files.entry(uri).or_insert(index);
+        }
+    }
+
+    pub fn update(&mut self, uri: Url, text: &str) -> Change {
+        self.update_document(uri, &Document::new(text.to_string()))
+    }
+
+    /// Like [`Workspace::update`], reusing the document's parse.
+    pub fn update_document(&mut self, uri: Url, doc: &Document) -> Change {
+        self.revision += 1;
+        let index = FileIndex::from_document(doc);
+        let old = self.files.insert(uri, index.clone()).unwrap_or_default();
+        Change {
+            defined: symmetric_difference(old.defined(), index.defined()),
+            referenced: symmetric_difference(old.referenced(), index.referenced()),
+        }
+    }
+
+    /// The files other than `uri` whose diagnostics can depend on `change`
+    /// to it: those referencing what it defines or no longer does, defining
+    /// what it references or no longer does, or instantiating its modules.
+    pub fn dependents(&self, uri: &Url, change: &Change) -> Vec<Url> {
+        if change.defined.is_empty() && change.referenced.is_empty() {
+            return Vec::new();
+        }
+        let within = |path: &str, id: &str| path.strip_prefix(id).is_some_and(|r| r.is_empty() || r.starts_with('.'));
+        self.files
+            .iter()
+            .filter(|(other, _)| *other != uri)
+            .filter(|(_, f)| {
+                f.references.iter().any(|r| change.defined.iter().any(|id| within(&r.path, id)))
+                    || f.components.iter().any(|c| {
+                        let declared = c.name.rsplit_once('.').map_or(c.name.as_str(), |(_, name)| name);
+                        change.referenced.iter().any(|path| within(path, &c.id())) || change.defined.contains(declared)
+                    })
+            })
+            .map(|(other, _)| other.clone())
+            .collect()
+    }
+
+    /// The indexed files.
+    pub fn uris(&self) -> impl Iterator<Item = &Url> {
+        self.files.keys()
+    }
+
+    pub fn components(&self) -> impl Iterator<Item = (&Url, &ComponentDef)> {
+        self.files.iter().flat_map(|(uri, f)| f.components.iter().map(move |c| (uri, c)))
+    }
+
+    pub fn declares(&self) -> impl Iterator<Item = (&Url, &DeclareDef)> {
+        self.files.iter().flat_map(|(uri, f)| f.declares.iter().map(move |d| (uri, d)))
+    }
+
+    pub fn references(&self) -> impl Iterator<Item = (&Url, &Reference)> {
+        self.files.iter().flat_map(|(uri, f)| f.references.iter().map(move |r| (uri, r)))
+    }
+
+    /// Finds the component a reference path points into: the one with the
+    /// longest ID that is a dotted prefix of `path`.
+    pub fn resolve(&self, path: &str) -> Option<(&Url, &ComponentDef)> {
+        self.components()
+            .filter(|(_, c)| {
+                let id = c.id();
+                path.strip_prefix(&id).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .max_by_key(|(_, c)| c.name.len() + c.label.len())
+    }
+
+    /// References whose path is `id` itself or starts with `id.`.
+    pub fn references_to<'a>(&'a self, id: &'a str) -> impl Iterator<Item = (&'a Url, &'a Reference)> {
+        self.files.iter().flat_map(move |(uri, f)| {
+            f.references
+                .iter()
+                .filter(move |r| {
+                    r.path.strip_prefix(id).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+                })
+                .map(move |r| (uri, r))
+        })
+    }
+
+    /// Finds a `declare` by the name it is instantiated with. Components
+    /// from imported modules are namespaced (`math.add`), so the import
+    /// label in front is ignored when looking for the declaration.
+    pub fn declare(&self, name: &str) -> Option<&DeclareDef> {
+        self.find_declare(name).map(|(_, d)| d)
+    }
+
+    fn find_declare(&self, name: &str) -> Option<(&Url, &DeclareDef)> {
+        let unqualified = name.split_once('.').map(|(_, rest)| rest);
+        self.files.iter().find_map(|(uri, f)| {
+            f.declares
+                .iter()
+                .find(|d| d.name == name || Some(d.name.as_str()) == unqualified)
+                .map(|d| (uri, d))
+        })
+    }
+
+    /// Like [`Workspace::declare`], but resolves names the way `uri` sees
+    /// them: through its loaded remote modules first, then its own file.
+    pub fn declare_from(&self, uri: &Url, name: &str) -> Option<(&Url, &DeclareDef)> {
+        if let Some((namespace, rest)) = name.split_once('.') {
+            if let Some(declares) = self.module(uri, namespace) {
+                return declares.iter().find(|(_, d)| d.name == rest).map(|(u, d)| (u, d));
+            }
+        }
+        self.files
+            .get_key_value(uri)
+            .and_then(|(uri, f)| f.declares.iter().find(|d| d.name == name).map(|d| (uri, d)))
+            .or_else(|| self.find_declare(name))
+    }
+
+    /// Short, human-readable name for a file: relative to the root when
+    /// possible.
+    pub fn display_path(&self, uri: &Url) -> String {
+        let Ok(path) = uri.to_file_path() else { return uri.to_string() };
+        self.root
+            .as_ref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(&path)
+            .display()
+            .to_string()
+    }
+
+    /// The `declare`d components `uri` can instantiate: its own, and those
+    /// of the modules it has loaded under their namespace.
+    pub fn custom_components(&self, uri: &Url) -> Vec<String> {
+        let local = self.files.get(uri).into_iter().flat_map(|f| f.declares.iter().map(|d| d.name.clone()));
+        let imported = self.modules.get(uri).into_iter().flatten().flat_map(|(namespace, declares)| {
+            declares.iter().map(move |(_, d)| format!("{namespace}.{}", d.name))
+        });
+        local.chain(imported).collect()
+    }
+
+    /// Edits keeping every `import.file` path pointing at the same file
+    /// once each `(old, new)` of `renames`, files or directories, has
+    /// moved. They are keyed by the files' URIs from before the move.
+    pub fn import_edits(&self, renames: &[(PathBuf, PathBuf)]) -> HashMap<Url, Vec<TextEdit>> {
+        let moved = |path: &Path| {
+            renames
+                .iter()
+                .find_map(|(old, new)| path.strip_prefix(old).ok().map(|rest| moved_to(new, rest)))
+                .unwrap_or_else(|| path.to_path_buf())
+        };
+        let mut edits: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for (uri, file) in &self.files {
+            let Ok(importer) = uri.to_file_path() else { continue };
+            for import in &file.imports {
+                let base = match (import.from_module, &self.root) {
+                    (true, _) => importer.parent().map(Path::to_path_buf),
+                    (false, root) => root.clone(),
+                };
+                let written = Path::new(&import.path);
+                let Some(base) = base.or_else(|| written.is_absolute().then(PathBuf::new)) else { continue };
+                let target = normalize(&base.join(written));
+                let new_base = match import.from_module {
+                    true => moved(&importer).parent().map_or(base.clone(), Path::to_path_buf),
+                    false => base.clone(),
+                };
+                let new_target = moved(&target);
+                if new_target == target && new_base == base {
+                    continue;
+                }
+                let path = match written.is_absolute() {
+                    true => new_target.to_string_lossy().into_owned(),
+                    false => relative_path(&new_base, &new_target),
+                };
+                let edit = TextEdit { range: import.range, new_text: syntax::quote(&path) };
+                edits.entry(uri.clone()).or_default().push(edit);
+            }
+        }
+        edits
+    }
+
+    /// Moves what is indexed for the files under each `(old, new)` of
+    /// `renames` to where they now are.
+    pub fn rename(&mut self, renames: &[(PathBuf, PathBuf)]) {
+        let uris: Vec<Url> = self.files.keys().chain(self.modules.keys()).cloned().collect();
+        for uri in uris {
+            let Some(new) = moved(renames, &uri) else { continue };
+            if let Some(index) = self.files.remove(&uri) {
+                self.files.insert(new.clone(), index);
+            }
+            if let Some(modules) = self.modules.remove(&uri) {
+                self.modules.insert(new, modules);
+            }
+        }
+        self.revision += 1;
+    }
+
+    pub fn env_names(&self) -> BTreeSet<&str> {
+        self.files
+            .values()
+            .flat_map(|f| f.env_names.iter().map(String::as_str))
+            .collect()
+    }
+}
+
+/// The shape of [`FileIndex`] as cached. Bump it with any change to what
+/// an index holds or means, so caches written before are not read back.
+const INDEX_FORMAT: u32 = 1;
+
+/// The index of a workspace's files as of its last scan, by the hash of
+/// their content. Only read back if it has the current [`INDEX_FORMAT`].
+#[derive(Serialize, Deserialize)]
+struct IndexCache {
+    format: u32,
+    files: HashMap<String, FileIndex>,
+}
+
+impl Default for IndexCache {
+    fn default() -> Self {
+        Self { format: INDEX_FORMAT, files: HashMap::new() }
+    }
+}
+
+impl IndexCache {
+    /// Where the index of the workspace at `root` is kept below `dir`.
+    fn file(dir: &Path, root: &Path) -> PathBuf {
+        dir.join("index").join(format!("{:016x}.json", fnv1a(root.to_string_lossy().as_bytes())))
+    }
+
+    fn key(text: &str) -> String {
+        format!("{:016x}-{}", fnv1a(text.as_bytes()), text.len())
+    }
+
+    /// The cache in `path`; empty if there is none, it is unreadable, or
+    /// it is in another format.
+    fn load(path: &Path) -> Self {
+        let cache = fs::read(path).ok().and_then(|bytes| serde_json::from_slice::<Self>(&bytes).ok());
+        cache.filter(|c| c.format == INDEX_FORMAT).unwrap_or_default()
+    }
+
+    /// Best effort: a cache that can't be written only costs a re-parse.
+    fn save(&self, path: &Path) {
+        let Some(dir) = path.parent() else { return };
+        let Ok(json) = serde_json::to_vec(self) else { return };
+        // Written aside and renamed, so a concurrent scan never reads half.
+        let partial = path.with_extension(format!("{}.partial", std::process::id()));
+        if fs::create_dir_all(dir).is_ok() && fs::write(&partial, json).is_ok() && fs::rename(&partial, path).is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+    }
+}
+
+/// `f` applied to each item, split over up to `threads` threads.
+pub(crate) fn parallel_map<T: Sync, R: Send>(items: &[T], threads: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    if threads <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let chunk = items.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> =
+            items.chunks(chunk).map(|chunk| scope.spawn(|| chunk.iter().map(&f).collect::<Vec<_>>())).collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    })
+}
+
+/// The config files below `dir`, skipping hidden and build directories.
+pub(crate) fn config_files(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    collect_config_files(dir, &mut paths);
+    paths.sort();
+    paths
+}
+
+fn collect_config_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                collect_config_files(&path, out);
+            }
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| CONFIG_EXTENSIONS.contains(&e))
+        {
+            out.push(path);
+        }
+    }
+}
+
+/// `to` relative to the directory `from`, `/`-separated.
+pub(crate) fn relative_path(from: &Path, to: &Path) -> String {
+    let (from, to): (Vec<_>, Vec<_>) = (from.components().collect(), to.components().collect());
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let up = std::iter::repeat_n("..".to_string(), from.len() - common);
+    let down = to[common..].iter().map(|c| c.as_os_str().to_string_lossy().into_owned());
+    up.chain(down).collect::<Vec<_>>().join("/")
+}
+
+/// Where the file at `uri` is after `renames`, if one of them moved it.
+pub fn moved(renames: &[(PathBuf, PathBuf)], uri: &Url) -> Option<Url> {
+    let path = uri.to_file_path().ok()?;
+    let (old, new) = renames.iter().find(|(old, _)| path.starts_with(old))?;
+    Url::from_file_path(moved_to(new, path.strip_prefix(old).ok()?)).ok()
+}
+
+/// Where `rest`, below a directory that moved to `new`, now is; `new`
+/// itself for an empty `rest`, without the trailing separator `join` adds.
+fn moved_to(new: &Path, rest: &Path) -> PathBuf {
+    match rest.as_os_str().is_empty() {
+        true => new.to_path_buf(),
+        false => new.join(rest),
+    }
+}
+
+/// `path` with `.` and `..` resolved without looking at the disk.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
@@ -0,0 +1,340 @@
+//! Drives the language server in-process: messages go straight into the
+//! `LspService`, and whatever the server sends the client is collected from
+//! its socket.
+
+#![allow(dead_code)]
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tower::{Service, ServiceExt};
+use tower_lsp::jsonrpc::{Request, Response};
+use tower_lsp::lsp_types::*;
+use tower_lsp::LspService;
+
+use alloy_hover_lsp::catch_panic::CatchPanic;
+use alloy_hover_lsp::docs::Docs;
+use alloy_hover_lsp::document::Document;
+use alloy_hover_lsp::server::{self, Backend};
+
+pub struct TestServer {
+    service: CatchPanic<LspService<Backend>>,
+    notifications: mpsc::UnboundedReceiver<Request>,
+    next_id: i64,
+    /// The server's half of the `initialize` handshake.
+    pub capabilities: Value,
+}
+
+/// A URI for a document that only exists in the editor.
+pub fn uri(name: &str) -> String {
+    format!("file:///test/{name}")
+}
+
+/// What a current editor declares: markdown hovers, snippet completions,
+/// code action literals and progress reports.
+pub fn client_capabilities() -> Value {
+    json!({
+        "textDocument": {
+            "hover": { "contentFormat": ["markdown", "plaintext"] },
+            "completion": {
+                "completionItem": {
+                    "snippetSupport": true,
+                    "documentationFormat": ["markdown", "plaintext"],
+                    "resolveSupport": { "properties": ["additionalTextEdits"] },
+                },
+            },
+            "codeAction": {
+                "codeActionLiteralSupport": { "codeActionKind": { "valueSet": ["quickfix"] } },
+            },
+        },
+        "window": { "workDoneProgress": true },
+        "workspace": { "workspaceEdit": { "documentChanges": true, "resourceOperations": ["create"] } },
+    })
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::with_options(Value::Null).await
+    }
+
+    /// Starts and initializes a server with `initializationOptions`.
+    pub async fn with_options(options: Value) -> Self {
+        Self::initialize(client_capabilities(), options, Value::Null).await
+    }
+
+    /// Starts a server for a client declaring `capabilities`.
+    pub async fn with_capabilities(capabilities: Value) -> Self {
+        Self::initialize(capabilities, Value::Null, Value::Null).await
+    }
+
+    /// Starts a server with `root` as the workspace folder.
+    pub async fn in_workspace(root: &std::path::Path) -> Self {
+        Self::in_workspace_with_options(root, Value::Null).await
+    }
+
+    /// Starts a server with `root` as the workspace folder and
+    /// `initializationOptions`.
+    pub async fn in_workspace_with_options(root: &std::path::Path, options: Value) -> Self {
+        let root = Url::from_directory_path(root).unwrap();
+        Self::initialize(client_capabilities(), options, json!(root)).await
+    }
+
+    /// Starts a server whose `initialize` names `root` as the `rootUri`,
+    /// whatever it is.
+    pub async fn with_root(root: Value) -> Self {
+        Self::initialize(client_capabilities(), Value::Null, root).await
+    }
+
+    async fn initialize(capabilities: Value, options: Value, root: Value) -> Self {
+        let (service, socket) = server::service(Docs::builtin(), 2);
+
+        // Answer the server's own requests with `null`, accepting edits it
+        // asks to apply, and queue them along with its notifications; the
+        // server blocks if nobody reads the socket.
+        let (tx, notifications) = mpsc::unbounded_channel();
+        let (mut messages, mut responses) = socket.split();
+        tokio::spawn(async move {
+            while let Some(message) = messages.next().await {
+                match message.id().cloned() {
+                    Some(id) if message.method() == "workspace/applyEdit" => {
+                        let _ = responses.send(Response::from_ok(id, json!({ "applied": true }))).await;
+                        let _ = tx.send(message);
+                    }
+                    Some(id) => {
+                        let _ = responses.send(Response::from_ok(id, Value::Null)).await;
+                        let _ = tx.send(message);
+                    }
+                    None => {
+                        let _ = tx.send(message);
+                    }
+                }
+            }
+        });
+
+        let mut server = TestServer { service, notifications, next_id: 0, capabilities: Value::Null };
+        let params = json!({ "capabilities": capabilities, "initializationOptions": options, "rootUri": root });
+        server.capabilities = server.request("initialize", params).await["capabilities"].take();
+        server.notify("initialized", json!({})).await;
+        server
+    }
+
+    /// Sends a request and returns its result, panicking on an error reply.
+    pub async fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let request = Request::build(method.to_string()).id(self.next_id).params(params).finish();
+        let response = self.call(request).await.expect("requests get a response");
+        let (_, result) = response.into_parts();
+        result.unwrap_or_else(|err| panic!("{method} failed: {err}"))
+    }
+
+    /// The message of the error a request fails with.
+    pub async fn request_error(&mut self, method: &str, params: Value) -> String {
+        self.next_id += 1;
+        let request = Request::build(method.to_string()).id(self.next_id).params(params).finish();
+        let response = self.call(request).await.expect("requests get a response");
+        match response.into_parts().1 {
+            Ok(result) => panic!("{method} succeeded with {result}"),
+            Err(err) => err.message.into_owned(),
+        }
+    }
+
+    pub async fn notify(&mut self, method: &str, params: Value) {
+        let notification = Request::build(method.to_string()).params(params).finish();
+        assert!(self.call(notification).await.is_none());
+    }
+
+    async fn call(&mut self, message: Request) -> Option<Response> {
+        let service = self.service.ready().await.expect("service is ready");
+        service.call(message).await.expect("service does not fail")
+    }
+
+    pub async fn open(&mut self, uri: &str, text: &str) {
+        let item = json!({ "uri": uri, "languageId": "alloy", "version": 1, "text": text });
+        self.notify("textDocument/didOpen", json!({ "textDocument": item })).await;
+    }
+
+    pub async fn change(&mut self, uri: &str, version: i32, text: &str) {
+        let params = json!({
+            "textDocument": { "uri": uri, "version": version },
+            "contentChanges": [{ "text": text }],
+        });
+        self.notify("textDocument/didChange", params).await;
+    }
+
+    /// Sends a `textDocument/*` request for a position.
+    pub async fn at(&mut self, method: &str, uri: &str, line: u32, character: u32) -> Value {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        });
+        self.request(method, params).await
+    }
+
+    /// The markdown of the hover at a position, if there is one.
+    pub async fn hover(&mut self, uri: &str, line: u32, character: u32) -> Option<String> {
+        let hover: Option<Hover> = serde_json::from_value(self.at("textDocument/hover", uri, line, character).await).unwrap();
+        match hover?.contents {
+            HoverContents::Markup(markup) => Some(markup.value),
+            other => panic!("unexpected hover contents {other:?}"),
+        }
+    }
+
+    pub async fn completion_labels(&mut self, uri: &str, line: u32, character: u32) -> Vec<String> {
+        let response: Option<CompletionResponse> =
+            serde_json::from_value(self.at("textDocument/completion", uri, line, character).await).unwrap();
+        let items = match response {
+            Some(CompletionResponse::Array(items)) => items,
+            Some(CompletionResponse::List(list)) => list.items,
+            None => Vec::new(),
+        };
+        items.into_iter().map(|i| i.label).collect()
+    }
+
+    /// The diagnostics most recently published for `uri`, waiting briefly
+    /// if none have been published yet.
+    pub async fn diagnostics(&mut self, uri: &str) -> Vec<Diagnostic> {
+        let mut latest = None;
+        loop {
+            let next = match latest {
+                None => tokio::time::timeout(Duration::from_secs(2), self.notifications.recv()).await.ok().flatten(),
+                Some(_) => self.notifications.try_recv().ok(),
+            };
+            let Some(message) = next else { break };
+            if message.method() != "textDocument/publishDiagnostics" {
+                continue;
+            }
+            let params: PublishDiagnosticsParams = serde_json::from_value(message.params().cloned().unwrap()).unwrap();
+            if params.uri.as_str() == uri {
+                latest = Some(params.diagnostics);
+            }
+        }
+        latest.unwrap_or_else(|| panic!("no diagnostics published for {uri}"))
+    }
+
+    /// The files diagnostics were published for since the last check, in
+    /// order, waiting briefly for any still on their way.
+    pub async fn published(&mut self) -> Vec<String> {
+        let mut uris = Vec::new();
+        let wait = Duration::from_millis(200);
+        while let Ok(Some(message)) = tokio::time::timeout(wait, self.notifications.recv()).await {
+            if message.method() == "textDocument/publishDiagnostics" {
+                uris.push(message.params().unwrap()["uri"].as_str().unwrap().to_string());
+            }
+        }
+        uris
+    }
+
+    /// The methods of what the server sent since the last check, in order,
+    /// waiting briefly for any still on their way.
+    pub async fn sent(&mut self) -> Vec<String> {
+        let mut methods = Vec::new();
+        let wait = Duration::from_millis(200);
+        while let Ok(Some(message)) = tokio::time::timeout(wait, self.notifications.recv()).await {
+            methods.push(message.method().to_string());
+        }
+        methods
+    }
+
+    /// What the server sent since the last check, as method and params, in
+    /// order, waiting briefly for any still on their way.
+    pub async fn sent_messages(&mut self) -> Vec<(String, Value)> {
+        let mut messages = Vec::new();
+        let wait = Duration::from_millis(200);
+        while let Ok(Some(message)) = tokio::time::timeout(wait, self.notifications.recv()).await {
+            messages.push((message.method().to_string(), message.params().cloned().unwrap_or_default()));
+        }
+        messages
+    }
+
+    /// The next edit the server asked the client to apply, skipping any
+    /// notifications before it.
+    pub async fn applied_edit(&mut self) -> Option<WorkspaceEdit> {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(2), self.notifications.recv()).await.ok()??;
+            if message.method() == "workspace/applyEdit" {
+                let params: ApplyWorkspaceEditParams = serde_json::from_value(message.params().cloned()?).unwrap();
+                return Some(params.edit);
+            }
+        }
+    }
+}
+
+/// Answers the way a running Alloy instance would: with the JSON body set
+/// for a path, the error set for it, or a 404.
+pub struct FakeInstance {
+    pub url: String,
+    routes: Arc<Mutex<HashMap<String, (u16, String)>>>,
+}
+
+impl FakeInstance {
+    /// Serves `routes`, path to body, on a local port.
+    pub fn start(routes: Vec<(&str, Value)>) -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes = routes.into_iter().map(|(path, body)| (path.to_string(), (200, body.to_string()))).collect();
+        let routes = Arc::new(Mutex::new(routes));
+        let served: Arc<Mutex<HashMap<String, (u16, String)>>> = Arc::clone(&routes);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut head = String::new();
+                let mut reader = std::io::BufReader::new(&mut stream);
+                while std::io::BufRead::read_line(&mut reader, &mut head).unwrap_or(0) > 0 && !head.ends_with("\r\n\r\n") {}
+                // Read the body too, or closing the socket may reset it
+                // before the response gets there.
+                let length = head
+                    .lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:")?.trim().parse().ok())
+                    .unwrap_or(0);
+                let _ = std::io::Read::read_exact(&mut reader, &mut vec![0; length]);
+                let path = head.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let response = match served.lock().unwrap().get(&path) {
+                    Some((status, body)) => {
+                        let kind = if *status == 200 { "application/json" } else { "text/plain" };
+                        let headers = format!("Content-Type: {kind}\r\nContent-Length: {}", body.len());
+                        format!("HTTP/1.1 {status} Status\r\n{headers}\r\n\r\n{body}")
+                    }
+                    None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string(),
+                };
+                let _ = std::io::Write::write_all(&mut stream, response.as_bytes());
+            }
+        });
+        Self { url, routes }
+    }
+
+    pub fn set(&self, path: &str, body: Value) {
+        self.routes.lock().unwrap().insert(path.to_string(), (200, body.to_string()));
+    }
+
+    /// Answers `path` with `status` and a plain text `body`.
+    pub fn fail(&self, path: &str, status: u16, body: &str) {
+        self.routes.lock().unwrap().insert(path.to_string(), (status, body.to_string()));
+    }
+}
+
+/// The string codes of `diagnostics`.
+pub fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+    diagnostics
+        .iter()
+        .filter_map(|d| match &d.code {
+            Some(NumberOrString::String(code)) => Some(code.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `text` with single-file `edits` applied.
+pub fn apply(text: &str, edits: &[TextEdit]) -> String {
+    let doc = Document::new(text.to_string());
+    let mut edits: Vec<_> =
+        edits.iter().map(|e| (doc.offset(e.range.start), doc.offset(e.range.end), &e.new_text)).collect();
+    edits.sort_by_key(|&(start, ..)| std::cmp::Reverse(start));
+    let mut out = text.to_string();
+    for (start, end, new_text) in edits {
+        out.replace_range(start..end, new_text);
+    }
+    out
+}
@@ -0,0 +1,99 @@
+//! The docs tooling: `check-docs` on the shipped docset and a broken one,
+//! `docs-coverage` on a config tree, and translated docsets.
+
+use std::{fs, path::PathBuf};
+
+use alloy_hover_lsp::docs::Docs;
+use alloy_hover_lsp::docs_check::{self, Severity};
+use alloy_hover_lsp::docs_coverage;
+
+#[test]
+fn bundled_docs_pass_check_docs() {
+    let docs = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../docs/alloy-hover.toml");
+    let errors: Vec<String> = docs_check::check(&docs)
+        .into_iter()
+        .filter(|p| p.severity == Severity::Error)
+        .map(|p| p.to_string())
+        .collect();
+    assert!(errors.is_empty(), "{errors:#?}");
+}
+
+#[test]
+fn check_docs_reports_each_kind_of_problem() {
+    let dir = std::env::temp_dir().join(format!("alloy-hover-docs-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let text = r#"
+"prometheus.scrape" = """
+See [the docs](https://grafana.com/docs/alloy).
+**Key attrs:** `targets`, `scrape_intervall`.
+"""
+
+"discovery.relabel" = """
+**Exports:** `targets`.
+"""
+
+"loki.write.endpoints" = "Sends to [Loki]( no such link)."
+"forward_to" = 3
+"broken = "x"
+"#;
+    fs::write(dir.join("a.toml"), text).unwrap();
+    fs::write(dir.join("b.toml"), "\"prometheus.scrape\" = \"again\"\n").unwrap();
+
+    let problems: Vec<String> = docs_check::check(&dir).iter().map(|p| p.to_string()).collect();
+    fs::remove_dir_all(&dir).unwrap();
+    let expected = [
+        "a.toml:2: error: `prometheus.scrape` has no attribute or block `scrape_intervall`",
+        "a.toml:7: error: `discovery.relabel` does not export `targets`",
+        "a.toml:11: error: link target contains spaces",
+        "a.toml:11: error: `loki.write` has no attribute or block `endpoints`",
+        "a.toml:12: error: `forward_to` must be a markdown string, not integer",
+        "a.toml:13: error:",
+        "b.toml:1: error: duplicate key `prometheus.scrape`, first defined at",
+    ];
+    assert_eq!(problems.len(), expected.len(), "{problems:#?}");
+    for expected in expected {
+        let (file, rest) = expected.split_once(':').unwrap();
+        let found = problems.iter().any(|p| p.contains(&format!("{file}:")) && p.contains(rest));
+        assert!(found, "{expected} not in {problems:#?}");
+    }
+}
+
+#[test]
+fn coverage_lists_what_the_docset_is_missing() {
+    let dir = std::env::temp_dir().join(format!("alloy-hover-coverage-{}", std::process::id()));
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    let config = "prometheus.scrape \"a\" {\n  targets    = []\n  forward_to = []\n  job_name   = \"a\"\n}\n";
+    fs::write(dir.join("a.alloy"), config).unwrap();
+    fs::write(dir.join("nested/b.alloy"), config).unwrap();
+    fs::write(dir.join("notes.txt"), "prometheus.scrape \"ignored\" {}\n").unwrap();
+
+    let docs = Docs::builtin();
+    let coverage = docs_coverage::scan(&dir, &docs);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(coverage.files, 2);
+    assert_eq!(coverage.components.len(), 1);
+    assert_eq!(coverage.undocumented_components().count(), 0);
+    let missing: Vec<(&str, usize)> =
+        coverage.undocumented_attributes().map(|u| (u.name.as_str(), u.uses)).collect();
+    assert_eq!(missing, [("prometheus.scrape.job_name", 2)]);
+}
+
+#[test]
+fn translations_fall_back_to_english_per_key() {
+    let dir = std::env::temp_dir().join(format!("alloy-hover-locale-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let base = dir.join("alloy-hover.toml");
+    fs::write(&base, "\"prometheus.scrape\" = \"Scrapes targets.\"\n\"forward_to\" = \"Receivers.\"\n").unwrap();
+    fs::write(dir.join("alloy-hover.de.toml"), "\"prometheus.scrape\" = \"Ruft Ziele ab.\"\n").unwrap();
+
+    let mut english = Docs::load(&base).unwrap();
+    assert_eq!(english.localize("en_US.UTF-8"), None);
+    let mut german = Docs::load(&base).unwrap();
+    let used = german.localize("de_DE.UTF-8");
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(used, Some(dir.join("alloy-hover.de.toml")));
+    assert_eq!(german.get("prometheus.scrape").as_deref(), Some("Ruft Ziele ab."));
+    assert_eq!(german.get("forward_to").as_deref(), Some("Receivers."));
+    assert_eq!(english.get("prometheus.scrape").as_deref(), Some("Scrapes targets."));
+}
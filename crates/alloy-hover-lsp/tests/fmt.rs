@@ -0,0 +1,82 @@
+//! Formatting checks: every file in `tests/fmt` is run through `alloy fmt`,
+//! when the binary is on PATH or `ALLOY_BIN` names it. The output must be
+//! left alone by a second run and parse to the same blocks and attributes
+//! as the input, so the corpus stays something the server can hand to
+//! `alloy fmt` safely.
+//!
+//! The server has no formatter of its own and formats through `alloy fmt`,
+//! so there is nothing to compare it with here. Without the binary the test
+//! is skipped, unless `ALLOY_FMT_REQUIRED` is set.
+
+use std::{fs, path::PathBuf};
+
+use alloy_hover_lsp::{
+    alloy_cli,
+    config::AlloyCli,
+    document::Document,
+    syntax::{Block, Stmt},
+};
+
+fn corpus() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fmt");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "alloy"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Block names, labels and attribute names in order, nested blocks
+/// indented: what formatting must not change.
+fn outline(text: &str) -> Vec<String> {
+    fn walk(body: &[Stmt], depth: usize, out: &mut Vec<String>) {
+        for stmt in body {
+            match stmt {
+                Stmt::Attribute(a) => out.push(format!("{:depth$}{}", "", a.name.name)),
+                Stmt::Block(b) => {
+                    out.push(format!("{:depth$}{} {:?}", "", b.full_name(), label(b)));
+                    walk(&b.body, depth + 2, out);
+                }
+            }
+        }
+    }
+    fn label(block: &Block) -> Option<&str> {
+        block.label.as_ref().map(|l| l.value.as_str())
+    }
+    let doc = Document::new(text.to_string());
+    let file = doc.syntax();
+    assert!(file.errors.is_empty(), "{:?} in\n{text}", file.errors);
+    let mut out = Vec::new();
+    walk(&file.body, 0, &mut out);
+    out
+}
+
+#[test]
+fn corpus_parses() {
+    let files = corpus();
+    assert!(!files.is_empty());
+    for path in files {
+        outline(&fs::read_to_string(&path).unwrap());
+    }
+}
+
+#[tokio::test]
+async fn alloy_fmt_is_idempotent_and_keeps_structure() {
+    let settings = AlloyCli { path: std::env::var_os("ALLOY_BIN").map(PathBuf::from), ..AlloyCli::default() };
+    let Some(alloy) = alloy_cli::binary(&settings) else {
+        assert!(std::env::var_os("ALLOY_FMT_REQUIRED").is_none(), "`alloy` is not on PATH and ALLOY_BIN is unset");
+        eprintln!("skipping: `alloy` is not on PATH and ALLOY_BIN is unset");
+        return;
+    };
+    for path in corpus() {
+        let input = fs::read_to_string(&path).unwrap();
+        let formatted =
+            alloy_cli::format(&alloy, &input).await.unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+        let again = alloy_cli::format(&alloy, &formatted).await.unwrap();
+        assert_eq!(again, formatted, "formatting {} twice changes it", path.display());
+        assert_eq!(outline(&formatted), outline(&input), "formatting {} changes its structure", path.display());
+    }
+}
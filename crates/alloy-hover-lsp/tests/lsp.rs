@@ -0,0 +1,2540 @@
+mod common;
+
+use alloy_hover_lsp::modules::{GitImport, HttpImport};
+use serde_json::json;
+use tower_lsp::lsp_types::*;
+
+use common::{apply, client_capabilities, codes, uri, FakeInstance, TestServer};
+
+const PIPELINE: &str = r#"prometheus.scrape "default" {
+  targets    = []
+  forward_to = [prometheus.remote_write.cloud.receiver]
+}
+
+prometheus.remote_write "cloud" {
+  endpoint {
+    url          = "https://example.com/api/prom/push"
+    bearer_token = "hunter2"
+  }
+}
+"#;
+
+#[tokio::test]
+async fn hover_shows_attribute_schema_and_masks_secrets() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let hover = server.hover(&file, 8, 6).await.expect("hover on bearer_token");
+    assert!(hover.contains("`bearer_token`: `secret`"), "{hover}");
+    assert!(hover.contains("••••"), "{hover}");
+    assert!(!hover.contains("hunter2"), "{hover}");
+}
+
+#[tokio::test]
+async fn a_file_opened_on_its_own_gets_every_feature_without_a_workspace() {
+    let dir = std::env::temp_dir().join(format!("alloy-hover-lone-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let text = format!("{PIPELINE}\nlocal.file \"a\" {{\n  filename = \"ot\"\n}}\n");
+    let path = dir.join("pipeline.alloy");
+    std::fs::write(&path, &text).unwrap();
+    std::fs::write(dir.join("other.alloy"), "local.file \"a\" {\n  filename = \"a\"\n}\n").unwrap();
+    let file = Url::from_file_path(&path).unwrap();
+
+    // No root at all, and the file itself as the root.
+    for root in [json!(null), json!(file)] {
+        let mut server = TestServer::with_root(root.clone()).await;
+        server.open(file.as_str(), &text).await;
+
+        let hover = server.hover(file.as_str(), 8, 6).await.expect("hover on bearer_token");
+        assert!(hover.contains("`bearer_token`: `secret`"), "{root}: {hover}");
+        assert_eq!(codes(&server.diagnostics(file.as_str()).await), ["plaintext-secret", "unused-component"], "{root}");
+        let labels = server.completion_labels(file.as_str(), 2, 16).await;
+        assert_eq!(labels, ["prometheus.remote_write.cloud.receiver"], "{root}");
+        assert_eq!(server.completion_labels(file.as_str(), 13, 16).await, ["other.alloy"], "{root}");
+        let status = server.request("alloy/status", json!(null)).await;
+        assert_eq!(status["workspace"]["filesIndexed"], 1, "nothing next to the file is indexed: {status}");
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn features_keep_working_past_a_syntax_error() {
+    let mut server = TestServer::start().await;
+    let file = uri("broken.alloy");
+    // Mid-edit: an unclosed list, and the last block not closed yet either.
+    let broken = PIPELINE.replacen("targets    = []", "targets    = [", 1);
+    server.open(&file, broken.trim_end().trim_end_matches('}')).await;
+
+    let hover = server.hover(&file, 8, 6).await.expect("hover on bearer_token");
+    assert!(hover.contains("`bearer_token`: `secret`"), "{hover}");
+    assert!(codes(&server.diagnostics(&file).await).contains(&"plaintext-secret"));
+    let ranges: Vec<FoldingRange> =
+        serde_json::from_value(server.request("textDocument/foldingRange", json!({ "textDocument": { "uri": file } })).await)
+            .unwrap();
+    let lines: Vec<(u32, u32)> = ranges.iter().map(|r| (r.start_line, r.end_line)).collect();
+    assert!(lines.contains(&(0, 2)) && lines.contains(&(5, 9)), "{lines:?}");
+    assert!(server.completion_labels(&file, 7, 4).await.contains(&"tls_config".to_string()));
+}
+
+#[tokio::test]
+async fn minimal_clients_get_what_they_support() {
+    let mut server = TestServer::with_capabilities(json!({})).await;
+    assert!(server.capabilities.get("codeActionProvider").is_none());
+
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    let hover: Hover = serde_json::from_value(server.at("textDocument/hover", &file, 8, 6).await).unwrap();
+    let HoverContents::Markup(markup) = hover.contents else { panic!("{:?}", hover.contents) };
+    assert_eq!(markup.kind, MarkupKind::PlainText);
+    assert!(markup.value.starts_with("bearer_token: secret\n"), "{}", markup.value);
+    assert!(markup.value.contains("\n    bearer_token = \"••••\"\n"), "{}", markup.value);
+    assert!(!markup.value.contains('`'), "{}", markup.value);
+}
+
+#[tokio::test]
+async fn hover_on_label_lists_references() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let hover = server.hover(&file, 5, 27).await.expect("hover on label");
+    assert!(hover.contains("`prometheus.remote_write.cloud` is referenced 1 time"), "{hover}");
+}
+
+#[tokio::test]
+async fn repeated_hover_sees_references_from_other_files() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    let hover = server.hover(&file, 5, 27).await.expect("hover on label");
+    assert!(hover.contains("referenced 1 time"), "{hover}");
+
+    let other = "prometheus.relabel \"a\" {\n  forward_to = [prometheus.remote_write.cloud.receiver]\n}\n";
+    server.open(&uri("other.alloy"), other).await;
+    let hover = server.hover(&file, 5, 27).await.expect("hover on label");
+    assert!(hover.contains("referenced 2 times"), "{hover}");
+}
+
+#[tokio::test]
+async fn closed_files_over_the_memory_cap_keep_only_their_index() {
+    let mut server = TestServer::with_options(json!({ "memory": { "maxMb": 1 } })).await;
+    let (file, other) = (uri("pipeline.alloy"), uri("other.alloy"));
+    server.open(&file, PIPELINE).await;
+    let relabel = "prometheus.relabel \"a\" {\n  forward_to = [prometheus.remote_write.cloud.receiver]\n}\n";
+    server.open(&other, relabel).await;
+
+    server.notify("textDocument/didClose", json!({ "textDocument": { "uri": file } })).await;
+    assert!(server.hover(&file, 5, 27).await.is_some(), "closed files under the cap are kept");
+
+    server.notify("workspace/didChangeConfiguration", json!({ "settings": { "memory": { "maxMb": 0 } } })).await;
+    assert_eq!(server.hover(&file, 5, 27).await, None);
+    let location = server.at("textDocument/definition", &other, 1, 40).await;
+    let location: Location = serde_json::from_value(location).expect("definition from the index");
+    assert_eq!((location.uri.as_str(), location.range.start), (file.as_str(), Position::new(5, 0)));
+
+    server.open(&file, PIPELINE).await;
+    assert!(server.hover(&file, 5, 27).await.is_some(), "reopened files are read again");
+}
+
+#[tokio::test]
+async fn only_open_files_are_linted_again_and_kept_documents_follow_renames() {
+    let mut server = TestServer::start().await;
+    let (file, other) = (uri("pipeline.alloy"), uri("other.alloy"));
+    server.open(&file, PIPELINE).await;
+    server.open(&other, "prometheus.relabel \"a\" {\n  forward_to = []\n}\n").await;
+    server.notify("textDocument/didClose", json!({ "textDocument": { "uri": file } })).await;
+    server.published().await;
+
+    server.notify("workspace/didChangeConfiguration", json!({ "settings": { "alloy": { "targetVersion": "v1.5.0" } } })).await;
+    assert_eq!(server.published().await, [other.as_str()]);
+
+    let moved = uri("moved.alloy");
+    server.notify("workspace/didRenameFiles", json!({ "files": [{ "oldUri": file, "newUri": moved }] })).await;
+    assert_eq!(server.published().await, [other.as_str()]);
+    assert_eq!(server.hover(&file, 5, 27).await, None);
+    assert!(server.hover(&moved, 5, 27).await.is_some(), "the closed file's document is kept under its new URI");
+}
+
+#[tokio::test]
+async fn the_workspace_index_is_cached_by_file_content() {
+    let root = std::env::temp_dir().join(format!("alloy-hover-index-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let sink = root.join("sink.alloy");
+    let text = "prometheus.remote_write \"cloud\" {\n  endpoint {\n    url = \"https://example.com/push\"\n  }\n}\n";
+    std::fs::write(&sink, text).unwrap();
+    // Hidden, so not scanned itself.
+    let options = json!({ "modules": { "cacheDir": root.join(".cache") } });
+    TestServer::in_workspace_with_options(&root, options.clone()).await;
+
+    // What the next run knows about an unchanged file is what the cache says.
+    let index = std::fs::read_dir(root.join(".cache/index")).unwrap().next().unwrap().unwrap().path();
+    let cached = std::fs::read_to_string(&index).unwrap();
+    assert!(cached.contains("\"label\":\"cloud\""), "{cached}");
+    std::fs::write(&index, cached.replace("\"label\":\"cloud\"", "\"label\":\"cached\"")).unwrap();
+    let scrape = |label| format!("prometheus.scrape \"a\" {{\n  forward_to = [prometheus.remote_write.{label}.receiver]\n}}\n");
+    let (file, sink_uri) = (uri("scrape.alloy"), json!(Url::from_file_path(&sink).unwrap()));
+    let mut server = TestServer::in_workspace_with_options(&root, options.clone()).await;
+    server.open(&file, &scrape("cached")).await;
+    let location = server.at("textDocument/definition", &file, 1, 40).await;
+    assert_eq!(location["uri"], sink_uri);
+
+    // A changed file is parsed again.
+    std::fs::write(&sink, "prometheus.remote_write \"cloud\" {\n}\n").unwrap();
+    let mut server = TestServer::in_workspace_with_options(&root, options.clone()).await;
+    server.open(&file, &scrape("cached")).await;
+    assert_eq!(server.at("textDocument/definition", &file, 1, 40).await, json!(null));
+    server.open(&file, &scrape("cloud")).await;
+    assert_eq!(server.at("textDocument/definition", &file, 1, 40).await["uri"], sink_uri);
+
+    // So is every file when the cache is in another format.
+    let cached = std::fs::read_to_string(&index).unwrap();
+    assert!(cached.contains("\"format\":1"), "{cached}");
+    let stale = cached.replace("\"format\":1", "\"format\":0").replace("\"label\":\"cloud\"", "\"label\":\"cached\"");
+    std::fs::write(&index, stale).unwrap();
+    let mut server = TestServer::in_workspace_with_options(&root, options).await;
+    server.open(&file, &scrape("cloud")).await;
+    assert_eq!(server.at("textDocument/definition", &file, 1, 40).await["uri"], sink_uri);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn hover_on_forward_to_entry_describes_the_target() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let hover = server.hover(&file, 2, 30).await.expect("hover on forward_to entry");
+    assert!(
+        hover.starts_with("`prometheus.remote_write` component `\"cloud\"`, defined at [/test/pipeline.alloy:6]"),
+        "{hover}"
+    );
+    assert!(hover.contains("`receiver`: `MetricsReceiver`"), "{hover}");
+}
+
+#[tokio::test]
+async fn hover_explains_relabel_action() {
+    let mut server = TestServer::start().await;
+    let file = uri("relabel.alloy");
+    let text = "discovery.relabel \"pods\" {\n  targets = []\n  rule {\n    action = \"labelmap\"\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let hover = server.hover(&file, 3, 15).await.expect("hover on action value");
+    assert!(hover.starts_with("`labelmap` relabel action"), "{hover}");
+    assert!(hover.contains("Uses: `regex`, `replacement`"), "{hover}");
+}
+
+#[tokio::test]
+async fn hover_on_a_duration_normalizes_it_and_compares_it_to_the_default() {
+    let mut server = TestServer::start().await;
+    let file = uri("durations.alloy");
+    let text = "prometheus.scrape \"a\" {\n  scrape_interval = \"180s\"\n  scrape_timeout  = \"1500ms\"\n}\n\n\
+                prometheus.remote_write \"b\" {\n  endpoint {\n    remote_timeout = \"30s\"\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let interval = server.hover(&file, 1, 22).await.expect("hover on scrape_interval's value");
+    assert_eq!(interval, "`3m`\n\n3× the default `scrape_interval` (`1m`)");
+    let timeout = server.hover(&file, 2, 22).await.expect("hover on scrape_timeout's value");
+    assert_eq!(timeout, "`1.5s`\n\n0.15× the default `scrape_timeout` (`10s`)");
+    let remote = server.hover(&file, 7, 22).await.expect("hover on remote_timeout's value");
+    assert_eq!(remote, "`30s`\n\nthe default `remote_timeout` (`30s`)");
+}
+
+#[tokio::test]
+async fn hover_style_trades_detail_for_brevity() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let full = server.hover(&file, 0, 5).await.expect("hover on the component name");
+    assert!(full.contains("```alloy\nprometheus.scrape \"default\" {\n  forward_to = []\n  targets    = []\n}\n```"), "{full}");
+    assert!(full.contains("| `scrape_interval` | `duration` | default `\"60s\"` |"), "{full}");
+    assert!(full.contains("(https://grafana.com/docs/alloy/latest/reference/components/prometheus/prometheus.scrape/)"), "{full}");
+
+    server.notify("workspace/didChangeConfiguration", json!({ "settings": { "hover": { "style": "concise" } } })).await;
+    let concise = server.hover(&file, 0, 5).await.expect("hover on the component name");
+    assert_eq!(
+        concise,
+        "`prometheus.scrape \"LABEL\" { forward_to, targets }`\n\n\
+         Scrapes metrics from `targets` and forwards them to `forward_to` receivers."
+    );
+    let attribute = server.hover(&file, 8, 6).await.expect("hover on bearer_token");
+    assert!(!attribute.contains("hunter2") && !attribute.contains("```"), "{attribute}");
+}
+
+#[tokio::test]
+async fn completes_component_exports() {
+    let mut server = TestServer::start().await;
+    let file = uri("exports.alloy");
+    let text = "prometheus.remote_write \"cloud\" {\n}\n\nprometheus.scrape \"a\" {\n  forward_to = [prometheus.remote_write.cloud.]\n}\n";
+    server.open(&file, text).await;
+
+    let labels = server.completion_labels(&file, 4, 46).await;
+    assert!(labels.contains(&"receiver".to_string()), "{labels:?}");
+}
+
+#[tokio::test]
+async fn completes_the_standard_library_in_expressions() {
+    let mut server = TestServer::start().await;
+    let file = uri("stdlib.alloy");
+    let text = "local.file \"a\" {\n  filename = co\n}\n\nlocal.file \"b\" {\n  filename = constants.\n}\n\nlocal.file \"c\" {\n  filename = string.trim_\n}\n";
+    server.open(&file, text).await;
+
+    let items = |response: serde_json::Value| -> Vec<(String, Option<CompletionItemKind>)> {
+        let Some(CompletionResponse::Array(items)) = serde_json::from_value(response).unwrap() else { panic!() };
+        items.into_iter().map(|i| (i.label, i.kind)).filter(|(l, _)| l.starts_with("co") || l.contains('_')).collect()
+    };
+    let top = items(server.at("textDocument/completion", &file, 1, 15).await);
+    assert_eq!(
+        top,
+        [
+            ("constants".to_string(), Some(CompletionItemKind::MODULE)),
+            ("convert".to_string(), Some(CompletionItemKind::MODULE)),
+            ("coalesce".to_string(), Some(CompletionItemKind::FUNCTION)),
+            ("json_path".to_string(), Some(CompletionItemKind::FUNCTION)),
+            ("module_path".to_string(), Some(CompletionItemKind::CONSTANT)),
+        ]
+    );
+    assert_eq!(server.completion_labels(&file, 5, 23).await, ["arch", "hostname", "os"]);
+
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 9, 25).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    let trim = items.iter().find(|i| i.label == "trim_prefix").expect("string.trim_prefix");
+    assert_eq!(trim.detail.as_deref(), Some("string.trim_prefix(string, prefix)"));
+    let Some(CompletionTextEdit::Edit(edit)) = &trim.text_edit else { panic!() };
+    assert_eq!(edit.new_text, "trim_prefix($1)");
+    assert_eq!(edit.range, Range::new(Position::new(9, 20), Position::new(9, 25)));
+}
+
+#[tokio::test]
+async fn env_lookups_complete_the_variable_names_used_in_the_workspace_first() {
+    let mut server = TestServer::start().await;
+    server.open(&uri("other.alloy"), "local.file \"token\" {\n  filename = env(\"GRAFANA_TOKEN_FILE\")\n}\n").await;
+    let file = uri("env.alloy");
+    let text = "prometheus.remote_write \"a\" {\n  endpoint {\n    url = env(\"GRA\")\n    name = sys.env(\"\")\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 2, 18).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    let used = items.iter().find(|i| i.label == "GRAFANA_TOKEN_FILE").expect("names used elsewhere");
+    assert_eq!((used.kind, used.detail.as_deref()), (Some(CompletionItemKind::VARIABLE), Some("used in workspace")));
+    let Some(CompletionTextEdit::Edit(edit)) = &used.text_edit else { panic!() };
+    assert_eq!(edit.range, Range::new(Position::new(2, 15), Position::new(2, 18)));
+    let path = items.iter().find(|i| i.label == "PATH").expect("the server's own environment");
+    assert_eq!(path.detail.as_deref(), Some("environment variable"));
+    assert!(used.sort_text < path.sort_text);
+
+    let labels = server.completion_labels(&file, 3, 20).await;
+    assert!(labels.contains(&"GRAFANA_TOKEN_FILE".to_string()), "{labels:?}");
+}
+
+#[tokio::test]
+async fn file_arguments_complete_paths_relative_to_the_config() {
+    let root = std::env::temp_dir().join(format!("alloy-hover-paths-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("logs/archive")).unwrap();
+    std::fs::write(root.join("logs/app.log"), "").unwrap();
+    let text = r#"local.file "a" {
+  filename = "logs/"
+}
+
+local.file_match "logs" {
+  path_targets = [{
+    "__path__" = "logs/a",
+    "",
+  }]
+}
+"#;
+    let inside = format!("{}/logs/", root.display());
+    let text = format!("{text}\nlocal.file \"b\" {{\n  filename = \"{inside}\"\n}}\n\nlocal.file \"c\" {{\n  filename = \"/\"\n}}\n");
+    let text = text.as_str();
+    let path = root.join("config.alloy");
+    std::fs::write(&path, text).unwrap();
+    let mut server = TestServer::in_workspace(&root).await;
+    let file = Url::from_file_path(&path).unwrap().to_string();
+    server.open(&file, text).await;
+
+    let mut labels = server.completion_labels(&file, 1, 19).await;
+    labels.sort();
+    assert_eq!(labels, ["app.log", "archive/"]);
+    let mut labels = server.completion_labels(&file, 6, 24).await;
+    labels.sort();
+    assert_eq!(labels, ["app.log", "archive/"]);
+    // Keys of a path target are labels, not paths.
+    assert_eq!(server.completion_labels(&file, 6, 5).await, Vec::<String>::new());
+    assert_eq!(server.completion_labels(&file, 7, 5).await, Vec::<String>::new());
+    // Absolute paths may not lead outside the workspace either.
+    let mut labels = server.completion_labels(&file, 12, 14 + inside.len() as u32).await;
+    labels.sort();
+    assert_eq!(labels, ["app.log", "archive/"]);
+    assert_eq!(server.completion_labels(&file, 16, 15).await, Vec::<String>::new());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn targets_lists_complete_a_target_map_and_its_well_known_keys() {
+    let mut server = TestServer::start().await;
+    let file = uri("targets.alloy");
+    let text = "prometheus.scrape \"a\" {\n  targets = [\n    \n    { \"__address__\" = \"localhost:9090\", \"\" },\n    { __ },\n  ]\n  forward_to = []\n}\n";
+    server.open(&file, text).await;
+
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 2, 4).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    assert_eq!(items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>(), ["{ \"__address__\" = … }"]);
+    let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else { panic!() };
+    assert_eq!(edit.new_text, "{ \"__address__\" = \"${1:host:port}\"$0 }");
+
+    let keys = server.completion_labels(&file, 3, 40).await;
+    let expected = ["__metrics_path__", "__scheme__", "__scrape_interval__", "__scrape_timeout__", "__param_<name>"];
+    assert_eq!(keys, [&expected[..], &["job", "instance"]].concat());
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 4, 8).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    let param = items.iter().find(|i| i.label == "__param_<name>").expect("templated key");
+    let Some(CompletionTextEdit::Edit(edit)) = &param.text_edit else { panic!() };
+    assert_eq!(edit.new_text, "\"__param_\" = ");
+    assert_eq!(edit.range, Range::new(Position::new(4, 6), Position::new(4, 8)));
+}
+
+#[tokio::test]
+async fn nested_otelcol_blocks_are_checked_completed_and_described_at_any_depth() {
+    let mut server = TestServer::start().await;
+    let file = uri("otel.alloy");
+    let text = "otelcol.receiver.otlp \"in\" {\n  grpc {\n    keepalive {\n      server_parameters {\n        time = \"30x\"\n        \n      }\n      \n    }\n  }\n  output {\n    traces = []\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["invalid-duration"]);
+    assert_eq!(diagnostics[0].range.start, Position::new(4, 15));
+
+    let labels = server.completion_labels(&file, 5, 8).await;
+    assert_eq!(labels, ["max_connection_age", "max_connection_age_grace", "max_connection_idle", "timeout"]);
+
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 7, 6).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    let labels: Vec<&str> = items.iter().map(|i| i.label.as_str()).collect();
+    assert_eq!(labels, ["enforcement_policy", "server_parameters"]);
+    let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else { panic!() };
+    assert_eq!(edit.new_text, "enforcement_policy {\n        $0\n      }");
+
+    let hover = server.hover(&file, 3, 10).await.expect("hover on server_parameters");
+    assert!(hover.starts_with("`grpc.keepalive.server_parameters` block in `otelcol.receiver.otlp`"), "{hover}");
+    assert!(hover.contains("| `max_connection_idle` | `duration` |"), "{hover}");
+}
+
+#[tokio::test]
+async fn completes_forward_to_with_receivers_of_the_right_kind() {
+    let mut server = TestServer::start().await;
+    let sinks = "prometheus.remote_write \"cloud\" {\n}\n\nloki.write \"default\" {\n}\n";
+    server.open(&uri("sinks.alloy"), sinks).await;
+    let file = uri("wiring.alloy");
+    let text = "prometheus.relabel \"drop\" {\n}\n\nprometheus.scrape \"a\" {\n  forward_to = [\n    pro\n  ]\n}\n";
+    server.open(&file, text).await;
+
+    let labels = server.completion_labels(&file, 5, 7).await;
+    assert_eq!(labels, ["prometheus.relabel.drop.receiver", "prometheus.remote_write.cloud.receiver"]);
+}
+
+#[tokio::test]
+async fn completes_meta_labels_of_the_feeding_discovery() {
+    let mut server = TestServer::start().await;
+    let file = uri("meta.alloy");
+    let text = r#"discovery.kubernetes "pods" {
+  role = "pod"
+}
+
+discovery.relabel "pods" {
+  targets = discovery.kubernetes.pods.targets
+  rule {
+    source_labels = ["__meta_kubernetes_pod_"]
+  }
+}
+"#;
+    server.open(&file, text).await;
+
+    let labels = server.completion_labels(&file, 7, 44).await;
+    assert!(labels.contains(&"__meta_kubernetes_pod_name".to_string()), "{labels:?}");
+    assert!(!labels.iter().any(|l| l.starts_with("__meta_ec2")), "{labels:?}");
+}
+
+#[tokio::test]
+async fn relabel_rules_complete_the_metric_and_label_names_of_a_configured_api() {
+    let success = |data: serde_json::Value| json!({ "status": "success", "data": data });
+    let api = FakeInstance::start(vec![
+        ("/api/v1/label/__name__/values", success(json!(["http_requests_total", "up"]))),
+        ("/api/v1/labels", success(json!(["job", "team"]))),
+        ("/api/v1/metadata", success(json!({ "up": [{ "help": "Whether the target is up." }] }))),
+    ]);
+    let mut server = TestServer::with_options(json!({ "metricsApi": { "url": api.url } })).await;
+    let file = uri("relabel.alloy");
+    let text = r#"prometheus.relabel "a" {
+  forward_to = []
+  rule {
+    source_labels = [""]
+    regex         = ""
+  }
+}
+"#;
+    server.open(&file, text).await;
+
+    // Fetched in the background after startup.
+    let mut regex = Vec::new();
+    for _ in 0..100 {
+        regex = server.completion_labels(&file, 4, 21).await;
+        if regex.contains(&"up".to_string()) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(regex.contains(&"http_requests_total".to_string()) && regex.contains(&"team".to_string()), "{regex:?}");
+    let labels = server.completion_labels(&file, 3, 22).await;
+    assert!(labels.contains(&"team".to_string()), "{labels:?}");
+    assert!(!labels.contains(&"up".to_string()), "metric names only complete a regex: {labels:?}");
+
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 4, 21).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    let up = items.into_iter().find(|i| i.label == "up").unwrap();
+    assert_eq!(up.documentation, Some(Documentation::String("Whether the target is up.".into())));
+}
+
+#[tokio::test]
+async fn well_known_labels_are_described_and_completed_where_they_mean_something() {
+    let mut server = TestServer::start().await;
+    let file = uri("labels.alloy");
+    let text = r#"discovery.relabel "probe" {
+  targets = [{ "__address__" = "example.com", "__param_module" = "http_2xx" }]
+  rule {
+    source_labels = ["__address__", ""]
+    target_label  = "__param_target"
+  }
+}
+
+prometheus.relabel "drop" {
+  forward_to = []
+  rule {
+    source_labels = [""]
+  }
+}
+"#;
+    server.open(&file, text).await;
+
+    let address = server.hover(&file, 1, 18).await.expect("hover on a target map key");
+    assert!(address.starts_with("`__address__` label\n\nThe `host:port` to scrape."), "{address}");
+    let param = server.hover(&file, 1, 50).await.expect("hover on a templated key");
+    assert!(param.starts_with("`__param_<name>` label"), "{param}");
+    let source = server.hover(&file, 3, 24).await.expect("hover on a source label");
+    assert!(source.starts_with("`__address__` label"), "{source}");
+    let target = server.hover(&file, 4, 24).await.expect("hover on the target label");
+    assert!(target.starts_with("`__param_<name>` label"), "{target}");
+
+    let targets = server.completion_labels(&file, 3, 37).await;
+    assert!(targets.contains(&"__scheme__".to_string()) && targets.contains(&"job".to_string()), "{targets:?}");
+    assert!(!targets.contains(&"__name__".to_string()), "{targets:?}");
+    let series = server.completion_labels(&file, 11, 22).await;
+    assert_eq!(series, ["job", "instance", "__name__"]);
+}
+
+#[tokio::test]
+async fn plaintext_secret_is_flagged_with_a_fix() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["plaintext-secret"]);
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    assert_eq!(edits[0].new_text, "env(\"CLOUD_BEARER_TOKEN\")");
+}
+
+#[tokio::test]
+async fn repeated_object_keys_are_flagged_with_a_fix_dropping_the_earlier_one() {
+    let mut server = TestServer::start().await;
+    let file = uri("keys.alloy");
+    let text = "discovery.relabel \"a\" {\n  targets = [{ \"__address__\" = \"a:80\", \"job\" = \"x\", \"__address__\" = \"b:80\" }]\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["duplicate-key", "unused-component"]);
+    assert_eq!(diagnostics[0].range, Range::new(Position::new(1, 52), Position::new(1, 65)));
+    let related = diagnostics[0].related_information.as_ref().unwrap();
+    assert_eq!(related[0].location.range, Range::new(Position::new(1, 15), Position::new(1, 28)));
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    assert_eq!(actions[0].title, "Remove the earlier `__address__`");
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    assert_eq!(edits[0].range, Range::new(Position::new(1, 15), Position::new(1, 39)));
+    assert_eq!(edits[0].new_text, "");
+}
+
+#[tokio::test]
+async fn style_lints_point_at_formatting_slips_and_fix_each_one() {
+    let mut server = TestServer::start().await;
+    let file = uri("style.alloy");
+    let text = "local.file \"a\" {\n  filename  = \"/etc/a\"\n  is_secret = false\n}\n\n\n\n\
+                local.file \"b\" {\n  filename=\"/etc/b\"\n}\n\n\
+                discovery.relabel \"c\" {\n  targets = [\n    { \"__address__\" = local.file.a.content },\n    \
+                { \"__address__\" = local.file.b.content }\n  ]\n}";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    let hints: Vec<&Diagnostic> =
+        diagnostics.iter().filter(|d| d.severity == Some(DiagnosticSeverity::HINT)).collect();
+    let codes: Vec<&str> = hints
+        .iter()
+        .map(|d| match &d.code {
+            Some(NumberOrString::String(code)) => code.as_str(),
+            _ => "",
+        })
+        .collect();
+    assert_eq!(codes, ["missing-final-newline", "assignment-spacing", "blank-lines", "missing-trailing-comma"]);
+    assert_eq!(hints[2].range, Range::new(Position::new(5, 0), Position::new(7, 0)));
+
+    let mut edits = Vec::new();
+    for diagnostic in hints {
+        let params = json!({
+            "textDocument": { "uri": file },
+            "range": diagnostic.range,
+            "context": { "diagnostics": [] },
+        });
+        let response = server.request("textDocument/codeAction", params).await;
+        let actions: Vec<CodeAction> = serde_json::from_value(response).unwrap();
+        let fix = actions.iter().find(|a| a.is_preferred == Some(true)).expect("a fix");
+        edits.extend(fix.edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()].clone());
+    }
+    let fixed = apply(text, &edits);
+    assert_eq!(
+        fixed,
+        "local.file \"a\" {\n  filename  = \"/etc/a\"\n  is_secret = false\n}\n\n\
+         local.file \"b\" {\n  filename = \"/etc/b\"\n}\n\n\
+         discovery.relabel \"c\" {\n  targets = [\n    { \"__address__\" = local.file.a.content },\n    \
+         { \"__address__\" = local.file.b.content },\n  ]\n}\n"
+    );
+}
+
+#[tokio::test]
+async fn misspelled_components_suggest_the_closest_name() {
+    let mut server = TestServer::start().await;
+    let file = uri("typo.alloy");
+    // `loki.source.syslog` is real but not in the schema: not a typo.
+    let text = "promethues.scrape \"a\" {\n  targets    = []\n  forward_to = []\n}\n\nloki.source.syslog \"b\" {\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["unknown-component"]);
+    assert_eq!(diagnostics[0].message, "unknown component `promethues.scrape`; did you mean `prometheus.scrape`?");
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    assert_eq!(edits[0].range, Range::new(Position::new(0, 0), Position::new(0, 17)));
+    assert_eq!(edits[0].new_text, "prometheus.scrape");
+}
+
+#[tokio::test]
+async fn blocks_their_component_does_not_define_are_errors() {
+    let mut server = TestServer::start().await;
+    let file = uri("blocks.alloy");
+    let text = "prometheus.remote_write \"cloud\" {\n  basic_auth {\n    username = \"u\"\n  }\n  endpoint {\n    url = \"https://example.com\"\n    tls_confg {\n      insecure_skip_verify = true\n    }\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    let code = Some(NumberOrString::String("unknown-block".into()));
+    let blocks: Vec<_> = diagnostics.iter().filter(|d| d.code == code).collect();
+    let messages: Vec<&str> = blocks.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "`basic_auth` is not a block of `prometheus.remote_write`",
+            "`tls_confg` is not a block of `endpoint` in `prometheus.remote_write`; did you mean `tls_config`?",
+        ]
+    );
+    assert_eq!(blocks[0].range, Range::new(Position::new(1, 2), Position::new(1, 12)));
+}
+
+#[tokio::test]
+async fn edits_relint_only_the_files_that_depend_on_them() {
+    let mut server = TestServer::with_options(json!({ "diagnostics": { "debounceMs": 0 } })).await;
+    let (scrape, sink, other) = (uri("scrape.alloy"), uri("sink.alloy"), uri("other.alloy"));
+    let forwarding = "prometheus.scrape \"a\" {\n  targets    = []\n  forward_to = [prometheus.remote_write.cloud.receiver]\n}\n";
+    server.open(&scrape, forwarding).await;
+    server.open(&other, "local.file \"token\" {\n  filename = \"/etc/token\"\n}\n").await;
+    assert_eq!(server.published().await, [scrape.as_str(), other.as_str()]);
+
+    let defining =
+        |label| format!("prometheus.remote_write \"{label}\" {{\n  endpoint {{\n    url = \"https://example.com\"\n  }}\n}}\n");
+    server.open(&sink, &defining("cloud")).await;
+    assert_eq!(server.published().await, [sink.as_str(), scrape.as_str()]);
+
+    server.change(&sink, 2, &format!("// Grafana Cloud\n{}", defining("cloud"))).await;
+    assert_eq!(server.published().await, [sink.as_str()]);
+
+    server.change(&sink, 3, &defining("mimir")).await;
+    let diagnostics = server.diagnostics(&scrape).await;
+    assert_eq!(codes(&diagnostics), ["unknown-reference"]);
+}
+
+#[tokio::test]
+async fn forwarding_to_the_wrong_kind_of_receiver_is_an_error() {
+    let mut server = TestServer::start().await;
+    let file = uri("types.alloy");
+    let text = "loki.write \"logs\" {\n}\n\nprometheus.remote_write \"metrics\" {\n  endpoint {\n    url = \"https://example.com/push\"\n  }\n}\n\n\
+                prometheus.scrape \"a\" {\n  targets    = []\n  \
+                forward_to = [prometheus.remote_write.metrics.receiver, loki.write.logs.receiver]\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["incompatible-type"]);
+    assert_eq!(
+        diagnostics[0].message,
+        "`loki.write.logs.receiver` is a `LogsReceiver`, but `forward_to` takes `MetricsReceiver`"
+    );
+    assert_eq!(diagnostics[0].range.start, Position::new(11, 58));
+}
+
+#[tokio::test]
+async fn diagnostics_follow_edits() {
+    let mut server = TestServer::start().await;
+    let file = uri("edit.alloy");
+    let broken = "prometheus.scrape \"a\" {\n  targets    = []\n  forward_to = [prometheus.remote_write.missing.receiver]\n  scrape_interval = \"15x\"\n}\n";
+    server.open(&file, broken).await;
+    assert_eq!(codes(&server.diagnostics(&file).await), ["unknown-reference", "invalid-duration"]);
+
+    let fixed = "prometheus.scrape \"a\" {\n  targets    = []\n  forward_to = [prometheus.remote_write.b.receiver]\n  scrape_interval = \"15s\"\n}\nprometheus.remote_write \"b\" {\n  endpoint {\n    url = \"https://example.com/push\"\n  }\n}\n";
+    server.change(&file, 2, fixed).await;
+    assert!(server.diagnostics(&file).await.is_empty());
+
+    // A burst of edits is linted once, against the last text.
+    server.change(&file, 3, broken).await;
+    server.change(&file, 4, fixed).await;
+    assert!(server.diagnostics(&file).await.is_empty());
+}
+
+#[tokio::test]
+async fn edits_are_linted_after_a_pause_but_opening_and_saving_lint_right_away() {
+    let mut server = TestServer::with_options(json!({ "diagnostics": { "debounceMs": 60000 } })).await;
+    let file = uri("edit.alloy");
+    let broken = "prometheus.scrape \"a\" {\n  targets    = []\n  forward_to = []\n  scrape_interval = \"15x\"\n}\n";
+    server.open(&file, broken).await;
+    assert_eq!(codes(&server.diagnostics(&file).await), ["invalid-duration", "unwired-producer"]);
+
+    server.change(&file, 2, &broken.replace("15x", "15s")).await;
+    assert_eq!(server.published().await, Vec::<String>::new(), "still waiting for the pause");
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    assert_eq!(codes(&server.diagnostics(&file).await), ["unwired-producer"]);
+}
+
+#[tokio::test]
+async fn relabel_regex_errors_are_reported() {
+    let mut server = TestServer::start().await;
+    let file = uri("regex.alloy");
+    let text = "prometheus.scrape \"s\" {\n  targets    = []\n  forward_to = [prometheus.relabel.a.receiver]\n}\nprometheus.relabel \"a\" {\n  forward_to = [prometheus.remote_write.b.receiver]\n  rule {\n    regex = \"(foo\"\n  }\n  rule {\n    regex = \"api.example.com\"\n  }\n}\nprometheus.remote_write \"b\" {\n  endpoint {\n    url = \"https://example.com/push\"\n  }\n}\n";
+    server.open(&file, text).await;
+
+    assert_eq!(
+        codes(&server.diagnostics(&file).await),
+        ["invalid-regex", "unescaped-dot", "unescaped-dot"]
+    );
+}
+
+#[tokio::test]
+async fn relabel_regexes_are_checked_after_their_escapes_are_decoded() {
+    let mut server = TestServer::start().await;
+    let file = uri("regex-escapes.alloy");
+    let text = "prometheus.scrape \"s\" {\n  targets    = []\n  forward_to = [prometheus.relabel.a.receiver]\n}\nprometheus.relabel \"a\" {\n  forward_to = [prometheus.remote_write.b.receiver]\n  rule {\n    regex = \"\\u00e9\\x28\"\n  }\n  rule {\n    regex = \"api\\x5c.example\\134.com\"\n  }\n}\nprometheus.remote_write \"b\" {\n  endpoint {\n    url = \"https://example.com/push\"\n  }\n}\n";
+    server.open(&file, text).await;
+
+    // `\x28` opens a group that is never closed; `\x5c` and `\134` are backslashes that escape the dots.
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["invalid-regex"]);
+    assert_eq!(diagnostics[0].range.start, Position::new(7, 19));
+}
+
+#[tokio::test]
+async fn malformed_durations_and_byte_sizes_are_errors() {
+    let mut server = TestServer::start().await;
+    let file = uri("units.alloy");
+    let text = r#"loki.write "default" {
+  endpoint {
+    url            = "https://example.com/loki/api/v1/push"
+    batch_wait     = "1d"
+    batch_size     = "1MiBs"
+    remote_timeout = "1m30s"
+  }
+}
+"#;
+    server.open(&file, text).await;
+    let diagnostics: Vec<Diagnostic> = server
+        .diagnostics(&file)
+        .await
+        .into_iter()
+        .filter(|d| matches!(&d.code, Some(NumberOrString::String(code)) if code.starts_with("invalid-")))
+        .collect();
+    assert_eq!(codes(&diagnostics), ["invalid-duration", "invalid-byte-size"]);
+    assert_eq!(diagnostics[0].range, Range::new(Position::new(3, 21), Position::new(3, 25)));
+    assert_eq!(
+        diagnostics[0].message,
+        "invalid `batch_wait` value \"1d\": unknown duration unit `d`; use ns, us, ms, s, m or h (e.g. `24h` for a day)"
+    );
+    assert_eq!(diagnostics[1].range, Range::new(Position::new(4, 21), Position::new(4, 28)));
+    assert!(diagnostics[1].message.starts_with("invalid `batch_size` value \"1MiBs\": "), "{}", diagnostics[1].message);
+    assert!(diagnostics.iter().all(|d| d.severity == Some(DiagnosticSeverity::ERROR)));
+}
+
+#[tokio::test]
+async fn missing_and_repeated_blocks_are_reported_on_their_parent() {
+    let mut server = TestServer::start().await;
+    let file = uri("arity.alloy");
+    let text = "otelcol.receiver.otlp \"in\" {\n  grpc { }\n}\n\nprometheus.scrape \"s\" {\n  targets    = []\n  forward_to = []\n  tls_config { }\n  tls_config { }\n}\n\nprometheus.remote_write \"out\" { }\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    let arity: Vec<_> = diagnostics.iter().filter(|d| d.message.contains(" block")).collect();
+    let messages: Vec<&str> = arity.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "`otelcol.receiver.otlp` is missing its required `output` block",
+            "`prometheus.scrape` has 2 `tls_config` blocks; only one is allowed",
+            "`prometheus.remote_write` is missing its required `endpoint` block",
+        ]
+    );
+    assert_eq!(arity[1].range, Range::new(Position::new(4, 0), Position::new(4, 17)));
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": arity[2].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    assert_eq!(actions[0].title, "Add the `endpoint` block");
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    let fixed = apply(text, edits);
+    assert!(fixed.ends_with("prometheus.remote_write \"out\" {\n  endpoint {\n  }\n}\n"), "{fixed}");
+}
+
+#[tokio::test]
+async fn unclosed_blocks_missing_a_block_get_no_fix() {
+    let mut server = TestServer::start().await;
+    let file = uri("unclosed.alloy");
+    // Ends in a multibyte character, where a fix would once cut the text.
+    server.open(&file, "otelcol.receiver.otlp \"x\" {\n  // é").await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    let missing = diagnostics.iter().find(|d| d.message.contains("missing its required `output` block")).unwrap();
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": missing.range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    assert!(actions.iter().all(|a| !a.title.starts_with("Add the")), "{actions:?}");
+}
+
+#[tokio::test]
+async fn syntax_errors_are_published_one_diagnostic_each() {
+    let mut server = TestServer::start().await;
+    let file = uri("broken.alloy");
+    let text = "local.file \"token\" {\n  filename = \n}\n\nlogging {\n  level = \"info\" \"debug\"\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    let code = Some(NumberOrString::String("syntax-error".into()));
+    let syntax: Vec<_> = diagnostics.iter().filter(|d| d.code == code).collect();
+    let found: Vec<(&str, Range)> = syntax.iter().map(|d| (d.message.as_str(), d.range)).collect();
+    assert_eq!(
+        found,
+        [
+            ("expected expression", Range::new(Position::new(2, 0), Position::new(2, 1))),
+            ("expected attribute or block", Range::new(Position::new(5, 17), Position::new(5, 24))),
+        ]
+    );
+    assert!(syntax.iter().all(|d| d.severity == Some(DiagnosticSeverity::ERROR)));
+}
+
+#[tokio::test]
+async fn invalid_string_escapes_are_errors() {
+    let mut server = TestServer::start().await;
+    let file = uri("escapes.alloy");
+    let text = "prometheus.scrape \"s\" {\n  targets    = []\n  forward_to = [prometheus.relabel.a.receiver]\n}\nprometheus.relabel \"a\" {\n  forward_to = [prometheus.remote_write.b.receiver]\n  rule {\n    regex       = \"(\\d+)\"\n    replacement = \"\\x4 \\u00e9 \\777 \\t\"\n  }\n}\nprometheus.remote_write \"b\" {\n  endpoint {\n    url = \"https://example.com/push\"\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["invalid-escape", "invalid-escape", "invalid-escape"]);
+    let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "`\\d` is not an escape sequence; a backslash is written `\\\\`",
+            "`\\x4` needs 2 hex digits",
+            "`\\777` is not a valid byte",
+        ]
+    );
+    assert_eq!(diagnostics[0].range, Range::new(Position::new(7, 20), Position::new(7, 22)));
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    assert_eq!(actions[0].title, "Escape the backslash");
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    let fixed = apply(text, edits);
+    assert!(fixed.contains(r#"regex       = "(\\d+)""#), "{fixed}");
+}
+
+#[tokio::test]
+async fn goes_to_the_referenced_component() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let location: Option<Location> =
+        serde_json::from_value(server.at("textDocument/definition", &file, 2, 30).await).unwrap();
+    let location = location.expect("definition of the remote_write reference");
+    assert_eq!(location.uri.as_str(), file);
+    assert_eq!(location.range.start, Position::new(5, 0));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn definitions_in_git_modules_are_fetched_into_a_read_only_cache() {
+    let root = std::env::temp_dir().join(format!("alloy-hover-git-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let repo = root.join("repo");
+    std::fs::create_dir_all(repo.join("modules")).unwrap();
+    std::fs::write(repo.join("modules/scrape.alloy"), "declare \"scrape\" {\n  argument \"targets\" { }\n}\n").unwrap();
+    let git = |args: &[&str]| {
+        let status = std::process::Command::new("git").arg("-C").arg(&repo).args(args).status().unwrap();
+        assert!(status.success(), "git {args:?}");
+    };
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-qm", "module"]);
+
+    let repository = Url::from_directory_path(&repo).unwrap();
+    let marker = root.join("pwned");
+    let text = format!(
+        "import.git \"remote\" {{\n  repository = \"{repository}\"\n  path       = \"modules/scrape.alloy\"\n}}\n\n\
+         remote.scrape \"a\" {{\n  targets = []\n}}\n\n\
+         import.git \"evil\" {{\n  repository = \"--upload-pack=touch {};\"\n  path       = \"x.alloy\"\n}}\n\n\
+         import.git \"absolute\" {{\n  repository = \"{repository}\"\n  path       = \"{}\"\n}}\n\n\
+         import.git \"up\" {{\n  repository = \"{repository}\"\n  path       = \"../../../repo/modules/scrape.alloy\"\n}}\n",
+        marker.display(),
+        repo.join("modules/scrape.alloy").display(),
+    );
+    let cache = root.join("cache");
+    let mut server = TestServer::with_options(json!({ "modules": { "cacheDir": cache, "fetch": true } })).await;
+    let file = uri("main.alloy");
+    server.open(&file, &text).await;
+
+    // Repositories starting with `-` are not handed to git, where they would be options.
+    let logged: Vec<String> = server
+        .sent_messages()
+        .await
+        .into_iter()
+        .filter(|(method, _)| method == "window/logMessage")
+        .map(|(_, params)| params["message"].as_str().unwrap().to_string())
+        .collect();
+    assert!(logged.iter().any(|m| m.starts_with("loading module `evil`: `--upload-pack")), "{logged:?}");
+    assert!(!marker.exists());
+    // Nor are paths read from outside the checkout.
+    for label in ["absolute", "up"] {
+        let prefix = format!("loading module `{label}`: ");
+        assert!(logged.iter().any(|m| m.starts_with(&prefix) && m.ends_with("is not a path inside the repository")), "{logged:?}");
+    }
+
+    let location: Location = serde_json::from_value(server.at("textDocument/definition", &file, 5, 8).await).unwrap();
+    let module = location.uri.to_file_path().unwrap();
+    assert!(module.starts_with(&cache) && module.ends_with("modules/scrape.alloy"), "{module:?}");
+    assert_eq!(location.range, Range::new(Position::new(0, 0), Position::new(2, 1)));
+    assert!(std::fs::metadata(&module).unwrap().permissions().readonly());
+
+    // The import itself goes to the top of the module.
+    let location: Location = serde_json::from_value(server.at("textDocument/definition", &file, 0, 3).await).unwrap();
+    assert_eq!((location.uri.to_file_path().unwrap(), location.range), (module, Range::default()));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn cached_remote_modules_are_checked_and_completed_without_fetching() {
+    let cache = std::env::temp_dir().join(format!("alloy-hover-module-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache);
+    let git = GitImport {
+        label: "remote".into(),
+        repository: "https://example.com/modules.git".into(),
+        revision: "HEAD".into(),
+        path: "scrape.alloy".into(),
+        span: Default::default(),
+    };
+    let checkout = git.checkout_dir(&cache);
+    std::fs::create_dir_all(&checkout).unwrap();
+    std::fs::write(checkout.join("scrape.alloy"), "declare \"scrape\" {\n  argument \"targets\" { }\n}\n").unwrap();
+    let http = HttpImport { label: "web".into(), url: "https://example.com/ship.alloy".into(), span: Default::default() };
+    let file = http.cache_file(&cache);
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(&file, "declare \"ship\" { }\n").unwrap();
+
+    let mut server = TestServer::with_options(json!({ "modules": { "cacheDir": cache, "fetch": false } })).await;
+    let main = uri("main.alloy");
+    let text = "import.git \"remote\" {\n  repository = \"https://example.com/modules.git\"\n  path       = \"scrape.alloy\"\n}\n\n\
+                import.http \"web\" {\n  url = \"https://example.com/ship.alloy\"\n}\n\n\
+                remote.scrape \"a\" {\n  targets = []\n}\n\nweb.shipp \"b\" { }\n\n";
+    server.open(&main, text).await;
+
+    let labels = server.completion_labels(&main, 15, 0).await;
+    assert!(["remote.scrape", "web.ship"].iter().all(|l| labels.iter().any(|i| i == l)), "{labels:?}");
+    // Diagnostics are published once before the modules are loaded.
+    let mut diagnostics = server.diagnostics(&main).await;
+    while diagnostics.is_empty() {
+        diagnostics = server.diagnostics(&main).await;
+    }
+    let messages: Vec<_> = diagnostics.iter().map(|d| (d.range.start.line, d.message.as_str())).collect();
+    assert_eq!(messages, [(13, "module `web` has no component `shipp`; did you mean `web.ship`?")]);
+
+    std::fs::remove_dir_all(&cache).unwrap();
+}
+
+#[tokio::test]
+async fn the_component_graph_follows_data_from_producers_to_receivers() {
+    let mut server = TestServer::start().await;
+    let (file, other) = (uri("pipeline.alloy"), uri("other.alloy"));
+    server.open(&file, PIPELINE).await;
+    let text = "prometheus.scrape \"other\" {\n  targets    = []\n  forward_to = [prometheus.remote_write.cloud.receiver]\n}\n";
+    server.open(&other, text).await;
+    let ids = |graph: &serde_json::Value| -> Vec<String> {
+        graph["nodes"].as_array().unwrap().iter().map(|n| n["id"].as_str().unwrap().to_string()).collect()
+    };
+    let edge = |from: &str| json!({ "from": from, "to": "prometheus.remote_write.cloud", "export": "receiver" });
+
+    let graph = server.request("alloy/componentGraph", json!({})).await;
+    assert_eq!(ids(&graph), ["prometheus.remote_write.cloud", "prometheus.scrape.default", "prometheus.scrape.other"]);
+    assert_eq!(graph["edges"], json!([edge("prometheus.scrape.default"), edge("prometheus.scrape.other")]));
+    let dot = graph["dot"].as_str().unwrap();
+    let arrow = "\"prometheus.scrape.default\" -> \"prometheus.remote_write.cloud\" [label=\"receiver\"];";
+    assert!(dot.contains(arrow), "{dot}");
+    let mermaid = graph["mermaid"].as_str().unwrap();
+    assert!(mermaid.starts_with("flowchart LR\n") && mermaid.contains(" -->|receiver| "), "{mermaid}");
+
+    // One file's components, with the edges leaving it.
+    let graph = server.request("alloy/componentGraph", json!({ "uri": other })).await;
+    assert_eq!(ids(&graph), ["prometheus.scrape.other"]);
+    assert_eq!(graph["edges"], json!([edge("prometheus.scrape.other")]));
+}
+
+#[tokio::test]
+async fn export_schema_returns_arguments_blocks_and_deprecations() {
+    let mut server = TestServer::start().await;
+    let schema = server.request("alloy/exportSchema", json!(null)).await;
+    assert_eq!(server.request("alloy/exportSchema", json!({})).await, schema);
+
+    let scrape = &schema["components"]["prometheus.scrape"];
+    assert_eq!(scrape["arguments"]["targets"]["type"], "list(map(string))");
+    assert_eq!(scrape["arguments"]["targets"]["required"], true);
+    assert_eq!(scrape["arguments"]["scrape_interval"]["default"], "\"60s\"");
+    assert_eq!(scrape["arguments"]["enable_protobuf_negotiation"]["deprecated"], "Use `scrape_protocols` instead.");
+    assert!(scrape["blocks"]["basic_auth"]["arguments"]["password"].is_object(), "{scrape}");
+    let endpoint = &schema["components"]["loki.write"]["blocks"]["endpoint"];
+    assert_eq!(endpoint["repeatable"], true);
+    assert_eq!(endpoint["arguments"]["url"]["required"], true);
+    assert_eq!(schema["components"]["otelcol.exporter.logging"]["deprecated"], "Use `otelcol.exporter.debug` instead.");
+}
+
+#[tokio::test]
+async fn export_json_schema_describes_every_component_the_server_knows() {
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_alloy-hover-lsp"))
+        .arg("export-json-schema")
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let json_schema: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let schema = TestServer::start().await.request("alloy/exportSchema", json!(null)).await;
+    let names = |map: &serde_json::Value| map.as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+    assert_eq!(names(&json_schema["properties"]), names(&schema["components"]));
+
+    let scrape = &json_schema["properties"]["prometheus.scrape"]["additionalProperties"];
+    assert!(scrape["required"].as_array().unwrap().contains(&json!("targets")), "{scrape}");
+    assert_eq!(scrape["additionalProperties"], false);
+    let interval = &scrape["properties"]["scrape_interval"];
+    assert_eq!([&interval["type"], &interval["x-alloy-type"], &interval["default"]], ["string", "duration", "60s"]);
+    assert!(interval["pattern"].is_string(), "{interval}");
+    assert_eq!(scrape["properties"]["basic_auth"]["type"], "array");
+    assert_eq!(json_schema["properties"]["otelcol.exporter.logging"]["additionalProperties"]["deprecated"], true);
+}
+
+#[tokio::test]
+async fn semantic_tokens_mark_secret_attributes_and_their_values() {
+    let mut server = TestServer::start().await;
+    let legend = &server.capabilities["semanticTokensProvider"]["legend"];
+    assert_eq!(legend["tokenModifiers"], json!(["secret"]));
+    let types: Vec<String> = serde_json::from_value(legend["tokenTypes"].clone()).unwrap();
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let params = json!({ "textDocument": { "uri": file } });
+    let tokens: SemanticTokens =
+        serde_json::from_value(server.request("textDocument/semanticTokens/full", params).await).unwrap();
+    // Back from deltas to (line, start, length, type, secret).
+    let mut at = (0, 0);
+    let tokens: Vec<(u32, u32, u32, &str, bool)> = tokens
+        .data
+        .iter()
+        .map(|t| {
+            at = (at.0 + t.delta_line, if t.delta_line == 0 { at.1 + t.delta_start } else { t.delta_start });
+            (at.0, at.1, t.length, types[t.token_type as usize].as_str(), t.token_modifiers_bitset == 1)
+        })
+        .collect();
+    let line = |n| tokens.iter().filter(|t| t.0 == n).copied().collect::<Vec<_>>();
+    assert_eq!(line(0), [(0, 0, 17, "type", false), (0, 18, 9, "string", false)]);
+    assert_eq!(line(7), [(7, 4, 3, "property", false), (7, 19, 35, "string", false)]);
+    assert_eq!(line(8), [(8, 4, 12, "property", true), (8, 19, 9, "string", true)]);
+}
+
+#[tokio::test]
+async fn editing_a_label_edits_it_in_the_references_of_the_same_file() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let linked: Option<LinkedEditingRanges> =
+        serde_json::from_value(server.at("textDocument/linkedEditingRange", &file, 5, 27).await).unwrap();
+    let linked = linked.expect("the remote_write label is referenced");
+    let range = |line, start| Range::new(Position::new(line, start), Position::new(line, start + 5));
+    let expected = [range(5, 25), range(2, 40)];
+    assert_eq!(linked.ranges, expected);
+    assert_eq!(linked.word_pattern.as_deref(), Some("[A-Za-z0-9_]+"));
+    let from_reference = server.at("textDocument/linkedEditingRange", &file, 2, 42).await;
+    assert_eq!(from_reference["ranges"], json!(expected));
+
+    // Nothing references the scrape.
+    assert_eq!(server.at("textDocument/linkedEditingRange", &file, 0, 22).await, json!(null));
+}
+
+#[tokio::test]
+async fn the_call_hierarchy_follows_data_between_components() {
+    let mut server = TestServer::start().await;
+    let file = uri("calls.alloy");
+    let text = r#"discovery.kubernetes "pods" {
+  role = "pod"
+}
+
+prometheus.scrape "default" {
+  targets    = discovery.kubernetes.pods.targets
+  forward_to = [prometheus.remote_write.cloud.receiver]
+}
+
+prometheus.remote_write "cloud" {
+  endpoint {
+    url = "https://example.com/push"
+  }
+}
+"#;
+    server.open(&file, text).await;
+    let items: Vec<CallHierarchyItem> =
+        serde_json::from_value(server.at("textDocument/prepareCallHierarchy", &file, 5, 3).await).unwrap();
+    assert_eq!(items.iter().map(|i| i.name.as_str()).collect::<Vec<_>>(), ["prometheus.scrape.default"]);
+    let params = json!({ "item": items[0] });
+
+    // The targets are pulled from discovery, the metrics pushed on.
+    let incoming: Vec<CallHierarchyIncomingCall> =
+        serde_json::from_value(server.request("callHierarchy/incomingCalls", params.clone()).await).unwrap();
+    let incoming: Vec<(&str, Vec<Position>)> =
+        incoming.iter().map(|c| (c.from.name.as_str(), c.from_ranges.iter().map(|r| r.start).collect())).collect();
+    assert_eq!(incoming, [("discovery.kubernetes.pods", vec![Position::new(5, 15)])]);
+    let outgoing: Vec<CallHierarchyOutgoingCall> =
+        serde_json::from_value(server.request("callHierarchy/outgoingCalls", params).await).unwrap();
+    let outgoing: Vec<(&str, Vec<Position>)> =
+        outgoing.iter().map(|c| (c.to.name.as_str(), c.from_ranges.iter().map(|r| r.start).collect())).collect();
+    assert_eq!(outgoing, [("prometheus.remote_write.cloud", vec![Position::new(6, 16)])]);
+
+    // Nothing is sent on from the remote_write.
+    let items = server.at("textDocument/prepareCallHierarchy", &file, 9, 3).await;
+    let outgoing = server.request("callHierarchy/outgoingCalls", json!({ "item": items[0] })).await;
+    assert_eq!(outgoing, json!([]));
+}
+
+#[tokio::test]
+async fn folds_blocks() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let ranges: Vec<FoldingRange> =
+        serde_json::from_value(server.request("textDocument/foldingRange", json!({ "textDocument": { "uri": file } })).await)
+            .unwrap();
+    let lines: Vec<(u32, u32)> = ranges.iter().map(|r| (r.start_line, r.end_line)).collect();
+    assert!(lines.contains(&(0, 2)), "{lines:?}");
+    assert!(lines.contains(&(5, 9)), "{lines:?}");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn commands_format_and_validate_open_documents() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Stands in for `alloy fmt -`: echoes stdin with the attribute aligned.
+    let alloy = std::env::temp_dir().join(format!("alloy-fmt-{}", std::process::id()));
+    std::fs::write(&alloy, "#!/bin/sh\nsed 's/targets=/targets = /'\n").unwrap();
+    std::fs::set_permissions(&alloy, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let mut server = TestServer::with_options(json!({ "alloy": { "path": alloy } })).await;
+    let file = uri("fmt.alloy");
+    let text = "prometheus.scrape \"a\" {\n  targets=[]\n  forward_to = [prometheus.remote_write.missing.receiver]\n}\n";
+    server.open(&file, text).await;
+
+    let command = json!({ "command": "alloy.fmt", "arguments": [file] });
+    assert_eq!(server.request("workspace/executeCommand", command).await, json!(null));
+    let edit = server.applied_edit().await.expect("alloy.fmt applies an edit");
+    std::fs::remove_file(&alloy).unwrap();
+    let edits = &edit.changes.expect("edits by URI")[&Url::parse(&file).unwrap()];
+    assert_eq!(edits[0].range, Range::new(Position::new(0, 0), Position::new(4, 0)));
+    assert_eq!(edits[0].new_text, text.replace("targets=", "targets = "));
+
+    let command = json!({ "command": "alloy.validate", "arguments": [file] });
+    let diagnostics: Vec<Diagnostic> =
+        serde_json::from_value(server.request("workspace/executeCommand", command).await).unwrap();
+    assert!(codes(&diagnostics).contains(&"unknown-reference"), "{diagnostics:?}");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn saved_files_get_the_diagnostics_alloy_validate_reports() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("alloy-hover-validate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // Stands in for `alloy`: formatting turns `\r\n` into `\n` and aligns
+    // `targets`, validation fails.
+    let alloy = dir.join("alloy");
+    let script = r#"#!/bin/sh
+case "$1" in
+  fmt) tr -d '\r' < "$2" | sed 's/^  targets = /  targets    = /' ;;
+  validate) echo "Error: $2:4:3: unrecognized attribute name \"bogus\"" >&2; exit 1 ;;
+esac
+"#;
+    std::fs::write(&alloy, script).unwrap();
+    std::fs::set_permissions(&alloy, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = dir.join("saved.alloy");
+    let text = "prometheus.scrape \"a\" {\n  targets    = []\n  forward_to = []\n  bogus      = true\n}\n";
+    std::fs::write(&path, text).unwrap();
+    let file = Url::from_file_path(&path).unwrap().to_string();
+    let from_alloy = |diagnostics: Vec<Diagnostic>| -> Vec<Diagnostic> {
+        diagnostics.into_iter().filter(|d| d.source.as_deref() == Some("alloy")).collect()
+    };
+
+    let mut server = TestServer::with_options(json!({ "alloy": { "path": alloy, "validateOnSave": true } })).await;
+    server.open(&file, text).await;
+    assert_eq!(from_alloy(server.diagnostics(&file).await), [], "only saved files are validated");
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    let diagnostics = from_alloy(server.diagnostics(&file).await);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0].message, "unrecognized attribute name \"bogus\"");
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(diagnostics[0].range, Range::new(Position::new(3, 2), Position::new(3, 7)));
+
+    // A file with `\r\n` line endings is not unformatted for them.
+    let crlf = text.replace('\n', "\r\n");
+    std::fs::write(&path, &crlf).unwrap();
+    server.change(&file, 2, &crlf).await;
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    let diagnostics = from_alloy(server.diagnostics(&file).await);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0].range, Range::new(Position::new(3, 2), Position::new(3, 7)));
+
+    // An unformatted file gets a hint on the first line `alloy fmt` changes.
+    let unformatted = crlf.replace("targets    =", "targets =");
+    std::fs::write(&path, &unformatted).unwrap();
+    server.change(&file, 3, &unformatted).await;
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    let diagnostics = from_alloy(server.diagnostics(&file).await);
+    assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    assert_eq!(diagnostics[0].message, "file is not formatted according to `alloy fmt`");
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+    assert_eq!(diagnostics[0].range, Range::new(Position::new(1, 0), Position::new(1, 14)));
+    std::fs::write(&path, text).unwrap();
+
+    // Off unless enabled.
+    let mut server = TestServer::with_options(json!({ "alloy": { "path": alloy } })).await;
+    server.open(&file, text).await;
+    server.diagnostics(&file).await;
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    assert_eq!(from_alloy(server.diagnostics(&file).await), []);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn closing_brace_hints_name_long_blocks_when_enabled() {
+    let file = uri("pipeline.alloy");
+    let range = json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 20, "character": 0 } });
+    let params = json!({ "textDocument": { "uri": file }, "range": range });
+
+    let mut server = TestServer::start().await;
+    server.open(&file, PIPELINE).await;
+    assert_eq!(server.request("textDocument/inlayHint", params.clone()).await, json!(null));
+
+    let mut server = TestServer::with_options(json!({ "features": { "closingBraceHints": true } })).await;
+    server.open(&file, PIPELINE).await;
+    let hints: Vec<InlayHint> = serde_json::from_value(server.request("textDocument/inlayHint", params).await).unwrap();
+    let hints: Vec<(Position, String)> = hints
+        .into_iter()
+        .map(|h| match h.label {
+            InlayHintLabel::String(label) => (h.position, label),
+            other => panic!("{other:?}"),
+        })
+        .collect();
+    assert_eq!(hints, [(Position::new(10, 1), "// prometheus.remote_write.cloud".to_string())]);
+}
+
+#[tokio::test]
+async fn switchable_features_are_registered_as_settings_change_when_the_client_allows() {
+    let server = TestServer::with_options(json!({ "features": { "codeLens": false } })).await;
+    assert_eq!(server.capabilities["codeLensProvider"], json!(null));
+    assert!(server.capabilities["semanticTokensProvider"].is_object(), "advertised up front when on");
+
+    let mut capabilities = client_capabilities();
+    capabilities["textDocument"]["codeLens"] = json!({ "dynamicRegistration": true });
+    capabilities["textDocument"]["inlayHint"] = json!({ "dynamicRegistration": true });
+    capabilities["textDocument"]["semanticTokens"] = json!({
+        "dynamicRegistration": true,
+        "requests": { "full": true },
+        "tokenTypes": [],
+        "tokenModifiers": [],
+        "formats": ["relative"],
+    });
+    let mut server = TestServer::with_capabilities(capabilities).await;
+    for provider in ["codeLensProvider", "semanticTokensProvider", "inlayHintProvider"] {
+        assert_eq!(server.capabilities[provider], json!(null), "{provider} is registered instead");
+    }
+    let ids = |messages: &[(String, serde_json::Value)], method: &str, key: &str| -> Vec<String> {
+        let registrations = messages.iter().filter(|(m, _)| m == method).flat_map(|(_, p)| p[key].as_array().unwrap());
+        registrations.map(|r| r["id"].as_str().unwrap().to_string()).collect()
+    };
+    let sent = server.sent_messages().await;
+    let registered = ids(&sent, "client/registerCapability", "registrations");
+    assert_eq!(registered, ["textDocument/codeLens", "textDocument/semanticTokens"]);
+    let registration = sent.iter().find(|(m, _)| m == "client/registerCapability").unwrap();
+    assert_eq!(registration.1["registrations"][1]["registerOptions"]["legend"]["tokenModifiers"], json!(["secret"]));
+
+    let settings = json!({ "settings": { "features": { "codeLens": false, "closingBraceHints": true } } });
+    server.notify("workspace/didChangeConfiguration", settings).await;
+    let sent = server.sent_messages().await;
+    assert_eq!(ids(&sent, "client/unregisterCapability", "unregisterations"), ["textDocument/codeLens"]);
+    assert_eq!(ids(&sent, "client/registerCapability", "registrations"), ["textDocument/inlayHint"]);
+}
+
+#[tokio::test]
+async fn consumer_lenses_list_where_exports_are_used() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let lenses: Vec<CodeLens> =
+        serde_json::from_value(server.request("textDocument/codeLens", json!({ "textDocument": { "uri": file } })).await)
+            .unwrap();
+    let consumers: Vec<&Command> =
+        lenses.iter().filter_map(|l| l.command.as_ref()).filter(|c| c.command == "alloy.goToConsumers").collect();
+    assert_eq!(consumers.len(), 1, "only the remote_write is consumed: {lenses:?}");
+    assert_eq!(consumers[0].title, "1 consumer");
+
+    let command = json!({ "command": consumers[0].command, "arguments": consumers[0].arguments });
+    let locations: Vec<Location> =
+        serde_json::from_value(server.request("workspace/executeCommand", command).await).unwrap();
+    assert_eq!(locations.len(), 1);
+    assert_eq!(locations[0].range.start, Position::new(2, 16));
+}
+
+#[tokio::test]
+async fn health_lenses_show_what_the_instance_reports_until_refreshed() {
+    let components = |scrape: &str| {
+        json!([
+            { "localID": "prometheus.scrape.default", "health": { "state": scrape, "message": "started component" } },
+            { "localID": "prometheus.remote_write.cloud", "health": { "state": "unhealthy", "message": "invalid URL" } },
+        ])
+    };
+    let instance = FakeInstance::start(vec![("/api/v0/web/components", components("healthy"))]);
+    let options = json!({ "instance": { "url": instance.url, "healthLens": true } });
+    let mut server = TestServer::with_options(options).await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    let health = async |server: &mut TestServer| -> Vec<(u32, String)> {
+        let lenses: Vec<CodeLens> =
+            serde_json::from_value(server.request("textDocument/codeLens", json!({ "textDocument": { "uri": file } })).await)
+                .unwrap();
+        lenses
+            .into_iter()
+            .filter_map(|l| Some((l.range.start.line, l.command?)))
+            .filter(|(_, c)| c.command == "alloy.refreshHealth")
+            .map(|(line, c)| (line, c.title))
+            .collect()
+    };
+    let expected = |scrape: &str| vec![(0, scrape.to_string()), (5, "unhealthy: invalid URL".to_string())];
+    assert_eq!(health(&mut server).await, expected("healthy"));
+
+    // Kept until asked for again.
+    instance.set("/api/v0/web/components", components("exited"));
+    assert_eq!(health(&mut server).await, expected("healthy"));
+    server.request("workspace/executeCommand", json!({ "command": "alloy.refreshHealth" })).await;
+    assert_eq!(health(&mut server).await, expected("exited: started component"));
+}
+
+#[tokio::test]
+async fn saved_buffers_are_validated_by_the_instance_when_enabled() {
+    let instance = FakeInstance::start(vec![]);
+    instance.fail("/-/validate", 400, "Error: pipeline.alloy:6:1: component failed to evaluate\n");
+    let options = json!({
+        "instance": { "url": instance.url, "liveValidation": true },
+    });
+    let mut server = TestServer::with_options(options).await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    let from_instance = |diagnostics: Vec<Diagnostic>| -> Vec<(Position, String)> {
+        diagnostics
+            .into_iter()
+            .filter(|d| d.source.as_deref() == Some("alloy-instance"))
+            .map(|d| (d.range.start, d.message))
+            .collect()
+    };
+    assert_eq!(from_instance(server.diagnostics(&file).await), [], "only saved buffers are sent");
+
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    let expected = (Position::new(5, 0), "component failed to evaluate".to_string());
+    assert_eq!(from_instance(server.diagnostics(&file).await), [expected]);
+
+    // Errors without a position are shown at the top.
+    instance.fail("/-/validate", 500, "config is broken");
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    assert_eq!(from_instance(server.diagnostics(&file).await), [(Position::new(0, 0), "config is broken".to_string())]);
+
+    instance.set("/-/validate", json!({}));
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    assert_eq!(from_instance(server.diagnostics(&file).await), []);
+}
+
+#[tokio::test]
+async fn components_the_instance_fails_to_run_are_reported_on_save() {
+    let components = json!([
+        { "localID": "prometheus.scrape.default", "moduleID": "", "health": { "state": "healthy" } },
+        {
+            "localID": "prometheus.remote_write.cloud",
+            "moduleID": "",
+            "health": { "state": "unhealthy", "message": "failed to build component: invalid URL" },
+        },
+        {
+            "localID": "prometheus.scrape.default",
+            "moduleID": "import.file.other",
+            "health": { "state": "unhealthy", "message": "in another module" },
+        },
+    ]);
+    let instance = FakeInstance::start(vec![("/api/v0/web/components", components)]);
+    let options = json!({
+        "instance": { "url": instance.url, "runtimeDiagnostics": true },
+        "diagnostics": { "plaintextSecret": "off" },
+    });
+    let mut server = TestServer::with_options(options).await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    assert_eq!(server.diagnostics(&file).await, []);
+
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0].source.as_deref(), Some("alloy-runtime"));
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(diagnostics[0].range.start, Position::new(5, 0));
+    assert!(diagnostics[0].message.ends_with("failed to build component: invalid URL"), "{}", diagnostics[0].message);
+}
+
+#[tokio::test]
+async fn declare_doc_comments_show_where_the_module_is_used() {
+    let mut server = TestServer::start().await;
+    let file = uri("modules.alloy");
+    let text = r#"// Scrapes a target and ships it to the cloud.
+// Needs a remote_write receiver.
+declare "shipper" {
+  // Where to scrape from.
+  argument "address" { }
+  argument "interval" {
+    optional = true
+  }
+  export "up" {
+    value = true
+  }
+}
+
+shipper "api" {
+  address = "api:9090"
+}
+
+x = shipper.api.
+"#;
+    server.open(&file, text).await;
+
+    let hover = server.hover(&file, 13, 3).await.expect("hover on the instance name");
+    assert!(hover.contains("module `shipper`, declared at"), "{hover}");
+    assert!(hover.contains("Scrapes a target and ships it to the cloud.\nNeeds a remote_write receiver."), "{hover}");
+    assert!(hover.contains("- `address`: Where to scrape from."), "{hover}");
+    assert!(hover.contains("- `interval` (optional)"), "{hover}");
+
+    let items: Vec<CompletionItem> =
+        serde_json::from_value(server.at("textDocument/completion", &file, 17, 16).await).unwrap();
+    let up = items.iter().find(|i| i.label == "up").expect("the module's export");
+    let doc = "Scrapes a target and ships it to the cloud.\nNeeds a remote_write receiver.";
+    assert_eq!(up.documentation, Some(Documentation::String(doc.into())));
+}
+
+#[tokio::test]
+async fn module_arguments_are_described_where_they_are_used_and_where_they_are_set() {
+    let mut server = TestServer::start().await;
+    let file = uri("arguments.alloy");
+    let text = r#"declare "bucket" {
+  // Region the bucket lives in.
+  argument "region" {
+    optional = true
+    default  = "eu-west-1"
+  }
+  argument "name" { }
+
+  local.file "f" {
+    filename = argument.region.value + argument.name.value
+  }
+}
+
+bucket "logs" {
+  region = "us-east-1"
+  name   = "logs"
+}
+"#;
+    server.open(&file, text).await;
+
+    let region = "`region`: argument of module `bucket` (optional)\n\nDefault: `\"eu-west-1\"`\n\nRegion the bucket lives in.\n";
+    assert_eq!(server.hover(&file, 9, 25).await.as_deref(), Some(region));
+    assert_eq!(server.hover(&file, 14, 3).await.as_deref(), Some(region));
+    let name = "`name`: argument of module `bucket` (required)\n\n";
+    assert_eq!(server.hover(&file, 9, 50).await.as_deref(), Some(name));
+    assert_eq!(server.hover(&file, 15, 3).await.as_deref(), Some(name));
+}
+
+#[tokio::test]
+async fn module_arguments_and_loop_variables_are_in_scope_where_they_are_defined() {
+    let mut server = TestServer::start().await;
+    let file = uri("scope.alloy");
+    let text = r#"declare "shipper" {
+  argument "address" { }
+
+  foreach "each" {
+    collection = [argument.address.value]
+    var        = "item"
+
+    template {
+      local.file "f" {
+        filename = item
+      }
+    }
+  }
+
+  local.file "g" {
+    filename = argument.port.value
+  }
+}
+
+local.file "h" {
+  filename = item
+}
+"#;
+    server.open(&file, text).await;
+
+    let unknown: Vec<_> = server
+        .diagnostics(&file)
+        .await
+        .into_iter()
+        .filter(|d| d.code == Some(NumberOrString::String("unknown-reference".into())))
+        .map(|d| d.range.start.line)
+        .collect();
+    assert_eq!(unknown, [15, 20], "only the missing argument and the loop variable outside its loop");
+
+    let in_template = server.completion_labels(&file, 9, 21).await;
+    assert!(["argument", "item"].iter().all(|l| in_template.iter().any(|i| i == l)), "{in_template:?}");
+    let outside = server.completion_labels(&file, 20, 15).await;
+    assert!(!outside.iter().any(|l| l == "argument" || l == "item"), "{outside:?}");
+    assert_eq!(server.completion_labels(&file, 4, 27).await, ["address"]);
+    assert_eq!(server.completion_labels(&file, 4, 35).await, ["value"]);
+}
+
+#[tokio::test]
+async fn pipeline_snippets_expand_into_wired_blocks() {
+    let file = uri("new.alloy");
+    let snippets = |items: serde_json::Value| -> Vec<CompletionItem> {
+        let items: Option<Vec<CompletionItem>> = serde_json::from_value(items).unwrap();
+        items.unwrap_or_default().into_iter().filter(|i| i.kind == Some(CompletionItemKind::SNIPPET)).collect()
+    };
+
+    let mut server = TestServer::start().await;
+    server.open(&file, "lo").await;
+    let items = snippets(server.at("textDocument/completion", &file, 0, 2).await);
+    assert_eq!(items.len(), 3);
+    assert!(items.iter().all(|i| i.insert_text_format == Some(InsertTextFormat::SNIPPET)));
+
+    // Inside a block, a name starts an attribute or a nested block instead.
+    server.change(&file, 2, "loki.write \"x\" {\n  en\n}\n").await;
+    assert!(snippets(server.at("textDocument/completion", &file, 1, 4).await).is_empty());
+
+    // Clients without snippet support get the default text, which is a
+    // complete, valid pipeline.
+    let mut server = TestServer::with_capabilities(json!({})).await;
+    server.open(&file, "").await;
+    let items = snippets(server.at("textDocument/completion", &file, 0, 0).await);
+    assert_eq!(items.len(), 3);
+    for item in items {
+        assert_eq!(item.insert_text_format, Some(InsertTextFormat::PLAIN_TEXT));
+        let Some(CompletionTextEdit::Edit(edit)) = item.text_edit else { panic!("{item:?}") };
+        assert!(!edit.new_text.contains('$'), "{}", edit.new_text);
+        let expanded = uri("expanded.alloy");
+        server.open(&expanded, &edit.new_text).await;
+        assert_eq!(server.diagnostics(&expanded).await, [], "{}", edit.new_text);
+    }
+}
+
+#[tokio::test]
+async fn component_names_used_in_the_workspace_rank_first() {
+    let mut server = TestServer::start().await;
+    let other = "loki.write \"a\" { }\nloki.write \"b\" { }\nprometheus.scrape \"c\" { }\n";
+    server.open(&uri("other.alloy"), other).await;
+    let file = uri("new.alloy");
+    server.open(&file, "declare \"tail\" { }\n\n").await;
+
+    let items: Option<Vec<CompletionItem>> =
+        serde_json::from_value(server.at("textDocument/completion", &file, 2, 0).await).unwrap();
+    let mut items: Vec<_> = items.unwrap_or_default().into_iter().filter(|i| i.sort_text.is_some()).collect();
+    items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+    let ranked: Vec<(&str, Option<&str>)> = items.iter().take(2).map(|i| (i.label.as_str(), i.detail.as_deref())).collect();
+    assert_eq!(
+        ranked,
+        [("loki.write", Some("used 2 times in the workspace")), ("prometheus.scrape", Some("used 1 time in the workspace"))]
+    );
+    let module = items.iter().find(|i| i.label == "tail").expect("the file's own declare");
+    assert_eq!(module.kind, Some(CompletionItemKind::MODULE));
+}
+
+#[tokio::test]
+async fn components_newer_than_the_target_version_are_flagged() {
+    let mut server = TestServer::with_options(json!({ "alloy": { "targetVersion": "1.4" } })).await;
+    let file = uri("queue.alloy");
+    server.open(&file, "prometheus.write.queue \"q\" { }\n\npr").await;
+    let diagnostics = server.diagnostics(&file).await;
+    let code = Some(NumberOrString::String("unavailable".into()));
+    let unavailable: Vec<_> = diagnostics.iter().filter(|d| d.code == code).collect();
+    assert_eq!(unavailable.len(), 1, "{diagnostics:?}");
+    assert_eq!(unavailable[0].message, "`prometheus.write.queue` needs Alloy 1.5 or later, but the target version is 1.4");
+
+    let items: Option<Vec<CompletionItem>> =
+        serde_json::from_value(server.at("textDocument/completion", &file, 2, 2).await).unwrap();
+    let items = items.unwrap_or_default();
+    let item = |label: &str| items.iter().find(|i| i.label == label).unwrap_or_else(|| panic!("no {label}"));
+    assert_eq!(item("prometheus.write.queue").detail.as_deref(), Some("needs Alloy 1.5 or later; targeting 1.4"));
+    assert!(item("prometheus.write.queue").sort_text > item("pyroscope.write").sort_text);
+
+    server.notify("workspace/didChangeConfiguration", json!({ "settings": { "alloy": { "targetVersion": "v1.5.0" } } })).await;
+    assert!(!codes(&server.diagnostics(&file).await).contains(&"unavailable"));
+}
+
+#[tokio::test]
+async fn a_newer_release_database_can_replace_the_bundled_one() {
+    let dir = std::env::temp_dir().join(format!("alloy-hover-releases-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("releases.toml");
+    let releases = "[components.\"prometheus.scrape\"]\nremoved = \"3.0\"\n\n\
+                    [arguments.\"prometheus.remote_write\"]\n\"endpoint.bearer_token\" = { since = \"1.8\" }\n";
+    std::fs::write(&path, releases).unwrap();
+    let settings = |version: &str| json!({ "alloy": { "targetVersion": version, "releasesPath": path } });
+
+    let mut server = TestServer::with_options(settings("1.7")).await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    let messages: Vec<String> = server.diagnostics(&file).await.into_iter().map(|d| d.message).collect();
+    assert!(messages.contains(&"`bearer_token` needs Alloy 1.8 or later, but the target version is 1.7".into()), "{messages:?}");
+    let hover = server.hover(&file, 8, 6).await.expect("hover on bearer_token");
+    assert!(hover.contains("\n\nIntroduced in Alloy 1.8.\n\n"), "{hover}");
+
+    server.notify("workspace/didChangeConfiguration", json!({ "settings": settings("3.0") })).await;
+    let messages: Vec<String> = server.diagnostics(&file).await.into_iter().map(|d| d.message).collect();
+    assert!(messages.contains(&"`prometheus.scrape` was removed in Alloy 3.0, but the target version is 3.0".into()), "{messages:?}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn producers_without_forward_to_get_wiring_fixes() {
+    let mut server = TestServer::start().await;
+    let file = uri("unwired.alloy");
+    let wired = "\n\nprometheus.scrape \"b\" {\n  targets    = []\n  forward_to = [prometheus.remote_write.cloud.receiver]\n}\n";
+    let text = &format!("prometheus.scrape \"a\" {{\n  targets = []\n}}\n\nprometheus.remote_write \"cloud\" {{\n  endpoint {{\n    url = \"https://example.com/push\"\n  }}\n}}{wired}");
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["unwired-producer"]);
+    assert_eq!(diagnostics[0].message, "`prometheus.scrape.a` forwards to nothing: `forward_to` is not set");
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    let titles: Vec<&str> = actions.iter().map(|a| a.title.as_str()).collect();
+    assert_eq!(
+        titles,
+        [
+            "Forward to `prometheus.remote_write.cloud.receiver`",
+            "Add a `prometheus.remote_write \"default\"` and forward to it",
+            "Suppress `unwired-producer` here",
+        ]
+    );
+    let url = Url::parse(&file).unwrap();
+    let edits = |action: &CodeAction| action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&url].clone();
+    assert_eq!(
+        apply(text, &edits(&actions[0])),
+        format!(
+            "prometheus.scrape \"a\" {{\n  targets = []\n  forward_to = [prometheus.remote_write.cloud.receiver]\n}}\n\nprometheus.remote_write \"cloud\" {{\n  endpoint {{\n    url = \"https://example.com/push\"\n  }}\n}}{wired}"
+        )
+    );
+
+    let stubbed = apply(text, &edits(&actions[1]));
+    assert!(stubbed.starts_with(
+        "prometheus.scrape \"a\" {\n  targets = []\n  forward_to = [prometheus.remote_write.default.receiver]\n}\n\nprometheus.remote_write \"default\" {\n  endpoint {\n    url = "
+    ), "{stubbed}");
+    server.change(&file, 2, &stubbed).await;
+    assert_eq!(server.diagnostics(&file).await, []);
+}
+
+#[tokio::test]
+async fn unused_components_can_be_removed() {
+    let mut server = TestServer::start().await;
+    let file = uri("unused.alloy");
+    let text = "local.file \"token\" {\n  filename = \"/etc/token\"\n}\n\nprometheus.scrape \"a\" {\n  targets    = []\n  forward_to = [prometheus.remote_write.cloud.receiver]\n}\n\nprometheus.remote_write \"cloud\" {\n  endpoint {\n    url = \"https://example.com/push\"\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["unused-component"]);
+    assert_eq!(diagnostics[0].message, "`local.file.token` is never used: nothing references its exports");
+    assert_eq!(diagnostics[0].tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    assert_eq!(actions[0].title, "Remove `local.file.token`");
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    assert!(apply(text, edits).starts_with("prometheus.scrape \"a\" {\n"), "{}", apply(text, edits));
+}
+
+#[tokio::test]
+async fn modules_from_other_files_can_be_imported() {
+    let mut server = TestServer::start().await;
+    server.open(&uri("modules/logs.alloy"), "declare \"tail\" {\n  argument \"path\" { }\n}\n").await;
+    let file = uri("main.alloy");
+    let text = "// Application logs.\n\ntail \"app\" {\n  path = \"/var/log/app.log\"\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["not-imported"]);
+    assert_eq!(diagnostics[0].message, "`tail` is declared in `/test/modules/logs.alloy`, which this file does not import");
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    assert_eq!(actions[0].title, "Import `tail` from `/test/modules/logs.alloy`");
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    let imported = apply(text, edits);
+    assert_eq!(
+        imported,
+        "// Application logs.\n\nimport.file \"logs\" {\n  filename = file.path_join(module_path, \"modules/logs.alloy\")\n}\n\n\
+         logs.tail \"app\" {\n  path = \"/var/log/app.log\"\n}\n"
+    );
+    server.change(&file, 2, &imported).await;
+    assert_eq!(server.diagnostics(&file).await, []);
+
+    // An import of the file written with Alloy escapes is found, not added again.
+    server.open(&uri("modules/cafe\u{301}.alloy"), "declare \"brew\" { }\n").await;
+    let text = "import.file \"cafe\" {\n  filename = file.path_join(module_path, \"modules/cafe\\u0301.alloy\")\n}\n\nbrew \"a\" { }\n";
+    server.change(&file, 3, text).await;
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["not-imported"]);
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    assert_eq!(apply(text, edits), text.replace("brew", "cafe.brew"));
+}
+
+#[tokio::test]
+async fn completions_resolve_to_their_documentation_and_the_imports_they_need() {
+    let mut server = TestServer::start().await;
+    server.open(&uri("modules/logs.alloy"), "declare \"tail\" {\n  argument \"path\" { }\n}\n").await;
+    let file = uri("main.alloy");
+    let text = "// Application logs.\n\nloki.write \"default\" {\n  endpoint {\n    url = \"http://loki:3100\"\n  }\n}\n\n";
+    server.open(&file, text).await;
+
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 8, 0).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    assert!(items.iter().all(|i| i.documentation.is_none() && i.additional_text_edits.is_none()));
+
+    let scrape = items.iter().find(|i| i.label == "prometheus.scrape").expect("prometheus.scrape");
+    let resolved: CompletionItem =
+        serde_json::from_value(server.request("completionItem/resolve", json!(scrape)).await).unwrap();
+    let Some(Documentation::MarkupContent(markup)) = resolved.documentation else { panic!() };
+    assert!(markup.value.contains("| `targets` | `list(map(string))` | required |"), "{}", markup.value);
+
+    let tail = items.iter().find(|i| i.label == "logs.tail").expect("logs.tail");
+    assert_eq!(tail.detail.as_deref(), Some("imports /test/modules/logs.alloy"));
+    let resolved: CompletionItem =
+        serde_json::from_value(server.request("completionItem/resolve", json!(tail)).await).unwrap();
+    let Some(CompletionTextEdit::Edit(edit)) = resolved.text_edit else { panic!() };
+    let edits = [resolved.additional_text_edits.unwrap(), vec![edit]].concat();
+    assert_eq!(
+        apply(text, &edits),
+        "// Application logs.\n\nimport.file \"logs\" {\n  filename = file.path_join(module_path, \"modules/logs.alloy\")\n}\n\n\
+         loki.write \"default\" {\n  endpoint {\n    url = \"http://loki:3100\"\n  }\n}\n\nlogs.tail"
+    );
+}
+
+#[tokio::test]
+async fn attribute_and_block_completions_are_documented_in_markdown_from_the_docs_bundle() {
+    let mut server = TestServer::start().await;
+    let file = uri("scrape.alloy");
+    let text = "prometheus.scrape \"a\" {\n  \n}\n";
+    server.open(&file, text).await;
+
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 1, 2).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    let interval = items.iter().find(|i| i.label == "scrape_interval").expect("scrape_interval");
+    let resolved: CompletionItem =
+        serde_json::from_value(server.request("completionItem/resolve", json!(interval)).await).unwrap();
+    let Some(Documentation::MarkupContent(markup)) = resolved.documentation else { panic!() };
+    assert_eq!(markup.kind, MarkupKind::Markdown);
+    assert!(
+        markup.value.starts_with(
+            "`scrape_interval`: `duration`\n\nHow frequently to scrape the targets of this scrape configuration.\n\n\
+             Default: `\"60s\"`\n\n---\n\n**scrape_interval**\nPeriod between scrapes"
+        ),
+        "{}",
+        markup.value
+    );
+
+    let basic_auth = items.iter().find(|i| i.label == "basic_auth").expect("basic_auth");
+    let resolved: CompletionItem =
+        serde_json::from_value(server.request("completionItem/resolve", json!(basic_auth)).await).unwrap();
+    let Some(Documentation::MarkupContent(markup)) = resolved.documentation else { panic!() };
+    assert!(markup.value.starts_with("`basic_auth` block in `prometheus.scrape`\n\n"), "{}", markup.value);
+    assert!(markup.value.contains("| `username` | `string` |"), "{}", markup.value);
+
+    // Clients that only show plain text get the same, without the markup.
+    let mut server = TestServer::with_capabilities(json!({})).await;
+    server.open(&file, text).await;
+    let resolved: CompletionItem =
+        serde_json::from_value(server.request("completionItem/resolve", json!(interval)).await).unwrap();
+    let Some(Documentation::String(plain)) = resolved.documentation else { panic!() };
+    assert!(plain.starts_with("scrape_interval: duration\n"), "{plain}");
+}
+
+#[tokio::test]
+#[allow(deprecated)]
+async fn deprecated_completions_are_tagged_and_ranked_below_current_ones() {
+    let mut server = TestServer::start().await;
+    let file = uri("deprecated.alloy");
+    let text = "\nprometheus.scrape \"a\" {\n  \n}\n";
+    server.open(&file, text).await;
+
+    let item = |items: &[CompletionItem], label: &str| items.iter().find(|i| i.label == label).cloned().unwrap();
+    let deprecated = Some(vec![CompletionItemTag::DEPRECATED]);
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 0, 0).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    let (logging, debug) = (item(&items, "otelcol.exporter.logging"), item(&items, "otelcol.exporter.debug"));
+    assert_eq!((logging.tags, logging.deprecated), (deprecated.clone(), Some(true)));
+    assert_eq!((debug.tags, debug.deprecated), (None, None));
+    assert!(logging.sort_text > debug.sort_text, "{:?} {:?}", logging.sort_text, debug.sort_text);
+
+    let response: CompletionResponse =
+        serde_json::from_value(server.at("textDocument/completion", &file, 2, 2).await).unwrap();
+    let CompletionResponse::Array(items) = response else { panic!() };
+    let (negotiation, protocols) = (item(&items, "enable_protobuf_negotiation"), item(&items, "scrape_protocols"));
+    assert_eq!(negotiation.tags, deprecated);
+    assert_eq!(protocols.tags, None);
+    assert!(negotiation.sort_text > protocols.sort_text);
+}
+
+#[tokio::test]
+async fn completion_kinds_say_what_is_being_inserted() {
+    let mut server = TestServer::start().await;
+    let file = uri("kinds.alloy");
+    let text = "\ndiscovery.relabel \"a\" {\n  targets = []\n  rule {\n    action = \"\"\n  }\n}\n\nprometheus.scrape \"b\" {\n  \n}\n";
+    server.open(&file, text).await;
+
+    let kinds = |response: serde_json::Value| -> Vec<(String, Option<CompletionItemKind>)> {
+        let Some(CompletionResponse::Array(items)) = serde_json::from_value(response).unwrap() else { panic!() };
+        items.into_iter().map(|i| (i.label, i.kind)).collect()
+    };
+    let kind = |items: &[(String, Option<CompletionItemKind>)], label: &str| {
+        items.iter().find(|(l, _)| l == label).and_then(|(_, kind)| *kind)
+    };
+    let top = kinds(server.at("textDocument/completion", &file, 0, 0).await);
+    assert_eq!(kind(&top, "prometheus.scrape"), Some(CompletionItemKind::MODULE));
+    let pipeline = "pipeline: Kubernetes pods → remote_write";
+    assert_eq!(kind(&top, pipeline), Some(CompletionItemKind::SNIPPET));
+
+    let body = kinds(server.at("textDocument/completion", &file, 9, 2).await);
+    assert_eq!(kind(&body, "job_name"), Some(CompletionItemKind::FIELD));
+    assert_eq!(kind(&body, "basic_auth"), Some(CompletionItemKind::STRUCT));
+
+    let actions = kinds(server.at("textDocument/completion", &file, 4, 14).await);
+    assert!(!actions.is_empty());
+    assert!(actions.iter().all(|(_, kind)| *kind == Some(CompletionItemKind::ENUM_MEMBER)), "{actions:?}");
+    assert_eq!(kind(&actions, "labelmap"), Some(CompletionItemKind::ENUM_MEMBER));
+}
+
+#[tokio::test]
+async fn sinks_nothing_forwards_to_are_flagged() {
+    let mut server = TestServer::start().await;
+    let file = uri("sinks.alloy");
+    let text = "loki.write \"logs\" { }\n\notelcol.exporter.otlp \"tempo\" {\n  client {\n    endpoint = \"tempo:4317\"\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["no-producers", "no-producers"]);
+    assert_eq!(diagnostics[0].message, "nothing forwards data to `loki.write.logs`, so it never sends anything");
+    assert_eq!(diagnostics[1].range.start, Position::new(2, 0));
+}
+
+#[tokio::test]
+async fn lint_severities_follow_the_configuration() {
+    let options = json!({ "diagnostics": { "debounceMs": 0, "plaintextSecret": "error", "unusedComponent": "off" } });
+    let mut server = TestServer::with_options(options).await;
+    let file = uri("pipeline.alloy");
+    let text = format!("{PIPELINE}\nlocal.file \"token\" {{\n  filename = \"/etc/token\"\n}}\n");
+    server.open(&file, &text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["plaintext-secret"]);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+
+    let settings = json!({ "settings": { "diagnostics": { "plaintextSecret": "off" } } });
+    server.notify("workspace/didChangeConfiguration", settings).await;
+    assert_eq!(codes(&server.diagnostics(&file).await), ["unused-component"]);
+
+    // A bad entry, or a bad section, is reported and skipped; the rest applies.
+    let diagnostics = json!({ "debounceMs": 0, "plaintextSecret": "error", "unusedComponent": "loud" });
+    let settings = json!({ "settings": { "diagnostics": diagnostics, "memory": { "maxMb": "lots" } } });
+    server.notify("workspace/didChangeConfiguration", settings).await;
+    let sent = server.sent_messages().await;
+    let shown: Vec<&str> =
+        sent.iter().filter(|(m, _)| m == "window/showMessage").map(|(_, p)| p["message"].as_str().unwrap()).collect();
+    assert_eq!(shown.len(), 2, "{shown:?}");
+    assert!(shown[0].starts_with("Alloy hover settings: `memory` settings ignored:"), "{shown:?}");
+    assert_eq!(shown[1], "Alloy hover settings: `diagnostics.unusedComponent` is not a severity");
+    let (_, published) = sent.iter().rfind(|(m, _)| m == "textDocument/publishDiagnostics").unwrap();
+    let published: Vec<Diagnostic> = serde_json::from_value(published["diagnostics"].clone()).unwrap();
+    assert_eq!(codes(&published), ["plaintext-secret", "unused-component"]);
+    assert_eq!(published[0].severity, Some(DiagnosticSeverity::ERROR));
+}
+
+#[tokio::test]
+async fn a_workspace_indexed_over_several_threads_is_indexed_whole() {
+    let root = std::env::temp_dir().join(format!("alloy-hover-threads-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    for n in 0..5 {
+        let sink = format!("prometheus.remote_write \"s{n}\" {{\n  endpoint {{\n    url = \"https://example.com/push\"\n  }}\n}}\n");
+        std::fs::write(root.join(format!("sink-{n}.alloy")), sink).unwrap();
+    }
+    let mut server = TestServer::in_workspace(&root).await;
+    assert_eq!(server.request("alloy/status", json!(null)).await["workspace"]["filesIndexed"], 5);
+
+    let file = uri("scrape.alloy");
+    server.open(&file, "prometheus.scrape \"a\" {\n  forward_to = [prometheus.remote_write.s4.receiver]\n}\n").await;
+    let location = server.at("textDocument/definition", &file, 1, 40).await;
+    assert_eq!(location["uri"], json!(Url::from_file_path(root.join("sink-4.alloy")).unwrap()));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn project_lint_config_sets_severities_per_path() {
+    let root = std::env::temp_dir().join(format!("alloy-hover-lintconfig-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("vendor")).unwrap();
+    std::fs::create_dir_all(root.join("modules")).unwrap();
+    let policy = "exclude = [\"vendor/**\"]\n\n[rules]\nplaintext-secret = \"error\"\n\n[[overrides]]\npaths = [\"modules/*.alloy\"]\nrules = { plaintextSecret = \"off\" }\n";
+    std::fs::write(root.join(".alloylint.toml"), policy).unwrap();
+
+    let mut server = TestServer::in_workspace(&root).await;
+    let severities = |diagnostics: Vec<Diagnostic>| -> Vec<Option<DiagnosticSeverity>> {
+        diagnostics.into_iter().map(|d| d.severity).collect()
+    };
+    let main = Url::from_file_path(root.join("main.alloy")).unwrap().to_string();
+    server.open(&main, PIPELINE).await;
+    assert_eq!(severities(server.diagnostics(&main).await), [Some(DiagnosticSeverity::ERROR)]);
+
+    let module = Url::from_file_path(root.join("modules/cloud.alloy")).unwrap().to_string();
+    server.open(&module, PIPELINE).await;
+    assert_eq!(server.diagnostics(&module).await, []);
+
+    let vendored = Url::from_file_path(root.join("vendor/upstream.alloy")).unwrap().to_string();
+    server.open(&vendored, "prometheus.scrape \"a\" { }\n").await;
+    assert_eq!(server.diagnostics(&vendored).await, []);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn suppression_directives_silence_lints_and_report_when_stale() {
+    let mut server = TestServer::start().await;
+    let file = uri("suppressed.alloy");
+    let text = r#"// alloy-lint:disable=unused-component
+local.file "token" {
+  filename = "/etc/token"
+}
+
+prometheus.remote_write "cloud" {
+  endpoint {
+    url          = "https://example.com"
+    bearer_token = "hunter2" // alloy-lint:disable=plaintextSecret,invalid-regex
+  }
+}
+
+prometheus.scrape "a" {
+  targets    = []
+  forward_to = [prometheus.remote_write.cloud.receiver]
+}
+"#;
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["unused-suppression"]);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::INFORMATION));
+    assert_eq!(diagnostics[0].message, "`invalid-regex` is not reported here; the suppression can be removed");
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    assert!(apply(text, edits).contains("\"hunter2\" // alloy-lint:disable=plaintextSecret\n"));
+}
+
+#[tokio::test]
+async fn clients_that_pull_diagnostics_get_reports_with_result_ids_instead_of_publishes() {
+    let mut capabilities = client_capabilities();
+    capabilities["textDocument"]["diagnostic"] = json!({ "dynamicRegistration": false });
+    capabilities["workspace"]["diagnostic"] = json!({ "refreshSupport": true });
+    let mut server = TestServer::with_capabilities(capabilities).await;
+    assert_eq!(server.capabilities["diagnosticProvider"]["identifier"], "alloy-hover");
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    assert!(!server.sent().await.iter().any(|m| m == "textDocument/publishDiagnostics"));
+
+    let pull = |previous: Option<&str>| json!({ "textDocument": { "uri": file }, "previousResultId": previous });
+    let report: DocumentDiagnosticReport =
+        serde_json::from_value(server.request("textDocument/diagnostic", pull(None)).await).unwrap();
+    let DocumentDiagnosticReport::Full(full) = report else { panic!("{report:?}") };
+    let full = full.full_document_diagnostic_report;
+    assert_eq!(codes(&full.items), ["plaintext-secret"]);
+    let id = full.result_id.unwrap();
+
+    let report: DocumentDiagnosticReport =
+        serde_json::from_value(server.request("textDocument/diagnostic", pull(Some(&id))).await).unwrap();
+    let DocumentDiagnosticReport::Unchanged(unchanged) = report else { panic!("{report:?}") };
+    assert_eq!(unchanged.unchanged_document_diagnostic_report.result_id, id);
+
+    // Pulled straight after an edit, before the debounced lint would run.
+    server.change(&file, 2, &PIPELINE.replace("\"hunter2\"", "env(\"TOKEN\")")).await;
+    let report: DocumentDiagnosticReport =
+        serde_json::from_value(server.request("textDocument/diagnostic", pull(Some(&id))).await).unwrap();
+    let DocumentDiagnosticReport::Full(full) = report else { panic!("{report:?}") };
+    assert_eq!(full.full_document_diagnostic_report.items, []);
+    assert_ne!(full.full_document_diagnostic_report.result_id, Some(id));
+
+    server.notify("textDocument/didSave", json!({ "textDocument": { "uri": file } })).await;
+    assert_eq!(server.sent().await, ["workspace/diagnostic/refresh"]);
+}
+
+#[tokio::test]
+async fn workspace_diagnostics_cover_the_files_that_are_not_open() {
+    let root = std::env::temp_dir().join(format!("alloy-hover-workspace-diagnostics-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let main = "import.file \"lib\" {\n  filename = \"lib.alloy\"\n}\n\nlib.add \"x\" {\n  value = local.file.missing.content\n}\n";
+    std::fs::write(root.join("main.alloy"), main).unwrap();
+    std::fs::write(root.join("lib.alloy"), "declare \"add\" {\n}\n\ndeclare \"unused\" {\n}\n").unwrap();
+    std::fs::write(root.join("open.alloy"), "declare \"scratch\" {\n}\n").unwrap();
+    let mut server = TestServer::in_workspace(&root).await;
+    let open = Url::from_file_path(root.join("open.alloy")).unwrap();
+    server.open(open.as_str(), "declare \"scratch\" {\n}\n").await;
+
+    let pull = |previous: Vec<serde_json::Value>| json!({ "previousResultIds": previous });
+    let report: WorkspaceDiagnosticReport =
+        serde_json::from_value(server.request("workspace/diagnostic", pull(Vec::new())).await).unwrap();
+    let mut reports: Vec<(String, Vec<String>, String)> = report
+        .items
+        .into_iter()
+        .map(|item| {
+            let WorkspaceDocumentDiagnosticReport::Full(full) = item else { panic!("{item:?}") };
+            let report = full.full_document_diagnostic_report;
+            let name = full.uri.path().rsplit('/').next().unwrap().to_string();
+            (name, codes(&report.items).into_iter().map(String::from).collect(), report.result_id.unwrap())
+        })
+        .collect();
+    reports.sort();
+    let found: Vec<(&str, Vec<&str>)> =
+        reports.iter().map(|(name, codes, _)| (name.as_str(), codes.iter().map(String::as_str).collect())).collect();
+    assert_eq!(found, [("lib.alloy", vec!["unused-module"]), ("main.alloy", vec!["unknown-reference"])]);
+
+    let previous = reports
+        .iter()
+        .map(|(name, _, id)| json!({ "uri": Url::from_file_path(root.join(name)).unwrap(), "value": id }))
+        .collect();
+    let report: WorkspaceDiagnosticReport =
+        serde_json::from_value(server.request("workspace/diagnostic", pull(previous)).await).unwrap();
+    assert_eq!(report.items.len(), 2);
+    assert!(report.items.iter().all(|item| matches!(item, WorkspaceDocumentDiagnosticReport::Unchanged(_))));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn any_lint_can_be_suppressed_from_a_code_action() {
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    let diagnostics = server.diagnostics(&file).await;
+
+    let params = json!({
+        "textDocument": { "uri": file },
+        "range": diagnostics[0].range,
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    let suppress = actions.iter().find(|a| a.title == "Suppress `plaintext-secret` here").expect("a suppress action");
+    assert_eq!(suppress.is_preferred, Some(false));
+    let edits = &suppress.edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&file).unwrap()];
+    let suppressed = apply(PIPELINE, edits);
+    assert!(suppressed.contains("    // alloy-lint:disable=plaintext-secret\n    bearer_token = \"hunter2\""), "{suppressed}");
+
+    server.change(&file, 2, &suppressed).await;
+    assert_eq!(server.diagnostics(&file).await, []);
+}
+
+#[tokio::test]
+async fn crlf_files_get_the_same_ranges_and_keep_their_line_endings() {
+    let mut server = TestServer::start().await;
+    let (lf, crlf) = (uri("lf.alloy"), uri("crlf.alloy"));
+    let text = PIPELINE.replace("\"hunter2\"", "\"hunter2\" // alloy-lint:disable=invalid-regex").replace('\n', "\r\n");
+    server.open(&lf, &text.replace("\r\n", "\n")).await;
+    let expected = server.diagnostics(&lf).await;
+    server.open(&crlf, &text).await;
+    let diagnostics = server.diagnostics(&crlf).await;
+    assert_eq!(codes(&diagnostics), ["plaintext-secret", "unused-suppression"]);
+    assert_eq!(diagnostics, expected);
+
+    let params = json!({
+        "textDocument": { "uri": crlf },
+        "range": Range::new(diagnostics[0].range.start, diagnostics[1].range.end),
+        "context": { "diagnostics": [] },
+    });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    let edits = |title: &str| {
+        let action = actions.iter().find(|a| a.title == title).unwrap_or_else(|| panic!("no `{title}` action"));
+        action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&Url::parse(&crlf).unwrap()].clone()
+    };
+    let unsuppressed = apply(&text, &edits("Remove the `invalid-regex` suppression"));
+    assert!(unsuppressed.contains("bearer_token = \"hunter2\"\r\n  }"), "{unsuppressed:?}");
+    let suppressed = apply(&text, &edits("Suppress `plaintext-secret` here"));
+    assert!(suppressed.contains("    // alloy-lint:disable=plaintext-secret\r\n    bearer_token"), "{suppressed:?}");
+    assert_eq!(suppressed.matches('\n').count(), suppressed.matches("\r\n").count());
+}
+
+#[tokio::test]
+async fn a_leading_bom_is_not_a_syntax_error_and_does_not_shift_ranges() {
+    let mut server = TestServer::start().await;
+    let (plain, bom) = (uri("plain.alloy"), uri("bom.alloy"));
+    let text = format!("// 1\n{PIPELINE}");
+    server.open(&plain, &text).await;
+    let expected = server.diagnostics(&plain).await;
+    server.open(&bom, &format!("\u{feff}{text}")).await;
+    assert_eq!(server.diagnostics(&bom).await, expected);
+
+    let text = PIPELINE.replace("prometheus.scrape", "prometheus.scrap");
+    server.change(&plain, 2, &text).await;
+    let expected = server.diagnostics(&plain).await;
+    server.change(&bom, 2, &format!("\u{feff}{text}")).await;
+    let diagnostics = server.diagnostics(&bom).await;
+    assert_eq!(diagnostics, expected);
+    assert!(diagnostics.iter().any(|d| d.range.start == Position::new(0, 0)), "{diagnostics:?}");
+}
+
+#[tokio::test]
+async fn untitled_buffers_get_the_same_features() {
+    let root = std::env::temp_dir().join(format!("alloy-hover-untitled-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("modules")).unwrap();
+    std::fs::write(root.join(".alloylint.toml"), "[rules]\nplaintext-secret = \"error\"\n").unwrap();
+    std::fs::write(root.join("modules/logs.alloy"), "declare \"tail\" {\n}\n").unwrap();
+
+    let mut server = TestServer::in_workspace(&root).await;
+    let file = "untitled:Untitled-1";
+    let text = format!("{PIPELINE}\ntail \"a\" {{\n}}\n\nlocal.file \"a\" {{\n  filename = \"mod\"\n}}\n");
+    server.open(file, &text).await;
+    let diagnostics = server.diagnostics(file).await;
+    assert_eq!(codes(&diagnostics), ["plaintext-secret", "unused-component", "not-imported"], "{diagnostics:?}");
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+
+    let hover = server.hover(file, 2, 30).await.expect("hover on forward_to entry");
+    assert!(hover.contains("defined at [untitled:Untitled-1:6]"), "{hover}");
+    let location: Option<Location> = serde_json::from_value(server.at("textDocument/definition", file, 2, 30).await).unwrap();
+    assert_eq!(location.expect("definition").uri.as_str(), file);
+    assert!(server.completion_labels(file, 11, 0).await.contains(&"prometheus.scrape".to_string()));
+    // Paths complete from the workspace folder, untitled buffers having none of their own.
+    assert_eq!(server.completion_labels(file, 16, 17).await, ["modules/"]);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn reload_docs_swaps_the_docset_live() {
+    let path = std::env::temp_dir().join(format!("alloy-hover-reload-{}.toml", std::process::id()));
+    std::fs::write(&path, "\"prometheus.scrape\" = \"Scrapes, first edition.\"\n").unwrap();
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    let builtin = server.hover(&file, 0, 3).await.expect("hover on prometheus.scrape");
+    assert!(!builtin.contains("first edition"), "{builtin}");
+
+    server.notify("alloy/reloadDocs", json!({ "path": path })).await;
+    let hover = server.hover(&file, 0, 3).await.unwrap();
+    assert!(hover.contains("Scrapes, first edition."), "{hover}");
+
+    std::fs::write(&path, "\"prometheus.scrape\" = \"Scrapes, second edition.\"\n").unwrap();
+    server.notify("alloy/reloadDocs", json!({})).await;
+    let hover = server.hover(&file, 0, 3).await.unwrap();
+    assert!(hover.contains("Scrapes, second edition."), "{hover}");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn broken_docs_keep_the_entries_that_parse_and_are_reported_to_the_user() {
+    let path = std::env::temp_dir().join(format!("alloy-hover-broken-docs-{}.toml", std::process::id()));
+    std::fs::write(&path, "\"prometheus.scrape\" = \"Scrapes, leniently.\"\n\"broken = \"x\"\n").unwrap();
+    let mut server = TestServer::start().await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    server.sent_messages().await;
+    let shown = |messages: Vec<(String, serde_json::Value)>| -> Vec<String> {
+        let shown = messages.into_iter().filter(|(method, _)| method == "window/showMessage");
+        shown.map(|(_, params)| params["message"].as_str().unwrap().to_string()).collect()
+    };
+
+    server.notify("alloy/reloadDocs", json!({ "path": path })).await;
+    let messages = shown(server.sent_messages().await);
+    assert_eq!(messages.len(), 1, "{messages:?}");
+    assert!(messages[0].starts_with(&format!("Alloy hover docs: {}:2: ", path.display())), "{messages:?}");
+    let hover = server.hover(&file, 0, 3).await.unwrap();
+    assert!(hover.contains("Scrapes, leniently."), "{hover}");
+
+    // With nothing usable, the built-in docs stay.
+    std::fs::remove_file(&path).unwrap();
+    server.notify("alloy/reloadDocs", json!({})).await;
+    let messages = shown(server.sent_messages().await);
+    assert!(messages.len() == 1 && messages[0].ends_with("; using the built-in docs"), "{messages:?}");
+    let hover = server.hover(&file, 0, 3).await.unwrap();
+    assert!(!hover.contains("Scrapes, leniently."), "{hover}");
+}
+
+#[tokio::test]
+async fn status_reports_what_is_loaded_and_pending() {
+    let mut server = TestServer::with_options(json!({ "diagnostics": { "debounceMs": 60000 } })).await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+    server.change(&file, 2, &format!("{PIPELINE}\n")).await;
+
+    let status = server.request("alloy/status", json!(null)).await;
+    assert_eq!(status["version"], env!("CARGO_PKG_VERSION"));
+    assert!(status["docs"]["entries"].as_u64().is_some_and(|n| n > 0), "{status}");
+    assert_eq!(status["docs"]["source"], json!(null), "the built-in docs: {status}");
+    assert_eq!(status["workspace"]["filesIndexed"], 1);
+    assert_eq!(status["documents"]["open"], 1);
+    assert!(status["documents"]["bytes"].as_u64().is_some_and(|n| n as usize >= PIPELINE.len()), "{status}");
+    assert_eq!(status["caches"]["metricNames"], json!(null));
+    assert_eq!(status["pending"]["lints"], 1, "the edit's lint is debounced: {status}");
+}
+
+#[tokio::test]
+async fn grafana_cloud_presets_complete_and_describe_endpoints() {
+    let text = "prometheus.remote_write \"cloud\" {\n  endpoint {\n    url = \"\"\n  }\n}\n";
+    let file = uri("cloud.alloy");
+    let mut server = TestServer::start().await;
+    server.open(&file, text).await;
+    assert_eq!(server.completion_labels(&file, 2, 11).await, Vec::<String>::new(), "presets are opt-in");
+
+    let mut server = TestServer::with_options(json!({ "grafanaCloud": { "presets": true } })).await;
+    server.open(&file, text).await;
+    let labels = server.completion_labels(&file, 2, 11).await;
+    let us_east = "https://prometheus-prod-13-prod-us-east-0.grafana.net/api/prom/push";
+    assert!(labels.iter().any(|l| l == us_east), "{labels:?}");
+    assert!(labels.iter().all(|l| l.contains("prometheus-prod")), "only metrics endpoints: {labels:?}");
+
+    server.change(&file, 2, &text.replace("\"\"", &format!("\"{us_east}\""))).await;
+    let hover = server.hover(&file, 2, 20).await.expect("hover on the endpoint");
+    assert!(hover.contains("Grafana Cloud Metrics") && hover.contains("prod-us-east-0"), "{hover}");
+}
+
+#[tokio::test]
+async fn hovering_a_scrape_component_shows_its_live_targets() {
+    let targets = json!([
+        { "url": "http://node:9100/metrics", "health": "up", "last_error": "" },
+        { "url": "http://db:9187/metrics", "health": "down", "last_error": "connection refused" },
+    ]);
+    let component = json!({
+        "localID": "prometheus.scrape.default",
+        "health": { "state": "healthy" },
+        "debugInfo": { "targets": targets },
+    });
+    let instance = FakeInstance::start(vec![("/api/v0/web/components/prometheus.scrape.default", component)]);
+    let mut server = TestServer::with_options(json!({ "instance": { "url": instance.url } })).await;
+    let file = uri("pipeline.alloy");
+    server.open(&file, PIPELINE).await;
+
+    let hover = server.hover(&file, 0, 3).await.expect("hover on prometheus.scrape");
+    assert!(hover.contains("2 targets discovered, 1 up, 1 down"), "{hover}");
+    assert!(hover.contains("`http://db:9187/metrics`: connection refused"), "{hover}");
+}
+
+#[tokio::test]
+async fn env_value_previews_are_opt_in_and_mask_secret_names() {
+    std::env::set_var("ALLOY_HOVER_TEST_CLUSTER", "prod-eu");
+    std::env::set_var("ALLOY_HOVER_TEST_TOKEN", "hunter2");
+    let text = "local.file \"a\" {\n  filename = env(\"ALLOY_HOVER_TEST_CLUSTER\")\n}\n\nlocal.file \"b\" {\n  filename = string.join([env(\"ALLOY_HOVER_TEST_TOKEN\"), env(\"ALLOY_HOVER_TEST_UNSET\")], \"\")\n}\n";
+    let file = uri("env.alloy");
+    let mut server = TestServer::start().await;
+    server.open(&file, text).await;
+    assert!(!server.hover(&file, 1, 20).await.unwrap_or_default().contains("prod-eu"), "previews are opt-in");
+    server.notify("workspace/didChangeConfiguration", json!({ "settings": { "hover": { "envValues": true } } })).await;
+    assert!(server.hover(&file, 1, 20).await.unwrap().contains("prod-eu"), "the cached hover is dropped");
+
+    let mut server = TestServer::with_options(json!({ "hover": { "envValues": true } })).await;
+    server.open(&file, text).await;
+    let hover = server.hover(&file, 1, 20).await.expect("hover on the env() call");
+    assert!(hover.contains("`ALLOY_HOVER_TEST_CLUSTER` = `prod-eu`"), "{hover}");
+    let hover = server.hover(&file, 5, 35).await.unwrap();
+    assert!(hover.contains("value masked") && !hover.contains("hunter2"), "{hover}");
+    let hover = server.hover(&file, 5, 70).await.unwrap();
+    assert!(hover.contains("`ALLOY_HOVER_TEST_UNSET` is not set"), "{hover}");
+}
+
+#[tokio::test]
+async fn exporter_arguments_are_completed_described_and_checked() {
+    let mut server = TestServer::start().await;
+    let file = uri("exporters.alloy");
+    let text = "prometheus.exporter.redis \"cache\" {\n  redis_addr = \"redis:6379\"\n  connection_timeout = \"5x\"\n  \n}\n\nprometheus.exporter.blackbox \"probes\" {\n  target {\n    name    = \"site\"\n    address = \"https://example.com\"\n  }\n  modules { }\n}\n";
+    server.open(&file, text).await;
+
+    let diagnostics = server.diagnostics(&file).await;
+    assert_eq!(codes(&diagnostics), ["invalid-duration", "unknown-block", "unused-component", "unused-component"]);
+    assert_eq!(diagnostics[1].range.start, Position::new(11, 2));
+
+    let labels = server.completion_labels(&file, 3, 2).await;
+    assert!(labels.contains(&"redis_password".to_string()), "{labels:?}");
+    assert!(!labels.contains(&"data_source_name".to_string()), "{labels:?}");
+
+    let hover = server.hover(&file, 1, 4).await.expect("hover on redis_addr");
+    assert!(hover.contains("Address of the Redis instance."), "{hover}");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn yaml_configs_are_converted_into_a_new_alloy_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("alloy-convert-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // Stands in for `alloy convert`, only knowing Prometheus configs.
+    let alloy = dir.join("alloy");
+    let script = "#!/bin/sh\n[ \"$1 $2\" = \"convert --source-format=prometheus\" ] || exit 1\nprintf 'prometheus.scrape \"x\" {\\n}\\n'\n";
+    std::fs::write(&alloy, script).unwrap();
+    std::fs::set_permissions(&alloy, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let source = dir.join("prometheus.yml");
+    std::fs::write(&source, "global:\n  scrape_interval: 15s\nscrape_configs:\n  - job_name: x\n").unwrap();
+    let file = Url::from_file_path(&source).unwrap().to_string();
+    let mut server = TestServer::with_options(json!({ "alloy": { "path": alloy } })).await;
+
+    // The format is guessed when left out.
+    let target = Url::from_file_path(dir.join("prometheus.alloy")).unwrap();
+    let params = json!({ "command": "alloy.convert", "arguments": [file] });
+    let converted = server.request("workspace/executeCommand", params.clone()).await;
+    assert_eq!(converted, json!({ "uri": target, "text": "prometheus.scrape \"x\" {\n}\n" }));
+    let edit = server.applied_edit().await.expect("the converted config is written out");
+    let Some(DocumentChanges::Operations(operations)) = edit.document_changes else { panic!("{edit:?}") };
+    assert!(matches!(&operations[0], DocumentChangeOperation::Op(ResourceOp::Create(c)) if c.uri == target));
+
+    // An existing file is left alone, and the user is told.
+    std::fs::write(dir.join("prometheus.alloy"), "").unwrap();
+    server.request("workspace/executeCommand", params).await;
+    let sent = server.sent_messages().await;
+    assert!(!sent.iter().any(|(method, _)| method == "workspace/applyEdit"), "{sent:?}");
+    let (_, shown) = sent.iter().find(|(method, _)| method == "window/showMessage").expect("a message");
+    assert!(shown["message"].as_str().unwrap().ends_with("prometheus.alloy already exists"), "{shown}");
+
+    // Helm values share top-level keys with agent and Prometheus configs.
+    let values = dir.join("values.yaml");
+    std::fs::write(&values, "global:\n  image: grafana/alloy\nmetrics:\n  enabled: true\nlogs:\n  enabled: false\n").unwrap();
+    let params = json!({ "command": "alloy.convert", "arguments": [Url::from_file_path(&values).unwrap()] });
+    assert!(server.request_error("workspace/executeCommand", params).await.contains("cannot tell what kind of config"));
+
+    let params = json!({ "command": "alloy.convert", "arguments": [file, "nginx"] });
+    assert!(server.request_error("workspace/executeCommand", params).await.contains("unknown format `nginx`"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn renaming_files_or_folders_updates_the_imports_of_them() {
+    let root = std::env::temp_dir().join(format!("alloy-hover-rename-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("modules")).unwrap();
+    std::fs::create_dir_all(root.join("lib")).unwrap();
+    let main = "import.file \"db\" {\n  filename = file.path_join(module_path, \"modules/db.alloy\")\n}\n\nimport.file \"util\" {\n  filename = \"lib/util.alloy\"\n}\n";
+    std::fs::write(root.join("main.alloy"), main).unwrap();
+    std::fs::write(root.join("modules/db.alloy"), "declare \"db\" { }\n").unwrap();
+    std::fs::write(root.join("lib/util.alloy"), "declare \"util\" { }\n").unwrap();
+    let mut server = TestServer::in_workspace(&root).await;
+    let at = |path: &str| Url::from_file_path(root.join(path)).unwrap();
+    let renamed = |from: &str, to: &str| json!({ "files": [{ "oldUri": at(from), "newUri": at(to) }] });
+
+    let params = renamed("modules/db.alloy", "shared/db.alloy");
+    let edit: WorkspaceEdit = serde_json::from_value(server.request("workspace/willRenameFiles", params).await).unwrap();
+    let edits = &edit.changes.unwrap()[&at("main.alloy")];
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].range, Range::new(Position::new(1, 41), Position::new(1, 59)));
+    assert_eq!(edits[0].new_text, "\"shared/db.alloy\"");
+
+    let edit: WorkspaceEdit =
+        serde_json::from_value(server.request("workspace/willRenameFiles", renamed("lib", "common")).await).unwrap();
+    let edits = &edit.changes.unwrap()[&at("main.alloy")];
+    assert_eq!(edits.iter().map(|e| e.new_text.as_str()).collect::<Vec<_>>(), ["\"common/util.alloy\""]);
+
+    // Moving the importer only moves the paths relative to it.
+    let params = renamed("main.alloy", "env/main.alloy");
+    let edit: WorkspaceEdit = serde_json::from_value(server.request("workspace/willRenameFiles", params).await).unwrap();
+    let edits = &edit.changes.unwrap()[&at("main.alloy")];
+    assert_eq!(edits.iter().map(|e| e.new_text.as_str()).collect::<Vec<_>>(), ["\"../modules/db.alloy\""]);
+    server.notify("workspace/didRenameFiles", renamed("main.alloy", "env/main.alloy")).await;
+
+    let edit: WorkspaceEdit =
+        serde_json::from_value(server.request("workspace/willRenameFiles", renamed("lib", "common")).await).unwrap();
+    assert!(edit.changes.unwrap().contains_key(&at("env/main.alloy")), "the index follows the rename");
+    let params = renamed("unrelated.alloy", "other.alloy");
+    assert_eq!(server.request("workspace/willRenameFiles", params).await, json!(null));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn fixing_a_misspelled_component_renames_its_references_in_other_files() {
+    let mut server = TestServer::start().await;
+    let (sink, scrape) = (uri("sink.alloy"), uri("scrape.alloy"));
+    server.open(&sink, "prometheus.remote_writ \"cloud\" {\n}\n").await;
+    let text = "prometheus.scrape \"a\" {\n  targets    = []\n  forward_to = [prometheus.remote_writ.cloud.receiver]\n}\n";
+    server.open(&scrape, text).await;
+
+    let range = json!({ "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } });
+    let params = json!({ "textDocument": { "uri": sink }, "range": range, "context": { "diagnostics": [] } });
+    let actions: Vec<CodeAction> = serde_json::from_value(server.request("textDocument/codeAction", params).await).unwrap();
+    assert_eq!(actions[0].title, "Change to `prometheus.remote_write`");
+    let changes = actions[0].edit.clone().unwrap().changes.unwrap();
+    assert_eq!(changes[&Url::parse(&sink).unwrap()][0].new_text, "prometheus.remote_write");
+    let elsewhere = &changes[&Url::parse(&scrape).unwrap()];
+    assert_eq!(elsewhere.len(), 1);
+    assert_eq!(elsewhere[0].range, Range::new(Position::new(2, 16), Position::new(2, 53)));
+    assert_eq!(elsewhere[0].new_text, "prometheus.remote_write.cloud.receiver");
+}
+
+#[tokio::test]
+async fn list_modules_returns_every_declare_with_its_interface() {
+    let mut server = TestServer::start().await;
+    let db = "// Reads a database.\ndeclare \"db\" {\n  argument \"dsn\" { }\n\n  argument \"timeout\" {\n    optional = true\n    default  = \"5s\"\n  }\n\n  export \"targets\" {\n    value = []\n  }\n}\n";
+    server.open(&uri("modules/db.alloy"), db).await;
+    server.open(&uri("cache.alloy"), "declare \"cache\" { }\n").await;
+
+    let command = json!({ "command": "alloy.listModules", "arguments": [] });
+    let modules = server.request("workspace/executeCommand", command).await;
+    let names: Vec<&str> = modules.as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["cache", "db"]);
+    let db = &modules[1];
+    assert_eq!(db["uri"], json!(uri("modules/db.alloy")));
+    assert_eq!(db["doc"], "Reads a database.");
+    assert_eq!(db["exports"], json!(["targets"]));
+    assert_eq!(db["arguments"][0]["name"], "dsn");
+    assert_eq!(db["arguments"][1]["optional"], true);
+    assert_eq!(db["arguments"][1]["default"], "\"5s\"");
+}
+
+#[tokio::test]
+async fn document_symbols_give_each_kind_of_block_its_own_kind_and_detail() {
+    let mut server = TestServer::start().await;
+    let file = uri("outline.alloy");
+    let text = "import.file \"utils\" {\n  filename = \"utils.alloy\"\n}\n\nprometheus.remote_write \"cloud\" {\n  endpoint {\n    url = \"https://example.com\"\n  }\n}\n\ndeclare \"db\" {\n  argument \"dsn\" { }\n\n  argument \"timeout\" {\n    optional = true\n  }\n}\n";
+    server.open(&file, text).await;
+
+    let response = server.request("textDocument/documentSymbol", json!({ "textDocument": { "uri": file } })).await;
+    let Some(DocumentSymbolResponse::Nested(symbols)) = serde_json::from_value(response).unwrap() else { panic!() };
+    let outline: Vec<(&str, SymbolKind, Option<&str>)> =
+        symbols.iter().map(|s| (s.name.as_str(), s.kind, s.detail.as_deref())).collect();
+    assert_eq!(
+        outline,
+        [
+            ("utils", SymbolKind::NAMESPACE, Some("import.file")),
+            ("prometheus.remote_write", SymbolKind::OBJECT, Some("\"cloud\" · receiver: MetricsReceiver")),
+            ("db", SymbolKind::FUNCTION, Some("(dsn, timeout?)")),
+        ]
+    );
+    let endpoint = &symbols[1].children.as_ref().unwrap()[0];
+    assert_eq!((endpoint.name.as_str(), endpoint.kind), ("endpoint", SymbolKind::STRUCT));
+    let url = &endpoint.children.as_ref().unwrap()[0];
+    assert_eq!((url.name.as_str(), url.kind, url.detail.as_deref()), ("url", SymbolKind::FIELD, Some("string")));
+    let arguments = symbols[2].children.as_ref().unwrap();
+    assert_eq!(arguments[1].detail.as_deref(), Some("optional argument"));
+    assert_eq!(arguments[1].selection_range, Range::new(Position::new(13, 11), Position::new(13, 20)));
+}
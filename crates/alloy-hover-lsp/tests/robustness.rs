@@ -0,0 +1,82 @@
+//! Replays the fuzz seeds and past fuzz findings through everything that
+//! runs on a document change or a keystroke, so fixed crashes stay fixed
+//! without needing a nightly toolchain.
+
+use std::{fs, path::PathBuf};
+use tower_lsp::lsp_types::{Position, Url};
+
+use alloy_hover_lsp::completion::{self, Options};
+use alloy_hover_lsp::{document::Document, folding, lint, semantic_tokens, workspace::Workspace};
+
+/// Runs `text` through everything that runs on a document change.
+fn analyze(text: String) {
+    let uri = Url::parse("file:///fuzz/config.alloy").unwrap();
+    let mut workspace = Workspace::default();
+    workspace.update(uri.clone(), &text);
+    let doc = Document::new(text);
+    let _ = lint::check(&doc, &uri, &workspace, None);
+    let _ = folding::ranges(&doc);
+    let _ = semantic_tokens::tokens(&doc);
+    // Completion looks back from the cursor, so try it at line ends; at
+    // most 256 of them, as each completion reads what comes before.
+    let lines = doc.text.matches('\n').count() + 1;
+    for line in (0..lines as u32).step_by(lines.div_ceil(256)) {
+        let pos = Position::new(line, u32::MAX);
+        let _ = completion::complete(&doc, &uri, pos, &workspace, None, Options::default(), None);
+    }
+}
+
+#[test]
+fn fuzz_inputs_do_not_panic() {
+    let fuzz = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fuzz");
+    let mut count = 0;
+    for dir in ["corpus/parse", "regressions"] {
+        for entry in fs::read_dir(fuzz.join(dir)).unwrap().flatten() {
+            analyze(String::from_utf8_lossy(&fs::read(entry.path()).unwrap()).into_owned());
+            count += 1;
+        }
+    }
+    assert!(count > 0);
+}
+
+#[test]
+fn deep_nesting_does_not_overflow_the_stack() {
+    let n = 10_000;
+    analyze(format!("{}{}", "a {\n".repeat(n / 2), "}\n".repeat(n / 2)));
+    analyze(format!("x = {}{}\n", "[".repeat(n), "]".repeat(n)));
+    analyze(format!("x = {}1{}\n", "(".repeat(n), ")".repeat(n)));
+    analyze(format!("x = {}true\ny ={} 2\n", "!".repeat(n), " 2 ^".repeat(n / 2)));
+}
+
+#[tokio::test]
+async fn a_panicking_handler_fails_only_its_own_message() {
+    use std::sync::{Arc, Mutex};
+    use tower::{service_fn, Service, ServiceExt};
+    use tower_lsp::jsonrpc::{ErrorCode, Request, Response};
+
+    use alloy_hover_lsp::catch_panic::CatchPanic;
+
+    let handler = service_fn(|request: Request| async move {
+        if request.method().starts_with("boom") {
+            panic!("bad offset math");
+        }
+        Ok::<_, std::convert::Infallible>(request.id().cloned().map(|id| Response::from_ok(id, "ok".into())))
+    });
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let seen = reports.clone();
+    let mut service = CatchPanic::new(handler, move |report| seen.lock().unwrap().push(report));
+    let mut call = async |request| service.ready().await.unwrap().call(request).await.unwrap();
+
+    let reply = call(Request::build("boom/request").id(1).finish()).await.expect("requests get a reply");
+    let error = reply.into_parts().1.unwrap_err();
+    assert_eq!(error.code, ErrorCode::InternalError);
+    assert!(error.message.starts_with("boom/request failed: bad offset math at tests/robustness.rs"), "{error:?}");
+    assert!(call(Request::build("boom/notification").finish()).await.is_none());
+    let reply = call(Request::build("textDocument/hover").id(2).finish()).await.unwrap();
+    assert_eq!(reply.into_parts().1.unwrap(), "ok");
+
+    let reports = reports.lock().unwrap();
+    let methods: Vec<&str> = reports.iter().map(|r| r.method.as_str()).collect();
+    assert_eq!(methods, ["boom/request", "boom/notification"]);
+    assert!(reports[0].backtrace.contains("robustness"), "{}", reports[0].backtrace);
+}
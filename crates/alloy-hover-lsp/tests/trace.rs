@@ -0,0 +1,75 @@
+//! `--trace`, run against the real binary over stdio.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+fn frame(message: Value) -> Vec<u8> {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len()).into_bytes()
+}
+
+/// Reads messages off `out` until the reply to request `id`.
+fn reply(out: &mut impl BufRead, id: u64) -> Value {
+    loop {
+        let mut length = 0;
+        let mut line = String::new();
+        while out.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+            line.clear();
+        }
+        let mut body = vec![0; length];
+        out.read_exact(&mut body).unwrap();
+        let message: Value = serde_json::from_slice(&body).unwrap();
+        if message["id"] == id {
+            return message;
+        }
+    }
+}
+
+#[test]
+fn trace_records_every_message_both_ways() {
+    let path = std::env::temp_dir().join(format!("alloy-hover-trace-{}.jsonl", std::process::id()));
+    let mut server = Command::new(env!("CARGO_BIN_EXE_alloy-hover-lsp"))
+        .arg("--trace")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let (mut stdin, mut stdout) = (server.stdin.take().unwrap(), BufReader::new(server.stdout.take().unwrap()));
+    let mut send = |message: Value| {
+        // Split mid-frame, as pipes are free to.
+        let bytes = frame(message);
+        let (head, tail) = bytes.split_at(bytes.len() / 2);
+        stdin.write_all(head).unwrap();
+        stdin.flush().unwrap();
+        stdin.write_all(tail).unwrap();
+        stdin.flush().unwrap();
+    };
+    send(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} } }));
+    reply(&mut stdout, 1);
+    send(json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }));
+    send(json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }));
+    reply(&mut stdout, 2);
+    send(json!({ "jsonrpc": "2.0", "method": "exit" }));
+    drop(stdin);
+    assert!(server.wait_with_output().unwrap().status.success());
+
+    let trace = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<Value> = trace.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let received: Vec<&str> = lines
+        .iter()
+        .filter(|l| l["direction"] == "received")
+        .map(|l| l["message"]["method"].as_str().unwrap())
+        .collect();
+    assert_eq!(received, ["initialize", "initialized", "shutdown", "exit"]);
+    let reply = lines.iter().find(|l| l["direction"] == "sent" && l["message"]["id"] == 1).expect("initialize reply");
+    assert!(reply["message"]["result"]["capabilities"].is_object(), "{reply}");
+    assert!(lines.iter().all(|l| l["timeMs"].as_u64().is_some_and(|t| t > 0)));
+
+    std::fs::remove_file(&path).unwrap();
+}
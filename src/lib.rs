@@ -2,13 +2,12 @@ use std::{
     env::current_dir,
     fs,
     fs::create_dir_all,
-    path::{Path, PathBuf},
+    path::PathBuf,
 };
 
 use zed_extension_api::{
     self as zed, Extension, LanguageServerId, Worktree, Os, current_platform,
-    serde_json::{self, Value},
-    settings::LspSettings,
+    serde_json::Value,
     register_extension,
 };
 